@@ -0,0 +1,140 @@
+//! HTTP access logging and per-route request statistics for `/api/stats`.
+//!
+//! Every request is logged at DEBUG under the `resource_monitor::access`
+//! target (`RUST_LOG=resource_monitor::access=debug` to see them). Streaming
+//! endpoints log when the stream opens and are left out of the statistics.
+
+use axum::body::Body;
+use axum::extract::{MatchedPath, Request, State};
+use axum::middleware::Next;
+use axum::response::Response;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tower_http::classify::{ServerErrorsAsFailures, SharedClassifier};
+use tower_http::trace::{MakeSpan, OnResponse, TraceLayer};
+use tracing::{debug, Span};
+
+pub const ACCESS_LOG_TARGET: &str = "resource_monitor::access";
+
+/// Upper bounds (inclusive) of the latency histogram buckets; one more
+/// bucket counts everything slower.
+pub const LATENCY_BUCKETS_MS: &[f64] = &[1.0, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0];
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct RouteStats {
+    pub count: u64,
+    /// Responses with a 5xx status.
+    pub server_errors: u64,
+    pub total_latency_ms: f64,
+    pub max_latency_ms: f64,
+    /// Counts per [`LATENCY_BUCKETS_MS`] bucket, plus the overflow bucket.
+    pub latency_buckets: Vec<u64>,
+}
+
+impl RouteStats {
+    fn record(&mut self, status: u16, latency: Duration) {
+        let ms = latency.as_secs_f64() * 1000.0;
+        if self.latency_buckets.is_empty() {
+            self.latency_buckets = vec![0; LATENCY_BUCKETS_MS.len() + 1];
+        }
+        let bucket = LATENCY_BUCKETS_MS
+            .iter()
+            .position(|bound| ms <= *bound)
+            .unwrap_or(LATENCY_BUCKETS_MS.len());
+        self.latency_buckets[bucket] += 1;
+        self.count += 1;
+        if status >= 500 {
+            self.server_errors += 1;
+        }
+        self.total_latency_ms += ms;
+        self.max_latency_ms = self.max_latency_ms.max(ms);
+    }
+}
+
+/// Request counts and latencies keyed by route pattern (e.g. `/api/alerts/:id/ack`).
+#[derive(Debug, Default)]
+pub struct RequestStats {
+    routes: Mutex<BTreeMap<String, RouteStats>>,
+}
+
+impl RequestStats {
+    pub fn record(&self, route: &str, status: u16, latency: Duration) {
+        let mut routes = self.routes.lock().unwrap_or_else(|p| p.into_inner());
+        routes
+            .entry(route.to_string())
+            .or_default()
+            .record(status, latency);
+    }
+
+    pub fn snapshot(&self) -> BTreeMap<String, RouteStats> {
+        self.routes
+            .lock()
+            .unwrap_or_else(|p| p.into_inner())
+            .clone()
+    }
+}
+
+/// Middleware recording each request into `stats`.
+pub async fn record_request(
+    State(stats): State<Arc<RequestStats>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let route = route_of(&request);
+    let start = Instant::now();
+    let response = next.run(request).await;
+    stats.record(&route, response.status().as_u16(), start.elapsed());
+    response
+}
+
+fn route_of(request: &Request) -> String {
+    request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_else(|| request.uri().path().to_string())
+}
+
+/// Opens a span carrying the method and route of each request.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AccessSpan;
+
+impl MakeSpan<Body> for AccessSpan {
+    fn make_span(&mut self, request: &Request) -> Span {
+        tracing::debug_span!(
+            target: ACCESS_LOG_TARGET,
+            "request",
+            method = %request.method(),
+            path = %route_of(request),
+        )
+    }
+}
+
+/// Logs the status and latency once the response head is ready.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AccessLog;
+
+impl<B> OnResponse<B> for AccessLog {
+    fn on_response(self, response: &Response<B>, latency: Duration, _span: &Span) {
+        debug!(
+            target: ACCESS_LOG_TARGET,
+            status = response.status().as_u16(),
+            latency_ms = latency.as_secs_f64() * 1000.0,
+            "request completed"
+        );
+    }
+}
+
+pub type AccessLogLayer =
+    TraceLayer<SharedClassifier<ServerErrorsAsFailures>, AccessSpan, (), AccessLog, ()>;
+
+/// `TraceLayer` emitting one access-log event per response.
+pub fn access_log_layer() -> AccessLogLayer {
+    TraceLayer::new_for_http()
+        .make_span_with(AccessSpan)
+        .on_request(())
+        .on_response(AccessLog)
+        .on_body_chunk(())
+}
@@ -1,188 +1,1081 @@
-use crate::bus::publish_snapshot;
+use crate::bus::{publish_snapshot, TransformChain};
+use crate::config::{
+    ChangeDeltas, CpuTotalMethod, DiskUsageBasis, PressureWeights, ProcessSelector,
+};
 use crate::metrics::{
-    now_timestamp_ms, BatteryMetrics, CpuMetrics, DiskMetrics, GpuMetrics, MemoryMetrics,
-    MetricsSnapshot, NetworkMetrics,
+    align_timestamp_ms, busy_cores, now_timestamp_ms, BatteryMetrics, CpuMetrics, DiskMetrics,
+    GpuMetrics, MemoryMetrics, MetricsSnapshot, NetworkMetrics, ProcessEntry, ProcessNetUsage,
+    WatchedProcess,
 };
+use crate::procfs;
+use crate::talkers::TopTalkers;
+use crate::topology::CoreTopology;
 use battery::{Manager, State};
+use std::collections::BTreeMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
-use sysinfo::{CpuRefreshKind, Disks, MemoryRefreshKind, Networks, RefreshKind, System};
+use sysinfo::{CpuRefreshKind, Disks, MemoryRefreshKind, Networks, Pid, RefreshKind, System};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
 use tokio::time::MissedTickBehavior;
 use tokio_util::sync::CancellationToken;
-use tracing::{debug, info, warn};
+use tracing::{debug, error, info, warn};
+
+/// Whether the OS has a load average; Windows does not.
+pub const HAS_LOAD_AVERAGE: bool = !cfg!(windows);
 
 pub struct AggregatorConfig {
     pub interval: Duration,
+    /// Round each snapshot timestamp to the nearest multiple of `interval`.
+    pub align_timestamps: bool,
+    /// Tick on wall-clock multiples of `interval` (e.g. every whole second)
+    /// rather than at offsets from startup.
+    pub align_ticks: bool,
+    /// Network rates above this many bytes/s are treated as counter glitches.
+    pub net_rate_max: Option<f32>,
+    /// Leading samples dropped instead of published. The first sample has no
+    /// previous counters to diff against, so its rates read as zero.
+    pub warmup_samples: u32,
+    /// New sampling intervals pushed by a config reload.
+    pub interval_updates: Option<watch::Receiver<Duration>>,
+    pub cpu_total_method: CpuTotalMethod,
+    /// Process tree summed into `watched_process` each tick.
+    pub watch_process: Option<ProcessSelector>,
+    /// How many processes `net_top_processes` lists; off when None.
+    pub net_top_processes: Option<usize>,
+    /// Groups cores for `per_socket_usage_pct`; off when None.
+    pub core_topology: Option<CoreTopology>,
+    /// Publishes only snapshots that pass this gate; every one when None.
+    pub publish_gate: Option<PublishGate>,
+    /// Weights of `pressure_score`, set on every snapshot.
+    pub pressure_weights: PressureWeights,
+    /// Written to each snapshot's `source`, telling apart aggregators that
+    /// share one bus.
+    pub source_label: Option<String>,
+    /// Added to each snapshot's `tags`.
+    pub tags: BTreeMap<String, String>,
+    /// What `disk.used_pct` is computed from.
+    pub disk_usage_basis: DiskUsageBasis,
+    /// Lengthens the interval when collection cannot keep up; off when None.
+    pub auto_tune: Option<IntervalAutoTune>,
+    /// A sample more than this many intervals after the previous one is
+    /// taken to follow a suspend/resume, see [`purge_resumed`]; off when None.
+    pub resume_gap_factor: Option<f32>,
+    /// Milliseconds since the Unix epoch, for timestamps, aligned ticks and
+    /// telling a suspend apart; [`now_timestamp_ms`] outside tests.
+    pub wall_clock: fn() -> u128,
+    /// Applied to each snapshot just before it is published.
+    pub transforms: TransformChain,
 }
 
 impl AggregatorConfig {
     pub fn new(interval: Duration) -> Self {
-        Self { interval }
+        Self {
+            interval,
+            align_timestamps: false,
+            align_ticks: false,
+            net_rate_max: None,
+            warmup_samples: 1,
+            interval_updates: None,
+            cpu_total_method: CpuTotalMethod::default(),
+            watch_process: None,
+            net_top_processes: None,
+            core_topology: None,
+            publish_gate: None,
+            pressure_weights: PressureWeights::default(),
+            source_label: None,
+            tags: BTreeMap::new(),
+            disk_usage_basis: DiskUsageBasis::default(),
+            auto_tune: None,
+            resume_gap_factor: None,
+            wall_clock: now_timestamp_ms,
+            transforms: TransformChain::default(),
+        }
+    }
+
+    pub fn with_aligned_timestamps(mut self, align: bool) -> Self {
+        self.align_timestamps = align;
+        self
+    }
+
+    pub fn with_aligned_ticks(mut self, align: bool) -> Self {
+        self.align_ticks = align;
+        self
+    }
+
+    pub fn with_net_rate_max(mut self, max: Option<f32>) -> Self {
+        self.net_rate_max = max;
+        self
+    }
+
+    pub fn with_warmup_samples(mut self, samples: u32) -> Self {
+        self.warmup_samples = samples;
+        self
+    }
+
+    pub fn with_interval_updates(mut self, updates: watch::Receiver<Duration>) -> Self {
+        self.interval_updates = Some(updates);
+        self
+    }
+
+    pub fn with_cpu_total_method(mut self, method: CpuTotalMethod) -> Self {
+        self.cpu_total_method = method;
+        self
+    }
+
+    pub fn with_disk_usage_basis(mut self, basis: DiskUsageBasis) -> Self {
+        self.disk_usage_basis = basis;
+        self
+    }
+
+    pub fn with_watch_process(mut self, target: Option<ProcessSelector>) -> Self {
+        self.watch_process = target;
+        self
+    }
+
+    pub fn with_net_top_processes(mut self, top_n: Option<usize>) -> Self {
+        self.net_top_processes = top_n;
+        self
+    }
+
+    pub fn with_core_topology(mut self, topology: Option<CoreTopology>) -> Self {
+        self.core_topology = topology;
+        self
+    }
+
+    pub fn with_publish_gate(mut self, gate: Option<PublishGate>) -> Self {
+        self.publish_gate = gate;
+        self
+    }
+
+    pub fn with_pressure_weights(mut self, weights: PressureWeights) -> Self {
+        self.pressure_weights = weights;
+        self
+    }
+
+    pub fn with_source_label(mut self, label: Option<String>) -> Self {
+        self.source_label = label;
+        self
+    }
+
+    pub fn with_tags(mut self, tags: BTreeMap<String, String>) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    pub fn with_resume_gap_factor(mut self, factor: Option<f32>) -> Self {
+        self.resume_gap_factor = factor;
+        self
+    }
+
+    pub fn with_auto_tune(mut self, auto_tune: Option<IntervalAutoTune>) -> Self {
+        self.auto_tune = auto_tune;
+        self
+    }
+
+    pub fn with_wall_clock(mut self, clock: fn() -> u128) -> Self {
+        self.wall_clock = clock;
+        self
+    }
+
+    /// Appends `transform` to the chain run on every published snapshot.
+    pub fn with_transform(
+        mut self,
+        transform: impl Fn(MetricsSnapshot) -> MetricsSnapshot + Send + Sync + 'static,
+    ) -> Self {
+        self.transforms.push(transform);
+        self
+    }
+}
+
+/// Default `--auto-tune-fraction`.
+pub const DEFAULT_AUTO_TUNE_FRACTION: f32 = 0.5;
+/// Default `--auto-tune-max-ms`.
+pub const DEFAULT_AUTO_TUNE_MAX_MS: u64 = 60_000;
+/// Consecutive slow collections after which the interval is lengthened.
+pub const AUTO_TUNE_SLOW_SAMPLES: u32 = 3;
+
+/// Interval auto-tuning (`--auto-tune-interval`): once
+/// [`AUTO_TUNE_SLOW_SAMPLES`] collections in a row take more than `fraction`
+/// of the interval, the interval doubles, up to `max`. It is not shortened
+/// again until the interval is reconfigured.
+#[derive(Clone, Debug)]
+pub struct IntervalAutoTune {
+    fraction: f32,
+    max: Duration,
+    slow_streak: u32,
+}
+
+impl IntervalAutoTune {
+    pub fn new(fraction: f32, max: Duration) -> Self {
+        Self {
+            fraction: fraction.clamp(0.01, 1.0),
+            max,
+            slow_streak: 0,
+        }
+    }
+
+    /// Records a collection that took `took` at `interval`; returns the
+    /// interval to switch to when collection has been slow for long enough.
+    pub fn observe(&mut self, took: Duration, interval: Duration) -> Option<Duration> {
+        if took.as_secs_f32() <= interval.as_secs_f32() * self.fraction {
+            self.slow_streak = 0;
+            return None;
+        }
+        self.slow_streak += 1;
+        if self.slow_streak < AUTO_TUNE_SLOW_SAMPLES {
+            return None;
+        }
+        self.slow_streak = 0;
+        let longer = (interval * 2).min(self.max);
+        (longer > interval).then_some(longer)
+    }
+
+    /// Forgets slow collections seen at a previous interval.
+    pub fn reset(&mut self) {
+        self.slow_streak = 0;
+    }
+}
+
+/// Default `--heartbeat-ms`: the longest a steady host goes unpublished.
+pub const DEFAULT_HEARTBEAT_MS: u64 = 60_000;
+
+/// Publish-on-change filter (`--publish-on-change`): a snapshot passes when
+/// any configured metric moved by at least its delta since the last one that
+/// passed, or when `heartbeat_ms` has elapsed since then.
+#[derive(Clone, Debug)]
+pub struct PublishGate {
+    deltas: ChangeDeltas,
+    heartbeat_ms: u128,
+    last: Option<MetricsSnapshot>,
+}
+
+impl PublishGate {
+    pub fn new(deltas: ChangeDeltas, heartbeat_ms: u64) -> Self {
+        Self {
+            deltas,
+            heartbeat_ms: u128::from(heartbeat_ms),
+            last: None,
+        }
+    }
+
+    /// Whether `snapshot` should be published; remembers it when it should.
+    pub fn admit(&mut self, snapshot: &MetricsSnapshot) -> bool {
+        let admit = match &self.last {
+            None => true,
+            Some(last) => {
+                snapshot.timestamp_ms.saturating_sub(last.timestamp_ms) >= self.heartbeat_ms
+                    || self.changed(last, snapshot)
+            }
+        };
+        if admit {
+            self.last = Some(snapshot.clone());
+        }
+        admit
+    }
+
+    fn changed(&self, old: &MetricsSnapshot, new: &MetricsSnapshot) -> bool {
+        let moved = |delta: Option<f32>, old: f32, new: f32| {
+            delta.is_some_and(|delta| (new - old).abs() >= delta)
+        };
+        let d = &self.deltas;
+        moved(d.cpu_pct, old.cpu.total_usage_pct, new.cpu.total_usage_pct)
+            || moved(d.mem_pct, mem_used_pct(old), mem_used_pct(new))
+            || moved(d.disk_pct, old.disk.used_pct, new.disk.used_pct)
+            || moved(
+                d.net_bytes_per_sec,
+                old.network.rx_bytes_per_sec,
+                new.network.rx_bytes_per_sec,
+            )
+            || moved(
+                d.net_bytes_per_sec,
+                old.network.tx_bytes_per_sec,
+                new.network.tx_bytes_per_sec,
+            )
+    }
+}
+
+fn mem_used_pct(snapshot: &MetricsSnapshot) -> f32 {
+    if snapshot.memory.total_bytes == 0 {
+        return 0.0;
+    }
+    snapshot.memory.used_bytes as f32 / snapshot.memory.total_bytes as f32 * 100.0
+}
+
+/// Measures the monotonic time between samples that rates are divided by.
+pub struct SampleClock {
+    interval: Duration,
+    last: Option<Instant>,
+}
+
+impl SampleClock {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last: None,
+        }
+    }
+
+    /// Seconds since the previous call (the configured interval on the first).
+    pub fn elapsed_secs(&self, now: Instant) -> f32 {
+        match self.last {
+            Some(last) => now.saturating_duration_since(last).as_secs_f32(),
+            None => self.interval.as_secs_f32().max(0.001),
+        }
+    }
+
+    /// Marks `now` as the time of the last recorded sample.
+    pub fn record(&mut self, now: Instant) {
+        self.last = Some(now);
+    }
+}
+
+/// Zeroes the rates of `snapshot` when more than `factor` sampling intervals
+/// passed since the previous sample, as after a suspend/resume: counters
+/// moved across the sleep, so its rates would be one bogus spike. The gap is
+/// the longer of the monotonic `dt` and the wall-clock `wall_dt` (seconds),
+/// since the monotonic clock stands still during suspend on Linux and macOS.
+/// The source has already taken this sample's counters as its new baseline,
+/// so the next sample's rates are sound again. Returns whether the snapshot
+/// was purged.
+pub fn purge_resumed(
+    snapshot: &mut MetricsSnapshot,
+    dt: f32,
+    wall_dt: f32,
+    interval: Duration,
+    factor: f32,
+) -> bool {
+    let resumed = dt.max(wall_dt) > interval.as_secs_f32() * factor;
+    if resumed {
+        snapshot.zero_rates();
+    }
+    resumed
+}
+
+/// Produces one snapshot per tick; the aggregator owns timing and publishing.
+pub trait MetricsSource: Send {
+    /// `dt` is the monotonic time in seconds since the last successful sample.
+    fn sample(&mut self, timestamp_ms: u128, dt: f32) -> MetricsSnapshot;
+}
+
+/// Consecutive panicking ticks after which collection pauses.
+const BREAKER_TRIP_AFTER: u64 = 5;
+/// Ticks skipped while the breaker is open before collection is retried.
+const BREAKER_COOLDOWN_TICKS: u64 = 10;
+
+/// Counters describing how reliably the collector is producing samples.
+#[derive(Debug, Default)]
+pub struct CollectorHealth {
+    panics: AtomicU64,
+    consecutive_panics: AtomicU64,
+    /// Sampling interval in use, in ms; 0 until the aggregator starts.
+    effective_interval_ms: AtomicU64,
+    last_collection_us: AtomicU64,
+}
+
+impl CollectorHealth {
+    /// Ticks whose collection panicked since startup.
+    pub fn panics(&self) -> u64 {
+        self.panics.load(Ordering::Relaxed)
+    }
+
+    /// True while the most recent collection attempt panicked.
+    pub fn is_failing(&self) -> bool {
+        self.consecutive_panics.load(Ordering::Relaxed) > 0
+    }
+
+    /// Interval the aggregator is sampling at, which auto-tuning may have
+    /// lengthened; None before it starts.
+    pub fn effective_interval(&self) -> Option<Duration> {
+        match self.effective_interval_ms.load(Ordering::Relaxed) {
+            0 => None,
+            ms => Some(Duration::from_millis(ms)),
+        }
+    }
+
+    /// How long the most recent successful collection took.
+    pub fn last_collection_duration(&self) -> Duration {
+        Duration::from_micros(self.last_collection_us.load(Ordering::Relaxed))
+    }
+
+    fn set_effective_interval(&self, interval: Duration) {
+        let ms = u64::try_from(interval.as_millis()).unwrap_or(u64::MAX);
+        self.effective_interval_ms
+            .store(ms.max(1), Ordering::Relaxed);
+    }
+
+    fn record_panic(&self) -> u64 {
+        self.panics.fetch_add(1, Ordering::Relaxed);
+        self.consecutive_panics.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    fn record_success(&self, took: Duration) {
+        self.consecutive_panics.store(0, Ordering::Relaxed);
+        let us = u64::try_from(took.as_micros()).unwrap_or(u64::MAX);
+        self.last_collection_us.store(us, Ordering::Relaxed);
     }
 }
 
 pub struct Aggregator {
     config: AggregatorConfig,
+    health: Arc<CollectorHealth>,
 }
 
 impl Aggregator {
     pub fn new(config: AggregatorConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            health: Arc::new(CollectorHealth::default()),
+        }
     }
 
-    pub async fn run(self, cancel: CancellationToken) {
-        let mut networks = Networks::new_with_refreshed_list();
-        let mut disks = Disks::new_with_refreshed_list();
-        let mut sys = System::new_with_specifics(
-            RefreshKind::everything()
-                .with_cpu(CpuRefreshKind::everything())
-                .with_memory(MemoryRefreshKind::everything()),
-        );
+    pub fn health(&self) -> Arc<CollectorHealth> {
+        self.health.clone()
+    }
 
-        sys.refresh_all();
-        networks.refresh(true);
-        disks.refresh(true);
+    pub async fn run(self, cancel: CancellationToken) {
+        let source = SystemSource::new()
+            .with_net_rate_max(self.config.net_rate_max)
+            .with_cpu_total_method(self.config.cpu_total_method)
+            .with_disk_usage_basis(self.config.disk_usage_basis)
+            .with_watch_process(self.config.watch_process.clone())
+            .with_net_top_processes(self.config.net_top_processes)
+            .with_core_topology(self.config.core_topology.clone());
+        self.run_with_source(source, cancel).await;
+    }
 
-        let mut last_time = Instant::now();
-        let mut last_rx_total: u64 = sum_network_rx(&networks);
-        let mut last_tx_total: u64 = sum_network_tx(&networks);
+    /// Runs the sampling loop over `source`. A panic inside `source` is caught,
+    /// logged and counted; after repeated panics sampling pauses for a while
+    /// instead of spinning on a broken source.
+    ///
+    /// Each sample runs on the blocking pool (disk refreshes and GPU tool
+    /// invocations can take tens of milliseconds) with at most one in flight.
+    /// Cancellation does not wait for a slow sample to finish.
+    pub async fn run_with_source(
+        mut self,
+        mut source: impl MetricsSource + 'static,
+        cancel: CancellationToken,
+    ) {
+        let mut interval = self.config.interval;
+        let mut interval_updates = self.config.interval_updates.take();
+        let mut clock = SampleClock::new(interval);
+        let mut auto_tune = self.config.auto_tune.take();
+        self.health.set_effective_interval(interval);
 
-        info!(
-            "Aggregator started with interval {:?}",
-            self.config.interval
-        );
+        info!("Aggregator started with interval {:?}", interval);
 
-        let mut ticker = tokio::time::interval(self.config.interval);
-        ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
-        let mut is_first = true;
+        let align_ticks = self.config.align_ticks;
+        let wall_clock = self.config.wall_clock;
+        let mut ticker = if align_ticks {
+            ticker_after(delay_to_boundary(wall_clock(), interval), interval)
+        } else {
+            ticker_after(Duration::ZERO, interval)
+        };
+        let mut last_timestamp_ms: u128 = 0;
+        let mut last_wall_ms: Option<u128> = None;
+        let mut cooldown_ticks: u64 = 0;
+        let mut warmup_left = self.config.warmup_samples;
+        let mut publish_gate = self.config.publish_gate.take();
 
         loop {
             tokio::select! {
                 _ = cancel.cancelled() => {
                     break;
                 }
+                new_interval = next_interval(&mut interval_updates) => {
+                    if new_interval != interval {
+                        info!("Sampling interval changed from {:?} to {:?}", interval, new_interval);
+                        interval = new_interval;
+                        ticker = restarted_ticker(interval, align_ticks, wall_clock);
+                        self.health.set_effective_interval(interval);
+                        if let Some(tune) = auto_tune.as_mut() {
+                            tune.reset();
+                        }
+                    }
+                    continue;
+                }
                 _ = ticker.tick() => {}
             }
 
+            if cooldown_ticks > 0 {
+                cooldown_ticks -= 1;
+                continue;
+            }
+
             let now = Instant::now();
-            let elapsed = now.saturating_duration_since(last_time);
-            let dt = if is_first {
-                self.config.interval.as_secs_f32().max(0.001)
-            } else {
-                elapsed.as_secs_f32()
-            };
+            let dt = clock.elapsed_secs(now);
             if dt <= 0.0 {
                 warn!("Non-positive elapsed time detected, skipping sample");
                 continue;
             }
 
-            sys.refresh_all();
-            networks.refresh(false);
-            disks.refresh(false);
+            let wall_ms = (self.config.wall_clock)();
+            let timestamp_ms = if self.config.align_timestamps {
+                align_timestamp_ms(wall_ms, interval.as_millis())
+            } else {
+                wall_ms
+            };
+            if self.config.align_timestamps && timestamp_ms <= last_timestamp_ms {
+                warn!(
+                    "Aligned timestamp {} collides with previous sample, skipping",
+                    timestamp_ms
+                );
+                continue;
+            }
 
-            let battery_metrics = get_battery_metrics();
-            let gpu_metrics = get_gpu_metrics();
+            let started = Instant::now();
+            let collection = tokio::task::spawn_blocking(move || {
+                let result =
+                    panic::catch_unwind(AssertUnwindSafe(|| source.sample(timestamp_ms, dt)));
+                (source, result)
+            });
+            let result = tokio::select! {
+                _ = cancel.cancelled() => {
+                    debug!("Cancelled during collection, not waiting for it to finish");
+                    break;
+                }
+                joined = collection => match joined {
+                    Ok((returned, result)) => {
+                        source = returned;
+                        result
+                    }
+                    Err(e) => {
+                        error!("Metrics collection task failed, stopping aggregator: {}", e);
+                        break;
+                    }
+                },
+            };
 
-            if let Some(battery) = &battery_metrics {
-                debug!(
-                    "Battery: {}% ({}), Power: {}W, Time to empty: {:?}",
-                    battery.percentage, battery.state, battery.power_now, battery.time_to_empty
+            let mut snapshot = match result {
+                Ok(snapshot) => snapshot,
+                Err(_) => {
+                    let consecutive = self.health.record_panic();
+                    error!(
+                        "Metrics collection panicked ({} in a row, {} total)",
+                        consecutive,
+                        self.health.panics()
+                    );
+                    if consecutive.is_multiple_of(BREAKER_TRIP_AFTER) {
+                        warn!(
+                            "Pausing collection for {} ticks after repeated panics",
+                            BREAKER_COOLDOWN_TICKS
+                        );
+                        cooldown_ticks = BREAKER_COOLDOWN_TICKS;
+                    }
+                    continue;
+                }
+            };
+            let took = started.elapsed();
+            self.health.record_success(took);
+            if let Some(longer) = auto_tune.as_mut().and_then(|t| t.observe(took, interval)) {
+                warn!(
+                    "Collection took {:?} at interval {:?}; sampling every {:?} instead",
+                    took, interval, longer
                 );
+                interval = longer;
+                ticker = restarted_ticker(interval, align_ticks, wall_clock);
+                self.health.set_effective_interval(interval);
             }
-            if let Some(gpu) = &gpu_metrics {
-                debug!(
-                    "GPU: {} util={}%, mem={}/{} unified={}",
-                    gpu.name,
-                    gpu.gpu_utilization_pct,
-                    gpu.vram_used_bytes,
-                    gpu.vram_total_bytes,
-                    gpu.is_unified_memory
-                );
+            if let Some(factor) = self.config.resume_gap_factor {
+                let wall_dt =
+                    last_wall_ms.map_or(0.0, |last| wall_ms.saturating_sub(last) as f32 / 1000.0);
+                if purge_resumed(&mut snapshot, dt, wall_dt, interval, factor) {
+                    warn!(
+                        "{:.1}s since the previous sample, assuming a suspend/resume; rates zeroed",
+                        dt.max(wall_dt)
+                    );
+                }
+            }
+            snapshot.pressure_score = Some(snapshot.pressure_score(&self.config.pressure_weights));
+            snapshot.cpu.busy_cores = (!snapshot.cpu.per_core_usage_pct.is_empty())
+                .then(|| busy_cores(&snapshot.cpu.per_core_usage_pct));
+            if let Some(label) = &self.config.source_label {
+                snapshot.source = Some(label.clone());
             }
+            snapshot.tags.extend(
+                self.config
+                    .tags
+                    .iter()
+                    .map(|(key, value)| (key.clone(), value.clone())),
+            );
 
-            let per_core: Vec<f32> = sys.cpus().iter().map(|c| c.cpu_usage()).collect();
-            let total_pct = if per_core.is_empty() {
-                0.0
-            } else {
-                per_core.iter().sum::<f32>() / per_core.len() as f32
-            };
+            clock.record(now);
+            last_timestamp_ms = timestamp_ms;
+            last_wall_ms = Some(wall_ms);
 
-            let la = System::load_average();
+            if warmup_left > 0 {
+                warmup_left -= 1;
+                debug!("Discarding warm-up sample at {}", timestamp_ms);
+                continue;
+            }
+            if let Some(gate) = publish_gate.as_mut() {
+                if !gate.admit(&snapshot) {
+                    debug!("No significant change at {}, not publishing", timestamp_ms);
+                    continue;
+                }
+            }
+            publish_snapshot(self.config.transforms.apply(snapshot));
+        }
+    }
+}
 
-            let total_mem_bytes = sys.total_memory();
-            let used_mem_bytes = sys.used_memory();
-            let avail_mem_bytes = sys.available_memory();
-            let swap_total_bytes = sys.total_swap();
-            let swap_used_bytes = sys.used_swap();
+/// A skipping ticker whose first tick is one `interval` from now, or with
+/// `align` on the next boundary of `wall_clock`.
+fn restarted_ticker(
+    interval: Duration,
+    align: bool,
+    wall_clock: fn() -> u128,
+) -> tokio::time::Interval {
+    let delay = if align {
+        delay_to_boundary(wall_clock(), interval)
+    } else {
+        interval
+    };
+    ticker_after(delay, interval)
+}
 
-            let rx_total = sum_network_rx(&networks);
-            let tx_total = sum_network_tx(&networks);
-            let rx_rate = if is_first {
-                0.0
-            } else if rx_total >= last_rx_total {
-                (rx_total - last_rx_total) as f32 / dt
-            } else {
-                warn!("Network RX counter decreased; possible interface reset");
-                0.0
-            };
-            let tx_rate = if is_first {
-                0.0
-            } else if tx_total >= last_tx_total {
-                (tx_total - last_tx_total) as f32 / dt
-            } else {
-                warn!("Network TX counter decreased; possible interface reset");
-                0.0
-            };
+fn ticker_after(delay: Duration, interval: Duration) -> tokio::time::Interval {
+    let mut ticker = tokio::time::interval_at(tokio::time::Instant::now() + delay, interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    ticker
+}
 
-            let disk_total = sum_disk_total(&disks);
-            let disk_avail = sum_disk_avail(&disks);
-            let disk_used_pct = if disk_total == 0 {
-                0.0
-            } else {
-                (disk_total.saturating_sub(disk_avail)) as f32 / disk_total as f32 * 100.0
-            };
+/// Time from `wall_ms` (ms since the Unix epoch) to the next multiple of
+/// `interval`; zero when already on one or for sub-millisecond intervals.
+pub fn delay_to_boundary(wall_ms: u128, interval: Duration) -> Duration {
+    let interval_ms = interval.as_millis();
+    if interval_ms == 0 {
+        return Duration::ZERO;
+    }
+    let rem = wall_ms % interval_ms;
+    if rem == 0 {
+        Duration::ZERO
+    } else {
+        Duration::from_millis((interval_ms - rem) as u64)
+    }
+}
 
-            let snapshot = MetricsSnapshot {
-                timestamp_ms: now_timestamp_ms(),
-                cpu: CpuMetrics {
-                    total_usage_pct: total_pct,
-                    per_core_usage_pct: per_core,
-                    load_avg_1: la.one as f32,
-                    load_avg_5: la.five as f32,
-                    load_avg_15: la.fifteen as f32,
-                    temperature_celsius: None,
-                },
-                memory: MemoryMetrics {
-                    total_bytes: total_mem_bytes,
-                    used_bytes: used_mem_bytes,
-                    available_bytes: avail_mem_bytes,
-                    swap_total_bytes,
-                    swap_used_bytes,
-                },
-                network: NetworkMetrics {
-                    rx_bytes_total: rx_total,
-                    tx_bytes_total: tx_total,
-                    rx_bytes_per_sec: rx_rate,
-                    tx_bytes_per_sec: tx_rate,
-                },
-                disk: DiskMetrics {
-                    total_bytes: disk_total,
-                    available_bytes: disk_avail,
-                    used_pct: disk_used_pct,
-                },
-                battery: battery_metrics,
-                gpu: gpu_metrics,
-            };
+/// Runs an extra aggregator over `source` whose snapshots carry `label`,
+/// publishing to the same bus (and so the same buffer and streams) as the
+/// host's own collector. Meant for developing multi-source views on one
+/// machine; the snapshots are kept out of the database, alerts and
+/// [`MetricsBuffer::latest`](crate::storage::MetricsBuffer::latest). The bus
+/// is per thread, so call this on the thread whose subscribers should
+/// receive the snapshots.
+pub fn spawn_labeled_aggregator(
+    label: impl Into<String>,
+    config: AggregatorConfig,
+    source: impl MetricsSource + 'static,
+    cancel: CancellationToken,
+) -> JoinHandle<()> {
+    let aggregator = Aggregator::new(config.with_source_label(Some(label.into())));
+    tokio::spawn(aggregator.run_with_source(source, cancel))
+}
 
-            publish_snapshot(snapshot);
+/// Deterministic stand-in for a host: CPU, memory and network follow slow
+/// waves offset by `phase`, so several instances draw distinct lines.
+pub struct SyntheticSource {
+    phase: f32,
+    cores: usize,
+    rx_total: u64,
+    tx_total: u64,
+}
+
+impl SyntheticSource {
+    pub fn new(phase: f32, cores: usize) -> Self {
+        Self {
+            phase,
+            cores,
+            rx_total: 0,
+            tx_total: 0,
+        }
+    }
+}
 
-            last_time = now;
-            last_rx_total = rx_total;
-            last_tx_total = tx_total;
-            is_first = false;
+impl MetricsSource for SyntheticSource {
+    fn sample(&mut self, timestamp_ms: u128, dt: f32) -> MetricsSnapshot {
+        const TOTAL_MEM: u64 = 16 * 1024 * 1024 * 1024;
+        let t = (timestamp_ms % 600_000) as f32 / 1000.0;
+        let wave = |period: f32, offset: f32| {
+            0.5 + 0.5 * (std::f32::consts::TAU * (t / period) + self.phase + offset).sin()
+        };
+        let per_core: Vec<f32> = (0..self.cores)
+            .map(|c| 100.0 * wave(30.0, c as f32 * 0.7))
+            .collect();
+        let total = per_core.iter().sum::<f32>() / per_core.len().max(1) as f32;
+        let rx = 1_000_000.0 * wave(20.0, 0.0);
+        let tx = 250_000.0 * wave(45.0, 1.0);
+        self.rx_total += (rx * dt) as u64;
+        self.tx_total += (tx * dt) as u64;
+        let used = (TOTAL_MEM as f32 * (0.3 + 0.4 * wave(120.0, 2.0))) as u64;
+        MetricsSnapshot {
+            timestamp_ms,
+            sample_interval_ms: dt * 1000.0,
+            cpu: CpuMetrics {
+                total_usage_pct: total,
+                per_core_usage_pct: per_core,
+                load_avg_1: Some(total / 25.0),
+                load_avg_5: Some(total / 30.0),
+                load_avg_15: Some(total / 35.0),
+                temperature_celsius: None,
+                breakdown: None,
+                per_socket_usage_pct: None,
+                per_core_changes: None,
+                busy_cores: None,
+            },
+            memory: MemoryMetrics {
+                total_bytes: TOTAL_MEM,
+                used_bytes: used,
+                available_bytes: TOTAL_MEM - used,
+                swap_total_bytes: 0,
+                swap_used_bytes: 0,
+                swap_in_bytes_per_sec: None,
+                swap_out_bytes_per_sec: None,
+            },
+            network: NetworkMetrics {
+                rx_bytes_total: self.rx_total,
+                tx_bytes_total: self.tx_total,
+                rx_bytes_per_sec: rx,
+                tx_bytes_per_sec: tx,
+            },
+            disk: DiskMetrics {
+                total_bytes: 512 * 1024 * 1024 * 1024,
+                available_bytes: 256 * 1024 * 1024 * 1024,
+                used_pct: 50.0,
+                used_bytes: None,
+            },
+            battery: None,
+            gpu: None,
+            scheduler: None,
+            watched_process: None,
+            net_top_processes: None,
+            pressure_score: None,
+            source: None,
+            resumed: false,
+            tags: Default::default(),
         }
     }
 }
 
+/// Resolves with the next interval sent on `updates`; never resolves when
+/// there is no update channel or its sender is gone.
+async fn next_interval(updates: &mut Option<watch::Receiver<Duration>>) -> Duration {
+    if let Some(rx) = updates {
+        if rx.changed().await.is_ok() {
+            return *rx.borrow_and_update();
+        }
+    }
+    *updates = None;
+    std::future::pending().await
+}
+
+/// Collects from the local machine via `sysinfo` and `/proc`.
+pub struct SystemSource {
+    sys: System,
+    networks: Networks,
+    disks: Disks,
+    last_rx_total: u64,
+    last_tx_total: u64,
+    last_rx_rate: f32,
+    last_tx_rate: f32,
+    net_rate_max: Option<f32>,
+    cpu_total_method: CpuTotalMethod,
+    disk_usage_basis: DiskUsageBasis,
+    watch_process: Option<ProcessSelector>,
+    talkers: Option<TopTalkers>,
+    core_topology: Option<CoreTopology>,
+    last_proc_stat: procfs::ProcStat,
+    last_vmstat: Option<procfs::VmStat>,
+    is_first: bool,
+}
+
+impl SystemSource {
+    pub fn new() -> Self {
+        let mut networks = Networks::new_with_refreshed_list();
+        let mut disks = Disks::new_with_refreshed_list();
+        let mut sys = System::new_with_specifics(
+            RefreshKind::everything()
+                .with_cpu(CpuRefreshKind::everything())
+                .with_memory(MemoryRefreshKind::everything()),
+        );
+
+        sys.refresh_all();
+        networks.refresh(true);
+        disks.refresh(true);
+
+        Self {
+            last_rx_total: sum_network_rx(&networks),
+            last_tx_total: sum_network_tx(&networks),
+            last_rx_rate: 0.0,
+            last_tx_rate: 0.0,
+            net_rate_max: None,
+            cpu_total_method: CpuTotalMethod::default(),
+            disk_usage_basis: DiskUsageBasis::default(),
+            watch_process: None,
+            talkers: None,
+            core_topology: None,
+            last_proc_stat: procfs::read_proc_stat(),
+            last_vmstat: procfs::read_vmstat(),
+            sys,
+            networks,
+            disks,
+            is_first: true,
+        }
+    }
+}
+
+impl SystemSource {
+    pub fn with_net_rate_max(mut self, max: Option<f32>) -> Self {
+        self.net_rate_max = max;
+        self
+    }
+
+    pub fn with_cpu_total_method(mut self, method: CpuTotalMethod) -> Self {
+        self.cpu_total_method = method;
+        self
+    }
+
+    pub fn with_disk_usage_basis(mut self, basis: DiskUsageBasis) -> Self {
+        self.disk_usage_basis = basis;
+        self
+    }
+
+    pub fn with_watch_process(mut self, target: Option<ProcessSelector>) -> Self {
+        self.watch_process = target;
+        self
+    }
+
+    /// Lists the `top_n` processes moving the most TCP traffic, where the
+    /// platform exposes per-socket counters.
+    pub fn with_net_top_processes(mut self, top_n: Option<usize>) -> Self {
+        self.talkers = top_n.and_then(TopTalkers::system);
+        if top_n.is_some() && self.talkers.is_none() {
+            warn!("--net-top-processes is only supported on Linux");
+        }
+        self
+    }
+
+    pub fn with_core_topology(mut self, topology: Option<CoreTopology>) -> Self {
+        self.core_topology = topology;
+        self
+    }
+
+    fn net_top_processes(&mut self, dt: f32) -> Option<Vec<ProcessNetUsage>> {
+        let sys = &self.sys;
+        self.talkers.as_mut()?.sample(dt, |pid| {
+            sys.process(Pid::from_u32(pid))
+                .map(|p| p.name().to_string_lossy().into_owned())
+        })
+    }
+
+    fn watched_process(&self) -> Option<WatchedProcess> {
+        let target = self.watch_process.as_ref()?;
+        let processes: Vec<ProcessEntry> = self
+            .sys
+            .processes()
+            .values()
+            .map(|p| ProcessEntry {
+                pid: p.pid().as_u32(),
+                parent_pid: p.parent().map(|pid| pid.as_u32()),
+                name: p.name().to_string_lossy().into_owned(),
+                cpu_usage_pct: p.cpu_usage(),
+                rss_bytes: p.memory(),
+            })
+            .collect();
+        let watched = WatchedProcess::from_processes(target, &processes);
+        if watched.process_count == 0 {
+            debug!("Watched process {} is not running", target);
+        }
+        Some(watched)
+    }
+}
+
+impl Default for SystemSource {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MetricsSource for SystemSource {
+    fn sample(&mut self, timestamp_ms: u128, dt: f32) -> MetricsSnapshot {
+        let is_first = self.is_first;
+        self.sys.refresh_all();
+        self.networks.refresh(false);
+        self.disks.refresh(false);
+
+        let battery_metrics = get_battery_metrics();
+        let gpu_metrics = get_gpu_metrics();
+
+        if let Some(battery) = &battery_metrics {
+            debug!(
+                "Battery: {}% ({}), Power: {}W, Time to empty: {:?}",
+                battery.percentage, battery.state, battery.power_now, battery.time_to_empty
+            );
+        }
+        if let Some(gpu) = &gpu_metrics {
+            debug!(
+                "GPU: {} util={}%, mem={}/{} unified={}",
+                gpu.name,
+                gpu.gpu_utilization_pct,
+                gpu.vram_used_bytes,
+                gpu.vram_total_bytes,
+                gpu.is_unified_memory
+            );
+        }
+
+        let per_core: Vec<f32> = self.sys.cpus().iter().map(|c| c.cpu_usage()).collect();
+        let total_pct = self.cpu_total_method.aggregate(&per_core);
+
+        let proc_stat = procfs::read_proc_stat();
+        let breakdown = match (&proc_stat.cpu_times, &self.last_proc_stat.cpu_times) {
+            (Some(now), Some(prev)) => now.breakdown_since(prev),
+            _ => None,
+        };
+
+        // sysinfo reports zeros where there is no load average; publish None.
+        let la = HAS_LOAD_AVERAGE.then(System::load_average);
+
+        let total_mem_bytes = self.sys.total_memory();
+        let used_mem_bytes = self.sys.used_memory();
+        let avail_mem_bytes = self.sys.available_memory();
+        let swap_total_bytes = self.sys.total_swap();
+        let swap_used_bytes = self.sys.used_swap();
+
+        let vmstat = procfs::read_vmstat();
+        let swap_rates = match (&vmstat, &self.last_vmstat) {
+            (Some(now), Some(prev)) if !is_first => now.swap_rates_since(prev, dt),
+            _ => None,
+        };
+
+        let scheduler = match (&proc_stat.sched, &self.last_proc_stat.sched) {
+            (Some(now), Some(prev)) if !is_first => now.rates_since(prev, dt),
+            _ => None,
+        };
+
+        let rx_total = sum_network_rx(&self.networks);
+        let tx_total = sum_network_tx(&self.networks);
+        let (rx_rate, tx_rate) = if is_first {
+            (0.0, 0.0)
+        } else {
+            (
+                counter_rate(
+                    "RX",
+                    rx_total,
+                    self.last_rx_total,
+                    dt,
+                    self.last_rx_rate,
+                    self.net_rate_max,
+                ),
+                counter_rate(
+                    "TX",
+                    tx_total,
+                    self.last_tx_total,
+                    dt,
+                    self.last_tx_rate,
+                    self.net_rate_max,
+                ),
+            )
+        };
+
+        let mut disk = DiskMetrics {
+            total_bytes: sum_disk_total(&self.disks),
+            available_bytes: sum_disk_avail(&self.disks),
+            used_pct: 0.0,
+            used_bytes: sum_disk_used(&self.disks),
+        };
+        disk.used_pct = disk.used_pct_by(self.disk_usage_basis);
+
+        let per_socket_usage_pct = self
+            .core_topology
+            .as_ref()
+            .map(|topology| topology.per_socket_usage(&per_core));
+        let snapshot = MetricsSnapshot {
+            timestamp_ms,
+            sample_interval_ms: dt * 1000.0,
+            cpu: CpuMetrics {
+                total_usage_pct: total_pct,
+                per_core_usage_pct: per_core,
+                load_avg_1: la.as_ref().map(|la| la.one as f32),
+                load_avg_5: la.as_ref().map(|la| la.five as f32),
+                load_avg_15: la.as_ref().map(|la| la.fifteen as f32),
+                temperature_celsius: None,
+                breakdown,
+                per_socket_usage_pct,
+                per_core_changes: None,
+                busy_cores: None,
+            },
+            memory: MemoryMetrics {
+                total_bytes: total_mem_bytes,
+                used_bytes: used_mem_bytes,
+                available_bytes: avail_mem_bytes,
+                swap_total_bytes,
+                swap_used_bytes,
+                swap_in_bytes_per_sec: swap_rates.map(|(rate_in, _)| rate_in),
+                swap_out_bytes_per_sec: swap_rates.map(|(_, rate_out)| rate_out),
+            },
+            network: NetworkMetrics {
+                rx_bytes_total: rx_total,
+                tx_bytes_total: tx_total,
+                rx_bytes_per_sec: rx_rate,
+                tx_bytes_per_sec: tx_rate,
+            },
+            disk,
+            battery: battery_metrics,
+            gpu: gpu_metrics,
+            scheduler,
+            watched_process: self.watched_process(),
+            net_top_processes: self.net_top_processes(dt),
+            pressure_score: None,
+            source: None,
+            resumed: false,
+            tags: Default::default(),
+        };
+
+        self.last_rx_total = rx_total;
+        self.last_tx_total = tx_total;
+        self.last_rx_rate = rx_rate;
+        self.last_tx_rate = tx_rate;
+        self.last_proc_stat = proc_stat;
+        self.last_vmstat = vmstat;
+        self.is_first = false;
+
+        snapshot
+    }
+}
+
+/// Per-second rate of a cumulative network counter. A counter that went
+/// backwards reports 0; a rate above `max_rate` (e.g. a huge delta over a tiny
+/// `dt` after resume) is treated as a glitch and repeats `prev_rate`.
+pub fn counter_rate(
+    label: &str,
+    total: u64,
+    last_total: u64,
+    dt: f32,
+    prev_rate: f32,
+    max_rate: Option<f32>,
+) -> f32 {
+    let Some(delta) = total.checked_sub(last_total) else {
+        warn!(
+            "Network {} counter decreased; possible interface reset",
+            label
+        );
+        return 0.0;
+    };
+    let rate = delta as f32 / dt;
+    match max_rate {
+        Some(max) if rate > max => {
+            warn!(
+                "Network {} rate {:.0} B/s over {:.3}s exceeds cap {:.0} B/s; keeping previous rate",
+                label, rate, dt, max
+            );
+            prev_rate
+        }
+        _ => rate,
+    }
+}
+
 fn get_battery_metrics() -> Option<BatteryMetrics> {
     let manager = match Manager::new() {
         Ok(m) => m,
@@ -258,6 +1151,30 @@ fn sum_disk_avail(disks: &Disks) -> u64 {
         .fold(0, |acc, disk| acc + disk.available_space())
 }
 
+/// Allocated bytes over all disks, from each mount's free block count;
+/// None when any mount cannot be queried.
+#[cfg(unix)]
+fn sum_disk_used(disks: &Disks) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+
+    disks.iter().try_fold(0u64, |acc, disk| {
+        let path = std::ffi::CString::new(disk.mount_point().as_os_str().as_bytes()).ok()?;
+        // SAFETY: statvfs is plain old data, filled in by the call below.
+        let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+        // SAFETY: `path` is NUL-terminated and `stat` outlives the call.
+        if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+            return None;
+        }
+        let blocks = (stat.f_blocks as u64).saturating_sub(stat.f_bfree as u64);
+        Some(acc + blocks * stat.f_frsize as u64)
+    })
+}
+
+#[cfg(not(unix))]
+fn sum_disk_used(_disks: &Disks) -> Option<u64> {
+    None
+}
+
 fn get_gpu_metrics() -> Option<GpuMetrics> {
     try_nvidia_smi().or_else(try_macos_ioreg)
 }
@@ -0,0 +1,249 @@
+//! Threshold alerts: one fires when a dashboard metric reaches its critical
+//! threshold and clears when the metric drops back below its clear level
+//! (the critical one unless set). With a minimum duration, the metric must
+//! stay past the level that long before the alert changes state, so a
+//! series hovering around the line does not flap. Transitions are
+//! kept in a bounded history so operators can audit what fired and when.
+//! The same thresholds drive [`healthy_fraction`], the share of samples in
+//! which nothing was even at its warning level.
+
+use crate::config::{SharedThresholds, Threshold, Thresholds};
+use crate::db::MetricsDb;
+use crate::metrics::{scalar_metric, MetricsSnapshot};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, MutexGuard};
+use thiserror::Error;
+use tracing::error;
+
+/// Alert records kept in memory (and reloaded from the database on start).
+pub const DEFAULT_ALERT_HISTORY: usize = 256;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AlertRecord {
+    pub id: u64,
+    /// Series name, as used by `/api/config` thresholds.
+    pub metric: String,
+    pub threshold: f32,
+    pub fired_at_ms: u128,
+    /// None while the alert is still firing.
+    pub cleared_at_ms: Option<u128>,
+    /// Highest value observed while firing.
+    pub peak: f32,
+    pub acknowledged: bool,
+}
+
+impl AlertRecord {
+    pub fn is_firing(&self) -> bool {
+        self.cleared_at_ms.is_none()
+    }
+}
+
+/// Thresholded series: name as in `/api/config`, scalar metric, levels.
+fn threshold_checks(thresholds: &Thresholds) -> [(&'static str, &'static str, Threshold); 2] {
+    [
+        ("cpu_total", "cpu", thresholds.cpu),
+        ("memory", "memory", thresholds.memory),
+    ]
+}
+
+/// Share of `snapshots` in which every thresholded metric was below its warn
+/// level (its crit level when no warn is set); None for an empty window.
+pub fn healthy_fraction(snapshots: &[MetricsSnapshot], thresholds: &Thresholds) -> Option<f64> {
+    if snapshots.is_empty() {
+        return None;
+    }
+    let limits: Vec<_> = threshold_checks(thresholds)
+        .into_iter()
+        .filter_map(|(_, scalar, t)| Some((scalar_metric(scalar)?, t.warn.or(t.crit)?)))
+        .collect();
+    let healthy = snapshots
+        .iter()
+        .filter(|snap| {
+            limits.iter().all(|(metric, limit)| {
+                (metric.extract)(snap)
+                    .filter(|v| v.is_finite())
+                    .is_none_or(|v| v < *limit)
+            })
+        })
+        .count();
+    Some(healthy as f64 / snapshots.len() as f64)
+}
+
+#[derive(Debug, Error)]
+pub enum AckError {
+    #[error("alert {0} not found")]
+    NotFound(u64),
+    #[error("alert {0} has already cleared")]
+    Cleared(u64),
+}
+
+/// A metric past its fire (or clear) level, waiting out the minimum duration.
+struct Pending {
+    since_ms: u128,
+    peak: f32,
+}
+
+struct AlertLog {
+    next_id: u64,
+    /// Oldest first.
+    records: VecDeque<AlertRecord>,
+    /// By series name.
+    pending: HashMap<&'static str, Pending>,
+}
+
+pub struct AlertTracker {
+    thresholds: SharedThresholds,
+    capacity: usize,
+    db: Option<Arc<MetricsDb>>,
+    log: Mutex<AlertLog>,
+}
+
+impl Default for AlertTracker {
+    fn default() -> Self {
+        Self::new(Thresholds::default(), DEFAULT_ALERT_HISTORY)
+    }
+}
+
+impl AlertTracker {
+    pub fn new(thresholds: impl Into<SharedThresholds>, capacity: usize) -> Self {
+        Self {
+            thresholds: thresholds.into(),
+            capacity: capacity.max(1),
+            db: None,
+            log: Mutex::new(AlertLog {
+                next_id: 1,
+                records: VecDeque::new(),
+                pending: HashMap::new(),
+            }),
+        }
+    }
+
+    /// Loads the most recent alerts from `db` and writes every transition back to it.
+    pub fn with_persistence(mut self, db: Arc<MetricsDb>) -> Self {
+        match db.load_alerts(self.capacity) {
+            Ok(records) => {
+                let log = self.log.get_mut().unwrap_or_else(|p| p.into_inner());
+                log.next_id = records.iter().map(|r| r.id + 1).max().unwrap_or(1);
+                log.records = records.into();
+            }
+            Err(e) => error!("Failed to load alert history: {}", e),
+        }
+        self.db = Some(db);
+        self
+    }
+
+    /// Fires, updates or clears alerts for the metrics in `snapshot`.
+    pub fn observe(&self, snapshot: &MetricsSnapshot) {
+        let thresholds = self.thresholds.get();
+        let now = snapshot.timestamp_ms;
+        let mut guard = self.lock();
+        let log = &mut *guard;
+        for (name, scalar, threshold) in threshold_checks(&thresholds) {
+            let (Some(crit), Some(clear), Some(metric)) = (
+                threshold.crit,
+                threshold.clear_level(),
+                scalar_metric(scalar),
+            ) else {
+                continue;
+            };
+            let Some(value) = (metric.extract)(snapshot).filter(|v| v.is_finite()) else {
+                continue;
+            };
+            let min_duration = u128::from(threshold.min_duration_ms.unwrap_or(0));
+            let firing = log
+                .records
+                .iter_mut()
+                .rev()
+                .find(|r| r.metric == name && r.is_firing());
+            match firing {
+                Some(record) => {
+                    record.peak = record.peak.max(value);
+                    if value >= clear {
+                        log.pending.remove(name);
+                        continue;
+                    }
+                    let since = log
+                        .pending
+                        .entry(name)
+                        .or_insert(Pending {
+                            since_ms: now,
+                            peak: value,
+                        })
+                        .since_ms;
+                    if now.saturating_sub(since) >= min_duration {
+                        log.pending.remove(name);
+                        record.cleared_at_ms = Some(now);
+                        let record = record.clone();
+                        self.persist(&record);
+                    }
+                }
+                None => {
+                    if value < crit {
+                        log.pending.remove(name);
+                        continue;
+                    }
+                    let pending = log.pending.entry(name).or_insert(Pending {
+                        since_ms: now,
+                        peak: value,
+                    });
+                    pending.peak = pending.peak.max(value);
+                    if now.saturating_sub(pending.since_ms) < min_duration {
+                        continue;
+                    }
+                    let peak = pending.peak;
+                    log.pending.remove(name);
+                    let record = AlertRecord {
+                        id: log.next_id,
+                        metric: name.to_string(),
+                        threshold: crit,
+                        fired_at_ms: now,
+                        cleared_at_ms: None,
+                        peak,
+                        acknowledged: false,
+                    };
+                    log.next_id += 1;
+                    if log.records.len() >= self.capacity {
+                        log.records.pop_front();
+                    }
+                    log.records.push_back(record.clone());
+                    self.persist(&record);
+                }
+            }
+        }
+    }
+
+    /// All retained alerts, newest first.
+    pub fn history(&self) -> Vec<AlertRecord> {
+        self.lock().records.iter().rev().cloned().collect()
+    }
+
+    /// Marks a firing alert as acknowledged; it keeps firing until it clears.
+    pub fn ack(&self, id: u64) -> Result<AlertRecord, AckError> {
+        let mut log = self.lock();
+        let record = log
+            .records
+            .iter_mut()
+            .find(|r| r.id == id)
+            .ok_or(AckError::NotFound(id))?;
+        if !record.is_firing() {
+            return Err(AckError::Cleared(id));
+        }
+        record.acknowledged = true;
+        let record = record.clone();
+        self.persist(&record);
+        Ok(record)
+    }
+
+    fn persist(&self, record: &AlertRecord) {
+        if let Some(db) = &self.db {
+            if let Err(e) = db.save_alert(record) {
+                error!("Failed to persist alert {}: {}", record.id, e);
+            }
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, AlertLog> {
+        self.log.lock().unwrap_or_else(|p| p.into_inner())
+    }
+}
@@ -1,34 +1,259 @@
+use crate::access::{self, RequestStats, RouteStats, LATENCY_BUCKETS_MS};
+use crate::aggregator::CollectorHealth;
+use crate::alerts::{self, AckError, AlertTracker};
+use crate::config::{
+    Aggregations, ByteUnits, HttpLimits, NetScale, NetUnits, ResourceCriticality, SharedThresholds,
+    Thresholds,
+};
 use crate::db::MetricsDb;
-use crate::metrics::{ErrorResponse, RpcMetricsSnapshot};
-use crate::storage::MetricsBuffer;
+use crate::delta::{Delta, DeltaEncoder};
+use crate::grafana;
+use crate::logs::LogRing;
+use crate::metrics::{
+    now_timestamp_ms, scalar_metric, ErrorCode, ErrorResponse, MetricsSnapshot, RpcMetricsSnapshot,
+    SCALAR_METRIC_NAMES,
+};
+use crate::storage::{
+    select_history, CpuHeatmap, Histogram, HistoryOrder, MetricsBuffer, SeriesStats, SnapshotStore,
+};
 use crate::web;
-use axum::extract::State;
-use axum::http::StatusCode;
+use axum::extract::rejection::{PathRejection, QueryRejection};
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{DefaultBodyLimit, FromRequestParts, State};
+use axum::http::request::Parts;
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
 use axum::response::sse::{Event, KeepAlive, Sse};
-use axum::response::IntoResponse;
-use axum::routing::get;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
 use axum::{Json, Router};
-use futures::StreamExt;
+use futures::{Stream, StreamExt};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::convert::Infallible;
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::broadcast;
+use tokio::sync::{broadcast, watch};
+use tokio::time::Instant;
 use tokio_stream::wrappers::BroadcastStream;
 use tokio_util::sync::CancellationToken;
+use tower_http::limit::RequestBodyLimitLayer;
+use tower_http::timeout::TimeoutLayer;
+use tracing::warn;
 
 #[derive(Clone)]
 pub struct AppState {
     pub buffer: Arc<MetricsBuffer>,
     pub db: Arc<MetricsDb>,
+    /// Snapshot store of `--storage sqlite`; when set, reads the buffer
+    /// cannot serve go to it instead of `db`.
+    pub store: Option<Arc<dyn SnapshotStore>>,
     pub stream_tx: broadcast::Sender<RpcMetricsSnapshot>,
     pub shutdown: CancellationToken,
+    pub collector: Arc<CollectorHealth>,
+    pub thresholds: SharedThresholds,
+    pub alerts: Arc<AlertTracker>,
+    pub limits: HttpLimits,
+    pub logs: Arc<LogRing>,
+    /// Bearer token required by protected endpoints (`/api/logs`); they are
+    /// refused outright when unset.
+    pub api_token: Option<String>,
+    pub net_scale: NetScale,
+    pub requests: Arc<RequestStats>,
+    /// History the dashboard loads on open, in ms; 0 loads everything.
+    pub initial_window_ms: u64,
+    /// Namespace prepended to exported metric names (Grafana targets).
+    pub metrics_prefix: Option<String>,
+    pub sampling: Sampling,
+    /// Disk and swap levels that mark `/api/health` degraded.
+    pub criticality: ResourceCriticality,
+}
+
+impl AppState {
+    /// Newest persisted snapshot.
+    fn stored_latest(&self) -> Result<Option<RpcMetricsSnapshot>, rusqlite::Error> {
+        match &self.store {
+            Some(store) => Ok(store.latest().map(|s| s.to_rpc_format())),
+            None => self.db.get_latest(),
+        }
+    }
+
+    /// Persisted snapshots in `from_ts..=to_ts`, newest first.
+    fn stored_range(
+        &self,
+        from_ts: u64,
+        to_ts: u64,
+        limit: Option<usize>,
+    ) -> Result<Vec<RpcMetricsSnapshot>, rusqlite::Error> {
+        let Some(store) = &self.store else {
+            return self.db.get_range(from_ts, to_ts, limit);
+        };
+        let items = store.window(Some(from_ts.into()), Some(to_ts.into()), limit);
+        Ok(items.iter().rev().map(|s| s.to_rpc_format()).collect())
+    }
+
+    /// Persisted snapshots from `since_ts` on, as
+    /// [`MetricsDb::get_history_from`] selects them.
+    fn stored_history(
+        &self,
+        limit: Option<usize>,
+        since_ts: Option<u64>,
+        from_start: bool,
+    ) -> Result<Vec<RpcMetricsSnapshot>, rusqlite::Error> {
+        let Some(store) = &self.store else {
+            return self.db.get_history_from(limit, since_ts, from_start);
+        };
+        let since = since_ts.map(u128::from);
+        let history: Vec<_> = if from_start {
+            let mut items = store.window(since, None, None);
+            items.truncate(limit.unwrap_or(usize::MAX));
+            items.iter().map(|s| s.to_rpc_format()).collect()
+        } else {
+            let items = store.window(since, None, limit);
+            items.iter().rev().map(|s| s.to_rpc_format()).collect()
+        };
+        Ok(history)
+    }
+}
+
+/// Consecutive points further apart than this many sampling intervals are
+/// treated as a gap in the data.
+pub const DEFAULT_GAP_FACTOR: f32 = 3.0;
+/// Points the dashboard keeps in memory before dropping the oldest.
+pub const DEFAULT_MAX_BROWSER_POINTS: usize = 20_000;
+
+/// Sampling settings reported by `/api/config`.
+#[derive(Clone, Debug)]
+pub struct Sampling {
+    /// Current sampling interval, following config reloads; None when unknown.
+    pub interval: Option<watch::Receiver<Duration>>,
+    /// Dashboard charts break the line where points are more than this many
+    /// intervals apart.
+    pub gap_factor: f32,
+    /// How each metric is combined wherever a series is downsampled.
+    pub aggregations: Aggregations,
+    /// Points the dashboard keeps in memory; older ones are dropped.
+    pub max_browser_points: usize,
+}
+
+impl Default for Sampling {
+    fn default() -> Self {
+        Self {
+            interval: None,
+            gap_factor: DEFAULT_GAP_FACTOR,
+            aggregations: Aggregations::default(),
+            max_browser_points: DEFAULT_MAX_BROWSER_POINTS,
+        }
+    }
+}
+
+impl Sampling {
+    /// The interval `collector` samples at once it has started, which
+    /// auto-tuning may have lengthened; the configured one before that.
+    pub fn interval_ms(&self, collector: &CollectorHealth) -> Option<u64> {
+        collector
+            .effective_interval()
+            .or_else(|| self.interval.as_ref().map(|rx| *rx.borrow()))
+            .map(|interval| interval.as_millis().try_into().unwrap_or(u64::MAX))
+    }
+
+    pub fn gap_threshold_ms(&self, collector: &CollectorHealth) -> Option<u64> {
+        self.interval_ms(collector)
+            .map(|ms| (ms as f64 * f64::from(self.gap_factor)).round() as u64)
+    }
 }
 
 #[derive(Deserialize)]
 pub struct HistoryQuery {
     pub limit: Option<usize>,
     pub since_ts: Option<u64>,
+    /// Only snapshots within this many ms of the newest one, buffered or,
+    /// with the buffer empty, stored (combined with `since_ts`, the later
+    /// bound wins).
+    pub window_ms: Option<u64>,
+    /// Cursor from a previous page; switches the response to paginated form.
+    pub after_ms: Option<u64>,
+    pub page_size: Option<usize>,
+    /// Newest first (`desc`) unless set.
+    pub order: Option<HistoryOrder>,
+    /// `?from_start=1` (or `true`) makes `limit` keep the oldest snapshots
+    /// rather than the newest.
+    pub from_start: Option<String>,
+}
+
+impl HistoryQuery {
+    pub fn is_from_start(&self) -> bool {
+        matches!(self.from_start.as_deref(), Some("1" | "true"))
+    }
+}
+
+const MAX_PAGE_SIZE: usize = 1000;
+
+/// `?tag.KEY=VALUE` parameters: only snapshots carrying every listed tag
+/// pass. Empty, and so passing everything, when none are given.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TagFilter(BTreeMap<String, String>);
+
+impl TagFilter {
+    /// The `tag.`-prefixed entries of a query string's parameters.
+    pub fn from_params(params: &HashMap<String, String>) -> Self {
+        Self(
+            params
+                .iter()
+                .filter_map(|(key, value)| Some((key.strip_prefix("tag.")?, value)))
+                .map(|(key, value)| (key.to_string(), value.clone()))
+                .collect(),
+        )
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn matches(&self, tags: &BTreeMap<String, String>) -> bool {
+        self.0
+            .iter()
+            .all(|(key, value)| tags.get(key) == Some(value))
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ColumnsQuery {
+    pub since_ms: Option<u64>,
+    pub until_ms: Option<u64>,
+    /// Keep only the most recent `limit` samples of the window.
+    pub limit: Option<usize>,
+}
+
+/// Presentation options accepted by every snapshot-returning endpoint.
+#[derive(Deserialize, Default)]
+pub struct PresentationQuery {
+    #[serde(default)]
+    pub net_units: NetUnits,
+    /// `?byte_units=si` asks for byte values in KB/MB/GB instead of KiB/MiB/GiB.
+    #[serde(default)]
+    pub byte_units: ByteUnits,
+    /// `?pretty=1` (or `true`) indents the JSON body for humans.
+    #[serde(default)]
+    pub pretty: Option<String>,
+    /// `?decimals=N` rounds percentage series to N places; full precision
+    /// when absent.
+    #[serde(default)]
+    pub decimals: Option<u32>,
+}
+
+impl PresentationQuery {
+    pub fn is_pretty(&self) -> bool {
+        matches!(self.pretty.as_deref(), Some("1" | "true"))
+    }
+
+    /// Applies the unit and precision options to one snapshot.
+    pub fn apply(&self, snapshot: RpcMetricsSnapshot) -> RpcMetricsSnapshot {
+        snapshot
+            .with_net_units(self.net_units)
+            .with_byte_units(self.byte_units)
+            .with_decimals(self.decimals)
+    }
 }
 
 #[derive(Deserialize)]
@@ -38,126 +263,873 @@ pub struct RangeQuery {
     pub limit: Option<usize>,
 }
 
-fn api_routes() -> Router<AppState> {
-    Router::new()
+#[derive(Deserialize)]
+pub struct HistogramQuery {
+    pub metric: String,
+    pub bins: Option<usize>,
+    pub since_ms: Option<u64>,
+    pub until_ms: Option<u64>,
+}
+
+#[derive(Deserialize)]
+pub struct HeatmapQuery {
+    /// Time buckets per core; 120 when absent.
+    pub cols: Option<usize>,
+    pub since_ms: Option<u64>,
+    pub until_ms: Option<u64>,
+}
+
+#[derive(Deserialize)]
+pub struct NetworkPeaksQuery {
+    pub since_ms: Option<u64>,
+    pub until_ms: Option<u64>,
+}
+
+#[derive(Deserialize)]
+pub struct StreamQuery {
+    /// Send at most one snapshot per this many ms, skipping to the newest;
+    /// every snapshot when absent or 0.
+    pub min_interval_ms: Option<u64>,
+    /// Replay up to this many buffered snapshots, oldest first, before the
+    /// live ones.
+    pub replay: Option<usize>,
+    /// `?delta=1` (or `true`) sends a full `snapshot` event, then `patch`
+    /// events holding JSON merge patches; see [`crate::delta`].
+    pub delta: Option<String>,
+    /// Streams only the first this many cores of `cpu_cores`; `?per_core=0`
+    /// leaves the series empty. History keeps every core.
+    pub per_core: Option<usize>,
+}
+
+impl StreamQuery {
+    pub fn is_delta(&self) -> bool {
+        matches!(self.delta.as_deref(), Some("1" | "true"))
+    }
+}
+
+#[derive(Deserialize)]
+pub struct LogsQuery {
+    pub limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+pub struct CompareQuery {
+    pub a_from: u64,
+    pub a_to: u64,
+    pub b_from: u64,
+    pub b_to: u64,
+}
+
+fn api_routes(state: &AppState) -> Router<AppState> {
+    let limits = state.limits;
+    let requests = Router::new()
         .route("/api/health", get(health))
+        .route("/api/config", get(get_config))
         .route("/api/latest", get(get_latest))
         .route("/api/metrics", get(get_latest))
         .route("/api/range", get(get_range))
         .route("/api/history", get(get_history))
-        .route("/api/stream", get(stream))
+        .route("/api/history/columns", get(get_history_columns))
+        .route("/api/histogram", get(get_histogram))
+        .route("/api/heatmap/cpu", get(cpu_heatmap))
+        .route("/api/network/peaks", get(network_peaks))
+        .route("/api/stats/compare", get(compare_stats))
         .route("/api/db/stats", get(db_stats))
+        .route("/api/alerts/history", get(alert_history))
+        .route("/api/alerts/:id/ack", post(ack_alert))
+        .route("/api/logs", get(get_logs))
+        .route("/api/stats", get(request_stats))
+        .merge(grafana::routes())
+        .layer(TimeoutLayer::with_status_code(
+            StatusCode::REQUEST_TIMEOUT,
+            limits.request_timeout,
+        ))
+        .layer(axum::middleware::map_response(timeout_error))
+        .layer(axum::middleware::from_fn_with_state(
+            state.requests.clone(),
+            access::record_request,
+        ));
+    // Long-lived by design, so kept out of the request timeout.
+    let streams = Router::new()
+        .route("/api/stream", get(stream))
+        .route("/api/ws", get(ws_stream));
+
+    requests
+        .merge(streams)
+        .layer(DefaultBodyLimit::disable())
+        .layer(RequestBodyLimitLayer::new(limits.max_body_bytes))
+        .layer(access::access_log_layer())
 }
 
 /// API-only router: no web page (used by server)
 pub fn api_only_router(state: AppState) -> Router {
-    api_routes().with_state(state)
+    api_routes(&state).with_state(state)
 }
 
 /// Full router: API endpoints + web page (used by client)
 pub fn router(state: AppState) -> Router {
     Router::new()
         .route("/", get(index))
-        .merge(api_routes())
+        .merge(api_routes(&state))
         .with_state(state)
 }
 
+/// Window `/api/stats` computes `healthy_fraction` over by default.
+pub const DEFAULT_HEALTH_WINDOW_MS: u64 = 3_600_000;
+
+#[derive(Deserialize)]
+pub struct StatsQuery {
+    /// Window ending at the newest sample for `healthy_fraction`.
+    pub window_ms: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct RequestStatsResponse {
+    latency_bucket_bounds_ms: &'static [f64],
+    routes: BTreeMap<String, RouteStats>,
+    health_window_ms: u64,
+    /// Share of samples in the window with every metric below its warn
+    /// threshold; null when the window holds no samples.
+    healthy_fraction: Option<f64>,
+}
+
+async fn request_stats(
+    State(state): State<AppState>,
+    Query(query): Query<StatsQuery>,
+) -> impl IntoResponse {
+    let window_ms = query.window_ms.unwrap_or(DEFAULT_HEALTH_WINDOW_MS);
+    let snapshots = match state.buffer.latest() {
+        Some(latest) => state.buffer.range(
+            Some(latest.timestamp_ms.saturating_sub(u128::from(window_ms))),
+            None,
+        ),
+        None => Vec::new(),
+    };
+    Json(RequestStatsResponse {
+        latency_bucket_bounds_ms: LATENCY_BUCKETS_MS,
+        routes: state.requests.snapshot(),
+        health_window_ms: window_ms,
+        healthy_fraction: alerts::healthy_fraction(&snapshots, &state.thresholds.get()),
+    })
+}
+
 #[derive(Serialize)]
 struct HealthResponse {
     status: &'static str,
+    /// Set with `status: "degraded"`; liveness still answers 200 so only
+    /// readiness checks that read the body drain the node.
+    degraded: bool,
+    /// Why the node is degraded.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    reasons: Vec<String>,
+    collector_panics: u64,
+    /// Approximate memory held by the in-memory history.
+    estimated_buffer_bytes: usize,
+    /// Time since the newest buffered snapshot was taken; absent before the
+    /// first one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_sample_age_ms: Option<u64>,
+}
+
+async fn health(State(state): State<AppState>) -> impl IntoResponse {
+    let latest = state.buffer.latest();
+    let mut reasons = Vec::new();
+    if state.buffer.is_poisoned() {
+        reasons.push("history buffer lock poisoned".to_string());
+    }
+    if state.collector.is_failing() {
+        reasons.push("collector failing".to_string());
+    }
+    if let Some(latest) = &latest {
+        reasons.extend(critical_resources(&state.criticality, latest));
+    }
+    let degraded = !reasons.is_empty();
+    (
+        StatusCode::OK,
+        Json(HealthResponse {
+            status: if degraded { "degraded" } else { "ok" },
+            degraded,
+            reasons,
+            collector_panics: state.collector.panics(),
+            estimated_buffer_bytes: state.buffer.estimated_bytes(),
+            last_sample_age_ms: latest.map(|latest| {
+                let age = now_timestamp_ms().saturating_sub(latest.timestamp_ms);
+                u64::try_from(age).unwrap_or(u64::MAX)
+            }),
+        }),
+    )
+        .into_response()
 }
 
-async fn health() -> impl IntoResponse {
-    (StatusCode::OK, Json(HealthResponse { status: "ok" })).into_response()
+/// Disk and swap usage in `snapshot` at or above their critical levels.
+fn critical_resources(levels: &ResourceCriticality, snapshot: &MetricsSnapshot) -> Vec<String> {
+    let memory = &snapshot.memory;
+    let swap_pct = (memory.swap_total_bytes > 0)
+        .then(|| memory.swap_used_bytes as f32 / memory.swap_total_bytes as f32 * 100.0);
+    [
+        ("disk", Some(snapshot.disk.used_pct), levels.disk_used_pct),
+        ("swap", swap_pct, levels.swap_used_pct),
+    ]
+    .into_iter()
+    .filter_map(|(name, used, crit)| {
+        let (used, crit) = (used?, crit?);
+        (used >= crit).then(|| format!("{name} used {used:.1}% >= critical {crit:.1}%"))
+    })
+    .collect()
 }
 
-async fn index() -> impl IntoResponse {
-    web::index().await
+#[derive(Serialize)]
+struct ConfigResponse {
+    thresholds: Thresholds,
+    network_scale: NetScale,
+    initial_window_ms: u64,
+    interval_ms: Option<u64>,
+    /// Gaps between points longer than this are not bridged by chart lines.
+    gap_threshold_ms: Option<u64>,
+    /// Per-metric aggregation of downsampled series.
+    downsampling: Aggregations,
+    /// Points the dashboard keeps before dropping the oldest.
+    max_browser_points: usize,
 }
 
-async fn get_latest(State(state): State<AppState>) -> impl IntoResponse {
-    if let Some(snap) = state.buffer.latest() {
-        return (StatusCode::OK, Json(snap.to_rpc_format())).into_response();
+async fn get_config(State(state): State<AppState>) -> impl IntoResponse {
+    Json(ConfigResponse {
+        thresholds: state.thresholds.get(),
+        network_scale: state.net_scale,
+        initial_window_ms: state.initial_window_ms,
+        interval_ms: state.sampling.interval_ms(&state.collector),
+        gap_threshold_ms: state.sampling.gap_threshold_ms(&state.collector),
+        downsampling: state.sampling.aggregations,
+        max_browser_points: state.sampling.max_browser_points.max(1),
+    })
+}
+
+async fn index(query: axum::extract::Query<web::ViewQuery>) -> impl IntoResponse {
+    web::index(query).await
+}
+
+/// Error returned by API handlers: sent as an [`ErrorResponse`] body with
+/// the status its [`ErrorCode`] maps to.
+#[derive(Debug)]
+pub struct ApiError {
+    code: ErrorCode,
+    message: String,
+    details: Option<serde_json::Value>,
+}
+
+impl ApiError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            details: None,
+        }
     }
 
-    match state.db.get_latest() {
-        Ok(Some(snap)) => (StatusCode::OK, Json(snap)).into_response(),
-        Ok(None) => (
-            StatusCode::NOT_FOUND,
-            Json(ErrorResponse {
-                error: "no data yet".to_string(),
-            }),
-        )
-            .into_response(),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorResponse {
-                error: format!("database error: {}", e),
-            }),
-        )
-            .into_response(),
+    /// Attaches structured context, e.g. the accepted values of a parameter.
+    pub fn with_details(mut self, details: serde_json::Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+
+    pub fn code(&self) -> ErrorCode {
+        self.code
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self.code {
+            ErrorCode::NoData | ErrorCode::NotFound => StatusCode::NOT_FOUND,
+            ErrorCode::InvalidParameter => StatusCode::BAD_REQUEST,
+            ErrorCode::Conflict => StatusCode::CONFLICT,
+            ErrorCode::EndpointDisabled => StatusCode::FORBIDDEN,
+            ErrorCode::Unauthorized => StatusCode::UNAUTHORIZED,
+            ErrorCode::Database | ErrorCode::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+            ErrorCode::Timeout => StatusCode::REQUEST_TIMEOUT,
+            ErrorCode::Upstream => StatusCode::BAD_GATEWAY,
+        }
+    }
+}
+
+impl From<rusqlite::Error> for ApiError {
+    fn from(e: rusqlite::Error) -> Self {
+        Self::new(ErrorCode::Database, format!("database error: {}", e))
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        let body = ErrorResponse {
+            code: self.code,
+            message: self.message,
+            details: self.details,
+        };
+        (status, Json(body)).into_response()
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        ApiError::new(ErrorCode::InvalidParameter, rejection.body_text())
+    }
+}
+
+impl From<PathRejection> for ApiError {
+    fn from(rejection: PathRejection) -> Self {
+        ApiError::new(ErrorCode::InvalidParameter, rejection.body_text())
     }
 }
 
+/// [`axum::extract::Query`] that rejects a malformed query string with an
+/// [`ApiError`] body instead of plain text.
+pub struct Query<T>(pub T);
+
+#[axum::async_trait]
+impl<T: DeserializeOwned, S: Send + Sync> FromRequestParts<S> for Query<T> {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let axum::extract::Query(value) =
+            axum::extract::Query::<T>::from_request_parts(parts, state).await?;
+        Ok(Query(value))
+    }
+}
+
+/// Gives the empty `408` [`TimeoutLayer`] answers with an [`ApiError`] body.
+async fn timeout_error(response: Response) -> Response {
+    if response.status() == StatusCode::REQUEST_TIMEOUT {
+        return ApiError::new(ErrorCode::Timeout, "request timed out").into_response();
+    }
+    response
+}
+
+/// Serves the newest snapshot with an `ETag` of its timestamp; a matching
+/// `If-None-Match` gets `304 Not Modified` until a newer sample arrives.
+async fn get_latest(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(pres): Query<PresentationQuery>,
+) -> Result<Response, ApiError> {
+    let latest = match state.buffer.latest() {
+        Some(snap) => Some(snap.to_rpc_format()),
+        None => state.stored_latest()?,
+    };
+    let snap = latest.ok_or_else(|| ApiError::new(ErrorCode::NoData, "no data yet"))?;
+    let etag = snapshot_etag(snap.timestamp_ms, &pres);
+    let mut response = if if_none_match(&headers, &etag) {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        json_response(StatusCode::OK, &pres.apply(snap), &pres)
+    };
+    if let Ok(value) = HeaderValue::from_str(&etag) {
+        response.headers_mut().insert(header::ETAG, value);
+    }
+    Ok(response)
+}
+
 async fn get_range(
     State(state): State<AppState>,
-    axum::extract::Query(query): axum::extract::Query<RangeQuery>,
+    Query(query): Query<RangeQuery>,
+    Query(pres): Query<PresentationQuery>,
 ) -> impl IntoResponse {
-    match state.db.get_range(query.from_ts, query.to_ts, query.limit) {
-        Ok(snapshots) => (StatusCode::OK, Json(snapshots)).into_response(),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorResponse {
-                error: format!("database error: {}", e),
-            }),
+    match state.stored_range(query.from_ts, query.to_ts, query.limit) {
+        Ok(snapshots) => {
+            json_response(StatusCode::OK, &apply_presentation(snapshots, &pres), &pres)
+        }
+        Err(e) => ApiError::from(e).into_response(),
+    }
+}
+
+#[derive(Serialize)]
+struct HistoryPageResponse {
+    items: Vec<RpcMetricsSnapshot>,
+    next_cursor: Option<u64>,
+}
+
+async fn get_history(
+    State(state): State<AppState>,
+    Query(query): Query<HistoryQuery>,
+    Query(pres): Query<PresentationQuery>,
+    Query(params): Query<HashMap<String, String>>,
+) -> impl IntoResponse {
+    let tags = TagFilter::from_params(&params);
+    if query.after_ms.is_some() || query.page_size.is_some() {
+        let page_size = query.page_size.unwrap_or(100);
+        if page_size == 0 || page_size > MAX_PAGE_SIZE {
+            return ApiError::new(
+                ErrorCode::InvalidParameter,
+                format!("page_size must be between 1 and {}", MAX_PAGE_SIZE),
+            )
+            .into_response();
+        }
+        let page = state
+            .buffer
+            .page_after(query.after_ms.map(u128::from), page_size);
+        let body = HistoryPageResponse {
+            // Filtered within the page, so a page may come back short.
+            items: page
+                .items
+                .iter()
+                .filter(|s| tags.matches(&s.tags))
+                .map(|s| pres.apply(s.to_rpc_format()))
+                .collect(),
+            next_cursor: page.next_cursor.map(|c| c.try_into().unwrap_or(u64::MAX)),
+        };
+        return json_response(StatusCode::OK, &body, &pres);
+    }
+
+    let window_since = match query.window_ms {
+        Some(window) => {
+            let newest = match state.buffer.latest() {
+                Some(latest) => Some(latest.timestamp_ms),
+                None => match state.stored_latest() {
+                    Ok(stored) => stored.map(|s| s.timestamp_ms),
+                    Err(e) => return ApiError::from(e).into_response(),
+                },
+            };
+            newest.map(|ts| ts.saturating_sub(u128::from(window)))
+        }
+        None => None,
+    };
+    let since_ts = match (query.since_ts.map(u128::from), window_since) {
+        (Some(since), Some(window)) => Some(since.max(window)),
+        (since, window) => since.or(window),
+    };
+
+    let from_start = query.is_from_start();
+    let order = query.order.unwrap_or(HistoryOrder::Desc);
+
+    // Serve windows the buffer fully covers from memory.
+    if let Some(since) = since_ts {
+        if state
+            .buffer
+            .oldest_timestamp()
+            .is_some_and(|oldest| oldest <= since)
+        {
+            let mut snapshots = state.buffer.range(Some(since), None);
+            snapshots.retain(|s| tags.matches(&s.tags));
+            let history = select_history(snapshots, query.limit, from_start, order)
+                .iter()
+                .map(|s| s.to_rpc_format())
+                .collect();
+            return json_response(StatusCode::OK, &apply_presentation(history, &pres), &pres);
+        }
+    }
+
+    let since_ts = since_ts.map(|s| u64::try_from(s).unwrap_or(u64::MAX));
+    // With tags, the limit applies after filtering.
+    let db_limit = if tags.is_empty() { query.limit } else { None };
+    match state.stored_history(db_limit, since_ts, from_start) {
+        Ok(mut history) => {
+            if !tags.is_empty() {
+                history.retain(|s| tags.matches(&s.tags));
+                history.truncate(query.limit.unwrap_or(usize::MAX));
+            }
+            // The database returns rows in the order it selected them.
+            let db_order = if from_start {
+                HistoryOrder::Asc
+            } else {
+                HistoryOrder::Desc
+            };
+            if order != db_order {
+                history.reverse();
+            }
+            json_response(StatusCode::OK, &apply_presentation(history, &pres), &pres)
+        }
+        Err(e) => ApiError::from(e).into_response(),
+    }
+}
+
+/// Parallel arrays, one entry per sample; `null` where a value is unavailable.
+#[derive(Serialize)]
+struct HistoryColumns {
+    timestamp_ms: Vec<u64>,
+    cpu_total: Vec<Option<f32>>,
+    mem_used_pct: Vec<Option<f32>>,
+    rx: Vec<Option<f32>>,
+    tx: Vec<Option<f32>>,
+}
+
+async fn get_history_columns(
+    State(state): State<AppState>,
+    Query(query): Query<ColumnsQuery>,
+    Query(pres): Query<PresentationQuery>,
+) -> impl IntoResponse {
+    let mut snapshots = state.buffer.range(
+        query.since_ms.map(u128::from),
+        query.until_ms.map(u128::from),
+    );
+    if let Some(limit) = query.limit {
+        let skip = snapshots.len().saturating_sub(limit);
+        snapshots.drain(..skip);
+    }
+
+    let column = |name: &str| {
+        let metric = scalar_metric(name).expect("built-in scalar metric");
+        snapshots.iter().map(metric.extract).collect::<Vec<_>>()
+    };
+    let net_scale = match pres.net_units {
+        NetUnits::Bytes => 1.0,
+        NetUnits::Bits => 8.0,
+    };
+    let net_column = |name: &str| {
+        column(name)
+            .into_iter()
+            .map(|v| v.map(|v| v * net_scale))
+            .collect::<Vec<_>>()
+    };
+
+    let body = HistoryColumns {
+        timestamp_ms: snapshots
+            .iter()
+            .map(|s| s.timestamp_ms.try_into().unwrap_or(u64::MAX))
+            .collect(),
+        cpu_total: column("cpu"),
+        mem_used_pct: column("memory"),
+        rx: net_column("net_rx"),
+        tx: net_column("net_tx"),
+    };
+    json_response(StatusCode::OK, &body, &pres)
+}
+
+fn apply_presentation(
+    snapshots: Vec<RpcMetricsSnapshot>,
+    pres: &PresentationQuery,
+) -> Vec<RpcMetricsSnapshot> {
+    snapshots.into_iter().map(|s| pres.apply(s)).collect()
+}
+
+/// Validator of the snapshot taken at `timestamp_ms` as `pres` renders it:
+/// presentation options off their defaults are part of the tag, so a body in
+/// other units or precision never matches.
+fn snapshot_etag(timestamp_ms: u128, pres: &PresentationQuery) -> String {
+    let mut tag = timestamp_ms.to_string();
+    if pres.net_units != NetUnits::default() {
+        tag.push_str(&format!("-net-{:?}", pres.net_units).to_lowercase());
+    }
+    if pres.byte_units != ByteUnits::default() {
+        tag.push_str(&format!("-bytes-{:?}", pres.byte_units).to_lowercase());
+    }
+    if let Some(decimals) = pres.decimals {
+        tag.push_str(&format!("-decimals-{decimals}"));
+    }
+    if pres.is_pretty() {
+        tag.push_str("-pretty");
+    }
+    format!("\"{tag}\"")
+}
+
+/// Whether `If-None-Match` lists `etag` (weak or strong) or is `*`.
+fn if_none_match(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == etag)
+}
+
+/// Compact JSON by default; indented when the caller asked for `?pretty=1`.
+fn json_response<T: Serialize>(status: StatusCode, body: &T, pres: &PresentationQuery) -> Response {
+    if !pres.is_pretty() {
+        return (status, Json(body)).into_response();
+    }
+    match serde_json::to_string_pretty(body) {
+        Ok(text) => (
+            status,
+            [(axum::http::header::CONTENT_TYPE, "application/json")],
+            text,
         )
             .into_response(),
+        Err(e) => ApiError::new(ErrorCode::Internal, format!("serialization error: {}", e))
+            .into_response(),
     }
 }
 
-async fn get_history(
+#[derive(Serialize)]
+struct HistogramResponse {
+    metric: String,
+    #[serde(flatten)]
+    histogram: Histogram,
+}
+
+async fn get_histogram(
     State(state): State<AppState>,
-    axum::extract::Query(query): axum::extract::Query<HistoryQuery>,
+    Query(query): Query<HistogramQuery>,
 ) -> impl IntoResponse {
-    match state.db.get_history(query.limit, query.since_ts) {
-        Ok(history) => (StatusCode::OK, Json(history)).into_response(),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorResponse {
-                error: format!("database error: {}", e),
+    let Some(metric) = scalar_metric(&query.metric) else {
+        return ApiError::new(
+            ErrorCode::InvalidParameter,
+            format!(
+                "unknown metric '{}', expected one of: {}",
+                query.metric,
+                SCALAR_METRIC_NAMES.join(", ")
+            ),
+        )
+        .with_details(serde_json::json!({ "expected": SCALAR_METRIC_NAMES }))
+        .into_response();
+    };
+    let bins = query.bins.unwrap_or(10);
+    if bins == 0 || bins > 1000 {
+        return ApiError::new(
+            ErrorCode::InvalidParameter,
+            "bins must be between 1 and 1000",
+        )
+        .into_response();
+    }
+
+    match state.buffer.histogram(
+        query.since_ms.map(u128::from),
+        query.until_ms.map(u128::from),
+        bins,
+        metric.range,
+        metric.extract,
+    ) {
+        Some(histogram) => (
+            StatusCode::OK,
+            Json(HistogramResponse {
+                metric: query.metric,
+                histogram,
             }),
         )
             .into_response(),
+        None => ApiError::new(ErrorCode::NoData, "no data in window").into_response(),
+    }
+}
+
+/// Per-core usage pre-binned to `cols` time buckets with the CPU
+/// aggregation, so a heatmap needs no client-side resampling.
+async fn cpu_heatmap(
+    State(state): State<AppState>,
+    Query(query): Query<HeatmapQuery>,
+) -> Result<Json<CpuHeatmap>, ApiError> {
+    let cols = query.cols.unwrap_or(120);
+    if cols == 0 || cols > 2000 {
+        return Err(ApiError::new(
+            ErrorCode::InvalidParameter,
+            "cols must be between 1 and 2000",
+        ));
     }
+    state
+        .buffer
+        .cpu_heatmap(
+            query.since_ms.map(u128::from),
+            query.until_ms.map(u128::from),
+            cols,
+            state.sampling.aggregations.cpu,
+        )
+        .map(Json)
+        .ok_or_else(|| ApiError::new(ErrorCode::NoData, "no per-core data in window"))
+}
+
+/// Separate RX and TX maxima, for the dashboard's split network axes.
+async fn network_peaks(
+    State(state): State<AppState>,
+    Query(query): Query<NetworkPeaksQuery>,
+) -> impl IntoResponse {
+    match state.buffer.network_peaks(
+        query.since_ms.map(u128::from),
+        query.until_ms.map(u128::from),
+    ) {
+        Some(peaks) => (StatusCode::OK, Json(peaks)).into_response(),
+        None => ApiError::new(ErrorCode::NoData, "no data in window").into_response(),
+    }
+}
+
+type WindowStats = BTreeMap<&'static str, SeriesStats>;
+
+#[derive(Serialize)]
+struct CompareResponse {
+    a: WindowStats,
+    b: WindowStats,
+    /// Relative change of the mean from `a` to `b`, in percent. Absent when
+    /// either window lacks the metric or `a`'s mean is zero.
+    mean_change_pct: BTreeMap<&'static str, f32>,
+}
+
+fn window_stats(buffer: &MetricsBuffer, from_ms: u64, to_ms: u64) -> WindowStats {
+    SCALAR_METRIC_NAMES
+        .iter()
+        .filter_map(|name| {
+            let metric = scalar_metric(name)?;
+            let stats = buffer.stats(
+                Some(u128::from(from_ms)),
+                Some(u128::from(to_ms)),
+                metric.extract,
+            )?;
+            Some((*name, stats))
+        })
+        .collect()
+}
+
+async fn compare_stats(
+    State(state): State<AppState>,
+    Query(query): Query<CompareQuery>,
+) -> impl IntoResponse {
+    let a = window_stats(&state.buffer, query.a_from, query.a_to);
+    let b = window_stats(&state.buffer, query.b_from, query.b_to);
+    for (label, window) in [("a", &a), ("b", &b)] {
+        if window.is_empty() {
+            return ApiError::new(
+                ErrorCode::NoData,
+                format!("window {} contains no samples", label),
+            )
+            .into_response();
+        }
+    }
+
+    let mean_change_pct = a
+        .iter()
+        .filter_map(|(name, a_stats)| {
+            let b_stats = b.get(name)?;
+            if a_stats.mean == 0.0 {
+                return None;
+            }
+            Some((
+                *name,
+                (b_stats.mean - a_stats.mean) / a_stats.mean.abs() * 100.0,
+            ))
+        })
+        .collect();
+
+    (
+        StatusCode::OK,
+        Json(CompareResponse {
+            a,
+            b,
+            mean_change_pct,
+        }),
+    )
+        .into_response()
 }
 
 async fn db_stats(State(state): State<AppState>) -> impl IntoResponse {
-    match state.db.get_stats() {
+    let stats = match state.store.as_ref().and_then(|store| store.stats()) {
+        Some(stats) => Ok(stats),
+        None => state.db.get_stats(),
+    };
+    match stats {
         Ok(stats) => (StatusCode::OK, Json(stats)).into_response(),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorResponse {
-                error: format!("database error: {}", e),
-            }),
-        )
+        Err(e) => ApiError::from(e).into_response(),
+    }
+}
+
+async fn alert_history(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.alerts.history())
+}
+
+async fn ack_alert(
+    State(state): State<AppState>,
+    id: Result<axum::extract::Path<u64>, PathRejection>,
+) -> Result<Response, ApiError> {
+    let axum::extract::Path(id) = id?;
+    Ok(match state.alerts.ack(id) {
+        Ok(alert) => (StatusCode::OK, Json(alert)).into_response(),
+        Err(e) => {
+            let code = match e {
+                AckError::NotFound(_) => ErrorCode::NotFound,
+                AckError::Cleared(_) => ErrorCode::Conflict,
+            };
+            ApiError::new(code, e.to_string()).into_response()
+        }
+    })
+}
+
+async fn get_logs(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Query(q): Query<LogsQuery>,
+) -> impl IntoResponse {
+    if let Some(denied) = deny_unauthorized(&state, &headers) {
+        return denied;
+    }
+    (StatusCode::OK, Json(state.logs.recent(q.limit))).into_response()
+}
+
+/// Checks `Authorization: Bearer <token>` against the configured API token,
+/// returning the error response to send when access is refused.
+fn deny_unauthorized(state: &AppState, headers: &axum::http::HeaderMap) -> Option<Response> {
+    let Some(expected) = state.api_token.as_deref() else {
+        return Some(
+            ApiError::new(
+                ErrorCode::EndpointDisabled,
+                "endpoint disabled: start the server with --api-token",
+            )
             .into_response(),
+        );
+    };
+    let provided = headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    if provided == Some(expected) {
+        return None;
     }
+    Some(
+        (
+            [(axum::http::header::WWW_AUTHENTICATE, "Bearer")],
+            ApiError::new(ErrorCode::Unauthorized, "missing or invalid bearer token"),
+        )
+            .into_response(),
+    )
 }
 
 async fn stream(
     State(state): State<AppState>,
+    Query(query): Query<StreamQuery>,
+    Query(params): Query<HashMap<String, String>>,
 ) -> Sse<impl tokio_stream::Stream<Item = Result<Event, Infallible>>> {
+    let tags = TagFilter::from_params(&params);
+    // Subscribe before reading the buffer so nothing published in between
+    // is lost; live snapshots the replay already covered are skipped.
     let rx = state.stream_tx.subscribe();
+    let replay: Vec<RpcMetricsSnapshot> = match query.replay {
+        Some(n) if n > 0 => state
+            .buffer
+            .history(Some(n))
+            .iter()
+            .filter(|s| tags.matches(&s.tags))
+            .map(|s| s.to_rpc_format())
+            .collect(),
+        _ => Vec::new(),
+    };
+    let replayed_until = replay.last().map(|s| s.timestamp_ms);
     let shutdown = state.shutdown.clone();
-    let stream = BroadcastStream::new(rx)
+    let min_interval = Duration::from_millis(query.min_interval_ms.unwrap_or(0));
+    let tagged = BroadcastStream::new(rx).filter(move |msg| {
+        let other = matches!(msg, Ok(s) if !tags.matches(&s.tags));
+        std::future::ready(!other)
+    });
+    let live = throttle_latest(tagged, min_interval).filter(move |msg| {
+        let seen = matches!((msg, replayed_until), (Ok(s), Some(until)) if s.timestamp_ms <= until);
+        std::future::ready(!seen)
+    });
+    let mut encoder = query.is_delta().then(DeltaEncoder::default);
+    let per_core = query.per_core;
+    let stream = futures::stream::iter(replay.into_iter().map(Ok))
+        .chain(live)
         .take_until(async move { shutdown.cancelled().await })
-        .map(|msg| match msg {
-            Ok(snapshot) => match serde_json::to_string(&snapshot) {
-                Ok(json) => Ok(Event::default().data(json)),
-                Err(e) => Ok(Event::default()
-                    .event("error")
-                    .data(format!("serialize_error: {e}"))),
-            },
+        .map(move |msg| match msg {
+            Ok(snapshot) => {
+                let snapshot = snapshot.with_per_core_limit(per_core);
+                let event = match encoder.as_mut() {
+                    Some(encoder) => encoder.encode(&snapshot).and_then(|delta| match delta {
+                        Delta::Full(doc) => serde_json::to_string(&doc)
+                            .map(|json| Event::default().event("snapshot").data(json)),
+                        Delta::Patch(patch) => serde_json::to_string(&patch)
+                            .map(|json| Event::default().event("patch").data(json)),
+                    }),
+                    None => {
+                        serde_json::to_string(&snapshot).map(|json| Event::default().data(json))
+                    }
+                };
+                Ok(event.unwrap_or_else(|e| {
+                    Event::default()
+                        .event("error")
+                        .data(format!("serialize_error: {e}"))
+                }))
+            }
             Err(e) => Ok(Event::default()
                 .event("error")
                 .data(format!("stream_error: {e}"))),
@@ -169,3 +1141,83 @@ async fn stream(
             .text("keep-alive"),
     )
 }
+
+struct Throttle<S: Stream> {
+    inner: S,
+    done: bool,
+    pending: Option<S::Item>,
+    next_at: Instant,
+}
+
+/// Passes at most one item per `interval`: the first straight away, then the
+/// newest one received once the interval has elapsed, dropping those between.
+fn throttle_latest<S>(inner: S, interval: Duration) -> impl Stream<Item = S::Item>
+where
+    S: Stream + Unpin,
+{
+    let state = Throttle {
+        inner,
+        done: false,
+        pending: None,
+        next_at: Instant::now(),
+    };
+    futures::stream::unfold(state, move |mut st| async move {
+        loop {
+            if st.pending.is_some() && (st.done || Instant::now() >= st.next_at) {
+                st.next_at = Instant::now() + interval;
+                let item = st.pending.take()?;
+                return Some((item, st));
+            }
+            if st.done {
+                return None;
+            }
+            let received = tokio::select! {
+                item = st.inner.next() => Some(item),
+                _ = tokio::time::sleep_until(st.next_at), if st.pending.is_some() => None,
+            };
+            match received {
+                Some(Some(item)) => st.pending = Some(item),
+                Some(None) => st.done = true,
+                None => {}
+            }
+        }
+    })
+}
+
+async fn ws_stream(ws: WebSocketUpgrade, State(state): State<AppState>) -> Response {
+    ws.on_upgrade(move |socket| ws_push_snapshots(socket, state))
+}
+
+/// Every frame is one full snapshot with a top-level `timestamp_ms`, so a
+/// reconnecting client can resume from `/api/history?since_ts=<last>`.
+async fn ws_push_snapshots(mut socket: WebSocket, state: AppState) {
+    let mut rx = state.stream_tx.subscribe();
+    loop {
+        tokio::select! {
+            _ = state.shutdown.cancelled() => break,
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => {}
+            },
+            msg = rx.recv() => match msg {
+                Ok(snapshot) => {
+                    let json = match serde_json::to_string(&snapshot) {
+                        Ok(json) => json,
+                        Err(e) => {
+                            warn!("Failed to serialize snapshot for WebSocket: {}", e);
+                            continue;
+                        }
+                    };
+                    if socket.send(Message::Text(json)).await.is_err() {
+                        return;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    warn!("WebSocket subscriber lagged, skipped {} snapshots", n);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+        }
+    }
+    let _ = socket.send(Message::Close(None)).await;
+}
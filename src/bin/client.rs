@@ -1,17 +1,29 @@
 use axum::body::Body;
+use axum::extract::ws::{Message, WebSocketUpgrade};
 use axum::extract::State;
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
-use axum::routing::get;
+use axum::routing::{get, post};
 use axum::Router;
 use clap::Parser;
-use futures::StreamExt;
+use futures::{SinkExt, StreamExt};
+use resource_monitor::api::ApiError;
+use resource_monitor::check::{self, CheckReport};
+use resource_monitor::config::{
+    ByteUnits, ClientMode, CpuDisplay, DisplayUnits, FsyncPolicy, NetUnits, TapFormat,
+};
 use resource_monitor::console;
-use resource_monitor::metrics::RpcMetricsSnapshot;
+use resource_monitor::journal::{
+    run_rpc_recorder, Rotation, SnapshotJournal, DEFAULT_PERSIST_FLUSH_MS, DEFAULT_ROTATE_BYTES,
+    DEFAULT_ROTATE_KEEP,
+};
+use resource_monitor::metrics::{ErrorCode, RpcMetricsSnapshot};
 use resource_monitor::runtime;
 use resource_monitor::web;
+use std::io::Write;
 use std::net::{IpAddr, SocketAddr};
-use std::sync::{Arc, RwLock};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, RwLock};
 use std::time::Duration;
 use tokio_util::sync::CancellationToken;
 use tracing::{error, info};
@@ -22,14 +34,27 @@ use tracing::{error, info};
     about = "Resource Monitor web client (serves UI + proxies API to server)"
 )]
 struct Args {
+    /// Run mode (web/tap)
+    #[arg(long, value_enum, default_value_t = ClientMode::Web)]
+    mode: ClientMode,
+
+    /// Line format for --mode tap (tsv/json)
+    #[arg(long, value_enum, default_value_t = TapFormat::Tsv)]
+    tap_format: TapFormat,
+
     /// Server HTTP API URL (backend)
     #[arg(long, default_value = "http://127.0.0.1:9000")]
     api_url: String,
 
-    /// RPC server address (for console mode)
+    /// RPC server address (for console and tap modes)
     #[arg(long, default_value = "127.0.0.1:50051")]
     rpc_addr: SocketAddr,
 
+    /// zstd-compress RPC frames; the server answers in kind. Worth it for
+    /// large history responses over slow links
+    #[arg(long)]
+    rpc_compress: bool,
+
     /// HTTP bind address for this client
     #[arg(long, default_value = "127.0.0.1")]
     bind: IpAddr,
@@ -38,9 +63,60 @@ struct Args {
     #[arg(long, default_value_t = 8080)]
     port: u16,
 
+    /// Serve the dashboard and API under this path prefix (e.g. /monitor)
+    /// for reverse proxies
+    #[arg(long, default_value = "")]
+    base_path: String,
+
+    /// Poll interval in ms when the RPC server cannot stream and does not
+    /// report its sampling interval
+    #[arg(long, default_value_t = 1000)]
+    poll_interval_ms: u64,
+
     /// Also show console output (via RPC)
     #[arg(long, default_value_t = false)]
     console: bool,
+
+    /// Console repaint period in ms; snapshots arriving in between are
+    /// coalesced into one frame
+    #[arg(long, default_value_t = 1000)]
+    console_refresh_ms: u64,
+
+    /// Unit for network rates in the console (bytes/bits)
+    #[arg(long, value_enum, default_value_t = NetUnits::Bytes)]
+    net_units: NetUnits,
+
+    /// Prefixes for byte sizes in the console: iec (KiB, MiB, powers of
+    /// 1024) or si (KB, MB, powers of 1000)
+    #[arg(long, value_enum, default_value_t = ByteUnits::Iec)]
+    byte_units: ByteUnits,
+
+    /// Total CPU in the console: percent of capacity, or cores (also how
+    /// many cores' worth are busy, e.g. 3.4 of 8)
+    #[arg(long, value_enum, default_value_t = CpuDisplay::Percent)]
+    cpu_display: CpuDisplay,
+
+    /// On shutdown, time in-flight HTTP requests get to complete (new
+    /// connections are refused meanwhile)
+    #[arg(long, default_value_t = 2000)]
+    http_drain_ms: u64,
+
+    /// Append every snapshot received over RPC to this NDJSON file, in any
+    /// mode, independently of the server's own persistence
+    #[arg(long)]
+    record: Option<PathBuf>,
+
+    /// Rotate the --record file once it reaches this many bytes
+    #[arg(long, default_value_t = DEFAULT_ROTATE_BYTES, requires = "record")]
+    record_max_bytes: u64,
+
+    /// Rotated --record files to keep (<path>.1 is the newest)
+    #[arg(long, default_value_t = DEFAULT_ROTATE_KEEP, requires = "record")]
+    record_keep: usize,
+
+    /// Validate config, the local bind and server reachability, print a report and exit
+    #[arg(long, default_value_t = false)]
+    check: bool,
 }
 
 #[derive(Clone)]
@@ -51,15 +127,40 @@ struct ProxyState {
 
 #[tokio::main(flavor = "current_thread")]
 async fn main() {
-    runtime::init_tracing();
     let args = Args::parse();
+    if args.check {
+        let report = run_checks(&args).await;
+        print!("{}", report.render());
+        std::process::exit(if report.passed() { 0 } else { 1 });
+    }
+    if args.mode == ClientMode::Tap {
+        runtime::init_tracing_to_stderr();
+    } else {
+        runtime::init_tracing();
+    }
     info!(
         "Starting client: api_url={}, bind={}:{}, console={}",
         args.api_url, args.bind, args.port, args.console
     );
 
     let cancel = CancellationToken::new();
+    let recorder_handle = spawn_recorder(&args, cancel.clone());
 
+    if args.mode == ClientMode::Tap {
+        run_tap(&args, cancel).await;
+        join_recorder(recorder_handle).await;
+        return;
+    }
+
+    let base_path = match web::normalize_base_path(&args.base_path) {
+        Ok(path) => path,
+        Err(e) => {
+            error!("--base-path {}", e);
+            cancel.cancel();
+            join_recorder(recorder_handle).await;
+            return;
+        }
+    };
     let proxy_state = ProxyState {
         api_url: args.api_url.trim_end_matches('/').to_string(),
         http: reqwest::Client::new(),
@@ -68,31 +169,43 @@ async fn main() {
     let app = Router::new()
         .route("/", get(index))
         .route("/api/health", get(proxy_health))
+        .route("/api/config", get(proxy_config))
         .route("/api/latest", get(proxy_latest))
         .route("/api/metrics", get(proxy_latest))
         .route("/api/range", get(proxy_range))
         .route("/api/history", get(proxy_history))
+        .route("/api/history/columns", get(proxy_history_columns))
+        .route("/api/histogram", get(proxy_histogram))
+        .route("/api/heatmap/cpu", get(proxy_cpu_heatmap))
+        .route("/api/stats/compare", get(proxy_compare_stats))
+        .route("/api/alerts/history", get(proxy_alert_history))
+        .route("/api/alerts/:id/ack", post(proxy_ack_alert))
+        .route("/api/logs", get(proxy_logs))
         .route("/api/stream", get(proxy_stream))
+        .route("/api/ws", get(proxy_ws))
         .with_state(proxy_state);
+    let app = web::with_base_path(app, &base_path);
 
     let addr = SocketAddr::from((args.bind, args.port));
     let listener = match tokio::net::TcpListener::bind(addr).await {
         Ok(l) => l,
         Err(e) => {
             error!("Failed to bind {}: {}", addr, e);
+            cancel.cancel();
+            join_recorder(recorder_handle).await;
             return;
         }
     };
     info!(
-        "Web UI available at http://{}",
-        listener.local_addr().unwrap_or(addr)
+        "Web UI available at http://{}{}/",
+        listener.local_addr().unwrap_or(addr),
+        base_path
     );
 
     let web_shutdown = cancel.clone();
+    let http_drain = Duration::from_millis(args.http_drain_ms);
     let web_handle = tokio::spawn(async move {
-        let res = axum::serve(listener, app)
-            .with_graceful_shutdown(async move { web_shutdown.cancelled().await })
-            .await;
+        let res = runtime::serve_with_drain(listener, app, web_shutdown, http_drain).await;
         if let Err(e) = res {
             error!("Client HTTP error: {}", e);
         }
@@ -103,17 +216,31 @@ async fn main() {
         let latest: Arc<RwLock<Option<RpcMetricsSnapshot>>> = Arc::new(RwLock::new(None));
         let rpc_cancel = cancel.clone();
         let rpc_addr = args.rpc_addr;
+        let rpc_compress = args.rpc_compress;
         let rpc_latest = latest.clone();
+        let poll_interval = Duration::from_millis(args.poll_interval_ms.max(1));
         tokio::spawn(async move {
-            resource_monitor::rpc::run_rpc_client_streamer(rpc_addr, rpc_cancel, move |snap| {
-                let mut guard = rpc_latest.write().unwrap_or_else(|p| p.into_inner());
-                *guard = Some(snap);
-            })
+            resource_monitor::rpc::run_rpc_client_streamer(
+                rpc_addr,
+                rpc_compress,
+                poll_interval,
+                rpc_cancel,
+                move |snap| {
+                    let mut guard = rpc_latest.write().unwrap_or_else(|p| p.into_inner());
+                    *guard = Some(snap);
+                },
+            )
             .await;
         });
         let console_cancel = cancel.clone();
+        let units = DisplayUnits {
+            net: args.net_units,
+            bytes: args.byte_units,
+            cpu: args.cpu_display,
+        };
+        let refresh = Duration::from_millis(args.console_refresh_ms.max(1));
         Some(tokio::spawn(async move {
-            console::run_rpc_console(latest, Duration::from_millis(1000), console_cancel).await;
+            console::run_rpc_console(latest, refresh, units, console_cancel).await;
         }))
     } else {
         None
@@ -125,29 +252,152 @@ async fn main() {
 
     let shutdown_timeout = Duration::from_secs(2);
 
-    if tokio::time::timeout(shutdown_timeout, web_handle)
-        .await
-        .is_err()
-    {
-        info!("Web shutdown timeout");
-    }
+    // The web task bounds its own drain.
+    let _ = web_handle.await;
     if let Some(h) = console_handle {
         if tokio::time::timeout(shutdown_timeout, h).await.is_err() {
             info!("Console shutdown timeout");
         }
     }
+    join_recorder(recorder_handle).await;
 
     info!("Client stopped");
 }
 
-async fn index() -> impl IntoResponse {
-    web::index().await
+/// Starts `--record`, when set, on its own RPC connection.
+fn spawn_recorder(args: &Args, cancel: CancellationToken) -> Option<tokio::task::JoinHandle<()>> {
+    let path = args.record.as_ref()?;
+    let rotation = Rotation {
+        max_bytes: args.record_max_bytes.max(1),
+        keep: args.record_keep,
+    };
+    let journal = match SnapshotJournal::open(path, FsyncPolicy::Interval) {
+        Ok(journal) => journal.with_rotation(rotation),
+        Err(e) => {
+            error!("Failed to open record file {}: {}", path.display(), e);
+            return None;
+        }
+    };
+    info!(
+        "Recording snapshots to {} (rotating at {} bytes, keeping {})",
+        path.display(),
+        rotation.max_bytes,
+        rotation.keep
+    );
+    Some(tokio::spawn(run_rpc_recorder(
+        Arc::new(Mutex::new(journal)),
+        args.rpc_addr,
+        args.rpc_compress,
+        Duration::from_millis(args.poll_interval_ms.max(1)),
+        Duration::from_millis(DEFAULT_PERSIST_FLUSH_MS),
+        cancel,
+    )))
+}
+
+/// Waits for the recorder's final flush after cancellation.
+async fn join_recorder(handle: Option<tokio::task::JoinHandle<()>>) {
+    if let Some(h) = handle {
+        if tokio::time::timeout(Duration::from_secs(2), h)
+            .await
+            .is_err()
+        {
+            info!("Recorder shutdown timeout");
+        }
+    }
+}
+
+/// Prints one line per streamed snapshot until interrupted; logs go to stderr.
+async fn run_tap(args: &Args, cancel: CancellationToken) {
+    let rpc_addr = args.rpc_addr;
+    let rpc_compress = args.rpc_compress;
+    let format = args.tap_format;
+    let net_units = args.net_units;
+    let poll_interval = Duration::from_millis(args.poll_interval_ms.max(1));
+    let tap_cancel = cancel.clone();
+    let handle = tokio::spawn(async move {
+        resource_monitor::rpc::run_rpc_client_streamer(
+            rpc_addr,
+            rpc_compress,
+            poll_interval,
+            tap_cancel,
+            move |snap| {
+                let line = console::format_tap_line(&snap.with_net_units(net_units), format);
+                let mut out = std::io::stdout().lock();
+                // A closed pipe (e.g. `| head`) is not worth a log line per snapshot.
+                let _ = writeln!(out, "{}", line).and_then(|_| out.flush());
+            },
+        )
+        .await;
+    });
+
+    runtime::shutdown_signal().await;
+    cancel.cancel();
+    if tokio::time::timeout(Duration::from_secs(2), handle)
+        .await
+        .is_err()
+    {
+        info!("Tap shutdown timeout");
+    }
+}
+
+async fn run_checks(args: &Args) -> CheckReport {
+    const TIMEOUT: Duration = Duration::from_secs(3);
+    let mut report = CheckReport::new();
+    let api_url = args.api_url.trim_end_matches('/');
+    report.record(
+        "config",
+        match reqwest::Url::parse(api_url) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => Ok(String::new()),
+            Ok(url) => Err(format!(
+                "--api-url scheme '{}' is not http(s)",
+                url.scheme()
+            )),
+            Err(e) => Err(format!("--api-url '{}' is invalid: {}", api_url, e)),
+        },
+    );
+    if args.mode == ClientMode::Web {
+        report.record(
+            "http bind",
+            check::check_bind(SocketAddr::from((args.bind, args.port))),
+        );
+        let health = reqwest::Client::new()
+            .get(format!("{api_url}/api/health"))
+            .timeout(TIMEOUT)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status());
+        report.record(
+            "server api",
+            health.map(|r| format!("{} {}", r.url(), r.status())),
+        );
+    }
+    if let Some(path) = &args.record {
+        report.record(
+            "record file",
+            SnapshotJournal::open(path, FsyncPolicy::Interval).map(|_| path.display().to_string()),
+        );
+    }
+    if args.console || args.record.is_some() || args.mode == ClientMode::Tap {
+        report.record(
+            "server rpc",
+            check::check_connect(args.rpc_addr, TIMEOUT).await,
+        );
+    }
+    report
+}
+
+async fn index(query: axum::extract::Query<web::ViewQuery>) -> impl IntoResponse {
+    web::index(query).await
 }
 
 async fn proxy_health(State(st): State<ProxyState>) -> Response {
     proxy_get(&st, "/api/health", "").await
 }
 
+async fn proxy_config(State(st): State<ProxyState>) -> Response {
+    proxy_get(&st, "/api/config", "").await
+}
+
 async fn proxy_latest(
     State(st): State<ProxyState>,
     axum::extract::RawQuery(query): axum::extract::RawQuery,
@@ -172,9 +422,71 @@ async fn proxy_history(
     proxy_get(&st, "/api/history", &qs).await
 }
 
+async fn proxy_history_columns(
+    State(st): State<ProxyState>,
+    axum::extract::RawQuery(query): axum::extract::RawQuery,
+) -> Response {
+    let qs = query.map(|q| format!("?{}", q)).unwrap_or_default();
+    proxy_get(&st, "/api/history/columns", &qs).await
+}
+
+async fn proxy_histogram(
+    State(st): State<ProxyState>,
+    axum::extract::RawQuery(query): axum::extract::RawQuery,
+) -> Response {
+    let qs = query.map(|q| format!("?{}", q)).unwrap_or_default();
+    proxy_get(&st, "/api/histogram", &qs).await
+}
+
+async fn proxy_cpu_heatmap(
+    State(st): State<ProxyState>,
+    axum::extract::RawQuery(query): axum::extract::RawQuery,
+) -> Response {
+    let qs = query.map(|q| format!("?{}", q)).unwrap_or_default();
+    proxy_get(&st, "/api/heatmap/cpu", &qs).await
+}
+
+async fn proxy_compare_stats(
+    State(st): State<ProxyState>,
+    axum::extract::RawQuery(query): axum::extract::RawQuery,
+) -> Response {
+    let qs = query.map(|q| format!("?{}", q)).unwrap_or_default();
+    proxy_get(&st, "/api/stats/compare", &qs).await
+}
+
+async fn proxy_alert_history(State(st): State<ProxyState>) -> Response {
+    proxy_get(&st, "/api/alerts/history", "").await
+}
+
+async fn proxy_ack_alert(
+    State(st): State<ProxyState>,
+    axum::extract::Path(id): axum::extract::Path<u64>,
+) -> Response {
+    let url = format!("{}/api/alerts/{}/ack", st.api_url, id);
+    relay(st.http.post(&url)).await
+}
+
+async fn proxy_logs(
+    State(st): State<ProxyState>,
+    headers: axum::http::HeaderMap,
+    axum::extract::RawQuery(query): axum::extract::RawQuery,
+) -> Response {
+    let qs = query.map(|q| format!("?{}", q)).unwrap_or_default();
+    let mut request = st.http.get(format!("{}/api/logs{}", st.api_url, qs));
+    if let Some(auth) = headers.get(axum::http::header::AUTHORIZATION) {
+        request = request.header(reqwest::header::AUTHORIZATION, auth.as_bytes());
+    }
+    relay(request).await
+}
+
 async fn proxy_get(st: &ProxyState, path: &str, query: &str) -> Response {
     let url = format!("{}{}{}", st.api_url, path, query);
-    match st.http.get(&url).send().await {
+    relay(st.http.get(&url)).await
+}
+
+/// Sends `request` to the server and passes its status, content type and body through.
+async fn relay(request: reqwest::RequestBuilder) -> Response {
+    match request.send().await {
         Ok(resp) => {
             let status = StatusCode::from_u16(resp.status().as_u16()).unwrap_or(StatusCode::OK);
             let content_type = resp
@@ -189,17 +501,23 @@ async fn proxy_get(st: &ProxyState, path: &str, query: &str) -> Response {
                     .header("content-type", content_type)
                     .body(Body::from(body))
                     .unwrap_or_else(|_| {
-                        (StatusCode::INTERNAL_SERVER_ERROR, "response build error").into_response()
+                        ApiError::new(ErrorCode::Internal, "response build error").into_response()
                     }),
-                Err(e) => (StatusCode::BAD_GATEWAY, format!("read error: {e}")).into_response(),
+                Err(e) => {
+                    ApiError::new(ErrorCode::Upstream, format!("read error: {e}")).into_response()
+                }
             }
         }
-        Err(e) => (StatusCode::BAD_GATEWAY, format!("proxy error: {e}")).into_response(),
+        Err(e) => ApiError::new(ErrorCode::Upstream, format!("proxy error: {e}")).into_response(),
     }
 }
 
-async fn proxy_stream(State(st): State<ProxyState>) -> Response {
-    let url = format!("{}/api/stream", st.api_url);
+async fn proxy_stream(
+    State(st): State<ProxyState>,
+    axum::extract::RawQuery(query): axum::extract::RawQuery,
+) -> Response {
+    let qs = query.map(|q| format!("?{}", q)).unwrap_or_default();
+    let url = format!("{}/api/stream{}", st.api_url, qs);
     match st.http.get(&url).send().await {
         Ok(resp) => {
             let byte_stream = resp.bytes_stream();
@@ -209,9 +527,45 @@ async fn proxy_stream(State(st): State<ProxyState>) -> Response {
                 .header("cache-control", "no-cache")
                 .body(Body::from_stream(body_stream))
                 .unwrap_or_else(|_| {
-                    (StatusCode::INTERNAL_SERVER_ERROR, "stream build error").into_response()
+                    ApiError::new(ErrorCode::Internal, "stream build error").into_response()
                 })
         }
-        Err(e) => (StatusCode::BAD_GATEWAY, format!("proxy error: {e}")).into_response(),
+        Err(e) => ApiError::new(ErrorCode::Upstream, format!("proxy error: {e}")).into_response(),
     }
 }
+
+async fn proxy_ws(State(st): State<ProxyState>, ws: WebSocketUpgrade) -> Response {
+    // http://host -> ws://host, https://host -> wss://host
+    let url = format!("{}/api/ws", st.api_url.replacen("http", "ws", 1));
+    ws.on_upgrade(move |socket| async move {
+        let upstream = match tokio_tungstenite::connect_async(&url).await {
+            Ok((upstream, _)) => upstream,
+            Err(e) => {
+                error!("WebSocket proxy connect error to {}: {}", url, e);
+                return;
+            }
+        };
+        let (_up_tx, mut up_rx) = upstream.split();
+        let (mut down_tx, mut down_rx) = socket.split();
+        loop {
+            tokio::select! {
+                msg = up_rx.next() => match msg {
+                    Some(Ok(tokio_tungstenite::tungstenite::Message::Text(text))) => {
+                        if down_tx.send(Message::Text(text)).await.is_err() {
+                            break;
+                        }
+                    }
+                    Some(Ok(tokio_tungstenite::tungstenite::Message::Close(_)))
+                    | Some(Err(_))
+                    | None => break,
+                    Some(Ok(_)) => {}
+                },
+                msg = down_rx.next() => match msg {
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => {}
+                },
+            }
+        }
+        let _ = down_tx.send(Message::Close(None)).await;
+    })
+}
@@ -1,17 +1,46 @@
 use clap::Parser;
-use resource_monitor::aggregator::{Aggregator, AggregatorConfig};
-use resource_monitor::api::{api_only_router, AppState};
+use resource_monitor::aggregator::{
+    spawn_labeled_aggregator, AggregatorConfig, IntervalAutoTune, PublishGate, SyntheticSource,
+    DEFAULT_AUTO_TUNE_FRACTION, DEFAULT_AUTO_TUNE_MAX_MS, DEFAULT_HEARTBEAT_MS,
+};
+use resource_monitor::alerts::{AlertTracker, DEFAULT_ALERT_HISTORY};
+use resource_monitor::api::{
+    api_only_router, router, AppState, Sampling, DEFAULT_GAP_FACTOR, DEFAULT_MAX_BROWSER_POINTS,
+};
+use resource_monitor::check::{self, CheckReport};
+use resource_monitor::config::{
+    Aggregations, ByteUnits, ChangeDeltas, CpuDisplay, CpuTotalMethod, DiskUsageBasis,
+    DisplayUnits, FsyncPolicy, HttpLimits, NetAxes, NetScale, NetScaleMode, NetUnits,
+    OverflowPolicy, PressureWeights, ProcessSelector, ResourceCriticality, SharedThresholds,
+    StorageBackend, Tag, Threshold, Thresholds,
+};
 use resource_monitor::console;
 use resource_monitor::db::MetricsDb;
-use resource_monitor::metrics::{MetricsSnapshot, RpcMetricsSnapshot};
+use resource_monitor::journal::{
+    register_journal_subscriber, replay, run_journal_flusher, SnapshotJournal,
+    DEFAULT_PERSIST_FLUSH_MS,
+};
+use resource_monitor::monitor::Monitor;
+use resource_monitor::net::{bind_tokio_listener, ListenOptions, DEFAULT_BACKLOG};
+use resource_monitor::reload::{ConfigFile, Reloader, Settings};
+use resource_monitor::rpc::MetricsRpcServer;
 use resource_monitor::runtime;
-use resource_monitor::storage::MetricsBuffer;
+#[cfg(unix)]
+use resource_monitor::sink::{register_sink_subscriber, SocketSink};
+use resource_monitor::sqlite_store::SqliteStore;
+use resource_monitor::statsd::{register_statsd_subscriber, StatsdSink};
+use resource_monitor::storage::{MetricsBuffer, SnapshotStore};
+use resource_monitor::topology::CoreTopology;
+use resource_monitor::web;
 use std::net::{IpAddr, SocketAddr};
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use tokio_util::sync::CancellationToken;
-use tracing::{error, info};
+use tracing::{debug, error, info, warn};
+
+/// How often `--compact-after-ms` compaction runs.
+const COMPACT_PERIOD: Duration = Duration::from_secs(60);
 
 #[derive(Debug, Parser)]
 #[command(
@@ -23,14 +52,142 @@ struct Args {
     #[arg(long, default_value_t = 1000)]
     interval_ms: u64,
 
+    /// Round snapshot timestamps to the nearest multiple of the interval
+    #[arg(long, default_value_t = false)]
+    align_timestamps: bool,
+
+    /// Sample on wall-clock multiples of the interval (e.g. at :00, :01, :02
+    /// seconds) instead of at offsets from startup
+    #[arg(long, default_value_t = false)]
+    align_ticks: bool,
+
+    /// Treat network rates above this many bytes/s as counter glitches
+    #[arg(long)]
+    net_rate_max: Option<f32>,
+
+    /// How the total CPU usage is derived from the per-core usages
+    #[arg(long, value_enum, default_value_t = CpuTotalMethod::Mean)]
+    cpu_total_method: CpuTotalMethod,
+
+    /// What the disk used percentage is computed from: total minus
+    /// available, used bytes over total, or used over used plus available
+    /// as `df` reports it
+    #[arg(long, value_enum, default_value_t = DiskUsageBasis::Available)]
+    disk_used_basis: DiskUsageBasis,
+
+    /// Track a process (name or pid) with all its descendants, summing CPU
+    /// and resident memory into each snapshot
+    #[arg(long)]
+    watch_process: Option<ProcessSelector>,
+
+    /// List this many processes moving the most TCP traffic in each snapshot
+    /// (Linux; best-effort, other users' processes need root)
+    #[arg(long)]
+    net_top_processes: Option<usize>,
+
+    /// Report mean CPU usage per socket and group the console's per-core
+    /// lines by socket (Linux topology; one group elsewhere)
+    #[arg(long, default_value_t = false)]
+    group_cores_by_socket: bool,
+
+    /// Publish a snapshot only when a metric moved by at least its delta since
+    /// the last published one, e.g. `cpu=5,mem=2,disk=1,net=100000`
+    /// (percentage points; net in bytes/s)
+    #[arg(long)]
+    publish_on_change: Option<ChangeDeltas>,
+
+    /// With --publish-on-change, publish at least this often even when
+    /// nothing changed
+    #[arg(long, default_value_t = DEFAULT_HEARTBEAT_MS, requires = "publish_on_change")]
+    heartbeat_ms: u64,
+
+    /// Lengthen the sampling interval when collection keeps taking more than
+    /// --auto-tune-fraction of it, so the monitor cannot eat the CPU itself
+    #[arg(long, default_value_t = false)]
+    auto_tune_interval: bool,
+
+    /// With --auto-tune-interval, share of the interval collection may take
+    /// before the interval is doubled
+    #[arg(long, default_value_t = DEFAULT_AUTO_TUNE_FRACTION, requires = "auto_tune_interval")]
+    auto_tune_fraction: f32,
+
+    /// With --auto-tune-interval, the longest interval it may reach
+    #[arg(long, default_value_t = DEFAULT_AUTO_TUNE_MAX_MS, requires = "auto_tune_interval")]
+    auto_tune_max_ms: u64,
+
+    /// Treat a sample this many intervals after the previous one as the
+    /// first after a suspend/resume: its rates are zeroed and it is flagged
+    /// `resumed` instead of showing one giant spike
+    #[arg(long)]
+    resume_gap_factor: Option<f32>,
+
+    /// Label every snapshot with `key=value` (repeatable), e.g.
+    /// `--tag datacenter=fra1 --tag role=web`; `/api/history` and
+    /// `/api/stream` filter on them with `?tag.role=web`
+    #[arg(long = "tag")]
+    tags: Vec<Tag>,
+
+    /// Weights of CPU, memory, disk and swap usage in the 0-100 pressure
+    /// score, e.g. `cpu=0.4,mem=0.3,disk=0.2,swap=0.1` (the default)
+    #[arg(long, default_value_t = PressureWeights::default())]
+    pressure_weights: PressureWeights,
+
+    /// Also run a synthetic collector whose snapshots carry this source
+    /// label, into the same buffer and streams (repeatable; for developing
+    /// multi-source views without more machines)
+    #[arg(long)]
+    synthetic_source: Vec<String>,
+
+    /// Number of initial samples to discard (their rates have no baseline)
+    #[arg(long, default_value_t = 1)]
+    warmup_samples: u32,
+
     /// History depth (number of snapshots kept in memory)
     #[arg(long, default_value_t = 3600)]
     history: usize,
 
+    /// Keep per-core CPU usage only for this many newest snapshots (totals keep full history)
+    #[arg(long)]
+    per_core_history: Option<usize>,
+
+    /// Hold per-core CPU usage in memory sparsely: only cores that moved by
+    /// more than this many percentage points since the previous snapshot
+    /// (0 keeps the history exact)
+    #[arg(long)]
+    sparse_per_core: Option<f32>,
+
+    /// What a full history does with new snapshots: drop the oldest, or
+    /// keep the earliest and reject new ones
+    #[arg(long, value_enum, default_value_t = OverflowPolicy::DropOldest)]
+    history_overflow: OverflowPolicy,
+
+    /// Downsample buffered snapshots older than this many ms (relative to the
+    /// newest) to 1 in --compact-factor; disabled when unset. This keeps
+    /// every n-th snapshot as it is, ignoring --downsample
+    #[arg(long)]
+    compact_after_ms: Option<u64>,
+
+    /// Keep 1 in this many snapshots when compacting old history
+    #[arg(long, default_value_t = 10)]
+    compact_factor: usize,
+
     /// RPC bind address
     #[arg(long, default_value = "127.0.0.1:50051")]
     rpc_addr: SocketAddr,
 
+    /// Pending connections the RPC listener queues before refusing new ones
+    #[arg(long, default_value_t = DEFAULT_BACKLOG)]
+    rpc_backlog: u32,
+
+    /// Enable TCP keepalive on RPC connections, probing after this many idle seconds
+    #[arg(long)]
+    rpc_keepalive_secs: Option<u64>,
+
+    /// Open RPC connections served at once; further ones are closed as soon
+    /// as they are accepted. Unlimited when unset
+    #[arg(long)]
+    rpc_max_connections: Option<usize>,
+
     /// HTTP bind address
     #[arg(long, default_value = "127.0.0.1")]
     bind: IpAddr,
@@ -39,14 +196,170 @@ struct Args {
     #[arg(long, default_value_t = 9000)]
     port: u16,
 
+    /// Pending connections the HTTP listener queues before refusing new ones
+    #[arg(long, default_value_t = DEFAULT_BACKLOG)]
+    http_backlog: u32,
+
+    /// Enable TCP keepalive on HTTP connections, probing after this many idle seconds
+    #[arg(long)]
+    http_keepalive_secs: Option<u64>,
+
+    /// Serve the HTTP API under this path prefix (e.g. /monitor) for reverse proxies
+    #[arg(long, default_value = "")]
+    base_path: String,
+
     /// Disable HTTP API server
     #[arg(long, default_value_t = false)]
     no_http: bool,
 
+    /// All-in-one mode for a single host: serve the dashboard straight from
+    /// the in-memory history and do not start the RPC server
+    #[arg(long, default_value_t = false)]
+    standalone: bool,
+
+    /// Deadline for non-streaming HTTP requests, in milliseconds (408 when exceeded)
+    #[arg(long, default_value_t = 30_000)]
+    request_timeout_ms: u64,
+
+    /// Largest accepted HTTP request body, in bytes (413 when exceeded)
+    #[arg(long, default_value_t = 1024 * 1024)]
+    max_body_bytes: usize,
+
+    /// Bearer token required by protected endpoints such as /api/logs (disabled when unset)
+    #[arg(long, env = "RESOURCE_MONITOR_API_TOKEN")]
+    api_token: Option<String>,
+
+    /// PEM certificate chain; serves the HTTP API over HTTPS (requires --tls-key)
+    #[arg(long, requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+
+    /// PEM private key for --tls-cert
+    #[arg(long, requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+
     /// Also show console output
     #[arg(long, default_value_t = false)]
     console: bool,
 
+    /// Console repaint period in ms; samples arriving in between are
+    /// coalesced into one frame (default: the sampling interval)
+    #[arg(long)]
+    console_refresh_ms: Option<u64>,
+
+    /// Console peak-hold window in snapshots (default: the whole history buffer)
+    #[arg(long)]
+    console_peak_window: Option<usize>,
+
+    /// Show per-core lines only for this many of the busiest cores, plus a
+    /// summary of the rest (default: as many as fit the terminal)
+    #[arg(long)]
+    console_max_cores: Option<usize>,
+
+    /// Unit for network rates in the console (bytes/bits)
+    #[arg(long, value_enum, default_value_t = NetUnits::Bytes)]
+    net_units: NetUnits,
+
+    /// Prefixes for byte sizes in the console: iec (KiB, MiB, powers of
+    /// 1024) or si (KB, MB, powers of 1000)
+    #[arg(long, value_enum, default_value_t = ByteUnits::Iec)]
+    byte_units: ByteUnits,
+
+    /// Total CPU in the console: percent of capacity, or cores (also how
+    /// many cores' worth are busy, e.g. 3.4 of 8)
+    #[arg(long, value_enum, default_value_t = CpuDisplay::Percent)]
+    cpu_display: CpuDisplay,
+
+    /// History the dashboard loads when opened, in ms (0 loads everything);
+    /// older data is fetched when the view is widened
+    #[arg(long, default_value_t = 180_000)]
+    initial_window_ms: u64,
+
+    /// Dashboard charts break the line where consecutive points are more
+    /// than this many sampling intervals apart
+    #[arg(long, default_value_t = DEFAULT_GAP_FACTOR)]
+    gap_factor: f32,
+
+    /// How downsampled series combine the points of a bucket, per metric,
+    /// e.g. `net=max,cpu=mean,disk=last` (mean/max/min/last). Unlisted
+    /// metrics keep their defaults: max for net, last for disk, mean
+    /// otherwise. Applies to the series the API and Grafana endpoints
+    /// downsample, not to --compact-after-ms, which drops whole snapshots
+    #[arg(long)]
+    downsample: Option<Aggregations>,
+
+    /// Points the dashboard keeps in browser memory before dropping the
+    /// oldest; lower it to save memory and rendering time
+    #[arg(long, default_value_t = DEFAULT_MAX_BROWSER_POINTS)]
+    max_browser_points: usize,
+
+    /// Default y-axis scaling of the dashboard network chart (auto/fixed/log)
+    #[arg(long, value_enum, default_value_t = NetScaleMode::Auto)]
+    net_scale: NetScaleMode,
+
+    /// Network chart ceiling in bytes/s for `--net-scale fixed`
+    #[arg(long)]
+    net_scale_max: Option<f64>,
+
+    /// Whether the dashboard network chart scales RX and TX on one shared
+    /// axis or on separate left/right axes (shared/split)
+    #[arg(long, value_enum, default_value_t = NetAxes::Shared)]
+    net_axes: NetAxes,
+
+    /// Namespace for exported metric names, e.g. `hostmon` exports the
+    /// Grafana target `cpu.total` as `hostmon.cpu.total`
+    #[arg(long)]
+    metrics_prefix: Option<String>,
+
+    /// CPU chart warning threshold (%)
+    #[arg(long, default_value_t = 70.0)]
+    cpu_warn: f32,
+
+    /// CPU chart critical threshold (%)
+    #[arg(long, default_value_t = 90.0)]
+    cpu_crit: f32,
+
+    /// Memory chart warning threshold (%)
+    #[arg(long, default_value_t = 70.0)]
+    mem_warn: f32,
+
+    /// Memory chart critical threshold (%)
+    #[arg(long, default_value_t = 90.0)]
+    mem_crit: f32,
+
+    /// A firing CPU alert clears only below this level (%); defaults to
+    /// --cpu-crit
+    #[arg(long)]
+    cpu_clear: Option<f32>,
+
+    /// A firing memory alert clears only below this level (%); defaults to
+    /// --mem-crit
+    #[arg(long)]
+    mem_clear: Option<f32>,
+
+    /// CPU must stay past a level this long (ms) before its alert fires or
+    /// clears
+    #[arg(long, default_value_t = 0)]
+    cpu_alert_min_ms: u64,
+
+    /// Memory must stay past a level this long (ms) before its alert fires
+    /// or clears
+    #[arg(long, default_value_t = 0)]
+    mem_alert_min_ms: u64,
+
+    /// /api/health reports the node degraded while disk use is at or above
+    /// this level (%), so orchestrators can drain it
+    #[arg(long)]
+    health_disk_crit: Option<f32>,
+
+    /// /api/health reports the node degraded while swap use is at or above
+    /// this level (%)
+    #[arg(long)]
+    health_swap_crit: Option<f32>,
+
+    /// Snapshot storage backend (memory/sqlite)
+    #[arg(long, value_enum, default_value_t = StorageBackend::Memory)]
+    storage: StorageBackend,
+
     /// Path to SQLite database file
     #[arg(long, default_value = "metrics.db")]
     db_path: PathBuf,
@@ -54,17 +367,80 @@ struct Args {
     /// Automatically cleanup old records after N hours (0 to disable)
     #[arg(long, default_value_t = 168)] // 7 days
     db_cleanup_hours: u64,
+
+    /// JSON file with thresholds, interval and addresses; re-read on SIGHUP
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Also write each snapshot as a JSON line to this Unix datagram socket
+    /// or FIFO; snapshots are dropped while the reader is not keeping up
+    #[cfg(unix)]
+    #[arg(long)]
+    sink_socket: Option<PathBuf>,
+
+    /// Append each snapshot as a JSON line to this file
+    #[arg(long)]
+    persist_file: Option<PathBuf>,
+
+    /// How often buffered --persist-file lines are written out
+    #[arg(long, default_value_t = DEFAULT_PERSIST_FLUSH_MS, requires = "persist_file")]
+    persist_flush_ms: u64,
+
+    /// When --persist-file writes are fsynced: never (fastest, a power loss
+    /// can drop unsynced lines), interval (lose at most one flush interval)
+    /// or always (sync every snapshot)
+    #[arg(long, value_enum, default_value_t = FsyncPolicy::Interval, requires = "persist_file")]
+    persist_fsync: FsyncPolicy,
+
+    /// On startup, load the existing --persist-file (plain or
+    /// zstd-compressed) into the history buffer; a truncated or malformed
+    /// last line is skipped
+    #[arg(long, default_value_t = false, requires = "persist_file")]
+    persist_replay: bool,
+
+    /// Send each snapshot as StatsD gauges over UDP to this address
+    #[arg(long)]
+    statsd_addr: Option<SocketAddr>,
+
+    /// Namespace for StatsD gauge names, e.g. `hostmon` for `hostmon.cpu.total`
+    #[arg(long, requires = "statsd_addr")]
+    statsd_prefix: Option<String>,
+
+    /// Validate config, binds and file access, print a report and exit
+    #[arg(long, default_value_t = false)]
+    check: bool,
 }
 
 #[tokio::main(flavor = "current_thread")]
 async fn main() {
-    runtime::init_tracing();
-    let args = Args::parse();
+    let log_ring = runtime::init_tracing();
+    let mut args = Args::parse();
+    // A config file that fails to load is reported by the reloader below.
+    if let Some(file) = args
+        .config
+        .as_ref()
+        .and_then(|path| ConfigFile::load(path).ok())
+    {
+        file.apply_listen_addrs(&mut args.bind, &mut args.port, &mut args.rpc_addr);
+    }
+    if args.check {
+        let report = run_checks(&args).await;
+        print!("{}", report.render());
+        std::process::exit(if report.passed() { 0 } else { 1 });
+    }
+    if let Err(e) = validate_args(&args) {
+        error!("Invalid arguments: {}", e);
+        std::process::exit(1);
+    }
     info!(
         "Starting server: interval={}ms, history={}, rpc={}, http={}:{}, http_enabled={}, console={}, db={}",
         args.interval_ms,
         args.history,
-        args.rpc_addr,
+        if args.standalone {
+            "off (standalone)".to_string()
+        } else {
+            args.rpc_addr.to_string()
+        },
         args.bind,
         args.port,
         !args.no_http,
@@ -80,8 +456,21 @@ async fn main() {
         }
     };
 
+    let store = if args.storage == StorageBackend::Sqlite {
+        match SqliteStore::open(&args.db_path) {
+            Ok(store) => Some(Arc::new(store)),
+            Err(e) => {
+                error!("Failed to initialize SQLite snapshot store: {}", e);
+                return;
+            }
+        }
+    } else {
+        None
+    };
+
     if args.db_cleanup_hours > 0 {
         let db_clone = db.clone();
+        let store = store.clone();
         let cleanup_interval = Duration::from_secs(3600);
         tokio::spawn(async move {
             let mut interval = tokio::time::interval(cleanup_interval);
@@ -90,78 +479,339 @@ async fn main() {
                 if let Err(e) = db_clone.cleanup_old(args.db_cleanup_hours) {
                     error!("Database cleanup failed: {}", e);
                 }
+                if let Some(store) = &store {
+                    if let Err(e) = store.cleanup_old(args.db_cleanup_hours) {
+                        error!("SQLite snapshot store cleanup failed: {}", e);
+                    }
+                }
             }
         });
     }
 
-    let buffer = Arc::new(MetricsBuffer::new(args.history));
+    let buffer = MetricsBuffer::new(args.history).with_overflow_policy(args.history_overflow);
+    let buffer = match args.per_core_history {
+        Some(keep) => buffer.with_per_core_retention(keep),
+        None => buffer,
+    };
+    let buffer = match args.sparse_per_core {
+        Some(threshold) => buffer.with_sparse_per_core(threshold),
+        None => buffer,
+    };
+    let buffer = Arc::new(buffer);
     let cancel = CancellationToken::new();
 
-    let (rpc_stream_tx, _) = tokio::sync::broadcast::channel::<RpcMetricsSnapshot>(256);
-    let (internal_stream_tx, _) = tokio::sync::broadcast::channel::<MetricsSnapshot>(256);
-
-    let _storage_activity = resource_monitor::bus::register_storage_subscriber_with_channel(
-        buffer.clone(),
-        internal_stream_tx.clone(),
-    );
-
-    let agg = Aggregator::new(AggregatorConfig::new(Duration::from_millis(
-        args.interval_ms,
-    )));
-    let agg_cancel = cancel.clone();
-    let agg_handle = tokio::spawn(async move { agg.run(agg_cancel).await });
-
-    let db_rx = internal_stream_tx.subscribe();
-    let db_writer_handle = {
-        let db = db.clone();
+    if let Some(after_ms) = args.compact_after_ms {
+        let buffer = buffer.clone();
+        let cancel = cancel.clone();
+        let factor = args.compact_factor;
         tokio::spawn(async move {
-            let mut rx = db_rx;
-            while let Ok(snapshot) = rx.recv().await {
-                if let Err(e) = db.insert(&snapshot) {
-                    error!("Failed to insert snapshot into database: {}", e);
+            let mut interval = tokio::time::interval(COMPACT_PERIOD);
+            loop {
+                tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    _ = interval.tick() => {}
+                }
+                let removed = buffer.compact(u128::from(after_ms), factor);
+                if removed > 0 {
+                    debug!("Compaction removed {} old snapshots", removed);
                 }
             }
-            info!("Database writer stopped");
+        });
+    }
+
+    #[cfg(unix)]
+    let sink = match args.sink_socket.as_ref().map(SocketSink::open) {
+        Some(Ok(sink)) => {
+            info!("Writing snapshots to sink {}", sink.path().display());
+            Some(sink)
+        }
+        Some(Err(e)) => {
+            error!("{}", e);
+            return;
+        }
+        None => None,
+    };
+    if let Some(path) = args.persist_file.as_ref().filter(|_| args.persist_replay) {
+        match replay(path, |snapshot| {
+            buffer.push(snapshot);
+        }) {
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => warn!("Failed to replay persist file {}: {}", path.display(), e),
+        }
+    }
+    let journal = match args.persist_file.as_ref() {
+        Some(path) => match SnapshotJournal::open(path, args.persist_fsync) {
+            Ok(journal) => {
+                info!(
+                    "Appending snapshots to {} (fsync {:?})",
+                    path.display(),
+                    args.persist_fsync
+                );
+                Some(Arc::new(Mutex::new(journal)))
+            }
+            Err(e) => {
+                error!("Failed to open persist file {}: {}", path.display(), e);
+                return;
+            }
+        },
+        None => None,
+    };
+    let subscribed_journal = journal.clone();
+    let journal_flusher_handle = journal.map(|journal| {
+        tokio::spawn(run_journal_flusher(
+            journal,
+            Duration::from_millis(args.persist_flush_ms.max(1)),
+            cancel.clone(),
+        ))
+    });
+    let statsd = match args.statsd_addr {
+        Some(addr) => match StatsdSink::open(addr, args.statsd_prefix.clone()) {
+            Ok(sink) => {
+                info!("Sending StatsD gauges to {}", addr);
+                Some(sink)
+            }
+            Err(e) => {
+                error!("Failed to open StatsD socket for {}: {}", addr, e);
+                return;
+            }
+        },
+        None => None,
+    };
+
+    let cli_thresholds = Thresholds {
+        cpu: Threshold {
+            warn: Some(args.cpu_warn),
+            crit: Some(args.cpu_crit),
+            clear: args.cpu_clear,
+            min_duration_ms: Some(args.cpu_alert_min_ms).filter(|&ms| ms > 0),
+        },
+        memory: Threshold {
+            warn: Some(args.mem_warn),
+            crit: Some(args.mem_crit),
+            clear: args.mem_clear,
+            min_duration_ms: Some(args.mem_alert_min_ms).filter(|&ms| ms > 0),
+        },
+    };
+    let thresholds = SharedThresholds::new(cli_thresholds);
+    let (interval_tx, mut interval_rx) =
+        tokio::sync::watch::channel(Duration::from_millis(args.interval_ms));
+    let mut reloader = args.config.as_ref().map(|path| {
+        Reloader::new(
+            path,
+            Settings {
+                interval: Duration::from_millis(args.interval_ms),
+                thresholds: cli_thresholds,
+                bind: args.bind,
+                port: args.port,
+                rpc_addr: args.rpc_addr,
+            },
+            thresholds.clone(),
+            interval_tx,
+        )
+    });
+    if let Some(reloader) = reloader.as_mut() {
+        if let Err(e) = reloader.reload() {
+            error!("Failed to load config file: {}", e);
+            return;
+        }
+    }
+    let interval = *interval_rx.borrow_and_update();
+
+    let core_topology = args.group_cores_by_socket.then(CoreTopology::detect);
+    let aggregator_config = AggregatorConfig::new(interval)
+        .with_aligned_timestamps(args.align_timestamps)
+        .with_aligned_ticks(args.align_ticks)
+        .with_net_rate_max(args.net_rate_max)
+        .with_cpu_total_method(args.cpu_total_method)
+        .with_disk_usage_basis(args.disk_used_basis)
+        .with_watch_process(args.watch_process.clone())
+        .with_net_top_processes(args.net_top_processes)
+        .with_core_topology(core_topology.clone())
+        .with_warmup_samples(args.warmup_samples)
+        .with_pressure_weights(args.pressure_weights)
+        .with_publish_gate(
+            args.publish_on_change
+                .map(|deltas| PublishGate::new(deltas, args.heartbeat_ms)),
+        )
+        .with_auto_tune(args.auto_tune_interval.then(|| {
+            IntervalAutoTune::new(
+                args.auto_tune_fraction,
+                Duration::from_millis(args.auto_tune_max_ms),
+            )
+        }))
+        .with_resume_gap_factor(args.resume_gap_factor)
+        .with_tags(
+            args.tags
+                .iter()
+                .map(|tag| (tag.key.clone(), tag.value.clone()))
+                .collect(),
+        )
+        .with_interval_updates(interval_rx.clone());
+    let rpc_interval_rx = interval_rx.clone();
+    let http_interval_rx = interval_rx;
+    let alerts = Arc::new(
+        AlertTracker::new(thresholds.clone(), DEFAULT_ALERT_HISTORY).with_persistence(db.clone()),
+    );
+    let synthetic_sources = args.synthetic_source.clone();
+    let synthetic_cancel = cancel.clone();
+    let monitor = Monitor::builder()
+        .aggregator(aggregator_config)
+        .buffer(buffer.clone())
+        .db(db.clone())
+        // The SQLite store keeps history on its own; archiving as well would
+        // write every snapshot twice.
+        .archive(store.is_none())
+        .alerts(alerts.clone())
+        .on_collector_thread(move || {
+            #[cfg(unix)]
+            let sink = sink.map(register_sink_subscriber);
+            #[cfg(not(unix))]
+            let sink = ();
+            let journal = subscribed_journal.map(register_journal_subscriber);
+            let statsd = statsd.map(register_statsd_subscriber);
+            let synthetic: Vec<_> = synthetic_sources
+                .into_iter()
+                .enumerate()
+                .map(|(i, label)| {
+                    info!("Starting synthetic source '{}'", label);
+                    spawn_labeled_aggregator(
+                        label,
+                        AggregatorConfig::new(interval).with_warmup_samples(0),
+                        SyntheticSource::new(i as f32, 4),
+                        synthetic_cancel.clone(),
+                    )
+                })
+                .collect();
+            Box::new((sink, journal, statsd, synthetic))
         })
+        .build()
+        .start(cancel.clone())
+        .await;
+    let monitor = match monitor {
+        Ok(monitor) => monitor,
+        Err(e) => {
+            error!("Failed to start monitor: {}", e);
+            cancel.cancel();
+            return;
+        }
     };
+    let collector_health = monitor.collector_health().clone();
+    let rpc_stream_tx = monitor.stream_tx().clone();
 
-    let converter_rx = internal_stream_tx.subscribe();
-    let rpc_stream_tx_for_converter = rpc_stream_tx.clone();
-    let converter_handle = tokio::spawn(async move {
-        let mut rx = converter_rx;
-        while let Ok(snapshot) = rx.recv().await {
-            let rpc_snapshot = snapshot.to_rpc_format();
-            if let Err(e) = rpc_stream_tx_for_converter.send(rpc_snapshot) {
-                tracing::warn!("Failed to send to broadcast channel: {}", e);
+    let store_writer_handle = if let Some(store) = store.clone() {
+        let mut rx = monitor.subscribe_snapshots();
+        Some(tokio::spawn(async move {
+            loop {
+                match rx.recv().await {
+                    Ok(snapshot) if snapshot.source.is_none() => store.push(snapshot),
+                    Ok(_) => {}
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(n)) => {
+                        warn!(
+                            "SQLite snapshot store writer lagged, {} snapshots not stored",
+                            n
+                        );
+                    }
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                }
             }
-        }
-        info!("Converter stopped");
-    });
+            info!("SQLite snapshot store writer stopped");
+        }))
+    } else {
+        None
+    };
 
     let rpc_cancel = cancel.clone();
     let rpc_buffer = buffer.clone();
+    let rpc_collector = collector_health.clone();
     let rpc_addr = args.rpc_addr;
+    let rpc_listen = ListenOptions {
+        backlog: args.rpc_backlog,
+        keepalive: args.rpc_keepalive_secs.map(Duration::from_secs),
+    };
+    let rpc_max_connections = args.rpc_max_connections.map(|max| max.max(1));
     let rpc_stream_tx_for_server = rpc_stream_tx.clone();
-    let rpc_handle = tokio::spawn(async move {
-        resource_monitor::rpc::run_rpc_server(
-            rpc_buffer,
-            rpc_stream_tx_for_server,
-            rpc_addr,
-            rpc_cancel,
-        )
-        .await;
+    let rpc_handle = (!args.standalone).then(|| {
+        tokio::spawn(async move {
+            resource_monitor::rpc::run_rpc_server(
+                MetricsRpcServer::new(rpc_buffer, rpc_stream_tx_for_server)
+                    .with_interval(rpc_interval_rx)
+                    .with_collector(rpc_collector),
+                rpc_addr,
+                rpc_listen,
+                rpc_max_connections,
+                rpc_cancel,
+            )
+            .await;
+        })
     });
 
     let web_handle = if !args.no_http {
+        let base_path = match web::normalize_base_path(&args.base_path) {
+            Ok(path) => path,
+            Err(e) => {
+                error!("--base-path {}", e);
+                cancel.cancel();
+                return;
+            }
+        };
         let state = AppState {
             buffer: buffer.clone(),
             db: db.clone(),
+            store: store.clone().map(|store| store as Arc<dyn SnapshotStore>),
             stream_tx: rpc_stream_tx.clone(),
             shutdown: cancel.clone(),
+            collector: collector_health.clone(),
+            thresholds,
+            alerts: alerts.clone(),
+            limits: HttpLimits {
+                request_timeout: Duration::from_millis(args.request_timeout_ms),
+                max_body_bytes: args.max_body_bytes,
+            },
+            logs: log_ring.clone(),
+            api_token: args.api_token.clone(),
+            net_scale: NetScale {
+                mode: args.net_scale,
+                max_bytes_per_sec: args.net_scale_max,
+                axes: args.net_axes,
+            },
+            requests: Default::default(),
+            initial_window_ms: args.initial_window_ms,
+            metrics_prefix: args.metrics_prefix.clone(),
+            sampling: Sampling {
+                interval: Some(http_interval_rx),
+                gap_factor: args.gap_factor,
+                aggregations: args.downsample.unwrap_or_default(),
+                max_browser_points: args.max_browser_points,
+            },
+            criticality: ResourceCriticality {
+                disk_used_pct: args.health_disk_crit,
+                swap_used_pct: args.health_swap_crit,
+            },
+        };
+        let app = if args.standalone {
+            router(state)
+        } else {
+            api_only_router(state)
+        };
+        let app = web::with_base_path(app, &base_path);
+        let tls = match (&args.tls_cert, &args.tls_key) {
+            (Some(cert), Some(key)) => match resource_monitor::tls::load_config(cert, key).await {
+                Ok(config) => Some(config),
+                Err(e) => {
+                    error!("Failed to load TLS certificate/key: {}", e);
+                    cancel.cancel();
+                    return;
+                }
+            },
+            _ => None,
         };
-        let app = api_only_router(state);
         let addr = SocketAddr::from((args.bind, args.port));
-        let listener = match tokio::net::TcpListener::bind(addr).await {
+        let listen = ListenOptions {
+            backlog: args.http_backlog,
+            keepalive: args.http_keepalive_secs.map(Duration::from_secs),
+        };
+        let listener = match bind_tokio_listener(addr, &listen) {
             Ok(l) => l,
             Err(e) => {
                 error!("Failed to bind HTTP {}: {}", addr, e);
@@ -170,14 +820,26 @@ async fn main() {
             }
         };
         info!(
-            "HTTP API listening on http://{}",
-            listener.local_addr().unwrap_or(addr)
+            "HTTP API listening on {}://{}{}",
+            if tls.is_some() { "https" } else { "http" },
+            listener.local_addr().unwrap_or(addr),
+            base_path
         );
         let shutdown = cancel.clone();
         Some(tokio::spawn(async move {
-            let res = axum::serve(listener, app)
-                .with_graceful_shutdown(async move { shutdown.cancelled().await })
-                .await;
+            let res = match tls {
+                Some(config) => match listener.into_std() {
+                    Ok(std_listener) => {
+                        resource_monitor::tls::serve(std_listener, app, config, shutdown).await
+                    }
+                    Err(e) => Err(e),
+                },
+                None => {
+                    axum::serve(listener, app)
+                        .with_graceful_shutdown(async move { shutdown.cancelled().await })
+                        .await
+                }
+            };
             if let Err(e) = res {
                 error!("HTTP server error: {}", e);
             }
@@ -189,15 +851,46 @@ async fn main() {
     let console_handle = if args.console {
         let console_cancel = cancel.clone();
         let console_buffer = buffer.clone();
-        let interval = Duration::from_millis(args.interval_ms);
+        let units = DisplayUnits {
+            net: args.net_units,
+            bytes: args.byte_units,
+            cpu: args.cpu_display,
+        };
+        let console_peak_window = args.console_peak_window;
+        let console_max_cores = args.console_max_cores;
+        let refresh = args
+            .console_refresh_ms
+            .map_or(interval, Duration::from_millis);
         Some(tokio::spawn(async move {
-            console::run_console(console_buffer, interval, console_cancel).await;
+            console::run_console(
+                console_buffer,
+                refresh,
+                units,
+                console_peak_window,
+                core_topology,
+                console_max_cores,
+                console_cancel,
+            )
+            .await;
             info!("Console stopped");
         }))
     } else {
         None
     };
 
+    tokio::spawn(runtime::reload_on_sighup(
+        cancel.clone(),
+        move || match reloader.as_mut() {
+            Some(reloader) => {
+                info!("SIGHUP received, reloading {}", reloader.path().display());
+                if let Err(e) = reloader.reload() {
+                    error!("Config reload failed, keeping current settings: {}", e);
+                }
+            }
+            None => warn!("SIGHUP received but no --config file was given"),
+        },
+    ));
+
     runtime::shutdown_signal().await;
     info!("Shutdown signal received, stopping server...");
     cancel.cancel();
@@ -209,36 +902,183 @@ async fn main() {
             info!("HTTP API shutdown timeout");
         }
     }
-    if tokio::time::timeout(shutdown_timeout, rpc_handle)
-        .await
-        .is_err()
-    {
-        info!("RPC server shutdown timeout");
-    }
-    if tokio::time::timeout(shutdown_timeout, converter_handle)
-        .await
-        .is_err()
-    {
-        info!("Converter shutdown timeout");
-    }
-    if tokio::time::timeout(shutdown_timeout, agg_handle)
-        .await
-        .is_err()
-    {
-        info!("Aggregator shutdown timeout");
+    if let Some(h) = rpc_handle {
+        if tokio::time::timeout(shutdown_timeout, h).await.is_err() {
+            info!("RPC server shutdown timeout");
+        }
     }
     if let Some(h) = console_handle {
         if tokio::time::timeout(shutdown_timeout, h).await.is_err() {
             info!("Console shutdown timeout");
         }
     }
-
-    if tokio::time::timeout(Duration::from_secs(2), db_writer_handle)
+    if tokio::time::timeout(shutdown_timeout, monitor.join())
         .await
         .is_err()
     {
-        info!("Database writer shutdown timeout");
+        info!("Collector shutdown timeout");
+    }
+
+    if let Some(h) = store_writer_handle {
+        if tokio::time::timeout(Duration::from_secs(2), h)
+            .await
+            .is_err()
+        {
+            info!("SQLite snapshot store writer shutdown timeout");
+        }
+    }
+    if let Some(h) = journal_flusher_handle {
+        if tokio::time::timeout(Duration::from_secs(2), h)
+            .await
+            .is_err()
+        {
+            info!("Journal flush shutdown timeout");
+        }
     }
 
     info!("Server stopped");
 }
+
+async fn run_checks(args: &Args) -> CheckReport {
+    let mut report = CheckReport::new();
+    report.record("config", validate_args(args));
+    if let Some(path) = &args.config {
+        report.record(
+            "config file",
+            ConfigFile::load(path).map(|_| path.display().to_string()),
+        );
+    }
+    #[cfg(unix)]
+    if let Some(path) = &args.sink_socket {
+        report.record(
+            "sink socket",
+            SocketSink::open(path).map(|_| path.display().to_string()),
+        );
+    }
+    report.record("sysinfo", check::check_sysinfo());
+    if !args.standalone {
+        report.record("rpc bind", check::check_bind(args.rpc_addr));
+    }
+    if !args.no_http {
+        report.record(
+            "http bind",
+            check::check_bind(SocketAddr::from((args.bind, args.port))),
+        );
+        if let (Some(cert), Some(key)) = (&args.tls_cert, &args.tls_key) {
+            report.record(
+                "tls",
+                resource_monitor::tls::load_config(cert, key)
+                    .await
+                    .map(|_| format!("{} / {}", cert.display(), key.display())),
+            );
+        }
+    }
+    report.record("database", check::check_database(&args.db_path));
+    if let Some(path) = &args.persist_file {
+        report.record(
+            "persist file",
+            SnapshotJournal::open(path, args.persist_fsync).map(|_| path.display().to_string()),
+        );
+    }
+    if args.storage == StorageBackend::Sqlite {
+        report.record("sqlite store", check::check_database(&args.db_path));
+    }
+    report
+}
+
+fn validate_args(args: &Args) -> Result<&'static str, String> {
+    if args.interval_ms == 0 {
+        return Err("--interval-ms must be greater than 0".to_string());
+    }
+    if args.persist_flush_ms == 0 {
+        return Err("--persist-flush-ms must be greater than 0".to_string());
+    }
+    if args.rpc_max_connections == Some(0) {
+        return Err("--rpc-max-connections must be greater than 0".to_string());
+    }
+    if args.rpc_backlog == 0 || args.http_backlog == 0 {
+        return Err("--rpc-backlog and --http-backlog must be greater than 0".to_string());
+    }
+    if args.rpc_keepalive_secs == Some(0) || args.http_keepalive_secs == Some(0) {
+        return Err(
+            "--rpc-keepalive-secs and --http-keepalive-secs must be greater than 0".to_string(),
+        );
+    }
+    if args.history == 0 {
+        return Err("--history must be greater than 0".to_string());
+    }
+    web::normalize_base_path(&args.base_path).map_err(|e| format!("--base-path {e}"))?;
+    if args.console_refresh_ms == Some(0) {
+        return Err("--console-refresh-ms must be greater than 0".to_string());
+    }
+    if args.net_top_processes == Some(0) {
+        return Err("--net-top-processes must be greater than 0".to_string());
+    }
+    if args
+        .sparse_per_core
+        .is_some_and(|threshold| !threshold.is_finite() || threshold < 0.0)
+    {
+        return Err("--sparse-per-core must be 0 or more".to_string());
+    }
+    if args.compact_factor < 2 {
+        return Err("--compact-factor must be at least 2".to_string());
+    }
+    if args.request_timeout_ms == 0 {
+        return Err("--request-timeout-ms must be greater than 0".to_string());
+    }
+    if args
+        .net_scale_max
+        .is_some_and(|max| !max.is_finite() || max <= 0.0)
+    {
+        return Err("--net-scale-max must be greater than 0".to_string());
+    }
+    if let Some(prefix) = &args.metrics_prefix {
+        if !resource_monitor::grafana::is_valid_metrics_prefix(prefix) {
+            return Err(format!(
+                "--metrics-prefix '{prefix}' must be letters, digits and '_', not starting with a digit"
+            ));
+        }
+    }
+    if let Some(prefix) = &args.statsd_prefix {
+        if !resource_monitor::grafana::is_valid_metrics_prefix(prefix) {
+            return Err(format!(
+                "--statsd-prefix '{prefix}' must be letters, digits and '_', not starting with a digit"
+            ));
+        }
+    }
+    if let Some(factor) = args.resume_gap_factor {
+        if !factor.is_finite() || factor <= 1.0 {
+            return Err("--resume-gap-factor must be greater than 1".to_string());
+        }
+    }
+    if !(args.auto_tune_fraction > 0.0 && args.auto_tune_fraction <= 1.0) {
+        return Err("--auto-tune-fraction must be in (0, 1]".to_string());
+    }
+    if !args.gap_factor.is_finite() || args.gap_factor < 1.0 {
+        return Err("--gap-factor must be at least 1".to_string());
+    }
+    if args.max_browser_points == 0 {
+        return Err("--max-browser-points must be at least 1".to_string());
+    }
+    if args.cpu_warn > args.cpu_crit {
+        return Err("--cpu-warn must not exceed --cpu-crit".to_string());
+    }
+    if args.mem_warn > args.mem_crit {
+        return Err("--mem-warn must not exceed --mem-crit".to_string());
+    }
+    if args.cpu_clear.is_some_and(|clear| clear > args.cpu_crit) {
+        return Err("--cpu-clear must not exceed --cpu-crit".to_string());
+    }
+    if args.mem_clear.is_some_and(|clear| clear > args.mem_crit) {
+        return Err("--mem-clear must not exceed --mem-crit".to_string());
+    }
+    for (flag, level) in [
+        ("--health-disk-crit", args.health_disk_crit),
+        ("--health-swap-crit", args.health_swap_crit),
+    ] {
+        if level.is_some_and(|pct| !(0.0..=100.0).contains(&pct)) {
+            return Err(format!("{flag} must be between 0 and 100"));
+        }
+    }
+    Ok("")
+}
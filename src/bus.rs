@@ -2,11 +2,38 @@ use crate::metrics::{MetricsSnapshot, RpcMetricsSnapshot};
 use crate::storage::MetricsBuffer;
 use std::sync::Arc;
 use tokio::sync::broadcast;
-use tracing::warn;
+use tracing::trace;
 
 #[derive(Clone, Debug)]
 pub struct MetricsEvent(pub MetricsSnapshot);
 
+/// Rewrites a snapshot before any subscriber sees it.
+pub type SnapshotTransform = dyn Fn(MetricsSnapshot) -> MetricsSnapshot + Send + Sync;
+
+/// Transforms run in order on each snapshot before it is published, e.g. to
+/// redact fields or add derived values before storage and streaming. Owned
+/// by whoever publishes, see [`AggregatorConfig::with_transform`], since the
+/// bus itself is per-thread.
+///
+/// [`AggregatorConfig::with_transform`]: crate::aggregator::AggregatorConfig::with_transform
+#[derive(Clone, Default)]
+pub struct TransformChain(Vec<Arc<SnapshotTransform>>);
+
+impl TransformChain {
+    pub fn push(
+        &mut self,
+        transform: impl Fn(MetricsSnapshot) -> MetricsSnapshot + Send + Sync + 'static,
+    ) {
+        self.0.push(Arc::new(transform));
+    }
+
+    pub fn apply(&self, snapshot: MetricsSnapshot) -> MetricsSnapshot {
+        self.0
+            .iter()
+            .fold(snapshot, |snap, transform| transform(snap))
+    }
+}
+
 pub fn register_storage_subscriber(
     buffer: Arc<MetricsBuffer>,
 ) -> nuts::ActivityId<Arc<MetricsBuffer>> {
@@ -27,9 +54,7 @@ pub fn register_storage_subscriber_with_channel(
         let snapshot = evt.0.clone();
         buf.push(snapshot.clone());
 
-        if let Err(e) = stream_tx.send(snapshot) {
-            warn!("Failed to broadcast snapshot to internal channel: {}", e);
-        }
+        send_to_subscribers(&stream_tx, || snapshot);
     });
     activity
 }
@@ -43,14 +68,23 @@ pub fn register_storage_and_stream_subscriber(
         let snapshot = evt.0.clone();
         buf.push(snapshot.clone());
 
-        let rpc_snapshot = snapshot.to_rpc_format();
-        if let Err(e) = stream_tx.send(rpc_snapshot) {
-            warn!("Failed to broadcast snapshot to stream: {}", e);
-        }
+        send_to_subscribers(&stream_tx, || snapshot.to_rpc_format());
     });
     activity
 }
 
+/// Broadcasts the value built by `make` when anyone is subscribed. A
+/// broadcast send only fails when there are no receivers, which is the normal
+/// state while no stream is open, so it is not reported above TRACE.
+pub fn send_to_subscribers<T>(tx: &broadcast::Sender<T>, make: impl FnOnce() -> T) {
+    if tx.receiver_count() == 0 {
+        return;
+    }
+    if tx.send(make()).is_err() {
+        trace!("Stream subscribers went away, snapshot not broadcast");
+    }
+}
+
 pub fn publish_snapshot(snapshot: MetricsSnapshot) {
     nuts::publish(MetricsEvent(snapshot));
 }
@@ -0,0 +1,147 @@
+//! Pre-flight checks behind `--check`: attempt each bind/open a real start
+//! would do, report the outcome, and exit without serving traffic.
+
+use crate::metrics::format_bytes_short;
+use rusqlite::{Connection, OpenFlags};
+use std::fmt::Display;
+use std::net::{SocketAddr, TcpListener};
+use std::path::Path;
+use std::time::Duration;
+use sysinfo::{CpuRefreshKind, MemoryRefreshKind, RefreshKind, System};
+
+#[derive(Debug, Default)]
+pub struct CheckReport {
+    results: Vec<(String, Result<String, String>)>,
+}
+
+impl CheckReport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record<T: Display, E: Display>(
+        &mut self,
+        name: impl Into<String>,
+        result: Result<T, E>,
+    ) {
+        let result = result.map(|ok| ok.to_string()).map_err(|e| e.to_string());
+        self.results.push((name.into(), result));
+    }
+
+    pub fn failures(&self) -> usize {
+        self.results.iter().filter(|(_, r)| r.is_err()).count()
+    }
+
+    pub fn passed(&self) -> bool {
+        self.failures() == 0
+    }
+
+    /// One line per check plus a summary line.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for (name, result) in &self.results {
+            match result {
+                Ok(detail) if detail.is_empty() => out.push_str(&format!("[ ok ] {name}\n")),
+                Ok(detail) => out.push_str(&format!("[ ok ] {name}: {detail}\n")),
+                Err(e) => out.push_str(&format!("[FAIL] {name}: {e}\n")),
+            }
+        }
+        if self.passed() {
+            out.push_str("check passed\n");
+        } else {
+            out.push_str(&format!(
+                "check failed: {} of {} checks failed\n",
+                self.failures(),
+                self.results.len()
+            ));
+        }
+        out
+    }
+}
+
+/// Binds `addr` and releases it immediately.
+pub fn check_bind(addr: SocketAddr) -> Result<String, String> {
+    let listener = TcpListener::bind(addr).map_err(|e| format!("cannot bind {addr}: {e}"))?;
+    let local = listener.local_addr().map_err(|e| e.to_string())?;
+    Ok(format!("{local} is free"))
+}
+
+/// Checks the SQLite file at `path` without creating it: an existing file is
+/// opened read-only and must be a writable database; a missing one needs a
+/// writable directory to be created in.
+pub fn check_database(path: &Path) -> Result<String, String> {
+    if path.exists() {
+        let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .map_err(|e| format!("cannot open {}: {e}", path.display()))?;
+        conn.query_row("SELECT count(*) FROM sqlite_master", [], |row| {
+            row.get::<_, i64>(0)
+        })
+        .map_err(|e| format!("{} is not a usable database: {e}", path.display()))?;
+        check_writable(path)?;
+        return Ok(format!("{} exists", path.display()));
+    }
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    if !dir.is_dir() {
+        return Err(format!("directory {} does not exist", dir.display()));
+    }
+    check_writable(dir)?;
+    Ok(format!("{} will be created", path.display()))
+}
+
+#[cfg(unix)]
+fn check_writable(path: &Path) -> Result<(), String> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = std::ffi::CString::new(path.as_os_str().as_bytes()).map_err(|e| e.to_string())?;
+    // SAFETY: `c_path` is NUL-terminated and outlives the call.
+    if unsafe { libc::access(c_path.as_ptr(), libc::W_OK) } != 0 {
+        return Err(format!(
+            "{} is not writable: {}",
+            path.display(),
+            std::io::Error::last_os_error()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_writable(path: &Path) -> Result<(), String> {
+    let metadata = std::fs::metadata(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    if metadata.permissions().readonly() {
+        return Err(format!("{} is read-only", path.display()));
+    }
+    Ok(())
+}
+
+/// Confirms sysinfo can see CPUs and memory on this host.
+pub fn check_sysinfo() -> Result<String, String> {
+    let mut sys = System::new_with_specifics(
+        RefreshKind::nothing()
+            .with_cpu(CpuRefreshKind::nothing())
+            .with_memory(MemoryRefreshKind::nothing().with_ram()),
+    );
+    sys.refresh_memory();
+    if sys.cpus().is_empty() {
+        return Err("no CPUs reported".to_string());
+    }
+    if sys.total_memory() == 0 {
+        return Err("total memory reported as 0".to_string());
+    }
+    Ok(format!(
+        "{} CPUs, {} memory",
+        sys.cpus().len(),
+        format_bytes_short(sys.total_memory())
+    ))
+}
+
+/// Opens (and drops) a TCP connection to `addr`.
+pub async fn check_connect(addr: SocketAddr, timeout: Duration) -> Result<String, String> {
+    match tokio::time::timeout(timeout, tokio::net::TcpStream::connect(addr)).await {
+        Ok(Ok(_)) => Ok(format!("{addr} reachable")),
+        Ok(Err(e)) => Err(format!("cannot connect to {addr}: {e}")),
+        Err(_) => Err(format!("timed out connecting to {addr}")),
+    }
+}
@@ -1,6 +1,10 @@
 use clap::{Parser, ValueEnum};
+use serde::{Deserialize, Serialize};
+use std::fmt;
 use std::net::IpAddr;
 use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
 use std::time::Duration;
 
 #[derive(Clone, Debug, ValueEnum)]
@@ -17,6 +21,613 @@ pub enum RpcMode {
     Client,
 }
 
+/// What the client binary runs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ClientMode {
+    /// Serve the dashboard and proxy the API
+    #[default]
+    Web,
+    /// Print one line per snapshot to stdout (via RPC)
+    Tap,
+}
+
+/// Line format for `--mode tap`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum TapFormat {
+    /// Tab-separated: timestamp_ms, cpu %, mem %, rx, tx
+    #[default]
+    Tsv,
+    /// One JSON object per line
+    Json,
+}
+
+/// Presentation unit for network rates; stored values are always bytes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NetUnits {
+    #[default]
+    Bytes,
+    Bits,
+}
+
+/// Prefix system for byte quantities: IEC (KiB, powers of 1024) or SI (KB,
+/// powers of 1000).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ByteUnits {
+    #[default]
+    Iec,
+    Si,
+}
+
+impl ByteUnits {
+    pub fn base(self) -> f64 {
+        match self {
+            Self::Iec => 1024.0,
+            Self::Si => 1000.0,
+        }
+    }
+
+    /// Prefixes for base^1 through base^4.
+    pub fn prefixes(self) -> [&'static str; 4] {
+        match self {
+            Self::Iec => ["Ki", "Mi", "Gi", "Ti"],
+            Self::Si => ["K", "M", "G", "T"],
+        }
+    }
+
+    /// `value` scaled to the largest prefix it reaches, e.g. `1.43 MiB` for
+    /// 1_500_000 with suffix `B`; plain values keep no decimals.
+    pub fn format(self, value: f64, suffix: &str) -> String {
+        let base = self.base();
+        let mut scaled = value;
+        let mut prefix = None;
+        for p in self.prefixes() {
+            if scaled.abs() < base {
+                break;
+            }
+            scaled /= base;
+            prefix = Some(p);
+        }
+        match prefix {
+            Some(p) => format!("{scaled:.2} {p}{suffix}"),
+            None => format!("{value:.0} {suffix}"),
+        }
+    }
+}
+
+/// How the console reports total CPU usage.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum CpuDisplay {
+    /// Percentage of total capacity, 0-100 whatever the core count
+    #[default]
+    Percent,
+    /// The percentage plus busy cores, the summed core usage / 100 (e.g.
+    /// 3.4 of 8)
+    Cores,
+}
+
+/// Units the console formats network rates, byte sizes and CPU usage in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DisplayUnits {
+    pub net: NetUnits,
+    pub bytes: ByteUnits,
+    pub cpu: CpuDisplay,
+}
+
+/// Y-axis scaling of the dashboard network chart.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NetScaleMode {
+    /// Fit the axis to the largest value in view
+    #[default]
+    Auto,
+    /// Keep the axis at a fixed ceiling so spikes do not flatten normal traffic
+    Fixed,
+    /// Logarithmic axis for wide dynamic ranges
+    Log,
+}
+
+/// How the receive and transmit lines of the network chart share its y-axis.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NetAxes {
+    /// One axis sized to the larger direction
+    #[default]
+    Shared,
+    /// RX on the left axis and TX on the right, each sized to its own peak
+    Split,
+}
+
+/// Process picked by `--watch-process`: a pid when the value is numeric,
+/// otherwise every process with that exact name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProcessSelector {
+    Pid(u32),
+    Name(String),
+}
+
+impl ProcessSelector {
+    pub fn matches(&self, pid: u32, name: &str) -> bool {
+        match self {
+            Self::Pid(want) => *want == pid,
+            Self::Name(want) => want == name,
+        }
+    }
+}
+
+impl FromStr for ProcessSelector {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err("process name or pid must not be empty".to_string());
+        }
+        Ok(s.parse()
+            .map_or_else(|_| Self::Name(s.to_string()), Self::Pid))
+    }
+}
+
+impl fmt::Display for ProcessSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Pid(pid) => write!(f, "{pid}"),
+            Self::Name(name) => f.write_str(name),
+        }
+    }
+}
+
+/// Per-metric change thresholds for `--publish-on-change`, parsed from e.g.
+/// `cpu=5,mem=2,disk=1,net=100000`. Percentages are in points; `net` is in
+/// bytes/s and applies to RX and TX separately. Unlisted metrics never
+/// trigger a publish on their own.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ChangeDeltas {
+    pub cpu_pct: Option<f32>,
+    pub mem_pct: Option<f32>,
+    pub disk_pct: Option<f32>,
+    pub net_bytes_per_sec: Option<f32>,
+}
+
+impl FromStr for ChangeDeltas {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut deltas = Self::default();
+        for part in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("expected metric=delta, got '{part}'"))?;
+            let value: f32 = value
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid delta '{}' for {}", value.trim(), key.trim()))?;
+            if !value.is_finite() || value < 0.0 {
+                return Err(format!("delta for {} must be non-negative", key.trim()));
+            }
+            let slot = match key.trim() {
+                "cpu" => &mut deltas.cpu_pct,
+                "mem" => &mut deltas.mem_pct,
+                "disk" => &mut deltas.disk_pct,
+                "net" => &mut deltas.net_bytes_per_sec,
+                other => {
+                    return Err(format!(
+                        "unknown metric '{other}', expected cpu, mem, disk or net"
+                    ))
+                }
+            };
+            *slot = Some(value);
+        }
+        if deltas == Self::default() {
+            return Err("at least one metric=delta is required".to_string());
+        }
+        Ok(deltas)
+    }
+}
+
+/// How the points that fall into one bucket are combined when a series is
+/// downsampled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Aggregation {
+    Mean,
+    Max,
+    Min,
+    Last,
+}
+
+/// Downsampling aggregation of each scalar metric (`--downsample`), parsed
+/// from e.g. `net=max,cpu=mean,disk=last`. By default rates keep their
+/// spikes (`max`), disk use keeps its current level (`last`) and the rest
+/// are averaged.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Aggregations {
+    pub cpu: Aggregation,
+    pub mem: Aggregation,
+    pub swap: Aggregation,
+    pub disk: Aggregation,
+    pub load: Aggregation,
+    /// RX and TX alike.
+    pub net: Aggregation,
+    pub gpu: Aggregation,
+}
+
+impl Default for Aggregations {
+    fn default() -> Self {
+        Self {
+            cpu: Aggregation::Mean,
+            mem: Aggregation::Mean,
+            swap: Aggregation::Mean,
+            disk: Aggregation::Last,
+            load: Aggregation::Mean,
+            net: Aggregation::Max,
+            gpu: Aggregation::Mean,
+        }
+    }
+}
+
+impl Aggregations {
+    /// Aggregation of the scalar metric `name` (`cpu`, `net_rx`, ... as in
+    /// `scalar_metric`); `mean` for names it does not know.
+    pub fn for_metric(&self, name: &str) -> Aggregation {
+        match name {
+            "cpu" => self.cpu,
+            "memory" => self.mem,
+            "swap" => self.swap,
+            "disk" => self.disk,
+            "load_1" => self.load,
+            "net_rx" | "net_tx" => self.net,
+            "gpu" => self.gpu,
+            _ => Aggregation::Mean,
+        }
+    }
+}
+
+impl FromStr for Aggregations {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut aggregations = Self::default();
+        for part in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("expected metric=aggregation, got '{part}'"))?;
+            let value = <Aggregation as ValueEnum>::from_str(value.trim(), true).map_err(|_| {
+                format!(
+                    "invalid aggregation '{}' for {}, expected mean, max, min or last",
+                    value.trim(),
+                    key.trim()
+                )
+            })?;
+            let slot = match key.trim() {
+                "cpu" => &mut aggregations.cpu,
+                "mem" => &mut aggregations.mem,
+                "swap" => &mut aggregations.swap,
+                "disk" => &mut aggregations.disk,
+                "load" => &mut aggregations.load,
+                "net" => &mut aggregations.net,
+                "gpu" => &mut aggregations.gpu,
+                other => {
+                    return Err(format!(
+                        "unknown metric '{other}', expected cpu, mem, swap, disk, load, net or gpu"
+                    ))
+                }
+            };
+            *slot = value;
+        }
+        Ok(aggregations)
+    }
+}
+
+/// One `--tag key=value` label attached to every snapshot.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Tag {
+    pub key: String,
+    pub value: String,
+}
+
+impl FromStr for Tag {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (key, value) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected key=value, got '{s}'"))?;
+        let key = key.trim();
+        if key.is_empty() {
+            return Err(format!("empty tag key in '{s}'"));
+        }
+        Ok(Self {
+            key: key.to_string(),
+            value: value.trim().to_string(),
+        })
+    }
+}
+
+/// Relative weights of CPU, memory, disk and swap usage in the composite
+/// `pressure_score`, parsed from e.g. `cpu=0.4,mem=0.3,disk=0.2,swap=0.1`.
+/// Metrics left out of the list weigh 0.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct PressureWeights {
+    pub cpu: f32,
+    pub mem: f32,
+    pub disk: f32,
+    pub swap: f32,
+}
+
+impl Default for PressureWeights {
+    fn default() -> Self {
+        Self {
+            cpu: 0.4,
+            mem: 0.3,
+            disk: 0.2,
+            swap: 0.1,
+        }
+    }
+}
+
+impl FromStr for PressureWeights {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut weights = Self {
+            cpu: 0.0,
+            mem: 0.0,
+            disk: 0.0,
+            swap: 0.0,
+        };
+        for part in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("expected metric=weight, got '{part}'"))?;
+            let value: f32 = value
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid weight '{}' for {}", value.trim(), key.trim()))?;
+            if !value.is_finite() || value < 0.0 {
+                return Err(format!("weight for {} must be non-negative", key.trim()));
+            }
+            let slot = match key.trim() {
+                "cpu" => &mut weights.cpu,
+                "mem" => &mut weights.mem,
+                "disk" => &mut weights.disk,
+                "swap" => &mut weights.swap,
+                other => {
+                    return Err(format!(
+                        "unknown metric '{other}', expected cpu, mem, disk or swap"
+                    ))
+                }
+            };
+            *slot = value;
+        }
+        if weights.total() <= 0.0 {
+            return Err("at least one weight must be positive".to_string());
+        }
+        Ok(weights)
+    }
+}
+
+impl fmt::Display for PressureWeights {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "cpu={},mem={},disk={},swap={}",
+            self.cpu, self.mem, self.disk, self.swap
+        )
+    }
+}
+
+impl PressureWeights {
+    pub fn total(&self) -> f32 {
+        self.cpu + self.mem + self.disk + self.swap
+    }
+}
+
+/// How `total_usage_pct` is derived from the per-core usages.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CpuTotalMethod {
+    /// Average over all cores
+    #[default]
+    Mean,
+    /// Busiest core, so a single pegged thread shows as 100%
+    Max,
+    /// 95th percentile core (nearest rank)
+    P95,
+}
+
+impl CpuTotalMethod {
+    /// Combines `per_core` into one percentage; 0 when there are no cores.
+    pub fn aggregate(self, per_core: &[f32]) -> f32 {
+        if per_core.is_empty() {
+            return 0.0;
+        }
+        match self {
+            Self::Mean => per_core.iter().sum::<f32>() / per_core.len() as f32,
+            Self::Max => per_core.iter().copied().fold(f32::NEG_INFINITY, f32::max),
+            Self::P95 => {
+                let mut sorted = per_core.to_vec();
+                sorted.sort_by(f32::total_cmp);
+                let rank = (sorted.len() as f64 * 0.95).ceil() as usize;
+                sorted[rank.clamp(1, sorted.len()) - 1]
+            }
+        }
+    }
+}
+
+/// What the in-memory history does with a new snapshot once it is full.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OverflowPolicy {
+    /// Drop the oldest snapshot to make room
+    #[default]
+    DropOldest,
+    /// Keep the earliest snapshots and discard the new one
+    RejectNewest,
+}
+
+/// What `DiskMetrics::used_pct` is computed from (`--disk-used-basis`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiskUsageBasis {
+    /// (total - available) / total; reserved blocks count as used
+    #[default]
+    Available,
+    /// used / total, from the free block count where the platform reports it
+    Total,
+    /// used / (used + available), matching `df`
+    Df,
+}
+
+/// Default network chart scaling sent to the dashboard; the user can
+/// override the mode from the dashboard.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct NetScale {
+    pub mode: NetScaleMode,
+    /// Ceiling used in fixed mode; the dashboard falls back to the largest
+    /// buffered value when unset.
+    pub max_bytes_per_sec: Option<f64>,
+    pub axes: NetAxes,
+}
+
+/// Warning/critical levels for one chart, in the chart's own unit.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Threshold {
+    pub warn: Option<f32>,
+    pub crit: Option<f32>,
+    /// A firing alert clears only below this level; `crit` when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clear: Option<f32>,
+    /// How long the metric must stay at or above `crit` before the alert
+    /// fires, and below the clear level before it clears; 0 when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_duration_ms: Option<u64>,
+}
+
+impl Threshold {
+    /// Level a firing alert must drop below to clear.
+    pub fn clear_level(&self) -> Option<f32> {
+        self.clear.or(self.crit)
+    }
+
+    /// Why these levels are inconsistent, if they are.
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if let (Some(warn), Some(crit)) = (self.warn, self.crit) {
+            if warn > crit {
+                return Err("warn exceeds crit");
+            }
+        }
+        if let (Some(clear), Some(crit)) = (self.clear, self.crit) {
+            if clear > crit {
+                return Err("clear exceeds crit");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Usage levels past which `/api/health` reports the node degraded, so an
+/// orchestrator can drain it; unset levels are not checked.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ResourceCriticality {
+    /// Disk used %, as in `disk.used_pct`.
+    pub disk_used_pct: Option<f32>,
+    /// Swap used % of the swap total.
+    pub swap_used_pct: Option<f32>,
+}
+
+/// Threshold lines served to the dashboard, keyed by series name.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Thresholds {
+    #[serde(rename = "cpu_total")]
+    pub cpu: Threshold,
+    pub memory: Threshold,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        let pct = Threshold {
+            warn: Some(70.0),
+            crit: Some(90.0),
+            ..Default::default()
+        };
+        Self {
+            cpu: pct,
+            memory: pct,
+        }
+    }
+}
+
+/// Thresholds shared by the API and the alert tracker; a config reload
+/// replaces the value for every clone.
+#[derive(Clone, Debug, Default)]
+pub struct SharedThresholds(Arc<RwLock<Thresholds>>);
+
+impl SharedThresholds {
+    pub fn new(thresholds: Thresholds) -> Self {
+        Self(Arc::new(RwLock::new(thresholds)))
+    }
+
+    pub fn get(&self) -> Thresholds {
+        *self.0.read().unwrap_or_else(|p| p.into_inner())
+    }
+
+    pub fn set(&self, thresholds: Thresholds) {
+        *self.0.write().unwrap_or_else(|p| p.into_inner()) = thresholds;
+    }
+}
+
+impl From<Thresholds> for SharedThresholds {
+    fn from(thresholds: Thresholds) -> Self {
+        Self::new(thresholds)
+    }
+}
+
+/// Per-request limits applied by the HTTP API router.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HttpLimits {
+    /// Deadline for producing a response; streaming endpoints are exempt.
+    pub request_timeout: Duration,
+    pub max_body_bytes: usize,
+}
+
+impl Default for HttpLimits {
+    fn default() -> Self {
+        Self {
+            request_timeout: Duration::from_secs(30),
+            max_body_bytes: 1024 * 1024,
+        }
+    }
+}
+
+/// When the `--persist-file` journal forces its writes to disk.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FsyncPolicy {
+    /// Hand buffered lines to the OS every flush interval but never fsync;
+    /// a power loss can drop whatever the OS had not written back yet
+    Never,
+    /// Write and fsync buffered lines every flush interval; a crash loses at
+    /// most one interval
+    #[default]
+    Interval,
+    /// Write and fsync every snapshot as it arrives; nothing acknowledged is
+    /// lost, at the cost of one disk sync per sample
+    Always,
+}
+
+/// Where the server keeps snapshots beyond the in-memory history buffer.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum StorageBackend {
+    /// In-memory buffer only (plus the JSON archive in `--db-path`)
+    #[default]
+    Memory,
+    /// Write snapshots to the columnar `snapshots` table in `--db-path` instead
+    /// of the JSON archive, and serve reads beyond the buffer from it
+    Sqlite,
+}
+
 #[derive(Clone, Debug, Parser)]
 #[command(
     name = "resource_monitor",
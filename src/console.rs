@@ -1,23 +1,216 @@
-use crate::metrics::{DisplayFormat, RpcMetricsSnapshot};
+use crate::config::{ByteUnits, CpuDisplay, DisplayUnits, TapFormat};
+use crate::metrics::{
+    busy_cores, format_bits_per_sec, format_net_rate, DisplayFormat, MetricsSnapshot,
+    RpcMetricsSnapshot, PRESSURE_CRIT, PRESSURE_WARN,
+};
 use crate::storage::MetricsBuffer;
+use crate::topology::CoreTopology;
 use crossterm::cursor::MoveTo;
-use crossterm::style::{Color, Stylize};
-use crossterm::terminal::{Clear, ClearType};
-use crossterm::ExecutableCommand;
-use std::io::{stdout, Write};
+use crossterm::queue;
+use crossterm::style::{Color, Print, Stylize};
+use crossterm::terminal::{self, Clear, ClearType};
+use std::collections::VecDeque;
+use std::io::{self, stdout, Write};
 use std::sync::{Arc, RwLock};
 use std::time::Duration;
 use tokio::time::MissedTickBehavior;
 use tokio_util::sync::CancellationToken;
 use tracing::error;
 
+/// Highest readings over a run of snapshots, shown next to the current values.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Peaks {
+    pub cpu_pct: Option<f32>,
+    pub mem_pct: Option<f32>,
+    pub rx_bytes_per_sec: Option<f32>,
+    pub tx_bytes_per_sec: Option<f32>,
+}
+
+impl Peaks {
+    pub fn from_snapshots(snapshots: &[MetricsSnapshot]) -> Self {
+        Self {
+            cpu_pct: peak(snapshots.iter().map(|s| s.cpu.total_usage_pct)),
+            mem_pct: peak(snapshots.iter().map(mem_pct)),
+            rx_bytes_per_sec: peak(snapshots.iter().map(|s| s.network.rx_bytes_per_sec)),
+            tx_bytes_per_sec: peak(snapshots.iter().map(|s| s.network.tx_bytes_per_sec)),
+        }
+    }
+}
+
+/// [`Peaks`] over the newest `window` snapshots of a buffer, kept as running
+/// maxima: each refresh reads only the snapshots that arrived since the
+/// previous one instead of copying the whole window.
+#[derive(Debug)]
+pub struct PeakTracker {
+    window: usize,
+    /// Snapshots fed so far; numbers each reading for eviction.
+    fed: u64,
+    last_ms: Option<u128>,
+    cpu_pct: WindowMax,
+    mem_pct: WindowMax,
+    rx_bytes_per_sec: WindowMax,
+    tx_bytes_per_sec: WindowMax,
+}
+
+impl PeakTracker {
+    pub fn new(window: usize) -> Self {
+        Self {
+            window: window.max(1),
+            fed: 0,
+            last_ms: None,
+            cpu_pct: WindowMax::default(),
+            mem_pct: WindowMax::default(),
+            rx_bytes_per_sec: WindowMax::default(),
+            tx_bytes_per_sec: WindowMax::default(),
+        }
+    }
+
+    /// Feeds the snapshots `buffer` gained since the previous call.
+    pub fn update(&mut self, buffer: &MetricsBuffer) -> Peaks {
+        for snap in buffer.range(self.last_ms.map(|ms| ms + 1), None) {
+            self.push(&snap);
+        }
+        self.peaks()
+    }
+
+    pub fn push(&mut self, snap: &MetricsSnapshot) {
+        let (seq, window) = (self.fed, self.window);
+        self.cpu_pct.push(seq, snap.cpu.total_usage_pct, window);
+        self.mem_pct.push(seq, mem_pct(snap), window);
+        self.rx_bytes_per_sec
+            .push(seq, snap.network.rx_bytes_per_sec, window);
+        self.tx_bytes_per_sec
+            .push(seq, snap.network.tx_bytes_per_sec, window);
+        self.fed += 1;
+        self.last_ms = Some(snap.timestamp_ms);
+    }
+
+    pub fn peaks(&self) -> Peaks {
+        Peaks {
+            cpu_pct: self.cpu_pct.max(),
+            mem_pct: self.mem_pct.max(),
+            rx_bytes_per_sec: self.rx_bytes_per_sec.max(),
+            tx_bytes_per_sec: self.tx_bytes_per_sec.max(),
+        }
+    }
+}
+
+/// Maximum over a sliding window: a deque of readings, each larger than
+/// every later one, so the front is the maximum.
+#[derive(Debug, Default)]
+struct WindowMax {
+    readings: VecDeque<(u64, f32)>,
+}
+
+impl WindowMax {
+    fn push(&mut self, seq: u64, value: f32, window: usize) {
+        if value.is_finite() {
+            while self.readings.back().is_some_and(|&(_, v)| v <= value) {
+                self.readings.pop_back();
+            }
+            self.readings.push_back((seq, value));
+        }
+        while self
+            .readings
+            .front()
+            .is_some_and(|&(s, _)| s + window as u64 <= seq)
+        {
+            self.readings.pop_front();
+        }
+    }
+
+    fn max(&self) -> Option<f32> {
+        self.readings.front().map(|&(_, v)| v)
+    }
+}
+
+/// Largest finite value in `values`.
+pub fn peak(values: impl IntoIterator<Item = f32>) -> Option<f32> {
+    values
+        .into_iter()
+        .filter(|v| v.is_finite())
+        .fold(None, |max, v| Some(max.map_or(v, |m: f32| m.max(v))))
+}
+
+fn mem_pct(snap: &MetricsSnapshot) -> f32 {
+    if snap.memory.total_bytes == 0 {
+        0.0
+    } else {
+        (snap.memory.used_bytes as f64 / snap.memory.total_bytes as f64 * 100.0) as f32
+    }
+}
+
+/// Full-screen clears are limited to one per this many frames; the frames in
+/// between only repaint lines that changed, which avoids flicker on short
+/// intervals. The periodic clear wipes stray output such as log lines.
+pub const FULL_CLEAR_EVERY: u32 = 60;
+
+/// Terminal painter that diffs each frame against the previous one.
+#[derive(Debug, Default)]
+pub struct Screen {
+    lines: Vec<String>,
+    /// None until the first frame, which always starts from a cleared screen.
+    frames_since_clear: Option<u32>,
+}
+
+impl Screen {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Draws `frame` from the top-left corner, rewriting only the lines that
+    /// differ from the previous frame and erasing any left below it.
+    pub fn paint(&mut self, out: &mut impl Write, frame: Vec<String>) -> io::Result<()> {
+        match self.frames_since_clear {
+            Some(n) if n < FULL_CLEAR_EVERY => self.frames_since_clear = Some(n + 1),
+            _ => {
+                queue!(out, MoveTo(0, 0), Clear(ClearType::All))?;
+                self.lines.clear();
+                self.frames_since_clear = Some(1);
+            }
+        }
+        for (row, line) in frame.iter().enumerate() {
+            if self.lines.get(row) != Some(line) {
+                queue!(
+                    out,
+                    MoveTo(0, row as u16),
+                    Print(line),
+                    Clear(ClearType::UntilNewLine)
+                )?;
+            }
+        }
+        if frame.len() < self.lines.len() {
+            queue!(
+                out,
+                MoveTo(0, frame.len() as u16),
+                Clear(ClearType::FromCursorDown)
+            )?;
+        }
+        out.flush()?;
+        self.lines = frame;
+        Ok(())
+    }
+}
+
+/// Renders the latest snapshot with peak-hold values over the newest
+/// `peak_window` snapshots (the whole buffer when None), repainting every
+/// `refresh`; snapshots arriving in between are coalesced into one frame.
+/// Per-core lines are grouped by socket when `topology` is given, and
+/// limited to the busiest `max_cores` (or as many as fit the terminal when
+/// None).
 pub async fn run_console(
     buffer: Arc<MetricsBuffer>,
-    interval: Duration,
+    refresh: Duration,
+    units: DisplayUnits,
+    peak_window: Option<usize>,
+    topology: Option<CoreTopology>,
+    max_cores: Option<usize>,
     cancel: CancellationToken,
 ) {
-    let mut ticker = tokio::time::interval(interval);
+    let mut ticker = tokio::time::interval(refresh);
     ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    let mut screen = Screen::new();
+    let mut peak_tracker = PeakTracker::new(peak_window.unwrap_or_else(|| buffer.capacity()));
 
     loop {
         tokio::select! {
@@ -25,7 +218,21 @@ pub async fn run_console(
                 break;
             }
             _ = ticker.tick() => {
-                if let Err(e) = render_once(&buffer) {
+                let snap = buffer.latest();
+                let peaks = peak_tracker.update(&buffer);
+                let render = |max_cores| {
+                    render_frame(snap.as_ref(), &peaks, units, topology.as_ref(), max_cores)
+                };
+                let mut frame = render(max_cores);
+                if max_cores.is_none() {
+                    let rows = terminal::size().map_or(usize::MAX, |(_, rows)| usize::from(rows));
+                    if frame.len() > rows {
+                        // One row goes to the summary of the hidden cores.
+                        let cores = snap.as_ref().map_or(0, |s| s.cpu.per_core_usage_pct.len());
+                        frame = render(Some(cores.saturating_sub(frame.len() - rows + 1)));
+                    }
+                }
+                if let Err(e) = screen.paint(&mut stdout(), frame) {
                     error!("Console render error: {}", e);
                 }
             }
@@ -33,19 +240,31 @@ pub async fn run_console(
     }
 }
 
-fn render_once(buffer: &MetricsBuffer) -> std::io::Result<()> {
-    let mut out = stdout();
-    out.execute(MoveTo(0, 0))?;
-    out.execute(Clear(ClearType::All))?;
+/// The console screen for `snap` as lines, without cursor movement. With
+/// `max_cores`, only that many of the busiest cores get a line, followed by
+/// a summary of the rest.
+pub fn render_frame(
+    snap: Option<&MetricsSnapshot>,
+    peaks: &Peaks,
+    units: DisplayUnits,
+    topology: Option<&CoreTopology>,
+    max_cores: Option<usize>,
+) -> Vec<String> {
+    let mut out = vec![
+        "Resource Monitor (console)".to_string(),
+        "Press Ctrl+C to exit.".to_string(),
+        String::new(),
+    ];
 
-    writeln!(out, "Resource Monitor (console)")?;
-    writeln!(out, "Press Ctrl+C to exit.")?;
-    writeln!(out)?;
+    let Some(snap) = snap else {
+        out.push("Waiting for first sample...".to_string());
+        return out;
+    };
 
-    let Some(snap) = buffer.latest() else {
-        writeln!(out, "Waiting for first sample...")?;
-        out.flush()?;
-        return Ok(());
+    let peak_pct = |v: Option<f32>| v.map(|p| format!(" (peak {p:.1}%)")).unwrap_or_default();
+    let peak_rate = |v: Option<f32>| {
+        v.map(|p| format!(" (peak {})", format_net_rate(p, units.net)))
+            .unwrap_or_default()
     };
 
     let cpu_total = snap.cpu.total_usage_pct;
@@ -53,33 +272,43 @@ fn render_once(buffer: &MetricsBuffer) -> std::io::Result<()> {
 
     let mem_total = snap.memory.total_bytes;
     let mem_used = snap.memory.used_bytes;
-    let mem_pct = if mem_total == 0 {
-        0.0
-    } else {
-        (mem_used as f64 / mem_total as f64 * 100.0) as f32
-    };
-    let mem_pct_colored = color_pct(mem_pct, 70.0, 90.0);
-
-    writeln!(
-        out,
-        "CPU total: {}   Load avg: {:.2} / {:.2} / {:.2}",
-        cpu_total_colored, snap.cpu.load_avg_1, snap.cpu.load_avg_5, snap.cpu.load_avg_15
-    )?;
-    writeln!(
-        out,
-        "Memory: {} used / {} total ({})",
-        format_bytes(mem_used),
-        format_bytes(mem_total),
-        mem_pct_colored
-    )?;
-    writeln!(
-        out,
-        "Network: RX {:.0} B/s  TX {:.0} B/s   (total RX {} / TX {})",
-        snap.network.rx_bytes_per_sec,
-        snap.network.tx_bytes_per_sec,
-        format_bytes(snap.network.rx_bytes_total),
-        format_bytes(snap.network.tx_bytes_total)
-    )?;
+    let mem_pct_colored = color_pct(mem_pct(snap), 70.0, 90.0);
+
+    let load = |v: Option<f32>| v.map_or_else(|| "N/A".to_string(), |v| format!("{v:.2}"));
+    if let Some(score) = snap.pressure_score {
+        out.push(format!("Pressure: {}", color_score(score)));
+    }
+    out.push(format!(
+        "CPU total: {}{}{}   Load avg: {} / {} / {}",
+        cpu_total_colored,
+        peak_pct(peaks.cpu_pct),
+        cores_busy(&snap.cpu.per_core_usage_pct, units.cpu),
+        load(snap.cpu.load_avg_1),
+        load(snap.cpu.load_avg_5),
+        load(snap.cpu.load_avg_15)
+    ));
+    if let Some(bd) = &snap.cpu.breakdown {
+        out.push(format!(
+            "CPU time: user {:.1}%  system {:.1}%  iowait {:.1}%  steal {:.1}%  idle {:.1}%",
+            bd.user_pct, bd.system_pct, bd.iowait_pct, bd.steal_pct, bd.idle_pct
+        ));
+    }
+    out.push(format!(
+        "Memory: {} used / {} total ({}){}",
+        format_bytes(mem_used, units.bytes),
+        format_bytes(mem_total, units.bytes),
+        mem_pct_colored,
+        peak_pct(peaks.mem_pct)
+    ));
+    out.push(format!(
+        "Network: RX {}{}  TX {}{}   (total RX {} / TX {})",
+        format_net_rate(snap.network.rx_bytes_per_sec, units.net),
+        peak_rate(peaks.rx_bytes_per_sec),
+        format_net_rate(snap.network.tx_bytes_per_sec, units.net),
+        peak_rate(peaks.tx_bytes_per_sec),
+        format_bytes(snap.network.rx_bytes_total, units.bytes),
+        format_bytes(snap.network.tx_bytes_total, units.bytes)
+    ));
 
     if let Some(gpu) = &snap.gpu {
         let mem_label = if gpu.is_unified_memory {
@@ -98,28 +327,93 @@ fn render_once(buffer: &MetricsBuffer) -> std::io::Result<()> {
             .temperature_celsius
             .map(|t| format!("  {t:.0}°C"))
             .unwrap_or_default();
-        writeln!(
-            out,
+        out.push(format!(
             "GPU: {} – {} util  {} {}: {} / {}{}",
             gpu.name,
             gpu_colored,
             mem_colored,
             mem_label,
-            format_bytes(gpu.vram_used_bytes),
-            format_bytes(gpu.vram_total_bytes),
+            format_bytes(gpu.vram_used_bytes, units.bytes),
+            format_bytes(gpu.vram_total_bytes, units.bytes),
             temp_str
-        )?;
+        ));
     }
 
-    writeln!(out)?;
-    writeln!(out, "Per-core CPU usage:")?;
-    for (i, pct) in snap.cpu.per_core_usage_pct.iter().enumerate() {
-        let colored = color_pct(*pct, 50.0, 80.0);
-        writeln!(out, "  Core {:>2}: {}", i, colored)?;
+    out.push(String::new());
+    out.push("Per-core CPU usage:".to_string());
+    let per_core = &snap.cpu.per_core_usage_pct;
+    let core_line = |i: usize| format!("  Core {:>2}: {}", i, color_pct(per_core[i], 50.0, 80.0));
+    let shown = match max_cores {
+        Some(n) if n < per_core.len() => {
+            let mut shown = vec![false; per_core.len()];
+            busiest_cores(per_core, n)
+                .into_iter()
+                .for_each(|i| shown[i] = true);
+            shown
+        }
+        _ => vec![true; per_core.len()],
+    };
+    match topology {
+        Some(topology) => {
+            for (socket, cores) in topology.groups(per_core.len()) {
+                let avg = cores.iter().map(|&c| per_core[c]).sum::<f32>() / cores.len() as f32;
+                out.push(format!(
+                    " Socket {}: {}",
+                    socket,
+                    color_pct(avg, 50.0, 80.0)
+                ));
+                out.extend(cores.into_iter().filter(|&c| shown[c]).map(core_line));
+            }
+        }
+        None => out.extend((0..per_core.len()).filter(|&c| shown[c]).map(core_line)),
+    }
+    let hidden: Vec<f32> = (0..per_core.len())
+        .filter(|&c| !shown[c])
+        .map(|c| per_core[c])
+        .collect();
+    if !hidden.is_empty() {
+        let avg = hidden.iter().sum::<f32>() / hidden.len() as f32;
+        let max = hidden.iter().copied().fold(0.0f32, f32::max);
+        out.push(format!(
+            "  ... {} quieter cores: avg {}, max {}",
+            hidden.len(),
+            color_pct(avg, 50.0, 80.0),
+            color_pct(max, 50.0, 80.0)
+        ));
     }
+    out
+}
 
-    out.flush()?;
-    Ok(())
+/// `  (3.4 of 8 cores busy)` with [`CpuDisplay::Cores`], else nothing;
+/// nothing either without per-core data.
+fn cores_busy(per_core: &[f32], display: CpuDisplay) -> String {
+    match display {
+        CpuDisplay::Percent => String::new(),
+        CpuDisplay::Cores if per_core.is_empty() => String::new(),
+        CpuDisplay::Cores => format!(
+            "  ({:.1} of {} cores busy)",
+            busy_cores(per_core),
+            per_core.len()
+        ),
+    }
+}
+
+/// Indices of the `n` busiest cores, in core order; ties go to the lower
+/// index and NaN readings count as idle.
+pub fn busiest_cores(per_core: &[f32], n: usize) -> Vec<usize> {
+    let usage = |i: usize| {
+        let v = per_core[i];
+        if v.is_nan() {
+            f32::NEG_INFINITY
+        } else {
+            v
+        }
+    };
+    let mut order: Vec<usize> = (0..per_core.len()).collect();
+    order.sort_by(|&a, &b| usage(b).total_cmp(&usage(a)).then(a.cmp(&b)));
+    order.truncate(n);
+    order.sort_unstable();
+    order
 }
 
 fn color_pct(value: f32, warn: f32, crit: f32) -> String {
@@ -133,40 +427,40 @@ fn color_pct(value: f32, warn: f32, crit: f32) -> String {
     }
 }
 
-fn format_bytes(bytes: u64) -> String {
-    const KB: f64 = 1024.0;
-    const MB: f64 = KB * 1024.0;
-    const GB: f64 = MB * 1024.0;
-    const TB: f64 = GB * 1024.0;
-
-    let b = bytes as f64;
-    if b >= TB {
-        format!("{:.2} TiB", b / TB)
-    } else if b >= GB {
-        format!("{:.2} GiB", b / GB)
-    } else if b >= MB {
-        format!("{:.2} MiB", b / MB)
-    } else if b >= KB {
-        format!("{:.2} KiB", b / KB)
+fn color_score(score: f32) -> String {
+    let s = format!("{score:.0}");
+    if score >= PRESSURE_CRIT {
+        s.with(Color::Red).to_string()
+    } else if score >= PRESSURE_WARN {
+        s.with(Color::Yellow).to_string()
     } else {
-        format!("{} B", bytes)
+        s.with(Color::Green).to_string()
     }
 }
 
+/// `bytes` with the largest `units` prefix it reaches, e.g. `1.43 MiB`.
+pub fn format_bytes(bytes: u64, units: ByteUnits) -> String {
+    units.format(bytes as f64, "B")
+}
+
 /// Console renderer for the client binary, which receives `RpcMetricsSnapshot` via tarpc.
 pub async fn run_rpc_console(
     latest: Arc<RwLock<Option<RpcMetricsSnapshot>>>,
-    interval: Duration,
+    refresh: Duration,
+    units: DisplayUnits,
     cancel: CancellationToken,
 ) {
-    let mut ticker = tokio::time::interval(interval);
+    let mut ticker = tokio::time::interval(refresh);
     ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    let mut screen = Screen::new();
 
     loop {
         tokio::select! {
             _ = cancel.cancelled() => break,
             _ = ticker.tick() => {
-                if let Err(e) = render_rpc_once(&latest) {
+                let snap = latest.read().unwrap_or_else(|p| p.into_inner()).clone();
+                let frame = render_rpc_frame(snap.as_ref(), units);
+                if let Err(e) = screen.paint(&mut stdout(), frame) {
                     error!("Console render error: {}", e);
                 }
             }
@@ -174,24 +468,22 @@ pub async fn run_rpc_console(
     }
 }
 
-fn render_rpc_once(latest: &RwLock<Option<RpcMetricsSnapshot>>) -> std::io::Result<()> {
-    let mut out = stdout();
-    out.execute(MoveTo(0, 0))?;
-    out.execute(Clear(ClearType::All))?;
-
-    writeln!(out, "Resource Monitor (RPC console client)")?;
-    writeln!(out, "Press Ctrl+C to exit.")?;
-    writeln!(out)?;
+/// The RPC console screen for `snap` as lines, without cursor movement.
+pub fn render_rpc_frame(snap: Option<&RpcMetricsSnapshot>, units: DisplayUnits) -> Vec<String> {
+    let mut out = vec![
+        "Resource Monitor (RPC console client)".to_string(),
+        "Press Ctrl+C to exit.".to_string(),
+        String::new(),
+    ];
 
-    let guard = match latest.read() {
-        Ok(g) => g,
-        Err(p) => p.into_inner(),
-    };
-    let Some(snap) = guard.as_ref() else {
-        writeln!(out, "Waiting for data from server...")?;
-        out.flush()?;
-        return Ok(());
+    let Some(snap) = snap else {
+        out.push("Waiting for data from server...".to_string());
+        return out;
     };
+    let snap = snap
+        .clone()
+        .with_net_units(units.net)
+        .with_byte_units(units.bytes);
 
     for series in &snap.data {
         let values: Vec<String> = series
@@ -222,11 +514,15 @@ fn render_rpc_once(latest: &RwLock<Option<RpcMetricsSnapshot>>) -> std::io::Resu
             })
             .collect();
 
-        writeln!(out, "{}: {}", series.beautiful_name, values.join("  "))?;
+        out.push(format!("{}: {}", series.beautiful_name, values.join("  ")));
+        if series.name == "cpu_cores" && units.cpu == CpuDisplay::Cores {
+            out.push(format!(
+                "CPU busy:{}",
+                cores_busy(&series.series, units.cpu)
+            ));
+        }
     }
-
-    out.flush()?;
-    Ok(())
+    out
 }
 
 fn color_pct_inverted(value: f32, warn: f32, crit: f32) -> String {
@@ -245,20 +541,46 @@ fn format_value(val: f32, fmt: &DisplayFormat) -> String {
         DisplayFormat::Percentage { decimals } => format!("{:.prec$}%", val, prec = decimals),
         DisplayFormat::Float { decimals } => format!("{:.prec$}", val, prec = decimals),
         DisplayFormat::Integer => format!("{}", val as i64),
-        DisplayFormat::Bytes { suffix } => {
-            let b = val as f64;
-            const KB: f64 = 1024.0;
-            const MB: f64 = KB * 1024.0;
-            const GB: f64 = MB * 1024.0;
-            if b >= GB {
-                format!("{:.2} Gi{}", b / GB, suffix)
-            } else if b >= MB {
-                format!("{:.2} Mi{}", b / MB, suffix)
-            } else if b >= KB {
-                format!("{:.2} Ki{}", b / KB, suffix)
-            } else {
-                format!("{:.0} {}", b, suffix)
-            }
+        DisplayFormat::Bits { .. } => format_bits_per_sec(val),
+        DisplayFormat::Bytes { suffix, units } => units.format(f64::from(val), suffix),
+    }
+}
+
+/// One compact line for `--mode tap`: timestamp, CPU %, memory %, and network
+/// rx/tx in the snapshot's unit. Missing values print as `-` (TSV) or `null`.
+pub fn format_tap_line(snap: &RpcMetricsSnapshot, format: TapFormat) -> String {
+    let value = |name: &str, idx: usize| {
+        snap.data
+            .iter()
+            .find(|s| s.name == name)
+            .and_then(|s| s.series.get(idx).copied())
+    };
+    let cpu = value("cpu_total", 0);
+    let mem = value("memory", 0);
+    let rx = value("network", 0);
+    let tx = value("network", 1);
+
+    match format {
+        TapFormat::Tsv => {
+            let col = |v: Option<f32>, decimals: usize| {
+                v.map_or_else(|| "-".to_string(), |v| format!("{:.*}", decimals, v))
+            };
+            format!(
+                "{}\t{}\t{}\t{}\t{}",
+                snap.timestamp_ms,
+                col(cpu, 1),
+                col(mem, 1),
+                col(rx, 0),
+                col(tx, 0)
+            )
         }
+        TapFormat::Json => serde_json::json!({
+            "timestamp_ms": snap.timestamp_ms as u64,
+            "cpu": cpu,
+            "mem_pct": mem,
+            "rx": rx,
+            "tx": tx,
+        })
+        .to_string(),
     }
 }
@@ -1,3 +1,4 @@
+use crate::alerts::AlertRecord;
 use crate::metrics::{MetricsSnapshot, RpcMetricsSnapshot};
 use rusqlite::{params, Connection, DatabaseName};
 use serde::Serialize;
@@ -37,6 +38,14 @@ impl MetricsDb {
             [],
         )?;
 
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS alerts (
+                id INTEGER PRIMARY KEY,
+                data TEXT NOT NULL
+            )",
+            [],
+        )?;
+
         info!("Database initialized at {}", path.display());
 
         Ok(Self {
@@ -123,34 +132,28 @@ impl MetricsDb {
         limit: Option<usize>,
         since_ts: Option<u64>,
     ) -> Result<Vec<RpcMetricsSnapshot>, rusqlite::Error> {
-        let conn = self.conn.lock().unwrap();
+        self.get_history_from(limit, since_ts, false)
+    }
 
-        let (query, needs_since, needs_limit) = match (since_ts.is_some(), limit.is_some()) {
-            (true, true) => (
-                "SELECT data FROM metrics WHERE timestamp_ms >= ?1 ORDER BY timestamp_ms DESC LIMIT ?2",
-                true, true,
-            ),
-            (true, false) => (
-                "SELECT data FROM metrics WHERE timestamp_ms >= ?1 ORDER BY timestamp_ms DESC",
-                true, false,
-            ),
-            (false, true) => (
-                "SELECT data FROM metrics ORDER BY timestamp_ms DESC LIMIT ?1",
-                false, true,
-            ),
-            (false, false) => (
-                "SELECT data FROM metrics ORDER BY timestamp_ms DESC",
-                false, false,
-            ),
-        };
+    /// Like [`get_history`](Self::get_history), but with `from_start` the
+    /// oldest `limit` rows are kept and returned oldest first.
+    pub fn get_history_from(
+        &self,
+        limit: Option<usize>,
+        since_ts: Option<u64>,
+        from_start: bool,
+    ) -> Result<Vec<RpcMetricsSnapshot>, rusqlite::Error> {
+        let conn = self.conn.lock().unwrap();
 
-        let mut stmt = conn.prepare(query)?;
-        let mut rows = match (needs_since, needs_limit) {
-            (true, true) => stmt.query(params![since_ts.unwrap() as i64, limit.unwrap() as i64])?,
-            (true, false) => stmt.query(params![since_ts.unwrap() as i64])?,
-            (false, true) => stmt.query(params![limit.unwrap() as i64])?,
-            (false, false) => stmt.query([])?,
-        };
+        let query = format!(
+            "SELECT data FROM metrics WHERE timestamp_ms >= ?1 ORDER BY timestamp_ms {} LIMIT ?2",
+            if from_start { "ASC" } else { "DESC" }
+        );
+        let since = since_ts.map_or(i64::MIN, |s| i64::try_from(s).unwrap_or(i64::MAX));
+        // SQLite treats a negative LIMIT as no limit.
+        let limit = limit.map_or(-1, |l| i64::try_from(l).unwrap_or(i64::MAX));
+        let mut stmt = conn.prepare(&query)?;
+        let mut rows = stmt.query(params![since, limit])?;
 
         let mut results = Vec::new();
         while let Some(row) = rows.next()? {
@@ -209,6 +212,36 @@ impl MetricsDb {
         })
     }
 
+    pub fn save_alert(&self, alert: &AlertRecord) -> Result<(), rusqlite::Error> {
+        let data = serde_json::to_string(alert)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO alerts (id, data) VALUES (?1, ?2)",
+            params![alert.id as i64, data],
+        )?;
+        Ok(())
+    }
+
+    /// The most recent `limit` alerts, oldest first.
+    pub fn load_alerts(&self, limit: usize) -> Result<Vec<AlertRecord>, rusqlite::Error> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT data FROM alerts ORDER BY id DESC LIMIT ?1")?;
+        let mut rows = stmt.query(params![limit as i64])?;
+
+        let mut results = Vec::new();
+        while let Some(row) = rows.next()? {
+            let data: String = row.get(0)?;
+            match serde_json::from_str(&data) {
+                Ok(alert) => results.push(alert),
+                Err(e) => warn!("Skipping corrupted alert row: {}", e),
+            }
+        }
+        results.reverse();
+
+        Ok(results)
+    }
+
     pub fn vacuum(&self) -> Result<(), rusqlite::Error> {
         let conn = self.conn.lock().unwrap();
         conn.execute("VACUUM", [])?;
@@ -0,0 +1,114 @@
+//! Delta encoding for `/api/stream?delta=1`: a full snapshot, then JSON
+//! merge patches (RFC 7386) against the previous one.
+//!
+//! Merge patches replace arrays wholesale, so delta documents key `data` by
+//! series name; a patch then carries only the series whose values changed,
+//! not legends or formats.
+
+use crate::metrics::RpcMetricsSnapshot;
+use serde_json::{Map, Value};
+
+/// Events between full snapshots, so a client that misapplied a patch
+/// recovers without reconnecting.
+pub const FULL_EVERY: u32 = 60;
+
+/// `snapshot` as a delta document: `data` becomes an object keyed by series name.
+pub fn keyed(snapshot: &RpcMetricsSnapshot) -> serde_json::Result<Value> {
+    let mut doc = serde_json::to_value(snapshot)?;
+    if let Some(obj) = doc.as_object_mut() {
+        if let Some(Value::Array(series)) = obj.remove("data") {
+            let by_name: Map<String, Value> = series
+                .into_iter()
+                .filter_map(|s| Some((s.get("name")?.as_str()?.to_string(), s)))
+                .collect();
+            obj.insert("data".to_string(), Value::Object(by_name));
+        }
+    }
+    Ok(doc)
+}
+
+/// Merge patch turning `old` into `new`; an empty object when they are equal.
+pub fn merge_diff(old: &Value, new: &Value) -> Value {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            let mut patch = Map::new();
+            for (key, old_value) in old {
+                match new.get(key) {
+                    None => {
+                        patch.insert(key.clone(), Value::Null);
+                    }
+                    Some(new_value) if new_value != old_value => {
+                        let diff = match (old_value, new_value) {
+                            (Value::Object(_), Value::Object(_)) => {
+                                merge_diff(old_value, new_value)
+                            }
+                            _ => new_value.clone(),
+                        };
+                        patch.insert(key.clone(), diff);
+                    }
+                    Some(_) => {}
+                }
+            }
+            for (key, new_value) in new {
+                if !old.contains_key(key) {
+                    patch.insert(key.clone(), new_value.clone());
+                }
+            }
+            Value::Object(patch)
+        }
+        _ => new.clone(),
+    }
+}
+
+/// Applies an RFC 7386 merge patch to `target` in place.
+pub fn apply_merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    let Value::Object(target) = target else {
+        return;
+    };
+    for (key, value) in patch {
+        if value.is_null() {
+            target.remove(key);
+        } else {
+            apply_merge_patch(target.entry(key.clone()).or_insert(Value::Null), value);
+        }
+    }
+}
+
+/// One encoded stream event.
+#[derive(Debug, PartialEq)]
+pub enum Delta {
+    Full(Value),
+    Patch(Value),
+}
+
+/// Per-connection encoder; starts with a full snapshot.
+#[derive(Default)]
+pub struct DeltaEncoder {
+    last: Option<Value>,
+    since_full: u32,
+}
+
+impl DeltaEncoder {
+    pub fn encode(&mut self, snapshot: &RpcMetricsSnapshot) -> serde_json::Result<Delta> {
+        let doc = keyed(snapshot)?;
+        let delta = match self.last.as_ref() {
+            Some(last) if self.since_full < FULL_EVERY => {
+                self.since_full += 1;
+                Delta::Patch(merge_diff(last, &doc))
+            }
+            _ => {
+                self.since_full = 1;
+                Delta::Full(doc.clone())
+            }
+        };
+        self.last = Some(doc);
+        Ok(delta)
+    }
+}
@@ -0,0 +1,150 @@
+//! Grafana SimpleJSON datasource adapter, served under `/grafana`.
+//!
+//! Point a SimpleJSON (or "JSON") datasource at `http://<server>/grafana`.
+//! With `--metrics-prefix hostmon` every target is exported as
+//! `hostmon.<name>`, e.g. `hostmon.cpu.total`.
+
+use crate::api::{ApiError, AppState};
+use crate::metrics::{scalar_metric, ErrorCode};
+use crate::storage::downsample;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+
+/// Grafana target name -> scalar metric name.
+const TARGETS: &[(&str, &str)] = &[
+    ("cpu.total", "cpu"),
+    ("memory.used_pct", "memory"),
+    ("swap.used_pct", "swap"),
+    ("disk.used_pct", "disk"),
+    ("load.1m", "load_1"),
+    ("network.rx_bytes_per_sec", "net_rx"),
+    ("network.tx_bytes_per_sec", "net_tx"),
+    ("gpu.utilization_pct", "gpu"),
+];
+
+/// Whether `prefix` may namespace exported metric names: ASCII letters,
+/// digits and `_`, not starting with a digit.
+pub fn is_valid_metrics_prefix(prefix: &str) -> bool {
+    let mut chars = prefix.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// `name` as exported under the configured prefix.
+pub fn exported_name(prefix: Option<&str>, name: &str) -> String {
+    match prefix {
+        Some(prefix) => format!("{prefix}.{name}"),
+        None => name.to_string(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct QueryRequest {
+    pub range: QueryRange,
+    pub targets: Vec<QueryTarget>,
+    #[serde(default, rename = "maxDataPoints")]
+    pub max_data_points: Option<usize>,
+}
+
+#[derive(Deserialize)]
+pub struct QueryRange {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Deserialize)]
+pub struct QueryTarget {
+    pub target: String,
+}
+
+#[derive(Serialize)]
+pub struct TimeSeries {
+    pub target: String,
+    /// `[value, timestamp_ms]` pairs, oldest first.
+    pub datapoints: Vec<(f32, u64)>,
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/grafana", get(test_connection))
+        .route("/grafana/", get(test_connection))
+        .route("/grafana/search", post(search))
+        .route("/grafana/query", post(query))
+}
+
+async fn test_connection() -> impl IntoResponse {
+    StatusCode::OK
+}
+
+async fn search(State(state): State<AppState>) -> impl IntoResponse {
+    let prefix = state.metrics_prefix.as_deref();
+    let names: Vec<String> = TARGETS
+        .iter()
+        .map(|(target, _)| exported_name(prefix, target))
+        .collect();
+    Json(names)
+}
+
+async fn query(State(state): State<AppState>, Json(req): Json<QueryRequest>) -> impl IntoResponse {
+    let (Some(since_ms), Some(until_ms)) = (parse_time(&req.range.from), parse_time(&req.range.to))
+    else {
+        return ApiError::new(
+            ErrorCode::InvalidParameter,
+            "range.from and range.to must be RFC 3339 timestamps",
+        )
+        .into_response();
+    };
+
+    let snapshots = state.buffer.range(Some(since_ms), Some(until_ms));
+
+    let prefix = state.metrics_prefix.as_deref();
+    let mut out = Vec::with_capacity(req.targets.len());
+    for target in &req.targets {
+        let Some((name, metric)) = TARGETS
+            .iter()
+            .find(|(name, _)| exported_name(prefix, name) == target.target)
+            .and_then(|(_, name)| Some((*name, scalar_metric(name)?)))
+        else {
+            return ApiError::new(
+                ErrorCode::InvalidParameter,
+                format!("unknown target '{}'", target.target),
+            )
+            .into_response();
+        };
+
+        let mut datapoints: Vec<(f32, u64)> = snapshots
+            .iter()
+            .filter_map(|s| {
+                let ts = u64::try_from(s.timestamp_ms).ok()?;
+                (metric.extract)(s).map(|v| (v, ts))
+            })
+            .collect();
+        if let Some(max) = req.max_data_points.filter(|m| *m > 0) {
+            // Grafana orders pairs value-first; buckets go by time.
+            let points: Vec<(u64, f32)> = datapoints.iter().map(|&(v, ts)| (ts, v)).collect();
+            datapoints = downsample(&points, max, state.sampling.aggregations.for_metric(name))
+                .into_iter()
+                .map(|(ts, v)| (v, ts))
+                .collect();
+        }
+
+        out.push(TimeSeries {
+            target: target.target.clone(),
+            datapoints,
+        });
+    }
+
+    (StatusCode::OK, Json(out)).into_response()
+}
+
+fn parse_time(s: &str) -> Option<u128> {
+    let dt = DateTime::parse_from_rfc3339(s).ok()?;
+    u128::try_from(dt.timestamp_millis()).ok()
+}
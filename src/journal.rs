@@ -0,0 +1,351 @@
+//! Append-only journal writing each snapshot as one JSON line to a file
+//! (`--persist-file <path>`), so history survives restarts without SQLite.
+//!
+//! Lines are buffered in memory and reach the file on the periodic flush
+//! (`--persist-flush-ms`). How durable they are then depends on
+//! `--persist-fsync`:
+//! - `never`: flushed to the OS only; a process crash loses at most one
+//!   flush interval, a power loss whatever the OS had not written back.
+//! - `interval`: flushed and fsynced every interval; a crash or power loss
+//!   loses at most one interval.
+//! - `always`: written and fsynced per snapshot, bypassing the buffer; nothing
+//!   published is lost, but every sample waits for the disk.
+//!
+//! [`replay`] reads a journal back, plain or zstd-compressed (e.g. a
+//! rotated file compressed afterwards), skipping malformed lines such as
+//! one cut short by a crash.
+//!
+//! The client's `--record <path>` reuses it for the snapshots it receives,
+//! rotating the file once it grows past a size limit.
+
+use crate::bus::MetricsEvent;
+use crate::config::FsyncPolicy;
+use crate::metrics::MetricsSnapshot;
+use crate::outage::Outage;
+use crate::rpc::{connect_client, run_rpc_client_streamer_with, MetricsRpcClient};
+use crate::rpc_codec::ZSTD_MAGIC;
+use serde::Serialize;
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::future::Future;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::MissedTickBehavior;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+pub const DEFAULT_PERSIST_FLUSH_MS: u64 = 1000;
+
+/// Bytes buffered between flushes before a write goes to the file early;
+/// enough for a few minutes of snapshots at the default interval.
+const BUFFER_BYTES: usize = 256 * 1024;
+
+pub const DEFAULT_ROTATE_BYTES: u64 = 64 * 1024 * 1024;
+pub const DEFAULT_ROTATE_KEEP: usize = 5;
+
+/// Size at which the file is rotated, and how many rotated files to keep.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rotation {
+    pub max_bytes: u64,
+    pub keep: usize,
+}
+
+impl Default for Rotation {
+    fn default() -> Self {
+        Self {
+            max_bytes: DEFAULT_ROTATE_BYTES,
+            keep: DEFAULT_ROTATE_KEEP,
+        }
+    }
+}
+
+/// `path` with `.n` appended, the name of its `n`th rotated file.
+pub fn rotated_path(path: &Path, n: usize) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(format!(".{n}"));
+    PathBuf::from(name)
+}
+
+pub struct SnapshotJournal {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    policy: FsyncPolicy,
+    rotation: Option<Rotation>,
+    /// Bytes in the current file, including buffered lines.
+    len: u64,
+    /// Lines written since the last flush.
+    dirty: bool,
+    outage: Outage,
+}
+
+/// A journal shared between the bus subscriber and the flush task.
+pub type SharedJournal = Arc<Mutex<SnapshotJournal>>;
+
+impl SnapshotJournal {
+    /// Opens `path` for appending, creating it if needed.
+    pub fn open(path: impl Into<PathBuf>, policy: FsyncPolicy) -> io::Result<Self> {
+        let path = path.into();
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let len = file.metadata()?.len();
+        Ok(Self {
+            path,
+            writer: BufWriter::with_capacity(BUFFER_BYTES, file),
+            policy,
+            rotation: None,
+            len,
+            dirty: false,
+            outage: Outage::new(),
+        })
+    }
+
+    /// Rotates the file once it reaches `rotation.max_bytes`: `path` becomes
+    /// `path.1`, `path.1` becomes `path.2` and so on, dropping files past
+    /// `rotation.keep`.
+    pub fn with_rotation(mut self, rotation: Rotation) -> Self {
+        self.rotation = Some(rotation);
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn policy(&self) -> FsyncPolicy {
+        self.policy
+    }
+
+    /// Appends `snapshot`; with [`FsyncPolicy::Always`] it is on disk when
+    /// this returns.
+    pub fn append<T: Serialize>(&mut self, snapshot: &T) -> io::Result<()> {
+        let mut line = serde_json::to_vec(snapshot)?;
+        line.push(b'\n');
+        self.writer.write_all(&line)?;
+        self.len += line.len() as u64;
+        self.dirty = true;
+        if self.policy == FsyncPolicy::Always {
+            self.flush()?;
+        }
+        if self.rotation.is_some_and(|r| self.len >= r.max_bytes) {
+            self.rotate()?;
+        }
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        let Some(rotation) = self.rotation else {
+            return Ok(());
+        };
+        self.flush()?;
+        if rotation.keep == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            let _ = fs::remove_file(rotated_path(&self.path, rotation.keep));
+            for n in (1..rotation.keep).rev() {
+                match fs::rename(rotated_path(&self.path, n), rotated_path(&self.path, n + 1)) {
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                    _ => {}
+                }
+            }
+            fs::rename(&self.path, rotated_path(&self.path, 1))?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.writer = BufWriter::with_capacity(BUFFER_BYTES, file);
+        self.len = 0;
+        debug!("Journal {} rotated", self.path.display());
+        Ok(())
+    }
+
+    /// Writes buffered lines to the file, and fsyncs unless the policy is
+    /// [`FsyncPolicy::Never`].
+    pub fn flush(&mut self) -> io::Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+        self.writer.flush()?;
+        if self.policy != FsyncPolicy::Never {
+            self.writer.get_ref().sync_data()?;
+        }
+        self.dirty = false;
+        Ok(())
+    }
+
+    fn record(&mut self, result: io::Result<()>, action: &str) {
+        let path = self.path.display();
+        match result {
+            Ok(()) => self
+                .outage
+                .succeeded(format_args!("Journal {path} writable again")),
+            Err(e) => self.outage.failed(
+                format_args!("Journal {path} {action} failed: {e}"),
+                format_args!("Journal {path} {action} failed: {e}"),
+            ),
+        }
+    }
+}
+
+/// Subscribes `journal` to published snapshots.
+pub fn register_journal_subscriber(journal: SharedJournal) -> nuts::ActivityId<SharedJournal> {
+    let activity = nuts::new_activity(journal);
+    activity.subscribe(|journal: &mut SharedJournal, evt: &MetricsEvent| {
+        let mut journal = journal.lock().unwrap_or_else(|e| e.into_inner());
+        let result = journal.append(&evt.0);
+        journal.record(result, "append");
+    });
+    activity
+}
+
+/// Flushes `journal` every `every` until `cancel` fires, then once more.
+pub async fn run_journal_flusher(
+    journal: SharedJournal,
+    every: Duration,
+    cancel: CancellationToken,
+) {
+    let mut ticker = tokio::time::interval(every);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    ticker.tick().await;
+    loop {
+        tokio::select! {
+            _ = cancel.cancelled() => break,
+            _ = ticker.tick() => {}
+        }
+        let mut journal = journal.lock().unwrap_or_else(|e| e.into_inner());
+        let result = journal.flush();
+        journal.record(result, "flush");
+    }
+    let mut journal = journal.lock().unwrap_or_else(|e| e.into_inner());
+    let result = journal.flush();
+    journal.record(result, "flush");
+}
+
+/// Appends every snapshot streamed from the RPC server at `addr` to
+/// `journal` (`--record`), flushing every `flush_every`, until `cancel` fires.
+pub async fn run_rpc_recorder(
+    journal: SharedJournal,
+    addr: SocketAddr,
+    compress: bool,
+    poll_interval: Duration,
+    flush_every: Duration,
+    cancel: CancellationToken,
+) {
+    run_rpc_recorder_with(
+        journal,
+        move || connect_client(addr, compress),
+        poll_interval,
+        flush_every,
+        cancel,
+    )
+    .await;
+}
+
+/// [`run_rpc_recorder`] through clients produced by `connect`.
+pub async fn run_rpc_recorder_with<C, Fut>(
+    journal: SharedJournal,
+    connect: C,
+    poll_interval: Duration,
+    flush_every: Duration,
+    cancel: CancellationToken,
+) where
+    C: FnMut() -> Fut,
+    Fut: Future<Output = io::Result<MetricsRpcClient>>,
+{
+    let flusher = tokio::spawn(run_journal_flusher(
+        journal.clone(),
+        flush_every,
+        cancel.clone(),
+    ));
+    run_rpc_client_streamer_with(connect, poll_interval, cancel, move |snap| {
+        let mut journal = journal.lock().unwrap_or_else(|e| e.into_inner());
+        let result = journal.append(&snap);
+        journal.record(result, "append");
+    })
+    .await;
+    let _ = flusher.await;
+}
+
+/// Outcome of a [`replay`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ReplayStats {
+    pub loaded: usize,
+    /// Malformed lines skipped, typically a final one cut short by a crash.
+    pub skipped: usize,
+}
+
+/// Reads the journal at `path`, plain or zstd-compressed, passing each
+/// snapshot to `on_snapshot` in file order.
+pub fn replay(
+    path: impl AsRef<Path>,
+    on_snapshot: impl FnMut(MetricsSnapshot),
+) -> io::Result<ReplayStats> {
+    let path = path.as_ref();
+    let stats = replay_from(File::open(path)?, on_snapshot)?;
+    if stats.skipped > 0 {
+        warn!(
+            "Replayed {} snapshots from {}, skipped {} malformed records",
+            stats.loaded,
+            path.display(),
+            stats.skipped
+        );
+    } else {
+        info!(
+            "Replayed {} snapshots from {}",
+            stats.loaded,
+            path.display()
+        );
+    }
+    Ok(stats)
+}
+
+/// [`replay`] from any reader. A compressed stream that ends mid-frame
+/// yields what decoded before the break, the partial line counted as skipped.
+pub fn replay_from(
+    reader: impl Read,
+    mut on_snapshot: impl FnMut(MetricsSnapshot),
+) -> io::Result<ReplayStats> {
+    let mut reader = BufReader::new(reader);
+    let compressed = reader.fill_buf()?.starts_with(&ZSTD_MAGIC);
+    let mut lines: Box<dyn BufRead> = if compressed {
+        Box::new(BufReader::new(zstd::stream::read::Decoder::with_buffer(
+            reader,
+        )?))
+    } else {
+        Box::new(reader)
+    };
+
+    let mut stats = ReplayStats::default();
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        let end = match lines.read_until(b'\n', &mut line) {
+            Ok(0) => break,
+            Ok(_) => false,
+            // Bytes read before the error are in `line`.
+            Err(e) if compressed => {
+                debug!("Compressed journal ends early: {}", e);
+                true
+            }
+            Err(e) => return Err(e),
+        };
+        if !line.iter().all(u8::is_ascii_whitespace) {
+            match serde_json::from_slice::<MetricsSnapshot>(&line) {
+                Ok(snapshot) => {
+                    stats.loaded += 1;
+                    on_snapshot(snapshot);
+                }
+                Err(e) => {
+                    debug!("Skipping malformed journal record: {}", e);
+                    stats.skipped += 1;
+                }
+            }
+        }
+        if end {
+            break;
+        }
+    }
+    Ok(stats)
+}
@@ -1,11 +1,31 @@
+pub mod access;
 pub mod aggregator;
+pub mod alerts;
 pub mod api;
 pub mod bus;
+pub mod check;
 pub mod config;
 pub mod console;
 pub mod db;
+pub mod delta;
+pub mod grafana;
+pub mod journal;
+pub mod logs;
 pub mod metrics;
+pub mod monitor;
+pub mod net;
+pub mod outage;
+pub mod procfs;
+pub mod reload;
 pub mod rpc;
+pub mod rpc_codec;
 pub mod runtime;
+#[cfg(unix)]
+pub mod sink;
+pub mod sqlite_store;
+pub mod statsd;
 pub mod storage;
+pub mod talkers;
+pub mod tls;
+pub mod topology;
 pub mod web;
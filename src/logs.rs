@@ -0,0 +1,116 @@
+//! Bounded ring of recent `tracing` events, served by `/api/logs` so
+//! operators can see collector warnings without access to the server console.
+
+use crate::metrics::now_timestamp_ms;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::fmt::{self, Write};
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+/// Log lines kept for `/api/logs`.
+pub const DEFAULT_LOG_LINES: usize = 500;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct LogEntry {
+    pub timestamp_ms: u128,
+    pub level: String,
+    pub target: String,
+    /// The event message followed by any other fields as `key=value`.
+    pub message: String,
+}
+
+pub struct LogRing {
+    capacity: usize,
+    entries: Mutex<VecDeque<LogEntry>>,
+}
+
+impl Default for LogRing {
+    fn default() -> Self {
+        Self::new(DEFAULT_LOG_LINES)
+    }
+}
+
+impl LogRing {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            entries: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    pub fn push(&self, entry: LogEntry) {
+        let mut entries = self.entries.lock().unwrap_or_else(|p| p.into_inner());
+        if entries.len() >= self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+    }
+
+    /// The most recent `limit` entries, oldest first.
+    pub fn recent(&self, limit: Option<usize>) -> Vec<LogEntry> {
+        let entries = self.entries.lock().unwrap_or_else(|p| p.into_inner());
+        let skip = entries.len().saturating_sub(limit.unwrap_or(entries.len()));
+        entries.iter().skip(skip).cloned().collect()
+    }
+
+    /// A `tracing` layer that records every event it sees into this ring.
+    pub fn layer(self: &Arc<Self>) -> LogRingLayer {
+        LogRingLayer { ring: self.clone() }
+    }
+}
+
+pub struct LogRingLayer {
+    ring: Arc<LogRing>,
+}
+
+impl<S: Subscriber> Layer<S> for LogRingLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        let meta = event.metadata();
+        self.ring.push(LogEntry {
+            timestamp_ms: now_timestamp_ms(),
+            level: meta.level().to_string(),
+            target: meta.target().to_string(),
+            message: visitor.finish(),
+        });
+    }
+}
+
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+    fields: String,
+}
+
+impl MessageVisitor {
+    fn finish(mut self) -> String {
+        if !self.fields.is_empty() {
+            if !self.message.is_empty() {
+                self.message.push(' ');
+            }
+            self.message.push_str(&self.fields);
+        }
+        self.message
+    }
+}
+
+impl Visit for MessageVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.record_debug(field, &format_args!("{}", value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{:?}", value);
+        } else {
+            if !self.fields.is_empty() {
+                self.fields.push(' ');
+            }
+            let _ = write!(self.fields, "{}={:?}", field.name(), value);
+        }
+    }
+}
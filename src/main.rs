@@ -4,5 +4,6 @@ fn main() {
     eprintln!("Examples:");
     eprintln!("  cargo run --bin server -- --rpc-addr 127.0.0.1:50051 --interval-ms 1000 --history 3600 --console");
     eprintln!("  cargo run --bin client -- --rpc-addr 127.0.0.1:50051 --mode web --bind 127.0.0.1 --port 8080 --history 3600");
+    eprintln!("  cargo run --bin server -- --standalone --port 8080 --console");
     std::process::exit(2);
 }
@@ -1,10 +1,92 @@
+use crate::config::{ByteUnits, DiskUsageBasis, NetUnits, PressureWeights, ProcessSelector};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::time::{SystemTime, UNIX_EPOCH};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct RpcMetricsSnapshot {
     pub timestamp_ms: u128,
+    /// See [`MetricsSnapshot::sample_interval_ms`].
+    #[serde(default)]
+    pub sample_interval_ms: f32,
     pub data: Vec<MetricSeries>,
+    /// See [`MetricsSnapshot::source`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// See [`MetricsSnapshot::resumed`].
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub resumed: bool,
+    /// See [`MetricsSnapshot::tags`].
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tags: BTreeMap<String, String>,
+}
+
+impl RpcMetricsSnapshot {
+    /// Re-expresses the `network` series in the requested unit.
+    pub fn with_net_units(mut self, units: NetUnits) -> Self {
+        if units == NetUnits::Bits {
+            for series in self.data.iter_mut().filter(|s| s.name == "network") {
+                if let DisplayFormat::Bytes { .. } = series.format {
+                    series.series.iter_mut().for_each(|v| *v *= 8.0);
+                    series.format = DisplayFormat::Bits {
+                        suffix: "b/s".to_string(),
+                    };
+                }
+            }
+        }
+        self
+    }
+
+    /// Marks byte-valued series to be shown with `units` prefixes.
+    pub fn with_byte_units(mut self, units: ByteUnits) -> Self {
+        for series in &mut self.data {
+            if let DisplayFormat::Bytes { units: u, .. } = &mut series.format {
+                *u = units;
+            }
+        }
+        self
+    }
+
+    /// Keeps only the first `limit` cores of the `cpu_cores` series, so
+    /// snapshots of many-core machines stay small; None keeps every core.
+    pub fn with_per_core_limit(mut self, limit: Option<usize>) -> Self {
+        if let Some(limit) = limit {
+            for series in self.data.iter_mut().filter(|s| s.name == "cpu_cores") {
+                series.series.truncate(limit);
+                series.legend.truncate(limit);
+            }
+        }
+        self
+    }
+
+    /// Rounds percentage series (CPU, memory, ...) to `decimals` places;
+    /// None keeps full precision.
+    pub fn with_decimals(mut self, decimals: Option<u32>) -> Self {
+        if let Some(decimals) = decimals {
+            for series in &mut self.data {
+                if let DisplayFormat::Percentage { .. } = series.format {
+                    series
+                        .series
+                        .iter_mut()
+                        .for_each(|v| *v = round_to(*v, decimals));
+                }
+            }
+        }
+        self
+    }
+}
+
+/// Most decimals worth keeping for an f32 percentage.
+pub const MAX_DECIMALS: u32 = 6;
+
+/// `value` rounded to `decimals` places (at most [`MAX_DECIMALS`]), so that
+/// it serializes as e.g. `42.9` instead of `42.857143`.
+pub fn round_to(value: f32, decimals: u32) -> f32 {
+    if !value.is_finite() {
+        return value;
+    }
+    let scale = 10f64.powi(decimals.min(MAX_DECIMALS) as i32);
+    ((f64::from(value) * scale).round() / scale) as f32
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -31,9 +113,21 @@ pub struct MetricLegend {
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(tag = "type", content = "params")]
 pub enum DisplayFormat {
-    Percentage { decimals: usize },
-    Bytes { suffix: String },
-    Float { decimals: usize },
+    Percentage {
+        decimals: usize,
+    },
+    Bytes {
+        suffix: String,
+        /// Prefix system the value should be scaled with; IEC when absent.
+        #[serde(default)]
+        units: ByteUnits,
+    },
+    Bits {
+        suffix: String,
+    },
+    Float {
+        decimals: usize,
+    },
     Integer,
 }
 
@@ -54,10 +148,70 @@ pub struct BatteryMetrics {
 pub struct CpuMetrics {
     pub total_usage_pct: f32,
     pub per_core_usage_pct: Vec<f32>,
-    pub load_avg_1: f32,
-    pub load_avg_5: f32,
-    pub load_avg_15: f32,
+    /// None where the platform has no load average (Windows).
+    pub load_avg_1: Option<f32>,
+    pub load_avg_5: Option<f32>,
+    pub load_avg_15: Option<f32>,
     pub temperature_celsius: Option<f32>,
+    #[serde(default)]
+    pub breakdown: Option<CpuTimeBreakdown>,
+    /// Mean core usage per socket, by ascending socket id; present when
+    /// `--group-cores-by-socket` is set.
+    #[serde(default)]
+    pub per_socket_usage_pct: Option<Vec<f32>>,
+    /// Set, with `per_core_usage_pct` left empty, on snapshots a sparse
+    /// [`MetricsBuffer`](crate::storage::MetricsBuffer) holds; snapshots read
+    /// back out of it always carry the full vector instead.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub per_core_changes: Option<SparseCores>,
+    /// [`busy_cores`] of `per_core_usage_pct`, set by the aggregator; None
+    /// when there is no per-core data.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub busy_cores: Option<f32>,
+}
+
+/// Per-core usages that changed against a reference vector, by core index.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SparseCores {
+    pub indices: Vec<u32>,
+    pub values: Vec<f32>,
+}
+
+impl SparseCores {
+    /// Cores of `current` more than `threshold` percentage points away from
+    /// the same core in `base`; with 0, every core that differs at all.
+    pub fn diff(base: &[f32], current: &[f32], threshold: f32) -> Self {
+        let mut changes = Self::default();
+        for (i, &value) in current.iter().enumerate() {
+            let unchanged = base
+                .get(i)
+                .is_some_and(|&old| (value - old).abs() <= threshold);
+            if !unchanged {
+                changes.indices.push(i as u32);
+                changes.values.push(value);
+            }
+        }
+        changes
+    }
+
+    /// Writes the changed cores into `base`; indices past its end are ignored.
+    pub fn apply(&self, base: &mut [f32]) {
+        for (&i, &value) in self.indices.iter().zip(&self.values) {
+            if let Some(core) = base.get_mut(i as usize) {
+                *core = value;
+            }
+        }
+    }
+}
+
+/// Share of CPU time per state since the previous sample; Linux only.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CpuTimeBreakdown {
+    pub user_pct: f32,
+    pub system_pct: f32,
+    pub iowait_pct: f32,
+    pub steal_pct: f32,
+    pub idle_pct: f32,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -67,6 +221,11 @@ pub struct MemoryMetrics {
     pub available_bytes: u64,
     pub swap_total_bytes: u64,
     pub swap_used_bytes: u64,
+    /// Swap traffic since the previous sample; Linux only.
+    #[serde(default)]
+    pub swap_in_bytes_per_sec: Option<f32>,
+    #[serde(default)]
+    pub swap_out_bytes_per_sec: Option<f32>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -81,7 +240,60 @@ pub struct NetworkMetrics {
 pub struct DiskMetrics {
     pub total_bytes: u64,
     pub available_bytes: u64,
+    /// Percentage chosen by `--disk-used-basis`; by default
+    /// `(total - available) / total`, which counts space reserved for root
+    /// as used.
     pub used_pct: f32,
+    /// Bytes actually allocated (`total - free`), where the platform reports
+    /// free blocks; unix only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub used_bytes: Option<u64>,
+}
+
+impl DiskMetrics {
+    /// Used bytes over total size. Falls back to `total - available` when
+    /// `used_bytes` is unknown.
+    pub fn used_pct_of_total(&self) -> f32 {
+        if self.total_bytes == 0 {
+            return 0.0;
+        }
+        let used = self
+            .used_bytes
+            .unwrap_or(self.total_bytes.saturating_sub(self.available_bytes));
+        used as f32 / self.total_bytes as f32 * 100.0
+    }
+
+    /// Used bytes over the space usable by unprivileged users
+    /// (`used + available`), as `df` reports it; reserved blocks count as
+    /// neither.
+    pub fn used_pct_available(&self) -> f32 {
+        let used = self
+            .used_bytes
+            .unwrap_or(self.total_bytes.saturating_sub(self.available_bytes));
+        let usable = used + self.available_bytes;
+        if usable == 0 {
+            0.0
+        } else {
+            used as f32 / usable as f32 * 100.0
+        }
+    }
+
+    /// The percentage `basis` describes.
+    pub fn used_pct_by(&self, basis: DiskUsageBasis) -> f32 {
+        match basis {
+            DiskUsageBasis::Available => {
+                if self.total_bytes == 0 {
+                    0.0
+                } else {
+                    self.total_bytes.saturating_sub(self.available_bytes) as f32
+                        / self.total_bytes as f32
+                        * 100.0
+                }
+            }
+            DiskUsageBasis::Total => self.used_pct_of_total(),
+            DiskUsageBasis::Df => self.used_pct_available(),
+        }
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -94,18 +306,218 @@ pub struct GpuMetrics {
     pub is_unified_memory: bool,
 }
 
+/// System-wide scheduler activity since the previous sample; Linux only.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SchedulerMetrics {
+    pub context_switches_per_sec: f32,
+    pub interrupts_per_sec: f32,
+}
+
+/// One row of the process table, as far as process-tree sums need it.
+#[derive(Clone, Debug)]
+pub struct ProcessEntry {
+    pub pid: u32,
+    pub parent_pid: Option<u32>,
+    pub name: String,
+    /// 100% is one fully used core.
+    pub cpu_usage_pct: f32,
+    pub rss_bytes: u64,
+}
+
+/// Combined usage of the `--watch-process` process(es) and all descendants.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WatchedProcess {
+    /// The `--watch-process` value.
+    pub target: String,
+    /// Processes summed; 0 while the watched process is not running.
+    pub process_count: usize,
+    /// 100% is one fully used core, so a busy tree can exceed 100.
+    pub cpu_usage_pct: f32,
+    pub rss_bytes: u64,
+}
+
+/// TCP traffic of one process over the last sampling interval.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ProcessNetUsage {
+    pub pid: u32,
+    pub name: String,
+    pub rx_bytes_per_sec: f32,
+    pub tx_bytes_per_sec: f32,
+}
+
+impl WatchedProcess {
+    /// Sums the processes matching `target` and their descendants, counting
+    /// each process once even when a match is itself a descendant of another.
+    pub fn from_processes(target: &ProcessSelector, processes: &[ProcessEntry]) -> Self {
+        let mut children: HashMap<u32, Vec<&ProcessEntry>> = HashMap::new();
+        for p in processes {
+            if let Some(parent) = p.parent_pid {
+                children.entry(parent).or_default().push(p);
+            }
+        }
+        let mut seen = HashSet::new();
+        let mut todo: Vec<&ProcessEntry> = processes
+            .iter()
+            .filter(|p| target.matches(p.pid, &p.name))
+            .collect();
+        let mut watched = Self {
+            target: target.to_string(),
+            process_count: 0,
+            cpu_usage_pct: 0.0,
+            rss_bytes: 0,
+        };
+        while let Some(p) = todo.pop() {
+            if !seen.insert(p.pid) {
+                continue;
+            }
+            watched.process_count += 1;
+            watched.cpu_usage_pct += p.cpu_usage_pct;
+            watched.rss_bytes += p.rss_bytes;
+            if let Some(kids) = children.get(&p.pid) {
+                todo.extend(kids.iter().copied());
+            }
+        }
+        watched
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct MetricsSnapshot {
     pub timestamp_ms: u128,
+    /// Monotonic time the rates in this snapshot were computed over, in ms.
+    /// Unlike `timestamp_ms` it is unaffected by wall-clock adjustments;
+    /// 0 in records written before it was collected.
+    #[serde(default)]
+    pub sample_interval_ms: f32,
     pub cpu: CpuMetrics,
     pub memory: MemoryMetrics,
     pub network: NetworkMetrics,
     pub disk: DiskMetrics,
     pub battery: Option<BatteryMetrics>,
     pub gpu: Option<GpuMetrics>,
+    #[serde(default)]
+    pub scheduler: Option<SchedulerMetrics>,
+    /// Present when `--watch-process` is set.
+    #[serde(default)]
+    pub watched_process: Option<WatchedProcess>,
+    /// Processes moving the most TCP traffic, busiest first; present when
+    /// `--net-top-processes` is set and per-socket counters are readable.
+    #[serde(default)]
+    pub net_top_processes: Option<Vec<ProcessNetUsage>>,
+    /// Weighted 0-100 blend of CPU, memory, disk and swap usage, see
+    /// [`MetricsSnapshot::pressure_score`]; set by the aggregator.
+    #[serde(default)]
+    pub pressure_score: Option<f32>,
+    /// Label of the aggregator that produced this snapshot when several run
+    /// in one process; None for the host's own collector.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// First sample after a suspend/resume: its rates are zeroed rather than
+    /// computed across the sleep, see [`MetricsSnapshot::zero_rates`].
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub resumed: bool,
+    /// Key/value labels of the collector (`--tag role=web`), for telling
+    /// hosts or sources apart and filtering by them.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tags: BTreeMap<String, String>,
 }
 
+/// `pressure_score` levels shown as warning and critical.
+pub const PRESSURE_WARN: f32 = 60.0;
+pub const PRESSURE_CRIT: f32 = 85.0;
+
+/// Series the dashboard reads from every live snapshot. `to_rpc_format` always
+/// emits them, and any trimmed or projected stream must keep them.
+pub const DASHBOARD_SERIES: &[&str] = &["cpu_total", "memory", "network"];
+
 impl MetricsSnapshot {
+    /// Zeroes every rate computed from counter deltas (network, swap paging,
+    /// scheduler and per-process traffic) and flags the snapshot `resumed`.
+    pub fn zero_rates(&mut self) {
+        self.network.rx_bytes_per_sec = 0.0;
+        self.network.tx_bytes_per_sec = 0.0;
+        for rate in [
+            &mut self.memory.swap_in_bytes_per_sec,
+            &mut self.memory.swap_out_bytes_per_sec,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            *rate = 0.0;
+        }
+        if let Some(scheduler) = &mut self.scheduler {
+            scheduler.context_switches_per_sec = 0.0;
+            scheduler.interrupts_per_sec = 0.0;
+        }
+        for process in self.net_top_processes.iter_mut().flatten() {
+            process.rx_bytes_per_sec = 0.0;
+            process.tx_bytes_per_sec = 0.0;
+        }
+        self.resumed = true;
+    }
+
+    /// Approximate memory held by this snapshot: the struct itself plus its
+    /// heap allocations (per-core vector, strings, process lists).
+    pub fn estimated_bytes(&self) -> usize {
+        let mut bytes = std::mem::size_of::<Self>()
+            + self.cpu.per_core_usage_pct.capacity() * std::mem::size_of::<f32>();
+        if let Some(sockets) = &self.cpu.per_socket_usage_pct {
+            bytes += sockets.capacity() * std::mem::size_of::<f32>();
+        }
+        if let Some(changes) = &self.cpu.per_core_changes {
+            bytes += changes.indices.capacity() * std::mem::size_of::<u32>()
+                + changes.values.capacity() * std::mem::size_of::<f32>();
+        }
+        if let Some(battery) = &self.battery {
+            bytes += battery.state.capacity();
+        }
+        if let Some(source) = &self.source {
+            bytes += source.capacity();
+        }
+        bytes += self
+            .tags
+            .iter()
+            .map(|(key, value)| key.capacity() + value.capacity())
+            .sum::<usize>();
+        if let Some(gpu) = &self.gpu {
+            bytes += gpu.name.capacity();
+        }
+        if let Some(watched) = &self.watched_process {
+            bytes += watched.target.capacity();
+        }
+        if let Some(top) = &self.net_top_processes {
+            bytes += top.capacity() * std::mem::size_of::<ProcessNetUsage>()
+                + top.iter().map(|p| p.name.capacity()).sum::<usize>();
+        }
+        bytes
+    }
+
+    /// Weighted mean of CPU, memory, disk and swap usage percentages,
+    /// clamped to 0-100; 0 when every weight is 0.
+    pub fn pressure_score(&self, weights: &PressureWeights) -> f32 {
+        let total = weights.total();
+        if total <= 0.0 {
+            return 0.0;
+        }
+        let pct = |used: u64, total: u64| {
+            if total > 0 {
+                used as f32 / total as f32 * 100.0
+            } else {
+                0.0
+            }
+        };
+        let weighted = weights.cpu * self.cpu.total_usage_pct
+            + weights.mem * pct(self.memory.used_bytes, self.memory.total_bytes)
+            + weights.disk * self.disk.used_pct
+            + weights.swap * pct(self.memory.swap_used_bytes, self.memory.swap_total_bytes);
+        let score = weighted / total;
+        if score.is_finite() {
+            score.clamp(0.0, 100.0)
+        } else {
+            0.0
+        }
+    }
+
     pub fn to_rpc_format(&self) -> RpcMetricsSnapshot {
         let total_mem_bytes = self.memory.total_bytes;
         let used_mem_bytes = self.memory.used_bytes;
@@ -159,14 +571,33 @@ impl MetricsSnapshot {
                 warn: None,
                 crit: None,
             },
-            MetricSeries {
+        ];
+
+        if let Some(busy) = self.cpu.busy_cores {
+            data.push(MetricSeries {
+                name: "cpu_busy_cores".to_string(),
+                beautiful_name: "CPU busy cores".to_string(),
+                series: vec![busy],
+                legend: vec![MetricLegend {
+                    name: "Busy".to_string(),
+                    color: "#f97316".to_string(),
+                    comment: Some(format!("of {} cores", self.cpu.per_core_usage_pct.len())),
+                }],
+                format: DisplayFormat::Float { decimals: 1 },
+                warn: None,
+                crit: None,
+            });
+        }
+
+        if let (Some(one), Some(five), Some(fifteen)) = (
+            self.cpu.load_avg_1,
+            self.cpu.load_avg_5,
+            self.cpu.load_avg_15,
+        ) {
+            data.push(MetricSeries {
                 name: "load_avg".to_string(),
                 beautiful_name: "CPU Load Average".to_string(),
-                series: vec![
-                    self.cpu.load_avg_1,
-                    self.cpu.load_avg_5,
-                    self.cpu.load_avg_15,
-                ],
+                series: vec![one, five, fifteen],
                 legend: vec![
                     MetricLegend {
                         name: "1m".to_string(),
@@ -187,7 +618,10 @@ impl MetricsSnapshot {
                 format: DisplayFormat::Float { decimals: 2 },
                 warn: None,
                 crit: None,
-            },
+            });
+        }
+
+        data.extend([
             MetricSeries {
                 name: "memory".to_string(),
                 beautiful_name: "Memory used (%)".to_string(),
@@ -232,6 +666,7 @@ impl MetricsSnapshot {
                 ],
                 format: DisplayFormat::Bytes {
                     suffix: "B/s".to_string(),
+                    units: ByteUnits::Iec,
                 },
                 warn: None,
                 crit: None,
@@ -249,7 +684,111 @@ impl MetricsSnapshot {
                 warn: Some(70.0),
                 crit: Some(90.0),
             },
-        ];
+        ]);
+
+        if let Some(score) = self.pressure_score {
+            data.push(MetricSeries {
+                name: "pressure".to_string(),
+                beautiful_name: "Pressure score".to_string(),
+                series: vec![score],
+                legend: vec![MetricLegend {
+                    name: "Pressure".to_string(),
+                    color: "#f472b6".to_string(),
+                    comment: None,
+                }],
+                format: DisplayFormat::Percentage { decimals: 0 },
+                warn: Some(PRESSURE_WARN),
+                crit: Some(PRESSURE_CRIT),
+            });
+        }
+
+        if let Some(sockets) = &self.cpu.per_socket_usage_pct {
+            data.push(MetricSeries {
+                name: "cpu_sockets".to_string(),
+                beautiful_name: "CPU per socket (%)".to_string(),
+                series: sockets.clone(),
+                legend: (0..sockets.len())
+                    .map(|i| {
+                        let hue = (i as f32 / sockets.len() as f32) * 360.0;
+                        MetricLegend {
+                            name: format!("S{}", i),
+                            color: format!("hsl({}, 70%, 50%)", hue),
+                            comment: None,
+                        }
+                    })
+                    .collect(),
+                format: DisplayFormat::Percentage { decimals: 1 },
+                warn: None,
+                crit: None,
+            });
+        }
+
+        if let Some(bd) = &self.cpu.breakdown {
+            data.push(MetricSeries {
+                name: "cpu_breakdown".to_string(),
+                beautiful_name: "CPU time breakdown (%)".to_string(),
+                series: vec![
+                    bd.user_pct,
+                    bd.system_pct,
+                    bd.iowait_pct,
+                    bd.steal_pct,
+                    bd.idle_pct,
+                ],
+                legend: vec![
+                    MetricLegend {
+                        name: "user".to_string(),
+                        color: "#c44".to_string(),
+                        comment: None,
+                    },
+                    MetricLegend {
+                        name: "system".to_string(),
+                        color: "#f59e0b".to_string(),
+                        comment: None,
+                    },
+                    MetricLegend {
+                        name: "iowait".to_string(),
+                        color: "#60a5fa".to_string(),
+                        comment: None,
+                    },
+                    MetricLegend {
+                        name: "steal".to_string(),
+                        color: "#e879f9".to_string(),
+                        comment: None,
+                    },
+                    MetricLegend {
+                        name: "idle".to_string(),
+                        color: "#6b7280".to_string(),
+                        comment: None,
+                    },
+                ],
+                format: DisplayFormat::Percentage { decimals: 1 },
+                warn: None,
+                crit: None,
+            });
+        }
+
+        if let Some(sched) = &self.scheduler {
+            data.push(MetricSeries {
+                name: "scheduler".to_string(),
+                beautiful_name: "Scheduler activity (/s)".to_string(),
+                series: vec![sched.context_switches_per_sec, sched.interrupts_per_sec],
+                legend: vec![
+                    MetricLegend {
+                        name: "Context switches".to_string(),
+                        color: "#f97316".to_string(),
+                        comment: None,
+                    },
+                    MetricLegend {
+                        name: "Interrupts".to_string(),
+                        color: "#22d3ee".to_string(),
+                        comment: None,
+                    },
+                ],
+                format: DisplayFormat::Float { decimals: 0 },
+                warn: None,
+                crit: None,
+            });
+        }
 
         if let Some(gpu) = &self.gpu {
             let vram_used_pct = if gpu.vram_total_bytes > 0 {
@@ -329,14 +868,87 @@ impl MetricsSnapshot {
 
         RpcMetricsSnapshot {
             timestamp_ms: self.timestamp_ms,
+            sample_interval_ms: self.sample_interval_ms,
             data,
+            source: self.source.clone(),
+            resumed: self.resumed,
+            tags: self.tags.clone(),
         }
     }
 }
 
+/// A single named value derived from a snapshot, for per-metric aggregations.
+#[derive(Clone, Copy)]
+pub struct ScalarMetric {
+    pub extract: fn(&MetricsSnapshot) -> Option<f32>,
+    /// Fixed value range (e.g. 0..100 for percentages); None means data-driven.
+    pub range: Option<(f32, f32)>,
+}
+
+pub const SCALAR_METRIC_NAMES: &[&str] = &[
+    "cpu", "memory", "swap", "disk", "load_1", "net_rx", "net_tx", "gpu",
+];
+
+pub fn scalar_metric(name: &str) -> Option<ScalarMetric> {
+    const PCT: Option<(f32, f32)> = Some((0.0, 100.0));
+    let (extract, range): (fn(&MetricsSnapshot) -> Option<f32>, _) = match name {
+        "cpu" => (|s| Some(s.cpu.total_usage_pct), PCT),
+        "memory" => (|s| pct_of(s.memory.used_bytes, s.memory.total_bytes), PCT),
+        "swap" => (
+            |s| pct_of(s.memory.swap_used_bytes, s.memory.swap_total_bytes),
+            PCT,
+        ),
+        "disk" => (|s| Some(s.disk.used_pct), PCT),
+        "load_1" => (|s| s.cpu.load_avg_1, None),
+        "net_rx" => (|s| Some(s.network.rx_bytes_per_sec), None),
+        "net_tx" => (|s| Some(s.network.tx_bytes_per_sec), None),
+        "gpu" => (|s| s.gpu.as_ref().map(|g| g.gpu_utilization_pct), PCT),
+        _ => return None,
+    };
+    Some(ScalarMetric { extract, range })
+}
+
+fn pct_of(used: u64, total: u64) -> Option<f32> {
+    if total == 0 {
+        None
+    } else {
+        Some(used as f32 / total as f32 * 100.0)
+    }
+}
+
+/// Machine-readable reason carried by every API error response.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// Nothing has been collected yet, or the requested window is empty.
+    NoData,
+    /// A query or body parameter is missing, malformed or out of range.
+    InvalidParameter,
+    /// The addressed resource, e.g. an alert id, does not exist.
+    NotFound,
+    /// The request conflicts with the resource's state.
+    Conflict,
+    /// The endpoint is turned off in the server configuration.
+    EndpointDisabled,
+    /// The request lacks a valid token for a protected endpoint.
+    Unauthorized,
+    /// The history database failed to answer.
+    Database,
+    /// Any other server-side failure.
+    Internal,
+    /// The request ran past the server's request timeout.
+    Timeout,
+    /// The server a client proxies to is unreachable or broke off its answer.
+    Upstream,
+}
+
+/// JSON body of every API error.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ErrorResponse {
-    pub error: String,
+    pub code: ErrorCode,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
 }
 
 pub fn now_timestamp_ms() -> u128 {
@@ -349,6 +961,20 @@ pub fn now_timestamp_ms() -> u128 {
     }
 }
 
+/// Cores' worth of work in `per_core` usages: their sum / 100, so 0 up to
+/// the core count. Non-finite readings are skipped.
+pub fn busy_cores(per_core: &[f32]) -> f32 {
+    per_core.iter().filter(|v| v.is_finite()).sum::<f32>() / 100.0
+}
+
+/// Rounds `ts_ms` to the nearest multiple of `interval_ms` (ties round up).
+pub fn align_timestamp_ms(ts_ms: u128, interval_ms: u128) -> u128 {
+    if interval_ms == 0 {
+        return ts_ms;
+    }
+    (ts_ms + interval_ms / 2) / interval_ms * interval_ms
+}
+
 pub fn format_bytes_short(bytes: u64) -> String {
     const KB: f64 = 1024.0;
     const MB: f64 = KB * 1024.0;
@@ -363,6 +989,30 @@ pub fn format_bytes_short(bytes: u64) -> String {
     }
 }
 
+/// Decimal (SI) bit-rate formatting, as used for link speeds.
+pub fn format_bits_per_sec(bits_per_sec: f32) -> String {
+    const K: f64 = 1000.0;
+    const M: f64 = K * 1000.0;
+    const G: f64 = M * 1000.0;
+    let bits = bits_per_sec as f64;
+    if bits >= G {
+        format!("{:.2} Gb/s", bits / G)
+    } else if bits >= M {
+        format!("{:.2} Mb/s", bits / M)
+    } else if bits >= K {
+        format!("{:.2} Kb/s", bits / K)
+    } else {
+        format!("{:.0} b/s", bits)
+    }
+}
+
+pub fn format_net_rate(bytes_per_sec: f32, units: NetUnits) -> String {
+    match units {
+        NetUnits::Bytes => format!("{:.0} B/s", bytes_per_sec),
+        NetUnits::Bits => format_bits_per_sec(bytes_per_sec * 8.0),
+    }
+}
+
 pub fn get_battery_color(percentage: f32) -> String {
     if percentage >= 90.0 {
         "#4ade80".to_string()
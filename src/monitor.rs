@@ -0,0 +1,478 @@
+//! Embedding entry point: runs the collector, in-memory history and,
+//! optionally, the HTTP and RPC servers inside a host application, the way
+//! the `server` binary wires them up.
+//!
+//! ```no_run
+//! # async fn demo() -> Result<(), resource_monitor::monitor::MonitorError> {
+//! use resource_monitor::monitor::Monitor;
+//! use std::time::Duration;
+//! use tokio_util::sync::CancellationToken;
+//!
+//! let cancel = CancellationToken::new();
+//! let handle = Monitor::builder()
+//!     .interval(Duration::from_secs(1))
+//!     .history(600)
+//!     .http(([127, 0, 0, 1], 9000).into())
+//!     .build()
+//!     .start(cancel.clone())
+//!     .await?;
+//! let latest = handle.latest();
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The pub/sub bus is per thread, so collection runs on a dedicated thread
+//! with its own single-threaded runtime; the servers run on the caller's.
+//! Bus subscribers of the host application are registered there through
+//! [`MonitorBuilder::on_collector_thread`].
+
+use crate::aggregator::{Aggregator, AggregatorConfig, CollectorHealth};
+use crate::alerts::AlertTracker;
+use crate::api::{api_only_router, router, AppState, Sampling};
+use crate::bus::{register_storage_subscriber_with_channel, send_to_subscribers};
+use crate::db::MetricsDb;
+use crate::metrics::{MetricsSnapshot, RpcMetricsSnapshot};
+use crate::net::{bind_tokio_listener, ListenOptions};
+use crate::rpc::{serve_rpc, MetricsRpcServer};
+use crate::storage::MetricsBuffer;
+use std::any::Any;
+use std::io;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::{broadcast, watch};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(1);
+pub const DEFAULT_HISTORY: usize = 3600;
+
+/// Snapshots each broadcast channel holds for slow subscribers.
+const CHANNEL_CAPACITY: usize = 256;
+
+#[derive(Debug, Error)]
+pub enum MonitorError {
+    #[error("database: {0}")]
+    Db(#[from] rusqlite::Error),
+    #[error("failed to bind HTTP {addr}: {source}")]
+    Bind { addr: SocketAddr, source: io::Error },
+    #[error("failed to bind RPC {addr}: {source}")]
+    BindRpc { addr: SocketAddr, source: io::Error },
+    #[error("failed to start collector thread: {0}")]
+    Collector(io::Error),
+    #[error("sampling interval must be greater than zero")]
+    ZeroInterval,
+}
+
+/// Setup run on the collector thread; what it returns lives as long as
+/// collection does.
+type CollectorSetup = Box<dyn FnOnce() -> Box<dyn Any> + Send>;
+
+pub struct MonitorBuilder {
+    interval: Duration,
+    history: usize,
+    http: Option<SocketAddr>,
+    dashboard: bool,
+    rpc: Option<SocketAddr>,
+    db_path: Option<PathBuf>,
+    db: Option<Arc<MetricsDb>>,
+    archive: bool,
+    aggregator: Option<AggregatorConfig>,
+    buffer: Option<Arc<MetricsBuffer>>,
+    alerts: Option<Arc<AlertTracker>>,
+    collector_setup: Vec<CollectorSetup>,
+}
+
+impl Default for MonitorBuilder {
+    fn default() -> Self {
+        Self {
+            interval: DEFAULT_INTERVAL,
+            history: DEFAULT_HISTORY,
+            http: None,
+            dashboard: true,
+            rpc: None,
+            db_path: None,
+            db: None,
+            archive: true,
+            aggregator: None,
+            buffer: None,
+            alerts: None,
+            collector_setup: Vec::new(),
+        }
+    }
+}
+
+impl MonitorBuilder {
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Snapshots kept in memory.
+    pub fn history(mut self, history: usize) -> Self {
+        self.history = history.max(1);
+        self
+    }
+
+    /// Serves the HTTP API on `addr`; port 0 picks a free one, see
+    /// [`MonitorHandle::http_addr`].
+    pub fn http(mut self, addr: SocketAddr) -> Self {
+        self.http = Some(addr);
+        self
+    }
+
+    /// Whether the HTTP server also serves the dashboard page (default) or
+    /// only the API.
+    pub fn dashboard(mut self, dashboard: bool) -> Self {
+        self.dashboard = dashboard;
+        self
+    }
+
+    /// Serves the RPC API on `addr`; port 0 picks a free one, see
+    /// [`MonitorHandle::rpc_addr`].
+    pub fn rpc(mut self, addr: SocketAddr) -> Self {
+        self.rpc = Some(addr);
+        self
+    }
+
+    /// Persists snapshots to the SQLite database at `path`; without it
+    /// history older than the buffer is kept in an in-memory database.
+    pub fn db_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.db_path = Some(path.into());
+        self
+    }
+
+    /// Archives snapshots into an already open `db`, taking precedence over
+    /// [`db_path`](Self::db_path).
+    pub fn db(mut self, db: Arc<MetricsDb>) -> Self {
+        self.db = Some(db);
+        self
+    }
+
+    /// Whether snapshots are archived into the database (default); off when
+    /// another store, such as a [`SqliteStore`], keeps the history instead.
+    ///
+    /// [`SqliteStore`]: crate::sqlite_store::SqliteStore
+    pub fn archive(mut self, archive: bool) -> Self {
+        self.archive = archive;
+        self
+    }
+
+    /// Collects with `config` rather than the defaults; its interval takes
+    /// precedence over [`interval`](Self::interval).
+    pub fn aggregator(mut self, config: AggregatorConfig) -> Self {
+        self.aggregator = Some(config);
+        self
+    }
+
+    /// Keeps history in `buffer`, taking precedence over
+    /// [`history`](Self::history).
+    pub fn buffer(mut self, buffer: Arc<MetricsBuffer>) -> Self {
+        self.buffer = Some(buffer);
+        self
+    }
+
+    /// Evaluates snapshots against `alerts` rather than default thresholds.
+    pub fn alerts(mut self, alerts: Arc<AlertTracker>) -> Self {
+        self.alerts = Some(alerts);
+        self
+    }
+
+    /// Runs `setup` on the collector thread before collection starts, e.g.
+    /// to register bus subscribers or spawn labeled aggregators, which only
+    /// reach subscribers on their own thread. What it returns, such as
+    /// activity ids, is kept until collection stops.
+    pub fn on_collector_thread(
+        mut self,
+        setup: impl FnOnce() -> Box<dyn Any> + Send + 'static,
+    ) -> Self {
+        self.collector_setup.push(Box::new(setup));
+        self
+    }
+
+    pub fn build(self) -> Monitor {
+        Monitor { config: self }
+    }
+}
+
+pub struct Monitor {
+    config: MonitorBuilder,
+}
+
+impl Monitor {
+    pub fn builder() -> MonitorBuilder {
+        MonitorBuilder::default()
+    }
+
+    /// Starts collecting, plus the configured servers, until `cancel` fires.
+    /// Listeners are bound before anything is spawned, so an error leaves
+    /// nothing running. Must be called from within a tokio runtime.
+    pub async fn start(self, cancel: CancellationToken) -> Result<MonitorHandle, MonitorError> {
+        let config = self.config;
+        let aggregator_config = config
+            .aggregator
+            .unwrap_or_else(|| AggregatorConfig::new(config.interval));
+        if aggregator_config.interval.is_zero() {
+            return Err(MonitorError::ZeroInterval);
+        }
+        let db = match config.db {
+            Some(db) => db,
+            None => Arc::new(MetricsDb::new(
+                config.db_path.as_deref().unwrap_or(Path::new(":memory:")),
+            )?),
+        };
+        let http_listener = match config.http {
+            Some(addr) => Some(
+                bind_tokio_listener(addr, &ListenOptions::default())
+                    .map(|listener| (addr, listener))
+                    .map_err(|source| MonitorError::Bind { addr, source })?,
+            ),
+            None => None,
+        };
+        let rpc_listener = match config.rpc {
+            Some(addr) => Some(
+                bind_tokio_listener(addr, &ListenOptions::default())
+                    .map(|listener| (addr, listener))
+                    .map_err(|source| MonitorError::BindRpc { addr, source })?,
+            ),
+            None => None,
+        };
+
+        let buffer = config
+            .buffer
+            .unwrap_or_else(|| Arc::new(MetricsBuffer::new(config.history)));
+        let (stream_tx, _) = broadcast::channel::<RpcMetricsSnapshot>(CHANNEL_CAPACITY);
+        let (internal_tx, _) = broadcast::channel::<MetricsSnapshot>(CHANNEL_CAPACITY);
+        // Reported by the API and RPC servers; constant unless the aggregator
+        // follows updates.
+        let interval_rx = aggregator_config
+            .interval_updates
+            .clone()
+            .unwrap_or_else(|| watch::channel(aggregator_config.interval).1);
+        // Subscribed before collection starts, so no snapshot is missed.
+        let converter_rx = internal_tx.subscribe();
+        let writer_rx = config.archive.then(|| internal_tx.subscribe());
+        let alerts_rx = internal_tx.subscribe();
+
+        let aggregator = Aggregator::new(aggregator_config);
+        let collector = aggregator.health();
+        let collector_thread = {
+            let buffer = buffer.clone();
+            let internal_tx = internal_tx.clone();
+            let cancel = cancel.clone();
+            let setup = config.collector_setup;
+            thread::Builder::new()
+                .name("monitor-collector".to_string())
+                .spawn(move || {
+                    let runtime = match tokio::runtime::Builder::new_current_thread()
+                        .enable_all()
+                        .build()
+                    {
+                        Ok(runtime) => runtime,
+                        Err(e) => {
+                            error!("Failed to start collector runtime: {}", e);
+                            return;
+                        }
+                    };
+                    runtime.block_on(async move {
+                        let _storage =
+                            register_storage_subscriber_with_channel(buffer, internal_tx);
+                        let _setup: Vec<_> = setup.into_iter().map(|setup| setup()).collect();
+                        aggregator.run(cancel).await;
+                    });
+                })
+                .map_err(MonitorError::Collector)?
+        };
+        let mut tasks = Vec::new();
+
+        let mut rx = converter_rx;
+        let converter_tx = stream_tx.clone();
+        tasks.push(tokio::spawn(async move {
+            while let Ok(snapshot) = rx.recv().await {
+                send_to_subscribers(&converter_tx, || snapshot.to_rpc_format());
+            }
+        }));
+
+        if let Some(mut rx) = writer_rx {
+            let writer_db = db.clone();
+            tasks.push(tokio::spawn(async move {
+                while let Ok(snapshot) = rx.recv().await {
+                    // Rows are keyed by timestamp alone: labeled sources would
+                    // overwrite the host's.
+                    if snapshot.source.is_some() {
+                        continue;
+                    }
+                    if let Err(e) = writer_db.insert(&snapshot) {
+                        error!("Failed to insert snapshot into database: {}", e);
+                    }
+                }
+            }));
+        }
+
+        let alerts = config.alerts.unwrap_or_default();
+        let mut rx = alerts_rx;
+        let watcher_alerts = alerts.clone();
+        tasks.push(tokio::spawn(async move {
+            loop {
+                match rx.recv().await {
+                    // Thresholds describe this host, not labeled sources.
+                    Ok(snapshot) if snapshot.source.is_none() => watcher_alerts.observe(&snapshot),
+                    Ok(_) => {}
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        warn!("Alert watcher lagged, {} snapshots not evaluated", n);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        }));
+
+        let http_addr = http_listener.map(|(addr, listener)| {
+            let bound = listener.local_addr().unwrap_or(addr);
+            let state = AppState {
+                buffer: buffer.clone(),
+                db: db.clone(),
+                store: None,
+                stream_tx: stream_tx.clone(),
+                shutdown: cancel.clone(),
+                collector: collector.clone(),
+                thresholds: Default::default(),
+                alerts: alerts.clone(),
+                limits: Default::default(),
+                logs: Default::default(),
+                api_token: None,
+                net_scale: Default::default(),
+                requests: Default::default(),
+                initial_window_ms: 0,
+                metrics_prefix: None,
+                sampling: Sampling {
+                    interval: Some(interval_rx.clone()),
+                    ..Default::default()
+                },
+                criticality: Default::default(),
+            };
+            let app = if config.dashboard {
+                router(state)
+            } else {
+                api_only_router(state)
+            };
+            let shutdown = cancel.clone();
+            info!("Monitor HTTP API listening on http://{}", bound);
+            tasks.push(tokio::spawn(async move {
+                if let Err(e) = axum::serve(listener, app)
+                    .with_graceful_shutdown(async move { shutdown.cancelled().await })
+                    .await
+                {
+                    error!("HTTP server error: {}", e);
+                }
+            }));
+            bound
+        });
+
+        let rpc_addr = rpc_listener.map(|(addr, listener)| {
+            let bound = listener.local_addr().unwrap_or(addr);
+            info!("Monitor RPC server listening on {}", bound);
+            let server = MetricsRpcServer::new(buffer.clone(), stream_tx.clone())
+                .with_interval(interval_rx)
+                .with_collector(collector.clone());
+            tasks.push(tokio::spawn(serve_rpc(
+                listener,
+                server,
+                None,
+                cancel.clone(),
+            )));
+            bound
+        });
+
+        Ok(MonitorHandle {
+            buffer,
+            db,
+            stream_tx,
+            internal_tx,
+            collector,
+            alerts,
+            http_addr,
+            rpc_addr,
+            collector_thread,
+            tasks,
+        })
+    }
+}
+
+/// A running [`Monitor`]; it stops when the token passed to
+/// [`Monitor::start`] is cancelled.
+pub struct MonitorHandle {
+    buffer: Arc<MetricsBuffer>,
+    db: Arc<MetricsDb>,
+    stream_tx: broadcast::Sender<RpcMetricsSnapshot>,
+    internal_tx: broadcast::Sender<MetricsSnapshot>,
+    collector: Arc<CollectorHealth>,
+    alerts: Arc<AlertTracker>,
+    http_addr: Option<SocketAddr>,
+    rpc_addr: Option<SocketAddr>,
+    collector_thread: thread::JoinHandle<()>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl MonitorHandle {
+    pub fn latest(&self) -> Option<MetricsSnapshot> {
+        self.buffer.latest()
+    }
+
+    pub fn buffer(&self) -> &Arc<MetricsBuffer> {
+        &self.buffer
+    }
+
+    pub fn db(&self) -> &Arc<MetricsDb> {
+        &self.db
+    }
+
+    /// Snapshots as they are collected, in RPC form.
+    pub fn subscribe(&self) -> broadcast::Receiver<RpcMetricsSnapshot> {
+        self.stream_tx.subscribe()
+    }
+
+    /// The channel [`subscribe`](Self::subscribe) listens on, for serving
+    /// the snapshots elsewhere.
+    pub fn stream_tx(&self) -> &broadcast::Sender<RpcMetricsSnapshot> {
+        &self.stream_tx
+    }
+
+    /// Snapshots as they are collected, whole.
+    pub fn subscribe_snapshots(&self) -> broadcast::Receiver<MetricsSnapshot> {
+        self.internal_tx.subscribe()
+    }
+
+    pub fn alerts(&self) -> &Arc<AlertTracker> {
+        &self.alerts
+    }
+
+    pub fn collector_health(&self) -> &Arc<CollectorHealth> {
+        &self.collector
+    }
+
+    /// Address the HTTP server is bound to, when enabled.
+    pub fn http_addr(&self) -> Option<SocketAddr> {
+        self.http_addr
+    }
+
+    /// Address the RPC server is bound to, when enabled.
+    pub fn rpc_addr(&self) -> Option<SocketAddr> {
+        self.rpc_addr
+    }
+
+    /// Waits for everything to stop after cancellation.
+    pub async fn join(self) {
+        let thread = self.collector_thread;
+        let _ = tokio::task::spawn_blocking(move || thread.join()).await;
+        // The collector held the last publishers, so the bus tasks now end.
+        drop(self.internal_tx);
+        drop(self.stream_tx);
+        for task in self.tasks {
+            let _ = task.await;
+        }
+    }
+}
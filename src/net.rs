@@ -0,0 +1,89 @@
+//! TCP listeners bound through socket2, so the accept backlog and TCP
+//! keepalive can be set before the socket starts listening, and a cap on
+//! the connections served at once.
+
+use socket2::{Domain, Protocol, Socket, TcpKeepalive, Type};
+use std::io;
+use std::net::{SocketAddr, TcpListener};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Backlog tokio uses for `TcpListener::bind`, kept as the default so an
+/// unconfigured listener behaves as before.
+pub const DEFAULT_BACKLOG: u32 = 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ListenOptions {
+    /// Pending connections the kernel queues before refusing new ones.
+    pub backlog: u32,
+    /// Idle time before keepalive probes start on accepted connections;
+    /// keepalive stays off when unset.
+    pub keepalive: Option<Duration>,
+}
+
+impl Default for ListenOptions {
+    fn default() -> Self {
+        Self {
+            backlog: DEFAULT_BACKLOG,
+            keepalive: None,
+        }
+    }
+}
+
+/// Binds a non-blocking listener on `addr` with `options` applied.
+/// Keepalive is set on the listening socket, which accepted connections
+/// inherit.
+pub fn bind_listener(addr: SocketAddr, options: &ListenOptions) -> io::Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    // Matches tokio's bind so a restart can reuse a port in TIME_WAIT.
+    #[cfg(not(windows))]
+    socket.set_reuse_address(true)?;
+    if let Some(idle) = options.keepalive {
+        socket.set_keepalive(true)?;
+        socket.set_tcp_keepalive(&TcpKeepalive::new().with_time(idle))?;
+    }
+    socket.bind(&addr.into())?;
+    let backlog = i32::try_from(options.backlog).unwrap_or(i32::MAX);
+    socket.listen(backlog)?;
+    socket.set_nonblocking(true)?;
+    Ok(socket.into())
+}
+
+/// Like [`bind_listener`], returning a tokio listener. Must be called from
+/// within a runtime.
+pub fn bind_tokio_listener(
+    addr: SocketAddr,
+    options: &ListenOptions,
+) -> io::Result<tokio::net::TcpListener> {
+    tokio::net::TcpListener::from_std(bind_listener(addr, options)?)
+}
+
+/// Caps the connections served at once (`--rpc-max-connections`).
+#[derive(Clone, Debug)]
+pub struct ConnectionLimit {
+    max: usize,
+    slots: Arc<Semaphore>,
+}
+
+impl ConnectionLimit {
+    pub fn new(max: usize) -> Self {
+        Self {
+            max,
+            slots: Arc::new(Semaphore::new(max)),
+        }
+    }
+
+    pub fn max(&self) -> usize {
+        self.max
+    }
+
+    /// A slot, held until the permit is dropped; None while all are taken.
+    pub fn try_acquire(&self) -> Option<OwnedSemaphorePermit> {
+        self.slots.clone().try_acquire_owned().ok()
+    }
+
+    pub fn in_use(&self) -> usize {
+        self.max - self.slots.available_permits()
+    }
+}
@@ -0,0 +1,46 @@
+//! Logging for outputs that keep running while their destination is down
+//! (sink socket, StatsD, journal): an outage is warned about once, further
+//! failures only at debug level, and the recovery at info.
+
+use std::fmt;
+use tracing::{debug, info, warn};
+
+#[derive(Debug, Default)]
+pub struct Outage {
+    failing: bool,
+    dropped: u64,
+}
+
+impl Outage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_failing(&self) -> bool {
+        self.failing
+    }
+
+    /// Failed writes so far.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Records a successful write, logging `recovered` when it ends an outage.
+    pub fn succeeded(&mut self, recovered: fmt::Arguments<'_>) {
+        if std::mem::take(&mut self.failing) {
+            info!("{}", recovered);
+        }
+    }
+
+    /// Records a failed write: warns with `started` when it begins an outage,
+    /// else logs `repeated` at debug level.
+    pub fn failed(&mut self, started: fmt::Arguments<'_>, repeated: fmt::Arguments<'_>) {
+        self.dropped += 1;
+        if self.failing {
+            debug!("{}", repeated);
+        } else {
+            warn!("{}", started);
+            self.failing = true;
+        }
+    }
+}
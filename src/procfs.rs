@@ -0,0 +1,231 @@
+//! Parsers for Linux `/proc` files that `sysinfo` does not expose.
+
+use crate::metrics::{CpuTimeBreakdown, SchedulerMetrics};
+use std::sync::OnceLock;
+
+/// Cumulative jiffy counters from the aggregate `cpu` line of `/proc/stat`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CpuTimes {
+    pub user: u64,
+    pub nice: u64,
+    pub system: u64,
+    pub idle: u64,
+    pub iowait: u64,
+    pub irq: u64,
+    pub softirq: u64,
+    pub steal: u64,
+}
+
+impl CpuTimes {
+    /// Parses a line such as `cpu  4705 356 584 3699 23 23 0 0 0 0`.
+    /// Kernels older than 2.6.11 omit steal, which is then taken as zero.
+    pub fn parse_line(line: &str) -> Option<Self> {
+        let mut fields = line.split_whitespace();
+        if fields.next()? != "cpu" {
+            return None;
+        }
+        let values: Vec<u64> = fields.map_while(|f| f.parse().ok()).collect();
+        if values.len() < 5 {
+            return None;
+        }
+        let at = |i: usize| values.get(i).copied().unwrap_or(0);
+        Some(Self {
+            user: at(0),
+            nice: at(1),
+            system: at(2),
+            idle: at(3),
+            iowait: at(4),
+            irq: at(5),
+            softirq: at(6),
+            steal: at(7),
+        })
+    }
+
+    pub fn parse_stat(text: &str) -> Option<Self> {
+        text.lines().find_map(Self::parse_line)
+    }
+
+    pub fn total(&self) -> u64 {
+        self.user
+            + self.nice
+            + self.system
+            + self.idle
+            + self.iowait
+            + self.irq
+            + self.softirq
+            + self.steal
+    }
+
+    /// Percentages of the time elapsed between `prev` and `self`.
+    /// Returns None when no time passed or the counters went backwards.
+    pub fn breakdown_since(&self, prev: &CpuTimes) -> Option<CpuTimeBreakdown> {
+        let delta = |now: u64, then: u64| now.checked_sub(then);
+        let user = delta(self.user + self.nice, prev.user + prev.nice)?;
+        let system = delta(
+            self.system + self.irq + self.softirq,
+            prev.system + prev.irq + prev.softirq,
+        )?;
+        let idle = delta(self.idle, prev.idle)?;
+        let iowait = delta(self.iowait, prev.iowait)?;
+        let steal = delta(self.steal, prev.steal)?;
+
+        let total = user + system + idle + iowait + steal;
+        if total == 0 {
+            return None;
+        }
+        let pct = |v: u64| v as f32 / total as f32 * 100.0;
+        Some(CpuTimeBreakdown {
+            user_pct: pct(user),
+            system_pct: pct(system),
+            iowait_pct: pct(iowait),
+            steal_pct: pct(steal),
+            idle_pct: pct(idle),
+        })
+    }
+}
+
+/// Swap page counters from `/proc/vmstat`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct VmStat {
+    pub pswpin: u64,
+    pub pswpout: u64,
+}
+
+/// Page size assumed where `sysconf` cannot report one.
+pub const FALLBACK_PAGE_BYTES: u64 = 4096;
+
+/// Bytes per page, which `pswpin`/`pswpout` count in: `sysconf(_SC_PAGESIZE)`,
+/// read once. Not always 4 KiB: arm64 and ppc64 kernels often use 16 or
+/// 64 KiB pages.
+pub fn page_bytes() -> u64 {
+    static PAGE_BYTES: OnceLock<u64> = OnceLock::new();
+    *PAGE_BYTES.get_or_init(|| {
+        #[cfg(unix)]
+        {
+            // SAFETY: sysconf only reads a system constant.
+            let size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+            if size > 0 {
+                return size as u64;
+            }
+        }
+        FALLBACK_PAGE_BYTES
+    })
+}
+
+impl VmStat {
+    /// Returns None unless both counters are present.
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut pswpin = None;
+        let mut pswpout = None;
+        for line in text.lines() {
+            let mut fields = line.split_whitespace();
+            match (fields.next(), fields.next().and_then(|v| v.parse().ok())) {
+                (Some("pswpin"), Some(v)) => pswpin = Some(v),
+                (Some("pswpout"), Some(v)) => pswpout = Some(v),
+                _ => {}
+            }
+        }
+        Some(Self {
+            pswpin: pswpin?,
+            pswpout: pswpout?,
+        })
+    }
+
+    /// Swap-in and swap-out bytes per second over `dt_secs` since `prev`.
+    /// Returns None when no time passed or the counters went backwards.
+    pub fn swap_rates_since(&self, prev: &VmStat, dt_secs: f32) -> Option<(f32, f32)> {
+        if dt_secs <= 0.0 {
+            return None;
+        }
+        let pages_in = self.pswpin.checked_sub(prev.pswpin)?;
+        let pages_out = self.pswpout.checked_sub(prev.pswpout)?;
+        let page_bytes = page_bytes();
+        let rate = |pages: u64| (pages * page_bytes) as f32 / dt_secs;
+        Some((rate(pages_in), rate(pages_out)))
+    }
+}
+
+/// The counters the collector takes from `/proc/stat`, read in one pass.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ProcStat {
+    pub cpu_times: Option<CpuTimes>,
+    pub sched: Option<SchedStat>,
+}
+
+impl ProcStat {
+    pub fn parse(text: &str) -> Self {
+        let mut cpu_times = None;
+        let mut ctxt = None;
+        let mut intr = None;
+        for line in text.lines() {
+            if cpu_times.is_none() {
+                if let Some(times) = CpuTimes::parse_line(line) {
+                    cpu_times = Some(times);
+                    continue;
+                }
+            }
+            let mut fields = line.split_whitespace();
+            match (fields.next(), fields.next().and_then(|v| v.parse().ok())) {
+                (Some("ctxt"), Some(v)) => ctxt = Some(v),
+                (Some("intr"), Some(v)) => intr = Some(v),
+                _ => {}
+            }
+        }
+        Self {
+            cpu_times,
+            sched: ctxt.zip(intr).map(|(ctxt, intr)| SchedStat { ctxt, intr }),
+        }
+    }
+}
+
+/// Context-switch and interrupt counters from `/proc/stat`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SchedStat {
+    pub ctxt: u64,
+    /// First field of the `intr` line: interrupts serviced across all sources.
+    pub intr: u64,
+}
+
+impl SchedStat {
+    /// Returns None unless both the `ctxt` and `intr` lines are present.
+    pub fn parse(text: &str) -> Option<Self> {
+        ProcStat::parse(text).sched
+    }
+
+    /// Per-second rates over `dt_secs` since `prev`.
+    /// Returns None when no time passed or the counters went backwards.
+    pub fn rates_since(&self, prev: &SchedStat, dt_secs: f32) -> Option<SchedulerMetrics> {
+        if dt_secs <= 0.0 {
+            return None;
+        }
+        let ctxt = self.ctxt.checked_sub(prev.ctxt)?;
+        let intr = self.intr.checked_sub(prev.intr)?;
+        Some(SchedulerMetrics {
+            context_switches_per_sec: ctxt as f32 / dt_secs,
+            interrupts_per_sec: intr as f32 / dt_secs,
+        })
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub fn read_proc_stat() -> ProcStat {
+    std::fs::read_to_string("/proc/stat")
+        .map(|text| ProcStat::parse(&text))
+        .unwrap_or_default()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn read_proc_stat() -> ProcStat {
+    ProcStat::default()
+}
+
+#[cfg(target_os = "linux")]
+pub fn read_vmstat() -> Option<VmStat> {
+    let text = std::fs::read_to_string("/proc/vmstat").ok()?;
+    VmStat::parse(&text)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn read_vmstat() -> Option<VmStat> {
+    None
+}
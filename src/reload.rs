@@ -0,0 +1,198 @@
+//! Config file re-read on SIGHUP. Thresholds and the sampling interval are
+//! applied in place; listen addresses are read at startup only, so changing
+//! them takes a restart.
+
+use crate::config::{SharedThresholds, Thresholds};
+use serde::Deserialize;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+/// Shortest sampling interval a reload may set.
+pub const MIN_RELOAD_INTERVAL: Duration = Duration::from_millis(100);
+/// Longest sampling interval a reload may set.
+pub const MAX_RELOAD_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Contents of `--config`, a JSON object. Absent keys fall back to the
+/// command-line value.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    pub interval_ms: Option<u64>,
+    pub thresholds: Option<Thresholds>,
+    pub bind: Option<IpAddr>,
+    pub port: Option<u16>,
+    pub rpc_addr: Option<SocketAddr>,
+}
+
+#[derive(Debug, Error)]
+pub enum ReloadError {
+    #[error("cannot read {path}: {source}")]
+    Read { path: String, source: io::Error },
+    #[error("cannot parse {path}: {source}")]
+    Parse {
+        path: String,
+        source: serde_json::Error,
+    },
+    #[error("invalid thresholds for {0}: {1}")]
+    Thresholds(&'static str, &'static str),
+}
+
+impl ConfigFile {
+    pub fn load(path: &Path) -> Result<Self, ReloadError> {
+        let text = std::fs::read_to_string(path).map_err(|source| ReloadError::Read {
+            path: path.display().to_string(),
+            source,
+        })?;
+        serde_json::from_str(&text).map_err(|source| ReloadError::Parse {
+            path: path.display().to_string(),
+            source,
+        })
+    }
+
+    /// Overrides the command-line listen addresses with those the file sets.
+    pub fn apply_listen_addrs(&self, bind: &mut IpAddr, port: &mut u16, rpc_addr: &mut SocketAddr) {
+        *bind = self.bind.unwrap_or(*bind);
+        *port = self.port.unwrap_or(*port);
+        *rpc_addr = self.rpc_addr.unwrap_or(*rpc_addr);
+    }
+}
+
+/// The settings a reload compares against.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Settings {
+    pub interval: Duration,
+    pub thresholds: Thresholds,
+    pub bind: IpAddr,
+    pub port: u16,
+    pub rpc_addr: SocketAddr,
+}
+
+/// What a reload changed.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReloadReport {
+    pub applied: Vec<String>,
+    /// Settings that differ from the running server but cannot change live.
+    pub restart_required: Vec<String>,
+}
+
+pub struct Reloader {
+    path: PathBuf,
+    /// Values from the command line, used for keys missing from the file.
+    defaults: Settings,
+    current: Settings,
+    thresholds: SharedThresholds,
+    interval_tx: watch::Sender<Duration>,
+}
+
+impl Reloader {
+    /// `defaults` must be the settings the server is running with; the
+    /// interval is published on `interval_tx` and the thresholds written to
+    /// `thresholds` whenever the file changes them.
+    pub fn new(
+        path: impl Into<PathBuf>,
+        defaults: Settings,
+        thresholds: SharedThresholds,
+        interval_tx: watch::Sender<Duration>,
+    ) -> Self {
+        Self {
+            path: path.into(),
+            defaults,
+            current: defaults,
+            thresholds,
+            interval_tx,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Re-reads the file and applies what changed, logging each change. A
+    /// file that fails to load or validate leaves every setting untouched.
+    pub fn reload(&mut self) -> Result<ReloadReport, ReloadError> {
+        let file = ConfigFile::load(&self.path)?;
+        let thresholds = file.thresholds.unwrap_or(self.defaults.thresholds);
+        for (name, t) in [("cpu_total", thresholds.cpu), ("memory", thresholds.memory)] {
+            t.validate()
+                .map_err(|reason| ReloadError::Thresholds(name, reason))?;
+        }
+
+        let mut report = ReloadReport::default();
+
+        // Only the file's value is bounded; the command line's stands as given.
+        let interval = match file.interval_ms.map(Duration::from_millis) {
+            Some(requested) => {
+                let interval = requested.clamp(MIN_RELOAD_INTERVAL, MAX_RELOAD_INTERVAL);
+                if interval != requested {
+                    warn!(
+                        "interval_ms {} is outside {:?}..={:?}, using {:?}",
+                        requested.as_millis(),
+                        MIN_RELOAD_INTERVAL,
+                        MAX_RELOAD_INTERVAL,
+                        interval
+                    );
+                }
+                interval
+            }
+            None => self.defaults.interval,
+        };
+        if interval != self.current.interval {
+            report.applied.push(format!(
+                "interval: {:?} -> {:?}",
+                self.current.interval, interval
+            ));
+            self.interval_tx.send_replace(interval);
+            self.current.interval = interval;
+        }
+
+        if thresholds != self.current.thresholds {
+            report.applied.push(format!(
+                "thresholds: {:?} -> {:?}",
+                self.current.thresholds, thresholds
+            ));
+            self.thresholds.set(thresholds);
+            self.current.thresholds = thresholds;
+        }
+
+        let restart_only = [
+            (
+                "bind",
+                file.bind.map(|b| b.to_string()),
+                self.current.bind.to_string(),
+            ),
+            (
+                "port",
+                file.port.map(|p| p.to_string()),
+                self.current.port.to_string(),
+            ),
+            (
+                "rpc_addr",
+                file.rpc_addr.map(|a| a.to_string()),
+                self.current.rpc_addr.to_string(),
+            ),
+        ];
+        for (name, wanted, running) in restart_only {
+            if let Some(wanted) = wanted.filter(|w| *w != running) {
+                report
+                    .restart_required
+                    .push(format!("{name}: {running} -> {wanted}"));
+            }
+        }
+
+        for change in &report.applied {
+            info!("Config reload applied {}", change);
+        }
+        for change in &report.restart_required {
+            warn!("Config reload: {} requires a restart", change);
+        }
+        if report.applied.is_empty() && report.restart_required.is_empty() {
+            info!("Config reload: no changes in {}", self.path.display());
+        }
+        Ok(report)
+    }
+}
@@ -1,29 +1,119 @@
+use crate::aggregator::CollectorHealth;
 use crate::metrics::RpcMetricsSnapshot;
-use crate::storage::MetricsBuffer;
+use crate::net::{self, ConnectionLimit, ListenOptions};
+use crate::rpc_codec::RpcCodec;
+use crate::storage::{select_history, HistoryOrder, MetricsBuffer};
 use futures::StreamExt;
+use serde::{Deserialize, Serialize};
 use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::Duration;
 use tarpc::context;
 use tarpc::server;
 use tarpc::server::Channel;
-use tokio::sync::broadcast;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, watch};
 use tokio::time::MissedTickBehavior;
 use tokio_serde::formats::Json;
+use tokio_util::codec::{Framed, LengthDelimitedCodec};
 use tokio_util::sync::CancellationToken;
 use tracing::{error, info, warn};
 
+/// Result of a long-poll that distinguishes a clean successor from a lagged one.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum StreamEvent {
+    Snapshot(RpcMetricsSnapshot),
+    /// The subscriber fell behind and `skipped` snapshots were dropped before
+    /// `latest`; the caller should backfill from `history`.
+    Gap {
+        skipped: u64,
+        latest: RpcMetricsSnapshot,
+    },
+}
+
+impl StreamEvent {
+    pub fn snapshot(&self) -> &RpcMetricsSnapshot {
+        match self {
+            StreamEvent::Snapshot(snap) => snap,
+            StreamEvent::Gap { latest, .. } => latest,
+        }
+    }
+
+    pub fn into_snapshot(self) -> RpcMetricsSnapshot {
+        match self {
+            StreamEvent::Snapshot(snap) => snap,
+            StreamEvent::Gap { latest, .. } => latest,
+        }
+    }
+}
+
+/// Bumped when an RPC method or payload changes incompatibly.
+pub const RPC_SCHEMA_VERSION: u32 = 1;
+
+/// Optional RPC capabilities advertised by [`ServerInfo::features`].
+pub const FEATURE_NEXT_EVENT: &str = "next_event";
+pub const FEATURE_NEXT_AFTER_UNTIL: &str = "next_after_until";
+pub const FEATURE_NEAREST: &str = "nearest";
+pub const FEATURE_HISTORY_ORDERED: &str = "history_ordered";
+pub const FEATURE_LATEST_BLOCKING: &str = "latest_blocking";
+
+/// What a server supports, so clients can adapt to older or trimmed-down servers.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ServerInfo {
+    pub schema_version: u32,
+    pub server_version: String,
+    /// Sampling interval; None when the server was not told it.
+    pub interval_ms: Option<u64>,
+    pub history_capacity: usize,
+    /// Series names in the latest snapshot (`cpu_total`, `memory`, `gpu`, ...).
+    pub collectors: Vec<String>,
+    pub features: Vec<String>,
+}
+
+impl ServerInfo {
+    pub fn supports(&self, feature: &str) -> bool {
+        self.features.iter().any(|f| f == feature)
+    }
+}
+
 #[tarpc::service]
 pub trait MetricsRpc {
     async fn latest() -> Option<RpcMetricsSnapshot>;
     async fn history(limit: Option<usize>, since_ms: Option<u64>) -> Vec<RpcMetricsSnapshot>;
-    async fn next_after(since_ms: u64, timeout_ms: u64) -> Option<RpcMetricsSnapshot>;
+    /// Like `history`, but `limit` keeps the oldest snapshots when
+    /// `from_start` is set, and the result comes in `order`.
+    async fn history_ordered(
+        limit: Option<usize>,
+        since_ms: Option<u64>,
+        from_start: bool,
+        order: HistoryOrder,
+    ) -> Vec<RpcMetricsSnapshot>;
+    /// The next snapshot after `since_ms`, waiting up to `timeout_ms` for one.
+    /// With `until_ms` set, snapshots are returned in order from the buffer and
+    /// the call yields None once the next one would be past the ceiling.
+    async fn next_after(
+        since_ms: u64,
+        timeout_ms: u64,
+        until_ms: Option<u64>,
+    ) -> Option<RpcMetricsSnapshot>;
+    /// Like `next_after`, but reports subscriber lag as a `Gap` instead of hiding it.
+    async fn next_event(since_ms: u64, timeout_ms: u64) -> Option<StreamEvent>;
+    /// The buffered snapshot closest to `timestamp_ms`; None outside the buffered range.
+    async fn nearest(timestamp_ms: u64) -> Option<RpcMetricsSnapshot>;
+    async fn server_info() -> ServerInfo;
+    /// The newest snapshot once one is newer than `since_ms`, waiting up to
+    /// `timeout_ms`. Unlike `next_after`, which a consumer calls once per
+    /// snapshot to see them all, snapshots published together while the
+    /// caller was away are coalesced: only the newest is returned.
+    async fn latest_blocking(since_ms: u64, timeout_ms: u64) -> Option<RpcMetricsSnapshot>;
 }
 
 #[derive(Clone)]
 pub struct MetricsRpcServer {
     buffer: Arc<MetricsBuffer>,
     stream_tx: broadcast::Sender<RpcMetricsSnapshot>,
+    interval: Option<watch::Receiver<Duration>>,
+    collector: Option<Arc<CollectorHealth>>,
 }
 
 impl MetricsRpcServer {
@@ -31,7 +121,25 @@ impl MetricsRpcServer {
         buffer: Arc<MetricsBuffer>,
         stream_tx: broadcast::Sender<RpcMetricsSnapshot>,
     ) -> Self {
-        Self { buffer, stream_tx }
+        Self {
+            buffer,
+            stream_tx,
+            interval: None,
+            collector: None,
+        }
+    }
+
+    /// Reports the current sampling interval (which a config reload may change) in `server_info`.
+    pub fn with_interval(mut self, interval: watch::Receiver<Duration>) -> Self {
+        self.interval = Some(interval);
+        self
+    }
+
+    /// Reports the interval `collector` actually samples at, which
+    /// auto-tuning may have lengthened, over the configured one.
+    pub fn with_collector(mut self, collector: Arc<CollectorHealth>) -> Self {
+        self.collector = Some(collector);
+        self
     }
 }
 
@@ -41,35 +149,145 @@ impl MetricsRpc for MetricsRpcServer {
     }
 
     async fn history(
+        self,
+        ctx: context::Context,
+        limit: Option<usize>,
+        since_ms: Option<u64>,
+    ) -> Vec<RpcMetricsSnapshot> {
+        self.history_ordered(ctx, limit, since_ms, false, HistoryOrder::Asc)
+            .await
+    }
+
+    async fn history_ordered(
         self,
         _ctx: context::Context,
         limit: Option<usize>,
         since_ms: Option<u64>,
+        from_start: bool,
+        order: HistoryOrder,
     ) -> Vec<RpcMetricsSnapshot> {
-        let mut snapshots = self.buffer.history(None);
+        let snapshots = match since_ms {
+            Some(since_ms) => self.buffer.range(Some(u128::from(since_ms)), None),
+            None => self.buffer.history(None),
+        };
+        select_history(snapshots, limit, from_start, order)
+            .iter()
+            .map(|s| s.to_rpc_format())
+            .collect()
+    }
 
-        if let Some(since_ms) = since_ms {
-            snapshots.retain(|s| s.timestamp_ms >= since_ms as u128);
+    async fn next_after(
+        self,
+        ctx: context::Context,
+        since_ms: u64,
+        timeout_ms: u64,
+        until_ms: Option<u64>,
+    ) -> Option<RpcMetricsSnapshot> {
+        let Some(until_ms) = until_ms else {
+            return self
+                .wait_next(ctx, since_ms, timeout_ms)
+                .await
+                .map(StreamEvent::into_snapshot);
+        };
+        if since_ms >= until_ms {
+            return None;
         }
+        // Walk the buffer one snapshot at a time so a bounded replay sees every
+        // sample in the window, not just the latest.
+        let next = match self
+            .buffer
+            .page_after(Some(u128::from(since_ms)), 1)
+            .items
+            .pop()
+        {
+            Some(snap) => snap.to_rpc_format(),
+            None => self
+                .wait_next(ctx, since_ms, timeout_ms)
+                .await
+                .map(StreamEvent::into_snapshot)?,
+        };
+        (next.timestamp_ms <= u128::from(until_ms)).then_some(next)
+    }
 
-        let mut rpc_snapshots: Vec<RpcMetricsSnapshot> =
-            snapshots.into_iter().map(|s| s.to_rpc_format()).collect();
+    async fn next_event(
+        self,
+        ctx: context::Context,
+        since_ms: u64,
+        timeout_ms: u64,
+    ) -> Option<StreamEvent> {
+        self.wait_next(ctx, since_ms, timeout_ms).await
+    }
 
-        if let Some(limit) = limit {
-            let len = rpc_snapshots.len();
-            let take = limit.min(len);
-            rpc_snapshots = rpc_snapshots.into_iter().skip(len - take).collect();
-        }
+    async fn nearest(
+        self,
+        _ctx: context::Context,
+        timestamp_ms: u64,
+    ) -> Option<RpcMetricsSnapshot> {
+        self.buffer
+            .nearest(u128::from(timestamp_ms))
+            .map(|snap| snap.to_rpc_format())
+    }
 
-        rpc_snapshots
+    async fn server_info(self, _ctx: context::Context) -> ServerInfo {
+        let collectors = self
+            .buffer
+            .latest()
+            .map(|snap| {
+                snap.to_rpc_format()
+                    .data
+                    .into_iter()
+                    .map(|series| series.name)
+                    .collect()
+            })
+            .unwrap_or_default();
+        ServerInfo {
+            schema_version: RPC_SCHEMA_VERSION,
+            server_version: env!("CARGO_PKG_VERSION").to_string(),
+            interval_ms: self
+                .collector
+                .as_ref()
+                .and_then(|c| c.effective_interval())
+                .or_else(|| self.interval.as_ref().map(|rx| *rx.borrow()))
+                .map(|interval| interval.as_millis().try_into().unwrap_or(u64::MAX)),
+            history_capacity: self.buffer.capacity(),
+            collectors,
+            features: [
+                FEATURE_NEXT_EVENT,
+                FEATURE_NEXT_AFTER_UNTIL,
+                FEATURE_NEAREST,
+                FEATURE_HISTORY_ORDERED,
+                FEATURE_LATEST_BLOCKING,
+            ]
+            .map(String::from)
+            .to_vec(),
+        }
     }
 
-    async fn next_after(
+    async fn latest_blocking(
         self,
         ctx: context::Context,
         since_ms: u64,
         timeout_ms: u64,
     ) -> Option<RpcMetricsSnapshot> {
+        let first = self
+            .wait_next(ctx, since_ms, timeout_ms)
+            .await?
+            .into_snapshot();
+        // Whatever else arrived meanwhile is in the buffer; skip to the newest.
+        Some(match self.buffer.latest() {
+            Some(latest) if latest.timestamp_ms > first.timestamp_ms => latest.to_rpc_format(),
+            _ => first,
+        })
+    }
+}
+
+impl MetricsRpcServer {
+    async fn wait_next(
+        &self,
+        ctx: context::Context,
+        since_ms: u64,
+        timeout_ms: u64,
+    ) -> Option<StreamEvent> {
         let deadline = ctx.deadline;
         let now = std::time::SystemTime::now();
         let until_deadline = match deadline.duration_since(now) {
@@ -89,21 +307,30 @@ impl MetricsRpc for MetricsRpcServer {
 
         if let Some(latest) = self.buffer.latest() {
             if latest.timestamp_ms > since_ms as u128 {
-                return Some(latest.to_rpc_format());
+                return Some(StreamEvent::Snapshot(latest.to_rpc_format()));
             }
         }
 
         let mut rx = self.stream_tx.subscribe();
         let fut = async move {
+            let mut skipped: u64 = 0;
             loop {
                 match rx.recv().await {
                     Ok(snap) => {
                         if snap.timestamp_ms > since_ms as u128 {
-                            return Some(snap);
+                            return Some(if skipped > 0 {
+                                StreamEvent::Gap {
+                                    skipped,
+                                    latest: snap,
+                                }
+                            } else {
+                                StreamEvent::Snapshot(snap)
+                            });
                         }
                     }
-                    Err(broadcast::error::RecvError::Lagged(_)) => {
-                        continue;
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        warn!("RPC stream subscriber lagged, skipped {} snapshots", n);
+                        skipped += n;
                     }
                     Err(broadcast::error::RecvError::Closed) => {
                         return None;
@@ -116,24 +343,40 @@ impl MetricsRpc for MetricsRpcServer {
     }
 }
 
+/// Binds `addr` and serves `server_impl` on it; see [`serve_rpc`].
 pub async fn run_rpc_server(
-    buffer: Arc<MetricsBuffer>,
-    stream_tx: broadcast::Sender<RpcMetricsSnapshot>,
+    server_impl: MetricsRpcServer,
     addr: SocketAddr,
+    listen: ListenOptions,
+    max_connections: Option<usize>,
     cancel: CancellationToken,
 ) {
-    info!("RPC server listening on {}", addr);
-
-    let listener = match tarpc::serde_transport::tcp::listen(addr, Json::default).await {
+    let listener = match net::bind_tokio_listener(addr, &listen) {
         Ok(l) => l,
         Err(e) => {
             error!("Failed to bind RPC listener {}: {}", addr, e);
             return;
         }
     };
+    info!(
+        "RPC server listening on {}",
+        listener.local_addr().unwrap_or(addr)
+    );
+
+    serve_rpc(listener, server_impl, max_connections, cancel).await;
+}
 
-    let server_impl = MetricsRpcServer::new(buffer, stream_tx);
-    let mut incoming = listener;
+/// Serves connections accepted on `listener` until `cancel` fires. With
+/// `max_connections` set, a connection arriving while that many are open is
+/// accepted and closed at once, so a flood cannot spawn unbounded tasks.
+pub async fn serve_rpc(
+    listener: TcpListener,
+    server_impl: MetricsRpcServer,
+    max_connections: Option<usize>,
+    cancel: CancellationToken,
+) {
+    let limit = max_connections.map(ConnectionLimit::new);
+    let mut rejected: u64 = 0;
 
     loop {
         tokio::select! {
@@ -141,15 +384,34 @@ pub async fn run_rpc_server(
                 info!("RPC server shutting down");
                 break;
             }
-            next = incoming.next() => {
-                let Some(next) = next else { break; };
-                let transport = match next {
-                    Ok(t) => t,
+            accepted = listener.accept() => {
+                let (stream, peer) = match accepted {
+                    Ok(accepted) => accepted,
                     Err(e) => {
                         error!("RPC accept error: {}", e);
                         continue;
                     }
                 };
+                let permit = match limit.as_ref().map(|l| (l.max(), l.try_acquire())) {
+                    Some((max, None)) => {
+                        rejected += 1;
+                        // A flood would otherwise log a line per connection.
+                        if rejected.is_power_of_two() {
+                            warn!(
+                                "RPC connection limit of {} reached, closing {} ({} rejected so far)",
+                                max, peer, rejected
+                            );
+                        }
+                        drop(stream);
+                        continue;
+                    }
+                    Some((_, permit)) => permit,
+                    None => None,
+                };
+                let transport = tarpc::serde_transport::new(
+                    Framed::new(stream, LengthDelimitedCodec::new()),
+                    RpcCodec::mirroring(),
+                );
                 let server_impl = server_impl.clone();
                 tokio::spawn(async move {
                     let channel = server::BaseChannel::with_defaults(transport);
@@ -159,6 +421,7 @@ pub async fn run_rpc_server(
                             fut.await;
                         })
                         .await;
+                    drop(permit);
                 });
             }
         }
@@ -167,17 +430,77 @@ pub async fn run_rpc_server(
 
 pub async fn run_rpc_client_poller(
     addr: SocketAddr,
+    compress: bool,
     interval: Duration,
     cancel: CancellationToken,
     on_snapshot: impl Fn(RpcMetricsSnapshot) + Send + Sync + 'static,
 ) {
+    run_rpc_client_poller_with(
+        move || connect_client(addr, compress),
+        interval,
+        None,
+        cancel,
+        on_snapshot,
+        |_| {},
+    )
+    .await;
+}
+
+/// Connects to `addr`, zstd-compressing frames when `compress` is set.
+pub async fn connect_client(addr: SocketAddr, compress: bool) -> std::io::Result<MetricsRpcClient> {
+    let config = tarpc::client::Config::default();
+    let client = if compress {
+        let stream = TcpStream::connect(addr).await?;
+        let transport = tarpc::serde_transport::new(
+            Framed::new(stream, LengthDelimitedCodec::new()),
+            RpcCodec::compressed(),
+        );
+        MetricsRpcClient::new(config, transport).spawn()
+    } else {
+        let transport = tarpc::serde_transport::tcp::connect(addr, Json::default).await?;
+        MetricsRpcClient::new(config, transport).spawn()
+    };
+    info!(
+        "RPC client connected to {}{}",
+        addr,
+        if compress { " (zstd)" } else { "" }
+    );
+    Ok(client)
+}
+
+/// Polls `latest` through clients produced by `connect`.
+///
+/// With `max_consecutive_failures` set, a run of that many failed connects or
+/// calls invokes `on_failure` with the count and stops the poller. Any
+/// successful call resets the count.
+pub async fn run_rpc_client_poller_with<C, Fut>(
+    mut connect: C,
+    interval: Duration,
+    max_consecutive_failures: Option<u32>,
+    cancel: CancellationToken,
+    on_snapshot: impl Fn(RpcMetricsSnapshot) + Send + Sync + 'static,
+    on_failure: impl FnOnce(u32),
+) where
+    C: FnMut() -> Fut,
+    Fut: std::future::Future<Output = std::io::Result<MetricsRpcClient>>,
+{
     let mut ticker = tokio::time::interval(interval);
     ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
 
     let on_snapshot = Arc::new(on_snapshot);
     let mut client: Option<MetricsRpcClient> = None;
+    let mut failures: u32 = 0;
 
     loop {
+        if max_consecutive_failures.is_some_and(|max| failures >= max) {
+            error!(
+                "RPC poller giving up after {} consecutive failures",
+                failures
+            );
+            on_failure(failures);
+            break;
+        }
+
         tokio::select! {
             _ = cancel.cancelled() => {
                 info!("RPC client poller shutting down");
@@ -187,15 +510,11 @@ pub async fn run_rpc_client_poller(
         }
 
         if client.is_none() {
-            match tarpc::serde_transport::tcp::connect(addr, Json::default).await {
-                Ok(transport) => {
-                    client = Some(
-                        MetricsRpcClient::new(tarpc::client::Config::default(), transport).spawn(),
-                    );
-                    info!("RPC client connected to {}", addr);
-                }
+            match connect().await {
+                Ok(c) => client = Some(c),
                 Err(e) => {
-                    error!("RPC connect error to {}: {}", addr, e);
+                    error!("RPC connect error: {}", e);
+                    failures += 1;
                     continue;
                 }
             }
@@ -207,46 +526,107 @@ pub async fn run_rpc_client_poller(
         let ctx = context::current();
         match c.latest(ctx).await {
             Ok(Some(snap)) => {
+                failures = 0;
                 (on_snapshot)(snap);
             }
             Ok(None) => {
+                failures = 0;
                 warn!("RPC latest returned no data");
             }
             Err(e) => {
                 error!("RPC latest error: {}", e);
+                failures += 1;
                 client = None;
             }
         }
     }
 }
 
+/// Snapshots further apart than this many sampling intervals mean the stream
+/// missed some, e.g. across a reconnect.
+pub const STREAM_GAP_INTERVALS: u64 = 2;
+
+/// Forwards the buffered snapshots after `since_ms` and before `until_ms`.
+/// A failed `history` call is retried once before the gap is left as is.
+async fn backfill<F: Fn(RpcMetricsSnapshot) + ?Sized>(
+    client: &MetricsRpcClient,
+    since_ms: u64,
+    until_ms: u128,
+    on_snapshot: &F,
+) {
+    for attempt in 0..2 {
+        match client
+            .history(context::current(), None, Some(since_ms.saturating_add(1)))
+            .await
+        {
+            Ok(missed) => {
+                for snap in missed.into_iter().filter(|s| s.timestamp_ms < until_ms) {
+                    on_snapshot(snap);
+                }
+                return;
+            }
+            Err(e) if attempt == 0 => warn!("RPC history backfill error: {}, retrying", e),
+            Err(e) => error!("RPC history backfill error: {}", e),
+        }
+    }
+}
+
+/// Long-polls `next_event`, backfilling gaps from `history`: those the server
+/// reports, and jumps of more than [`STREAM_GAP_INTERVALS`] sampling intervals
+/// between consecutive snapshots. Falls back to polling `latest` when the
+/// server cannot stream: either `server_info` says so, or the server predates
+/// `server_info` altogether. The poll interval is the server's sampling
+/// interval when known, else `poll_interval`. Polling lasts until the
+/// connection fails; `server_info` is asked again after every reconnect.
 pub async fn run_rpc_client_streamer(
     addr: SocketAddr,
+    compress: bool,
+    poll_interval: Duration,
     cancel: CancellationToken,
     on_snapshot: impl Fn(RpcMetricsSnapshot) + Send + Sync + 'static,
 ) {
+    run_rpc_client_streamer_with(
+        move || connect_client(addr, compress),
+        poll_interval,
+        cancel,
+        on_snapshot,
+    )
+    .await;
+}
+
+/// [`run_rpc_client_streamer`] through clients produced by `connect`.
+pub async fn run_rpc_client_streamer_with<C, Fut>(
+    mut connect: C,
+    poll_interval: Duration,
+    cancel: CancellationToken,
+    on_snapshot: impl Fn(RpcMetricsSnapshot) + Send + Sync + 'static,
+) where
+    C: FnMut() -> Fut,
+    Fut: std::future::Future<Output = std::io::Result<MetricsRpcClient>>,
+{
     let on_snapshot = Arc::new(on_snapshot);
     let mut client: Option<MetricsRpcClient> = None;
     let mut since_ms: u64 = 0;
+    // Asked once per connection: servers predating `server_info` may drop
+    // the connection on it.
+    let mut info_checked = false;
+    // Set while the connected server can only be polled.
+    let mut poll_ticker: Option<tokio::time::Interval> = None;
+    // The server's sampling interval, for telling a jump from a normal step.
+    let mut interval_ms: Option<u64> = None;
 
     loop {
         if client.is_none() {
-            let connect_fut = tarpc::serde_transport::tcp::connect(addr, Json::default);
             tokio::select! {
                 _ = cancel.cancelled() => {
                     info!("RPC client streamer shutting down");
                     break;
                 }
-                res = connect_fut => {
+                res = connect() => {
                     match res {
-                        Ok(transport) => {
-                            client = Some(
-                                MetricsRpcClient::new(tarpc::client::Config::default(), transport).spawn(),
-                            );
-                            info!("RPC client connected to {}", addr);
-                        }
+                        Ok(c) => client = Some(c),
                         Err(e) => {
-                            error!("RPC connect error to {}: {}", addr, e);
+                            error!("RPC connect error: {}", e);
                             tokio::select! {
                                 _ = cancel.cancelled() => break,
                                 _ = tokio::time::sleep(Duration::from_millis(500)) => {}
@@ -261,11 +641,98 @@ pub async fn run_rpc_client_streamer(
         let Some(c) = &client else {
             continue;
         };
+
+        if !info_checked {
+            info_checked = true;
+            let info = c.server_info(context::current()).await;
+            // A fresh connection that answered `latest`, to poll over.
+            let mut legacy_client = None;
+            let fallback = match info {
+                Ok(info) if !info.supports(FEATURE_NEXT_EVENT) => Some(
+                    info.interval_ms
+                        .map_or(poll_interval, Duration::from_millis),
+                ),
+                Ok(info) => {
+                    info!(
+                        "RPC server schema v{} ({}), interval {:?} ms, history {}; streaming",
+                        info.schema_version,
+                        info.server_version,
+                        info.interval_ms,
+                        info.history_capacity
+                    );
+                    interval_ms = info.interval_ms;
+                    None
+                }
+                Err(e) => {
+                    // Servers predating `server_info` drop the connection on
+                    // it; if `latest` still answers, that is what this is.
+                    let legacy = match connect().await {
+                        Ok(fresh) => {
+                            let answered = fresh.latest(context::current()).await.is_ok();
+                            if answered {
+                                legacy_client = Some(fresh);
+                            }
+                            answered
+                        }
+                        Err(_) => false,
+                    };
+                    if !legacy {
+                        warn!("RPC server_info failed ({}), retrying", e);
+                        client = None;
+                        info_checked = false;
+                        tokio::select! {
+                            _ = cancel.cancelled() => break,
+                            _ = tokio::time::sleep(Duration::from_millis(500)) => {}
+                        }
+                        continue;
+                    }
+                    Some(poll_interval)
+                }
+            };
+            if let Some(interval) = fallback {
+                let interval = interval.max(Duration::from_millis(1));
+                warn!(
+                    "RPC server cannot stream, polling every {:?} instead",
+                    interval
+                );
+                if legacy_client.is_some() {
+                    client = legacy_client;
+                }
+                let mut ticker = tokio::time::interval(interval);
+                ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+                poll_ticker = Some(ticker);
+                continue;
+            }
+        }
+
+        if let Some(ticker) = &mut poll_ticker {
+            tokio::select! {
+                _ = cancel.cancelled() => {
+                    info!("RPC client streamer cancelled");
+                    break;
+                }
+                _ = ticker.tick() => {}
+            }
+            match c.latest(context::current()).await {
+                Ok(Some(snap)) => (on_snapshot)(snap),
+                Ok(None) => warn!("RPC latest returned no data"),
+                Err(e) => {
+                    // The server_info failure that made this look like a
+                    // legacy server may have been transient: ask again.
+                    error!("RPC latest error: {}", e);
+                    client = None;
+                    info_checked = false;
+                    poll_ticker = None;
+                }
+            }
+            continue;
+        }
+
         let mut ctx = context::current();
         let long_poll_ms: u64 = 30_000;
         ctx.deadline = std::time::SystemTime::now() + Duration::from_millis(long_poll_ms + 1_000);
 
-        let req_fut = c.next_after(ctx, since_ms, long_poll_ms);
+        let req_fut = c.next_event(ctx, since_ms, long_poll_ms);
         tokio::select! {
             _ = cancel.cancelled() => {
                 info!("RPC client streamer cancelled");
@@ -273,10 +740,30 @@ pub async fn run_rpc_client_streamer(
             }
             res = req_fut => {
                 match res {
-                    Ok(Some(snap)) => {
+                    Ok(Some(StreamEvent::Snapshot(snap))) => {
+                        let jumped = interval_ms.is_some_and(|ms| {
+                            since_ms > 0
+                                && snap.timestamp_ms
+                                    > u128::from(since_ms)
+                                        + u128::from(ms.saturating_mul(STREAM_GAP_INTERVALS))
+                        });
+                        if jumped {
+                            warn!(
+                                "RPC stream jumped from {} to {} ms, backfilling",
+                                since_ms, snap.timestamp_ms
+                            );
+                            backfill(c, since_ms, snap.timestamp_ms, on_snapshot.as_ref()).await;
+                        }
                         since_ms = snap.timestamp_ms.try_into().unwrap_or(u64::MAX);
                         (on_snapshot)(snap);
                     }
+                    Ok(Some(StreamEvent::Gap { skipped, latest })) => {
+                        warn!("RPC stream gap of {} snapshots, backfilling", skipped);
+                        let latest_ms: u64 = latest.timestamp_ms.try_into().unwrap_or(u64::MAX);
+                        backfill(c, since_ms, latest.timestamp_ms, on_snapshot.as_ref()).await;
+                        since_ms = latest_ms;
+                        (on_snapshot)(latest);
+                    }
                     Ok(None) => {
                         continue;
                     }
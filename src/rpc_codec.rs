@@ -0,0 +1,93 @@
+//! Wire codec for RPC frames: JSON, optionally zstd-compressed
+//! (`--rpc-compress` on the client).
+//!
+//! Decoding accepts both forms, told apart by the zstd frame magic. The
+//! server's codec answers in whichever form it last received, so plain
+//! clients keep working unchanged and compressing clients get compressed
+//! replies without any server flag.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::io::{self, Read};
+use std::pin::Pin;
+use tokio_util::bytes::{Bytes, BytesMut};
+
+/// Leading bytes of every zstd frame; JSON never starts with them.
+pub(crate) const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+/// Compression level: fast, while still shrinking snapshot JSON several-fold.
+pub const RPC_ZSTD_LEVEL: i32 = 3;
+/// Largest frame accepted after decompression, so a small compressed frame
+/// cannot expand without bound.
+pub const MAX_DECODED_FRAME_BYTES: u64 = 256 * 1024 * 1024;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RpcCodec {
+    compress: bool,
+    /// Follow the form of the last decoded frame when encoding.
+    mirror: bool,
+}
+
+impl RpcCodec {
+    /// Plain JSON, as sent by clients without `--rpc-compress`.
+    pub fn plain() -> Self {
+        Self::default()
+    }
+
+    /// zstd-compressed JSON.
+    pub fn compressed() -> Self {
+        Self {
+            compress: true,
+            mirror: false,
+        }
+    }
+
+    /// Server side: replies plain until a compressed frame arrives.
+    pub fn mirroring() -> Self {
+        Self {
+            compress: false,
+            mirror: true,
+        }
+    }
+
+    pub fn compresses(&self) -> bool {
+        self.compress
+    }
+}
+
+impl<T: Serialize> tokio_serde::Serializer<T> for RpcCodec {
+    type Error = io::Error;
+
+    fn serialize(self: Pin<&mut Self>, item: &T) -> Result<Bytes, io::Error> {
+        let json = serde_json::to_vec(item)?;
+        if self.compress {
+            Ok(zstd::bulk::compress(&json, RPC_ZSTD_LEVEL)?.into())
+        } else {
+            Ok(json.into())
+        }
+    }
+}
+
+impl<T: DeserializeOwned> tokio_serde::Deserializer<T> for RpcCodec {
+    type Error = io::Error;
+
+    fn deserialize(mut self: Pin<&mut Self>, src: &BytesMut) -> Result<T, io::Error> {
+        let compressed = src.starts_with(&ZSTD_MAGIC);
+        if self.mirror {
+            self.compress = compressed;
+        }
+        if !compressed {
+            return Ok(serde_json::from_slice(src)?);
+        }
+        let mut json = Vec::new();
+        zstd::stream::read::Decoder::new(&src[..])?
+            .take(MAX_DECODED_FRAME_BYTES + 1)
+            .read_to_end(&mut json)?;
+        if json.len() as u64 > MAX_DECODED_FRAME_BYTES {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "decompressed RPC frame exceeds the size limit",
+            ));
+        }
+        Ok(serde_json::from_slice(&json)?)
+    }
+}
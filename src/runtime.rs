@@ -1,10 +1,36 @@
+use crate::logs::LogRing;
+use axum::Router;
+use std::future::IntoFuture;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
 use tokio::signal;
-use tracing::error;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, warn};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
 use tracing_subscriber::EnvFilter;
 
-pub fn init_tracing() {
+/// Installs the console logger plus a ring of recent events for `/api/logs`.
+pub fn init_tracing() -> Arc<LogRing> {
     let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
-    tracing_subscriber::fmt().with_env_filter(filter).init();
+    let ring = Arc::new(LogRing::default());
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_subscriber::fmt::layer())
+        .with(ring.layer())
+        .init();
+    ring
+}
+
+/// Like [`init_tracing`], but keeps stdout free for data output.
+pub fn init_tracing_to_stderr() {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .init();
 }
 
 pub async fn shutdown_signal() {
@@ -36,3 +62,68 @@ pub async fn shutdown_signal() {
         _ = terminate => {},
     }
 }
+
+/// Serves `app` on `listener` until `cancel` fires. From then on new
+/// connections are refused while in-flight requests get up to `drain` to
+/// complete; connections still open after that (e.g. event streams) are
+/// dropped.
+pub async fn serve_with_drain(
+    listener: TcpListener,
+    app: Router,
+    cancel: CancellationToken,
+    drain: Duration,
+) -> io::Result<()> {
+    let shutdown = cancel.clone();
+    let server = axum::serve(listener, app)
+        .with_graceful_shutdown(async move { shutdown.cancelled().await })
+        .into_future();
+    tokio::pin!(server);
+    tokio::select! {
+        res = &mut server => return res,
+        _ = cancel.cancelled() => {}
+    }
+    match tokio::time::timeout(drain, server).await {
+        Ok(res) => res,
+        Err(_) => {
+            warn!(
+                "HTTP connections still open after {:?} drain, closing them",
+                drain
+            );
+            Ok(())
+        }
+    }
+}
+
+/// Calls `on_reload` for every SIGHUP until `cancel` fires. Off Unix there is
+/// no reload signal and this only waits for `cancel`.
+pub async fn reload_on_sighup(cancel: CancellationToken, on_reload: impl FnMut()) {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut on_reload = on_reload;
+        let mut sighup = match signal(SignalKind::hangup()) {
+            Ok(s) => s,
+            Err(e) => {
+                error!("Failed to install SIGHUP handler: {}", e);
+                return;
+            }
+        };
+        loop {
+            tokio::select! {
+                _ = cancel.cancelled() => break,
+                received = sighup.recv() => {
+                    if received.is_none() {
+                        break;
+                    }
+                    on_reload();
+                }
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        drop(on_reload);
+        cancel.cancelled().await;
+    }
+}
@@ -0,0 +1,169 @@
+//! Local sink writing each snapshot as one JSON line to a Unix datagram
+//! socket or a FIFO (`--sink-socket <path>`), for collector agents on the same
+//! host. Writes never block: a snapshot the reader is not ready for is
+//! dropped and counted instead of stalling the aggregator.
+
+use crate::bus::MetricsEvent;
+use crate::metrics::MetricsSnapshot;
+use crate::outage::Outage;
+use std::fs::{File, OpenOptions};
+use std::io::{self, ErrorKind, Write};
+use std::os::unix::fs::{FileTypeExt, OpenOptionsExt};
+use std::os::unix::net::UnixDatagram;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use tracing::warn;
+
+#[derive(Debug, Error)]
+pub enum SinkError {
+    #[error("cannot open sink {path}: {source}")]
+    Open { path: String, source: io::Error },
+    #[error("sink {0} is neither a Unix datagram socket nor a FIFO")]
+    Unsupported(String),
+}
+
+enum Target {
+    Datagram(UnixDatagram),
+    /// Opened lazily, since opening a FIFO for writing fails until a reader
+    /// has it open.
+    Fifo(Option<File>),
+}
+
+pub struct SocketSink {
+    path: PathBuf,
+    target: Target,
+    /// Tail of a line a FIFO only partly accepted; it is finished before the
+    /// next line so readers never see interleaved JSON.
+    pending: Vec<u8>,
+    outage: Outage,
+}
+
+impl SocketSink {
+    /// Picks datagram or FIFO mode from the type of the file at `path`, which
+    /// must already exist (bound by the reader, or created with `mkfifo`).
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, SinkError> {
+        let path = path.into();
+        let open_err = |source| SinkError::Open {
+            path: path.display().to_string(),
+            source,
+        };
+        let file_type = std::fs::metadata(&path).map_err(open_err)?.file_type();
+        let target = if file_type.is_socket() {
+            let socket = UnixDatagram::unbound().map_err(open_err)?;
+            socket.set_nonblocking(true).map_err(open_err)?;
+            Target::Datagram(socket)
+        } else if file_type.is_fifo() {
+            Target::Fifo(None)
+        } else {
+            return Err(SinkError::Unsupported(path.display().to_string()));
+        };
+        Ok(Self {
+            path,
+            target,
+            pending: Vec::new(),
+            outage: Outage::new(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Snapshots dropped because the reader was missing or not keeping up.
+    pub fn dropped(&self) -> u64 {
+        self.outage.dropped()
+    }
+
+    /// Writes `snapshot` as a JSON line, dropping it if that would block.
+    pub fn send(&mut self, snapshot: &MetricsSnapshot) {
+        let mut line = match serde_json::to_vec(&snapshot.to_rpc_format()) {
+            Ok(line) => line,
+            Err(e) => {
+                warn!("Failed to encode snapshot for sink: {}", e);
+                return;
+            }
+        };
+        line.push(b'\n');
+
+        let result = match &mut self.target {
+            Target::Datagram(socket) => socket.send_to(&line, &self.path).map(|_| true),
+            Target::Fifo(file) => write_fifo(&self.path, file, &mut self.pending, &line),
+        };
+        match result {
+            Ok(true) => self.outage.succeeded(format_args!(
+                "Sink {} accepting snapshots again",
+                self.path.display()
+            )),
+            Ok(false) => self.drop_snapshot(None),
+            Err(e) => self.drop_snapshot(Some(e)),
+        }
+    }
+
+    fn drop_snapshot(&mut self, error: Option<io::Error>) {
+        let reason =
+            error.map_or_else(|| "reader is not keeping up".to_string(), |e| e.to_string());
+        let path = self.path.display();
+        self.outage.failed(
+            format_args!("Sink {path} dropping snapshots: {reason}"),
+            format_args!("Sink {path} dropped a snapshot: {reason}"),
+        );
+    }
+}
+
+/// Finishes any pending line, then writes `line`. Ok(false) means `line` was
+/// dropped because the FIFO is full.
+fn write_fifo(
+    path: &Path,
+    file: &mut Option<File>,
+    pending: &mut Vec<u8>,
+    line: &[u8],
+) -> io::Result<bool> {
+    if file.is_none() {
+        *file = Some(
+            OpenOptions::new()
+                .write(true)
+                .custom_flags(libc::O_NONBLOCK)
+                .open(path)?,
+        );
+    }
+    let Some(writer) = file.as_mut() else {
+        return Ok(false);
+    };
+    let result = flush_pending(writer, pending).and_then(|done| {
+        if !done {
+            return Ok(false);
+        }
+        pending.extend_from_slice(line);
+        flush_pending(writer, pending)?;
+        Ok(true)
+    });
+    if result.is_err() {
+        // The reader went away; reopen on the next snapshot.
+        *file = None;
+        pending.clear();
+    }
+    result
+}
+
+/// Writes as much of `pending` as the FIFO accepts; true once it is empty.
+fn flush_pending(writer: &mut File, pending: &mut Vec<u8>) -> io::Result<bool> {
+    while !pending.is_empty() {
+        match writer.write(pending) {
+            Ok(0) => return Ok(false),
+            Ok(n) => {
+                pending.drain(..n);
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(false),
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(true)
+}
+
+/// Subscribes `sink` to published snapshots.
+pub fn register_sink_subscriber(sink: SocketSink) -> nuts::ActivityId<SocketSink> {
+    let activity = nuts::new_activity(sink);
+    activity.subscribe(|sink: &mut SocketSink, evt: &MetricsEvent| sink.send(&evt.0));
+    activity
+}
@@ -0,0 +1,194 @@
+use crate::db::DbStats;
+use crate::metrics::MetricsSnapshot;
+use crate::storage::SnapshotStore;
+use rusqlite::{params, Connection, DatabaseName};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::{error, info, warn};
+
+/// Snapshot store persisted in SQLite, for retention beyond the in-memory buffer.
+///
+/// Headline values get their own columns so the table can be queried with
+/// plain SQL; the full snapshot is kept alongside as JSON for exact reads.
+pub struct SqliteStore {
+    conn: Mutex<Connection>,
+    path: PathBuf,
+}
+
+impl SqliteStore {
+    pub fn open(path: &Path) -> Result<Self, rusqlite::Error> {
+        let conn = Connection::open(path)?;
+        conn.pragma_update(Some(DatabaseName::Main), "journal_mode", "WAL")?;
+        let store = Self::with_connection(conn, path.to_path_buf())?;
+        info!("SQLite snapshot store initialized at {}", path.display());
+        Ok(store)
+    }
+
+    pub fn open_in_memory() -> Result<Self, rusqlite::Error> {
+        Self::with_connection(Connection::open_in_memory()?, PathBuf::new())
+    }
+
+    fn with_connection(conn: Connection, path: PathBuf) -> Result<Self, rusqlite::Error> {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS snapshots (
+                timestamp_ms INTEGER PRIMARY KEY,
+                cpu_total_pct REAL NOT NULL,
+                mem_used_bytes INTEGER NOT NULL,
+                mem_total_bytes INTEGER NOT NULL,
+                net_rx_bytes_per_sec REAL NOT NULL,
+                net_tx_bytes_per_sec REAL NOT NULL,
+                disk_used_pct REAL NOT NULL,
+                snapshot TEXT NOT NULL
+            )",
+            [],
+        )?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+            path,
+        })
+    }
+
+    pub fn insert(&self, snapshot: &MetricsSnapshot) -> Result<(), rusqlite::Error> {
+        let json = serde_json::to_string(snapshot)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        let conn = self.conn.lock().unwrap_or_else(|p| p.into_inner());
+        conn.execute(
+            "INSERT OR REPLACE INTO snapshots (
+                timestamp_ms, cpu_total_pct, mem_used_bytes, mem_total_bytes,
+                net_rx_bytes_per_sec, net_tx_bytes_per_sec, disk_used_pct, snapshot
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                snapshot.timestamp_ms as i64,
+                snapshot.cpu.total_usage_pct,
+                snapshot.memory.used_bytes as i64,
+                snapshot.memory.total_bytes as i64,
+                snapshot.network.rx_bytes_per_sec,
+                snapshot.network.tx_bytes_per_sec,
+                snapshot.disk.used_pct,
+                json,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Deletes snapshots older than `keep_hours`, returning how many.
+    pub fn cleanup_old(&self, keep_hours: u64) -> Result<usize, rusqlite::Error> {
+        let cutoff = chrono::Utc::now().timestamp_millis() - (keep_hours * 3600 * 1000) as i64;
+        let deleted = self
+            .conn
+            .lock()
+            .unwrap_or_else(|p| p.into_inner())
+            .execute(
+                "DELETE FROM snapshots WHERE timestamp_ms < ?1",
+                params![cutoff],
+            )?;
+        if deleted > 0 {
+            info!("Cleaned up {} old snapshots from the SQLite store", deleted);
+        }
+        Ok(deleted)
+    }
+
+    pub fn get_stats(&self) -> Result<DbStats, rusqlite::Error> {
+        let conn = self.conn.lock().unwrap_or_else(|p| p.into_inner());
+        let (total_records, oldest, newest) = conn.query_row(
+            "SELECT COUNT(*), MIN(timestamp_ms), MAX(timestamp_ms) FROM snapshots",
+            [],
+            |row| {
+                Ok((
+                    row.get::<_, usize>(0)?,
+                    row.get::<_, Option<i64>>(1)?,
+                    row.get::<_, Option<i64>>(2)?,
+                ))
+            },
+        )?;
+        Ok(DbStats {
+            total_records,
+            oldest_timestamp: oldest.map(|v| v as u64),
+            newest_timestamp: newest.map(|v| v as u64),
+            database_size_bytes: std::fs::metadata(&self.path).map(|m| m.len()).unwrap_or(0),
+        })
+    }
+
+    pub fn get_latest(&self) -> Result<Option<MetricsSnapshot>, rusqlite::Error> {
+        Ok(self
+            .query(
+                "SELECT snapshot FROM snapshots ORDER BY timestamp_ms DESC LIMIT 1",
+                params![],
+            )?
+            .pop())
+    }
+
+    /// Snapshots with `since_ms <= ts <= until_ms`, oldest first, keeping the
+    /// most recent `limit` when given.
+    pub fn get_window(
+        &self,
+        since_ms: Option<u128>,
+        until_ms: Option<u128>,
+        limit: Option<usize>,
+    ) -> Result<Vec<MetricsSnapshot>, rusqlite::Error> {
+        let since = since_ms.map_or(i64::MIN, |v| i64::try_from(v).unwrap_or(i64::MAX));
+        let until = until_ms.map_or(i64::MAX, |v| i64::try_from(v).unwrap_or(i64::MAX));
+        // SQLite treats a negative LIMIT as unbounded.
+        let limit = limit.map_or(-1, |v| i64::try_from(v).unwrap_or(i64::MAX));
+        let mut rows = self.query(
+            "SELECT snapshot FROM snapshots WHERE timestamp_ms BETWEEN ?1 AND ?2
+             ORDER BY timestamp_ms DESC LIMIT ?3",
+            params![since, until, limit],
+        )?;
+        rows.reverse();
+        Ok(rows)
+    }
+
+    fn query(
+        &self,
+        sql: &str,
+        params: impl rusqlite::Params,
+    ) -> Result<Vec<MetricsSnapshot>, rusqlite::Error> {
+        let conn = self.conn.lock().unwrap_or_else(|p| p.into_inner());
+        let mut stmt = conn.prepare(sql)?;
+        let mut rows = stmt.query(params)?;
+        let mut results = Vec::new();
+        while let Some(row) = rows.next()? {
+            let data: String = row.get(0)?;
+            match serde_json::from_str(&data) {
+                Ok(snapshot) => results.push(snapshot),
+                Err(e) => warn!("Skipping corrupted snapshot row: {}", e),
+            }
+        }
+        Ok(results)
+    }
+}
+
+impl SnapshotStore for SqliteStore {
+    fn push(&self, snapshot: MetricsSnapshot) {
+        if let Err(e) = self.insert(&snapshot) {
+            error!("Failed to store snapshot in SQLite: {}", e);
+        }
+    }
+
+    fn latest(&self) -> Option<MetricsSnapshot> {
+        self.get_latest().unwrap_or_else(|e| {
+            error!("Failed to read latest snapshot from SQLite: {}", e);
+            None
+        })
+    }
+
+    fn window(
+        &self,
+        since_ms: Option<u128>,
+        until_ms: Option<u128>,
+        limit: Option<usize>,
+    ) -> Vec<MetricsSnapshot> {
+        self.get_window(since_ms, until_ms, limit)
+            .unwrap_or_else(|e| {
+                error!("Failed to read snapshots from SQLite: {}", e);
+                Vec::new()
+            })
+    }
+
+    fn stats(&self) -> Option<DbStats> {
+        self.get_stats()
+            .map_err(|e| error!("Failed to read SQLite store stats: {}", e))
+            .ok()
+    }
+}
@@ -0,0 +1,91 @@
+//! StatsD/DogStatsD export (`--statsd-addr`): every published snapshot is
+//! sent as one UDP datagram of gauges, named like the Grafana targets.
+//! Sends never block; a datagram the socket cannot take is dropped.
+
+use crate::bus::MetricsEvent;
+use crate::grafana::exported_name;
+use crate::metrics::{scalar_metric, MetricsSnapshot};
+use crate::outage::Outage;
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+
+/// Exported gauge name -> scalar metric name.
+const GAUGES: &[(&str, &str)] = &[
+    ("cpu.total", "cpu"),
+    ("memory.used_pct", "memory"),
+    ("network.rx_bytes_per_sec", "net_rx"),
+    ("network.tx_bytes_per_sec", "net_tx"),
+    ("disk.used_pct", "disk"),
+];
+
+/// StatsD lines (`name:value|g`) for `snapshot`, one per available gauge.
+pub fn statsd_lines(prefix: Option<&str>, snapshot: &MetricsSnapshot) -> Vec<String> {
+    GAUGES
+        .iter()
+        .filter_map(|(name, metric)| {
+            let value = scalar_metric(metric).and_then(|m| (m.extract)(snapshot))?;
+            value
+                .is_finite()
+                .then(|| format!("{}:{}|g", exported_name(prefix, name), value))
+        })
+        .collect()
+}
+
+pub struct StatsdSink {
+    socket: UdpSocket,
+    addr: SocketAddr,
+    prefix: Option<String>,
+    outage: Outage,
+}
+
+impl StatsdSink {
+    /// Binds an ephemeral local port and connects it to the agent at `addr`.
+    pub fn open(addr: SocketAddr, prefix: Option<String>) -> io::Result<Self> {
+        let local: SocketAddr = if addr.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            ([0u16; 8], 0).into()
+        };
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(addr)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self {
+            socket,
+            addr,
+            prefix,
+            outage: Outage::new(),
+        })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Snapshots dropped because the send failed or would have blocked.
+    pub fn dropped(&self) -> u64 {
+        self.outage.dropped()
+    }
+
+    pub fn send(&mut self, snapshot: &MetricsSnapshot) {
+        let packet = statsd_lines(self.prefix.as_deref(), snapshot).join("\n");
+        if packet.is_empty() {
+            return;
+        }
+        match self.socket.send(packet.as_bytes()) {
+            Ok(_) => self
+                .outage
+                .succeeded(format_args!("StatsD export to {} recovered", self.addr)),
+            Err(e) => self.outage.failed(
+                format_args!("StatsD export to {} dropping snapshots: {}", self.addr, e),
+                format_args!("StatsD export to {} dropped a snapshot: {}", self.addr, e),
+            ),
+        }
+    }
+}
+
+/// Subscribes `sink` to published snapshots.
+pub fn register_statsd_subscriber(sink: StatsdSink) -> nuts::ActivityId<StatsdSink> {
+    let activity = nuts::new_activity(sink);
+    activity.subscribe(|sink: &mut StatsdSink, evt: &MetricsEvent| sink.send(&evt.0));
+    activity
+}
@@ -1,50 +1,804 @@
-use crate::metrics::MetricsSnapshot;
+use crate::config::{Aggregation, OverflowPolicy};
+use crate::db::DbStats;
+use crate::metrics::{MetricsSnapshot, SparseCores};
+use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
-use std::sync::RwLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use thiserror::Error;
+use tracing::warn;
+
+#[derive(Debug, Error)]
+pub enum StorageError {
+    #[error("metrics buffer lock poisoned: a writer panicked and may have left partial state")]
+    Poisoned,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Histogram {
+    /// `counts.len() + 1` ascending bin boundaries.
+    pub edges: Vec<f32>,
+    pub counts: Vec<usize>,
+    pub total: usize,
+}
+
+impl Histogram {
+    /// Bins `values` into `bins` equal-width buckets over `range`, or over the
+    /// observed min..max when no range is given. Out-of-range values are clamped.
+    pub fn from_values(values: &[f32], bins: usize, range: Option<(f32, f32)>) -> Option<Self> {
+        if values.is_empty() || bins == 0 {
+            return None;
+        }
+        let (lo, hi) = range.unwrap_or_else(|| {
+            values
+                .iter()
+                .fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), v| {
+                    (lo.min(*v), hi.max(*v))
+                })
+        });
+        // A constant series still gets a non-degenerate bin width.
+        let width = if hi > lo {
+            (hi - lo) / bins as f32
+        } else {
+            1.0 / bins as f32
+        };
+
+        let edges = (0..=bins).map(|i| lo + width * i as f32).collect();
+        let mut counts = vec![0; bins];
+        for v in values {
+            let idx = ((v - lo) / width).floor();
+            let idx = if idx.is_nan() || idx < 0.0 {
+                0
+            } else {
+                (idx as usize).min(bins - 1)
+            };
+            counts[idx] += 1;
+        }
+
+        Some(Self {
+            edges,
+            counts,
+            total: values.len(),
+        })
+    }
+}
+
+/// Summary of one metric over a window of snapshots.
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct SeriesStats {
+    pub count: usize,
+    pub min: f32,
+    pub max: f32,
+    pub mean: f32,
+}
+
+impl SeriesStats {
+    pub fn from_values(values: &[f32]) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+        let (min, max, sum) = values.iter().fold(
+            (f32::INFINITY, f32::NEG_INFINITY, 0.0f64),
+            |(min, max, sum), v| (min.min(*v), max.max(*v), sum + f64::from(*v)),
+        );
+        Some(Self {
+            count: values.len(),
+            min,
+            max,
+            mean: (sum / values.len() as f64) as f32,
+        })
+    }
+}
+
+/// Largest receive and transmit rates over a window, so each direction of
+/// the network chart can be scaled on its own.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct NetworkPeaks {
+    pub rx_max_bytes_per_sec: f32,
+    pub tx_max_bytes_per_sec: f32,
+    pub samples: usize,
+}
+
+/// `values` (oldest first) combined into one by `how`, ignoring non-finite
+/// ones; None when none are left.
+pub fn aggregate(values: &[f32], how: Aggregation) -> Option<f32> {
+    let mut finite = values.iter().copied().filter(|v| v.is_finite());
+    match how {
+        Aggregation::Mean => {
+            let (sum, n) = finite.fold((0.0f64, 0u32), |(sum, n), v| (sum + f64::from(v), n + 1));
+            (n > 0).then(|| (sum / f64::from(n)) as f32)
+        }
+        Aggregation::Max => finite.reduce(f32::max),
+        Aggregation::Min => finite.reduce(f32::min),
+        Aggregation::Last => finite.next_back(),
+    }
+}
+
+/// Reduces `(timestamp, value)` points, oldest first, to at most `max`
+/// points: consecutive runs of equal length combined by `how`, each stamped
+/// with its last timestamp so the newest point keeps its time.
+pub fn downsample<T: Copy>(points: &[(T, f32)], max: usize, how: Aggregation) -> Vec<(T, f32)> {
+    if max == 0 || points.len() <= max {
+        return points.to_vec();
+    }
+    let run = points.len().div_ceil(max);
+    // Runs end at the newest point, so only the oldest one may be short.
+    let first = points.len() % run;
+    let (head, tail) = points.split_at(first);
+    std::iter::once(head)
+        .filter(|h| !h.is_empty())
+        .chain(tail.chunks(run))
+        .filter_map(|bucket| {
+            let values: Vec<f32> = bucket.iter().map(|(_, v)| *v).collect();
+            Some((bucket.last()?.0, aggregate(&values, how)?))
+        })
+        .collect()
+}
+
+/// Per-core usage aggregated into equal time buckets, for a heatmap.
+#[derive(Clone, Debug, Serialize)]
+pub struct CpuHeatmap {
+    /// Start of the first bucket.
+    pub start_ms: u128,
+    /// End of the last bucket, inclusive.
+    pub end_ms: u128,
+    pub bucket_ms: f64,
+    /// `[core][bucket]` usage; None where a bucket holds no samples.
+    pub cores: Vec<Vec<Option<f32>>>,
+}
+
+impl CpuHeatmap {
+    /// Combines `(timestamp, per-core usage)` samples, oldest first, into
+    /// `cols` buckets spanning `since_ms..=until_ms` (the samples' own span
+    /// where a bound is not given) by `how`. Samples outside the span are
+    /// ignored.
+    pub fn from_samples<'a>(
+        samples: impl IntoIterator<Item = (u128, &'a [f32])> + Clone,
+        cols: usize,
+        since_ms: Option<u128>,
+        until_ms: Option<u128>,
+        how: Aggregation,
+    ) -> Option<Self> {
+        if cols == 0 {
+            return None;
+        }
+        let (first, last, num_cores) = samples.clone().into_iter().fold(
+            (u128::MAX, 0, 0),
+            |(first, last, cores), (ts, per_core)| {
+                (first.min(ts), last.max(ts), cores.max(per_core.len()))
+            },
+        );
+        if num_cores == 0 {
+            return None;
+        }
+        let start_ms = since_ms.unwrap_or(first);
+        let end_ms = until_ms.unwrap_or(last).max(start_ms);
+        // An instant still gets one non-degenerate millisecond.
+        let span = (end_ms - start_ms).max(1) as f64;
+        let bucket_ms = span / cols as f64;
+
+        let mut buckets = vec![vec![Vec::new(); cols]; num_cores];
+        for (ts, per_core) in samples {
+            if ts < start_ms || ts > end_ms {
+                continue;
+            }
+            let col = (((ts - start_ms) as f64 / bucket_ms) as usize).min(cols - 1);
+            for (core, v) in per_core.iter().enumerate() {
+                buckets[core][col].push(*v);
+            }
+        }
+        let cores = buckets
+            .into_iter()
+            .map(|row| row.iter().map(|values| aggregate(values, how)).collect())
+            .collect();
+        Some(Self {
+            start_ms,
+            end_ms,
+            bucket_ms,
+            cores,
+        })
+    }
+}
+
+/// Order history is returned in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryOrder {
+    /// Oldest first.
+    Asc,
+    /// Newest first.
+    Desc,
+}
+
+/// Keeps `limit` items of `oldest_first` (the oldest ones when `from_start`,
+/// else the newest) and returns them in `order`.
+pub fn select_history<T>(
+    mut oldest_first: Vec<T>,
+    limit: Option<usize>,
+    from_start: bool,
+    order: HistoryOrder,
+) -> Vec<T> {
+    if let Some(limit) = limit {
+        if from_start {
+            oldest_first.truncate(limit);
+        } else {
+            let skip = oldest_first.len().saturating_sub(limit);
+            oldest_first.drain(..skip);
+        }
+    }
+    if order == HistoryOrder::Desc {
+        oldest_first.reverse();
+    }
+    oldest_first
+}
+
+/// One chunk of history in ascending time order.
+#[derive(Clone, Debug)]
+pub struct HistoryPage {
+    pub items: Vec<MetricsSnapshot>,
+    /// Timestamp of the last item, if more snapshots follow it.
+    pub next_cursor: Option<u128>,
+}
+
+/// Common interface of the snapshot stores the collector can write into.
+pub trait SnapshotStore: Send + Sync {
+    fn push(&self, snapshot: MetricsSnapshot);
+
+    fn latest(&self) -> Option<MetricsSnapshot>;
+
+    /// Snapshots with `since_ms <= ts <= until_ms`, oldest first, keeping the
+    /// most recent `limit` when given.
+    fn window(
+        &self,
+        since_ms: Option<u128>,
+        until_ms: Option<u128>,
+        limit: Option<usize>,
+    ) -> Vec<MetricsSnapshot>;
+
+    /// Row counts and size on disk, for stores backed by a database.
+    fn stats(&self) -> Option<DbStats> {
+        None
+    }
+}
+
+/// Snapshots between full per-core vectors in a sparse buffer, bounding the
+/// work needed to rebuild any one of them.
+pub const SPARSE_KEYFRAME_EVERY: usize = 60;
+
+/// Encoder state of [`MetricsBuffer::with_sparse_per_core`].
+struct SparseState {
+    threshold: f32,
+    /// Per-core usage as rebuilt from the newest snapshot held.
+    base: Vec<f32>,
+    since_keyframe: usize,
+}
+
+impl SparseState {
+    /// Replaces `snapshot`'s per-core vector by the cores that moved by more
+    /// than `threshold` since the previous one, unless it is to be whole.
+    fn encode(&mut self, snapshot: &mut MetricsSnapshot, whole: bool, threshold: f32) {
+        let cores = &snapshot.cpu.per_core_usage_pct;
+        if whole
+            || self.since_keyframe + 1 >= SPARSE_KEYFRAME_EVERY
+            || self.base.len() != cores.len()
+        {
+            self.base = cores.clone();
+            self.since_keyframe = 0;
+            return;
+        }
+        let changes = SparseCores::diff(&self.base, cores, threshold);
+        changes.apply(&mut self.base);
+        self.since_keyframe += 1;
+        snapshot.cpu.per_core_usage_pct = Vec::new();
+        snapshot.cpu.per_core_changes = Some(changes);
+    }
+}
 
 pub struct MetricsBuffer {
     capacity: usize,
+    /// Newest snapshots that keep `per_core_usage_pct`; None keeps it for all.
+    per_core_keep: Option<usize>,
+    /// Reject snapshots not newer than the latest one.
+    strictly_increasing: bool,
+    overflow: OverflowPolicy,
+    /// Snapshots discarded under [`OverflowPolicy::RejectNewest`].
+    overflow_rejections: AtomicU64,
+    /// Only touched while `inner` is write-locked.
+    sparse: Option<Mutex<SparseState>>,
+    /// Snapshots older than this timestamp were already thinned by [`compact`](Self::compact).
+    compacted_until_ms: AtomicU64,
     inner: RwLock<VecDeque<MetricsSnapshot>>,
+    poison_recoveries: AtomicU64,
 }
 
 impl MetricsBuffer {
     pub fn new(capacity: usize) -> Self {
         Self {
             capacity,
+            per_core_keep: None,
+            strictly_increasing: false,
+            overflow: OverflowPolicy::default(),
+            overflow_rejections: AtomicU64::new(0),
+            sparse: None,
+            compacted_until_ms: AtomicU64::new(0),
             inner: RwLock::new(VecDeque::with_capacity(capacity)),
+            poison_recoveries: AtomicU64::new(0),
         }
     }
 
-    pub fn push(&self, snapshot: MetricsSnapshot) {
-        let mut guard = match self.inner.write() {
-            Ok(g) => g,
-            Err(poisoned) => {
-                // Continue with the inner value even if poisoned.
-                poisoned.into_inner()
+    /// Most snapshots held; see [`with_overflow_policy`](Self::with_overflow_policy)
+    /// for what happens past it.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Keeps per-core CPU usage only for the newest `keep` snapshots; older
+    /// ones retain their totals but report an empty `per_core_usage_pct`.
+    pub fn with_per_core_retention(mut self, keep: usize) -> Self {
+        self.per_core_keep = Some(keep);
+        self
+    }
+
+    /// Makes [`push`](Self::push) reject a snapshot whose timestamp is not
+    /// strictly greater than the latest one, so replayed or out-of-order
+    /// input cannot leave duplicates in the history.
+    pub fn with_strictly_increasing(mut self) -> Self {
+        self.strictly_increasing = true;
+        self
+    }
+
+    /// Chooses what a push into a full buffer does. With
+    /// [`OverflowPolicy::RejectNewest`] the earliest snapshots are kept and
+    /// new ones are discarded, counted in
+    /// [`overflow_rejections`](Self::overflow_rejections).
+    pub fn with_overflow_policy(mut self, policy: OverflowPolicy) -> Self {
+        self.overflow = policy;
+        self
+    }
+
+    /// Stores per-core CPU usage sparsely: each snapshot keeps only the cores
+    /// that moved by more than `threshold` percentage points since the
+    /// previous one, with a full vector every [`SPARSE_KEYFRAME_EVERY`]
+    /// snapshots. Reads rebuild the full vector, exact with a threshold of 0
+    /// and otherwise within `threshold` of what was pushed.
+    pub fn with_sparse_per_core(mut self, threshold: f32) -> Self {
+        self.sparse = Some(Mutex::new(SparseState {
+            threshold: threshold.max(0.0),
+            base: Vec::new(),
+            since_keyframe: 0,
+        }));
+        self
+    }
+
+    /// Appends a snapshot, continuing on a poisoned lock but logging and
+    /// counting it. Returns false if the snapshot was rejected as a duplicate
+    /// or because the buffer is full.
+    pub fn push(&self, snapshot: MetricsSnapshot) -> bool {
+        let mut guard = self.write_recovering();
+        self.push_locked(&mut guard, snapshot)
+    }
+
+    /// Appends a snapshot, refusing to write into a buffer whose lock is poisoned.
+    pub fn try_push(&self, snapshot: MetricsSnapshot) -> Result<bool, StorageError> {
+        let mut guard = self.inner.write().map_err(|_| StorageError::Poisoned)?;
+        Ok(self.push_locked(&mut guard, snapshot))
+    }
+
+    /// Keeps only the snapshots for which `keep` returns true.
+    pub fn retain(&self, keep: impl FnMut(&MetricsSnapshot) -> bool) {
+        let mut guard = self.write_recovering();
+        self.decode_all(&mut guard);
+        guard.retain(keep);
+        self.encode_all(&mut guard);
+    }
+
+    /// Downsamples, in place, snapshots more than `age_ms` older than the
+    /// latest one, keeping 1 in `factor`. Data thinned by an earlier call is
+    /// left alone, so each snapshot is thinned at most once. The kept
+    /// snapshots are unchanged: unlike [`downsample`], nothing is aggregated
+    /// into them, so spikes in the dropped ones are lost. Returns the number
+    /// of snapshots removed.
+    pub fn compact(&self, age_ms: u128, factor: usize) -> usize {
+        if factor < 2 {
+            return 0;
+        }
+        let mut guard = self.write_recovering();
+        let Some(cutoff) = guard.back().map(|s| s.timestamp_ms.saturating_sub(age_ms)) else {
+            return 0;
+        };
+        let done = u128::from(self.compacted_until_ms.load(Ordering::Relaxed));
+        let start = guard.partition_point(|s| s.timestamp_ms < done);
+        let end = guard.partition_point(|s| s.timestamp_ms < cutoff);
+        if end <= start {
+            return 0;
+        }
+
+        let before = guard.len();
+        let mut idx = 0;
+        self.decode_all(&mut guard);
+        guard.retain(|_| {
+            let keep = idx < start || idx >= end || (idx - start).is_multiple_of(factor);
+            idx += 1;
+            keep
+        });
+        self.encode_all(&mut guard);
+        let cutoff = u64::try_from(cutoff).unwrap_or(u64::MAX);
+        self.compacted_until_ms.fetch_max(cutoff, Ordering::Relaxed);
+        before - guard.len()
+    }
+
+    /// Approximate memory the held snapshots use, excluding spare ring
+    /// capacity; see [`MetricsSnapshot::estimated_bytes`].
+    pub fn estimated_bytes(&self) -> usize {
+        let guard = self.read_best_effort();
+        guard.iter().map(MetricsSnapshot::estimated_bytes).sum()
+    }
+
+    /// Newest snapshot of the host's own collector; those of labeled sources
+    /// (see [`MetricsSnapshot::source`]) are held but never the latest.
+    pub fn latest(&self) -> Option<MetricsSnapshot> {
+        let guard = self.read_best_effort();
+        let last = guard.iter().rposition(|s| s.source.is_none())?;
+        self.materialize(&guard, last, last + 1).pop()
+    }
+
+    /// Timestamp of the oldest snapshot still held.
+    pub fn oldest_timestamp(&self) -> Option<u128> {
+        let guard = self.read_best_effort();
+        guard.front().map(|s| s.timestamp_ms)
+    }
+
+    pub fn history(&self, limit: Option<usize>) -> Vec<MetricsSnapshot> {
+        let guard = self.read_best_effort();
+        let skip = limit.map_or(0, |limit| guard.len().saturating_sub(limit));
+        self.materialize(&guard, skip, guard.len())
+    }
+
+    /// Snapshots with `since_ms <= ts <= until_ms`, oldest first, located by
+    /// binary search. Relies on snapshots being pushed in timestamp order.
+    pub fn range(&self, since_ms: Option<u128>, until_ms: Option<u128>) -> Vec<MetricsSnapshot> {
+        let guard = self.read_best_effort();
+        let (start, end) = Self::bounds(&guard, since_ms, until_ms);
+        self.materialize(&guard, start, end)
+    }
+
+    /// The snapshot closest in time to `timestamp_ms` (the earlier one on a
+    /// tie), or None when `timestamp_ms` lies outside the buffered range.
+    /// Relies on snapshots being pushed in timestamp order.
+    pub fn nearest(&self, timestamp_ms: u128) -> Option<MetricsSnapshot> {
+        let guard = self.read_best_effort();
+        let (first, last) = (guard.front()?, guard.back()?);
+        if timestamp_ms < first.timestamp_ms || timestamp_ms > last.timestamp_ms {
+            return None;
+        }
+        let idx = guard.partition_point(|s| s.timestamp_ms < timestamp_ms);
+        let after = &guard[idx];
+        let nearest = match idx.checked_sub(1) {
+            Some(before)
+                if timestamp_ms - guard[before].timestamp_ms
+                    <= after.timestamp_ms - timestamp_ms =>
+            {
+                before
             }
+            _ => idx,
+        };
+        self.materialize(&guard, nearest, nearest + 1).pop()
+    }
+
+    /// Up to `page_size` snapshots strictly newer than `after_ms` (oldest first).
+    /// Relies on snapshots being pushed in timestamp order.
+    pub fn page_after(&self, after_ms: Option<u128>, page_size: usize) -> HistoryPage {
+        let guard = self.read_best_effort();
+        let start = match after_ms {
+            Some(after) => guard.partition_point(|s| s.timestamp_ms <= after),
+            None => 0,
+        };
+        let end = start.saturating_add(page_size).min(guard.len());
+        let items = self.materialize(&guard, start, end);
+        let next_cursor = if end < guard.len() {
+            items.last().map(|s| s.timestamp_ms)
+        } else {
+            None
         };
+        HistoryPage { items, next_cursor }
+    }
+
+    /// Distribution of `extract` over snapshots with `since_ms <= ts <= until_ms`.
+    /// Snapshots for which `extract` yields None are skipped.
+    pub fn histogram(
+        &self,
+        since_ms: Option<u128>,
+        until_ms: Option<u128>,
+        bins: usize,
+        range: Option<(f32, f32)>,
+        extract: impl Fn(&MetricsSnapshot) -> Option<f32>,
+    ) -> Option<Histogram> {
+        let values = self.window_values(since_ms, until_ms, extract);
+        Histogram::from_values(&values, bins, range)
+    }
+
+    /// Count/min/max/mean of `extract` over snapshots with `since_ms <= ts <= until_ms`.
+    pub fn stats(
+        &self,
+        since_ms: Option<u128>,
+        until_ms: Option<u128>,
+        extract: impl Fn(&MetricsSnapshot) -> Option<f32>,
+    ) -> Option<SeriesStats> {
+        let values = self.window_values(since_ms, until_ms, extract);
+        SeriesStats::from_values(&values)
+    }
+
+    /// Per-core usage of snapshots with `since_ms <= ts <= until_ms`, combined
+    /// by `how` into `cols` time buckets; None when the window holds no
+    /// per-core data.
+    pub fn cpu_heatmap(
+        &self,
+        since_ms: Option<u128>,
+        until_ms: Option<u128>,
+        cols: usize,
+        how: Aggregation,
+    ) -> Option<CpuHeatmap> {
+        let guard = self.read_best_effort();
+        let (start, end) = Self::bounds(&guard, since_ms, until_ms);
+        fn sample(s: &MetricsSnapshot) -> (u128, &[f32]) {
+            (s.timestamp_ms, s.cpu.per_core_usage_pct.as_slice())
+        }
+        if self.sparse.is_none() {
+            return CpuHeatmap::from_samples(
+                guard.range(start..end).map(sample),
+                cols,
+                since_ms,
+                until_ms,
+                how,
+            );
+        }
+        let window = self.materialize(&guard, start, end);
+        drop(guard);
+        CpuHeatmap::from_samples(window.iter().map(sample), cols, since_ms, until_ms, how)
+    }
+
+    /// Peak RX and TX rates over snapshots with `since_ms <= ts <= until_ms`;
+    /// None when the window is empty.
+    pub fn network_peaks(
+        &self,
+        since_ms: Option<u128>,
+        until_ms: Option<u128>,
+    ) -> Option<NetworkPeaks> {
+        let guard = self.read_best_effort();
+        let (start, end) = Self::bounds(&guard, since_ms, until_ms);
+        let window = guard.range(start..end);
+        let samples = window.len();
+        if samples == 0 {
+            return None;
+        }
+        let (rx, tx) = window.fold((0.0f32, 0.0f32), |(rx, tx), s| {
+            let finite = |v: f32| if v.is_finite() { v } else { 0.0 };
+            (
+                rx.max(finite(s.network.rx_bytes_per_sec)),
+                tx.max(finite(s.network.tx_bytes_per_sec)),
+            )
+        });
+        Some(NetworkPeaks {
+            rx_max_bytes_per_sec: rx,
+            tx_max_bytes_per_sec: tx,
+            samples,
+        })
+    }
+
+    /// True once a writer has panicked while holding the lock.
+    pub fn is_poisoned(&self) -> bool {
+        self.inner.is_poisoned()
+    }
+
+    /// Snapshots discarded because the buffer was full.
+    pub fn overflow_rejections(&self) -> u64 {
+        self.overflow_rejections.load(Ordering::Relaxed)
+    }
+
+    /// Number of writes that proceeded despite a poisoned lock.
+    pub fn poison_recoveries(&self) -> u64 {
+        self.poison_recoveries.load(Ordering::Relaxed)
+    }
+
+    fn window_values(
+        &self,
+        since_ms: Option<u128>,
+        until_ms: Option<u128>,
+        extract: impl Fn(&MetricsSnapshot) -> Option<f32>,
+    ) -> Vec<f32> {
+        let guard = self.read_best_effort();
+        let (start, end) = Self::bounds(&guard, since_ms, until_ms);
+        guard
+            .range(start..end)
+            .filter_map(&extract)
+            .filter(|v| v.is_finite())
+            .collect()
+    }
+
+    /// Index range of `since_ms <= ts <= until_ms` in a timestamp-ordered deque.
+    fn bounds(
+        snapshots: &VecDeque<MetricsSnapshot>,
+        since_ms: Option<u128>,
+        until_ms: Option<u128>,
+    ) -> (usize, usize) {
+        let start = since_ms.map_or(0, |since| {
+            snapshots.partition_point(|s| s.timestamp_ms < since)
+        });
+        let end = until_ms.map_or(snapshots.len(), |until| {
+            snapshots.partition_point(|s| s.timestamp_ms <= until)
+        });
+        (start, end.max(start))
+    }
+
+    fn push_locked(
+        &self,
+        guard: &mut VecDeque<MetricsSnapshot>,
+        snapshot: MetricsSnapshot,
+    ) -> bool {
+        if self.strictly_increasing
+            && guard
+                .back()
+                .is_some_and(|last| snapshot.timestamp_ms <= last.timestamp_ms)
+        {
+            return false;
+        }
         if guard.len() >= self.capacity {
-            // Trim oldest to make room.
-            guard.pop_front();
+            match self.overflow {
+                OverflowPolicy::DropOldest => Self::pop_oldest(guard),
+                OverflowPolicy::RejectNewest => {
+                    let count = self.overflow_rejections.fetch_add(1, Ordering::Relaxed) + 1;
+                    // Every rejection would flood the log once full.
+                    if count.is_power_of_two() {
+                        warn!(
+                            "History buffer full ({} snapshots), {} new snapshots rejected",
+                            self.capacity, count
+                        );
+                    }
+                    return false;
+                }
+            }
         }
+        let snapshot = self.encode_per_core(guard.is_empty(), snapshot);
         guard.push_back(snapshot);
+        // Each push moves exactly one snapshot out of the per-core window.
+        if let Some(keep) = self.per_core_keep {
+            if let Some(idx) = guard.len().checked_sub(keep + 1) {
+                // The next one may be rebuilt from this one; make it whole first.
+                if guard[idx + 1].cpu.per_core_changes.is_some() {
+                    if let Some(whole) = self.materialize(guard, idx + 1, idx + 2).pop() {
+                        guard[idx + 1] = whole;
+                    }
+                }
+                guard[idx].cpu.per_core_usage_pct = Vec::new();
+                guard[idx].cpu.per_core_changes = None;
+            }
+        }
+        true
     }
 
-    pub fn latest(&self) -> Option<MetricsSnapshot> {
-        let guard = match self.inner.read() {
-            Ok(g) => g,
-            Err(poisoned) => poisoned.into_inner(),
+    /// Drops the oldest snapshot, folding its per-core usage into the next
+    /// one if that is stored sparsely, so the oldest is always whole.
+    fn pop_oldest(guard: &mut VecDeque<MetricsSnapshot>) {
+        let Some(oldest) = guard.pop_front() else {
+            return;
         };
-        guard.back().cloned()
+        if let Some(next) = guard.front_mut() {
+            if let Some(changes) = next.cpu.per_core_changes.take() {
+                let mut cores = oldest.cpu.per_core_usage_pct;
+                changes.apply(&mut cores);
+                next.cpu.per_core_usage_pct = cores;
+            }
+        }
     }
 
-    pub fn history(&self, limit: Option<usize>) -> Vec<MetricsSnapshot> {
-        let guard = match self.inner.read() {
+    /// In a sparse buffer, replaces `snapshot`'s per-core vector by the cores
+    /// that changed since the previous snapshot, unless it is due to be whole.
+    fn encode_per_core(&self, first: bool, mut snapshot: MetricsSnapshot) -> MetricsSnapshot {
+        let Some(sparse) = &self.sparse else {
+            return snapshot;
+        };
+        let mut state = sparse.lock().unwrap_or_else(PoisonError::into_inner);
+        let threshold = state.threshold;
+        state.encode(&mut snapshot, first, threshold);
+        snapshot
+    }
+
+    /// Copies of `snapshots[start..end]` with whole per-core vectors, rebuilt
+    /// from the nearest whole snapshot at or before `start`.
+    fn materialize(
+        &self,
+        snapshots: &VecDeque<MetricsSnapshot>,
+        start: usize,
+        end: usize,
+    ) -> Vec<MetricsSnapshot> {
+        if self.sparse.is_none() || start >= end {
+            return snapshots.range(start..end).cloned().collect();
+        }
+        let from = (0..=start)
+            .rev()
+            .find(|&i| snapshots[i].cpu.per_core_changes.is_none())
+            .unwrap_or(0);
+        let mut cores = Vec::new();
+        let mut items = Vec::with_capacity(end - start);
+        for (i, snapshot) in snapshots.range(from..end).enumerate() {
+            match &snapshot.cpu.per_core_changes {
+                Some(changes) => changes.apply(&mut cores),
+                None => cores.clone_from(&snapshot.cpu.per_core_usage_pct),
+            }
+            if from + i >= start {
+                let mut whole = snapshot.clone();
+                whole.cpu.per_core_changes = None;
+                whole.cpu.per_core_usage_pct = cores.clone();
+                items.push(whole);
+            }
+        }
+        items
+    }
+
+    /// Makes every held snapshot whole, before removing arbitrary ones.
+    fn decode_all(&self, snapshots: &mut VecDeque<MetricsSnapshot>) {
+        if self.sparse.is_some() {
+            *snapshots = self.materialize(snapshots, 0, snapshots.len()).into();
+        }
+    }
+
+    /// Sparse-encodes whole snapshots again after [`decode_all`](Self::decode_all),
+    /// losslessly: cores are dropped only where they equal the rebuilt value.
+    fn encode_all(&self, snapshots: &mut VecDeque<MetricsSnapshot>) {
+        let Some(sparse) = &self.sparse else {
+            return;
+        };
+        let mut state = sparse.lock().unwrap_or_else(PoisonError::into_inner);
+        state.base.clear();
+        for (i, snapshot) in snapshots.iter_mut().enumerate() {
+            state.encode(snapshot, i == 0, 0.0);
+        }
+    }
+
+    fn write_recovering(&self) -> RwLockWriteGuard<'_, VecDeque<MetricsSnapshot>> {
+        match self.inner.write() {
+            Ok(g) => g,
+            Err(poisoned) => {
+                let count = self.poison_recoveries.fetch_add(1, Ordering::Relaxed) + 1;
+                warn!(
+                    "{} (continuing, recovery #{})",
+                    StorageError::Poisoned,
+                    count
+                );
+                poisoned.into_inner()
+            }
+        }
+    }
+
+    fn read_best_effort(&self) -> RwLockReadGuard<'_, VecDeque<MetricsSnapshot>> {
+        // Reads continue on a poisoned lock; the writer path reports it.
+        match self.inner.read() {
             Ok(g) => g,
             Err(poisoned) => poisoned.into_inner(),
-        };
-        let len = guard.len();
-        let take = limit.unwrap_or(len).min(len);
-        guard.iter().skip(len - take).cloned().collect()
+        }
+    }
+}
+
+impl SnapshotStore for MetricsBuffer {
+    fn push(&self, snapshot: MetricsSnapshot) {
+        let _ = MetricsBuffer::push(self, snapshot);
+    }
+
+    fn latest(&self) -> Option<MetricsSnapshot> {
+        MetricsBuffer::latest(self)
+    }
+
+    fn window(
+        &self,
+        since_ms: Option<u128>,
+        until_ms: Option<u128>,
+        limit: Option<usize>,
+    ) -> Vec<MetricsSnapshot> {
+        let mut items = self.range(since_ms, until_ms);
+        if let Some(limit) = limit {
+            let skip = items.len().saturating_sub(limit);
+            items.drain(..skip);
+        }
+        items
     }
 }
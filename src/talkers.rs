@@ -0,0 +1,347 @@
+//! "Top talkers": TCP traffic attributed to the processes owning the sockets
+//! (`--net-top-processes`).
+//!
+//! `/proc/net` has no per-socket byte counts, so on Linux the counters come
+//! from the `sock_diag` netlink interface (`tcp_info` bytes acked/received,
+//! kernel 4.2+) and sockets are matched to processes through the
+//! `socket:[inode]` links in `/proc/<pid>/fd`. This is best-effort: sockets of
+//! processes we may not inspect are skipped and UDP is not counted.
+
+use crate::metrics::ProcessNetUsage;
+use std::collections::HashMap;
+use tracing::warn;
+
+/// Cumulative byte counters of one socket and the process owning it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SocketBytes {
+    pub inode: u64,
+    pub pid: u32,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+}
+
+/// Where per-socket counters come from.
+pub trait SocketCounterSource: Send {
+    /// Counters of every attributable socket, or None when they cannot be read.
+    fn read(&mut self) -> Option<Vec<SocketBytes>>;
+}
+
+/// Turns successive counter readings into the busiest processes per interval.
+pub struct TopTalkers {
+    source: Box<dyn SocketCounterSource>,
+    top_n: usize,
+    /// (rx, tx) per socket inode at the previous reading.
+    last: HashMap<u64, (u64, u64)>,
+    primed: bool,
+    warned: bool,
+}
+
+impl TopTalkers {
+    pub fn new(source: impl SocketCounterSource + 'static, top_n: usize) -> Self {
+        Self {
+            source: Box::new(source),
+            top_n,
+            last: HashMap::new(),
+            primed: false,
+            warned: false,
+        }
+    }
+
+    /// [`TopTalkers`] over the platform's socket counters; None where there
+    /// are none.
+    pub fn system(top_n: usize) -> Option<Self> {
+        #[cfg(target_os = "linux")]
+        {
+            Some(Self::new(sock_diag::SockDiagSource, top_n))
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = top_n;
+            None
+        }
+    }
+
+    /// Reads the counters and returns up to `top_n` processes by bytes moved
+    /// since the previous call, busiest first. The first call only records a
+    /// baseline and returns an empty list.
+    pub fn sample(
+        &mut self,
+        dt_secs: f32,
+        name_of: impl Fn(u32) -> Option<String>,
+    ) -> Option<Vec<ProcessNetUsage>> {
+        let Some(sockets) = self.source.read() else {
+            if !self.warned {
+                warn!("Per-process network counters are unavailable; top talkers disabled");
+                self.warned = true;
+            }
+            return None;
+        };
+
+        let mut per_pid: HashMap<u32, (u64, u64)> = HashMap::new();
+        let mut current = HashMap::with_capacity(sockets.len());
+        for s in &sockets {
+            current.insert(s.inode, (s.rx_bytes, s.tx_bytes));
+            // Sockets opened since the last reading count from zero; a
+            // counter that went backwards is a reused inode.
+            let (rx, tx) = match self.last.get(&s.inode) {
+                Some(&(prev_rx, prev_tx)) => (
+                    s.rx_bytes.checked_sub(prev_rx).unwrap_or(s.rx_bytes),
+                    s.tx_bytes.checked_sub(prev_tx).unwrap_or(s.tx_bytes),
+                ),
+                None => (s.rx_bytes, s.tx_bytes),
+            };
+            let entry = per_pid.entry(s.pid).or_default();
+            entry.0 += rx;
+            entry.1 += tx;
+        }
+        self.last = current;
+        if !std::mem::replace(&mut self.primed, true) || dt_secs <= 0.0 {
+            return Some(Vec::new());
+        }
+
+        let mut busiest: Vec<(u32, u64, u64)> = per_pid
+            .into_iter()
+            .filter(|(_, (rx, tx))| rx + tx > 0)
+            .map(|(pid, (rx, tx))| (pid, rx, tx))
+            .collect();
+        busiest.sort_by(|a, b| (b.1 + b.2).cmp(&(a.1 + a.2)).then(a.0.cmp(&b.0)));
+        busiest.truncate(self.top_n);
+        Some(
+            busiest
+                .into_iter()
+                .map(|(pid, rx, tx)| ProcessNetUsage {
+                    pid,
+                    name: name_of(pid).unwrap_or_default(),
+                    rx_bytes_per_sec: rx as f32 / dt_secs,
+                    tx_bytes_per_sec: tx as f32 / dt_secs,
+                })
+                .collect(),
+        )
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod sock_diag {
+    use super::{SocketBytes, SocketCounterSource};
+    use std::collections::HashMap;
+    use std::io;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+    const SOCK_DIAG_BY_FAMILY: u16 = 20;
+    const INET_DIAG_INFO: u16 = 2;
+    const NLMSG_HDR_LEN: usize = 16;
+    /// Size of `struct inet_diag_req_v2`.
+    const REQ_LEN: usize = 56;
+    /// Size of `struct inet_diag_msg`, and the offset of `idiag_inode` in it.
+    const DIAG_MSG_LEN: usize = 72;
+    const DIAG_INODE_OFFSET: usize = 68;
+    /// Offsets of `tcpi_bytes_acked` and `tcpi_bytes_received` in `struct tcp_info`.
+    const TCPI_BYTES_ACKED: usize = 120;
+    const TCPI_BYTES_RECEIVED: usize = 128;
+
+    pub struct SockDiagSource;
+
+    impl SocketCounterSource for SockDiagSource {
+        fn read(&mut self) -> Option<Vec<SocketBytes>> {
+            let mut counters = Vec::new();
+            let mut any = false;
+            for family in [libc::AF_INET, libc::AF_INET6] {
+                if let Ok(found) = dump_tcp(family as u8) {
+                    counters.extend(found);
+                    any = true;
+                }
+            }
+            if !any {
+                return None;
+            }
+            let owners = socket_owners();
+            Some(
+                counters
+                    .into_iter()
+                    .filter_map(|(inode, tx_bytes, rx_bytes)| {
+                        Some(SocketBytes {
+                            inode,
+                            pid: *owners.get(&inode)?,
+                            rx_bytes,
+                            tx_bytes,
+                        })
+                    })
+                    .collect(),
+            )
+        }
+    }
+
+    /// Socket inode -> owning pid (the lowest, for sockets shared after fork).
+    fn socket_owners() -> HashMap<u64, u32> {
+        let mut owners = HashMap::new();
+        let Ok(procs) = std::fs::read_dir("/proc") else {
+            return owners;
+        };
+        for entry in procs.flatten() {
+            let Some(pid) = entry
+                .file_name()
+                .to_str()
+                .and_then(|n| n.parse::<u32>().ok())
+            else {
+                continue;
+            };
+            let Ok(fds) = std::fs::read_dir(entry.path().join("fd")) else {
+                continue;
+            };
+            for fd in fds.flatten() {
+                let Ok(target) = std::fs::read_link(fd.path()) else {
+                    continue;
+                };
+                let inode = target
+                    .to_str()
+                    .and_then(|t| t.strip_prefix("socket:["))
+                    .and_then(|t| t.strip_suffix(']'))
+                    .and_then(|t| t.parse::<u64>().ok());
+                if let Some(inode) = inode {
+                    owners
+                        .entry(inode)
+                        .and_modify(|p: &mut u32| *p = (*p).min(pid))
+                        .or_insert(pid);
+                }
+            }
+        }
+        owners
+    }
+
+    /// (inode, bytes acked, bytes received) of every TCP socket in `family`.
+    fn dump_tcp(family: u8) -> io::Result<Vec<(u64, u64, u64)>> {
+        // SAFETY: plain socket(2) call; the descriptor is owned below.
+        let raw = unsafe {
+            libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_DGRAM | libc::SOCK_CLOEXEC,
+                libc::NETLINK_SOCK_DIAG,
+            )
+        };
+        if raw < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `raw` is a freshly created descriptor nothing else owns.
+        let fd = unsafe { OwnedFd::from_raw_fd(raw) };
+
+        let mut req = Vec::with_capacity(NLMSG_HDR_LEN + REQ_LEN);
+        req.extend(((NLMSG_HDR_LEN + REQ_LEN) as u32).to_ne_bytes());
+        req.extend(SOCK_DIAG_BY_FAMILY.to_ne_bytes());
+        req.extend(((libc::NLM_F_REQUEST | libc::NLM_F_DUMP) as u16).to_ne_bytes());
+        req.extend(1u32.to_ne_bytes());
+        req.extend(0u32.to_ne_bytes());
+        req.extend([
+            family,
+            libc::IPPROTO_TCP as u8,
+            1 << (INET_DIAG_INFO - 1),
+            0,
+        ]);
+        req.extend(u32::MAX.to_ne_bytes());
+        req.extend([0u8; REQ_LEN - 8]);
+
+        // SAFETY: sockaddr_nl is plain data; all-zero is a valid value.
+        let mut kernel: libc::sockaddr_nl = unsafe { std::mem::zeroed() };
+        kernel.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+        // SAFETY: `req` and `kernel` outlive the call and the lengths match.
+        let sent = unsafe {
+            libc::sendto(
+                fd.as_raw_fd(),
+                req.as_ptr().cast(),
+                req.len(),
+                0,
+                (&kernel as *const libc::sockaddr_nl).cast(),
+                std::mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+            )
+        };
+        if sent < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut sockets = Vec::new();
+        let mut buf = vec![0u8; 64 * 1024];
+        loop {
+            // SAFETY: `buf` is valid for writes of its full length.
+            let n = unsafe { libc::recv(fd.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len(), 0) };
+            if n < 0 {
+                let err = io::Error::last_os_error();
+                if err.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(err);
+            }
+            if n == 0 {
+                return Ok(sockets);
+            }
+            if parse_messages(&buf[..n as usize], &mut sockets)? {
+                return Ok(sockets);
+            }
+        }
+    }
+
+    /// Appends the sockets in one netlink reply; true once the dump is done.
+    fn parse_messages(mut data: &[u8], sockets: &mut Vec<(u64, u64, u64)>) -> io::Result<bool> {
+        while data.len() >= NLMSG_HDR_LEN {
+            let len = u32_at(data, 0) as usize;
+            let kind = u16::from_ne_bytes([data[4], data[5]]);
+            if len < NLMSG_HDR_LEN || len > data.len() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "truncated netlink message",
+                ));
+            }
+            match kind as i32 {
+                libc::NLMSG_DONE => return Ok(true),
+                libc::NLMSG_ERROR => {
+                    let code = data
+                        .get(16..20)
+                        .map_or(0, |b| i32::from_ne_bytes(b.try_into().unwrap_or_default()));
+                    return Err(io::Error::from_raw_os_error(-code));
+                }
+                _ => {
+                    if let Some(socket) = parse_diag_msg(&data[NLMSG_HDR_LEN..len]) {
+                        sockets.push(socket);
+                    }
+                }
+            }
+            data = &data[align4(len).min(data.len())..];
+        }
+        Ok(false)
+    }
+
+    fn parse_diag_msg(msg: &[u8]) -> Option<(u64, u64, u64)> {
+        if msg.len() < DIAG_MSG_LEN {
+            return None;
+        }
+        let inode = u64::from(u32_at(msg, DIAG_INODE_OFFSET));
+        let mut attrs = &msg[DIAG_MSG_LEN..];
+        while attrs.len() >= 4 {
+            let len = usize::from(u16::from_ne_bytes([attrs[0], attrs[1]]));
+            let kind = u16::from_ne_bytes([attrs[2], attrs[3]]);
+            if len < 4 || len > attrs.len() {
+                return None;
+            }
+            let info = &attrs[4..len];
+            if kind == INET_DIAG_INFO && info.len() >= TCPI_BYTES_RECEIVED + 8 {
+                return Some((
+                    inode,
+                    u64_at(info, TCPI_BYTES_ACKED),
+                    u64_at(info, TCPI_BYTES_RECEIVED),
+                ));
+            }
+            attrs = &attrs[align4(len).min(attrs.len())..];
+        }
+        None
+    }
+
+    fn align4(len: usize) -> usize {
+        (len + 3) & !3
+    }
+
+    fn u32_at(data: &[u8], at: usize) -> u32 {
+        u32::from_ne_bytes(data[at..at + 4].try_into().unwrap_or_default())
+    }
+
+    fn u64_at(data: &[u8], at: usize) -> u64 {
+        u64::from_ne_bytes(data[at..at + 8].try_into().unwrap_or_default())
+    }
+}
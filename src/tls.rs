@@ -0,0 +1,41 @@
+//! HTTPS serving for the HTTP API.
+
+use axum::Router;
+use axum_server::tls_rustls::RustlsConfig;
+use axum_server::Handle;
+use std::io;
+use std::net::TcpListener;
+use std::path::Path;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+/// Time in-flight requests (including open SSE streams) get to finish on shutdown.
+const GRACEFUL_SHUTDOWN: Duration = Duration::from_secs(2);
+
+/// Loads a PEM certificate chain and private key.
+pub async fn load_config(cert: &Path, key: &Path) -> io::Result<RustlsConfig> {
+    // Only the ring provider is compiled in; installing it fails harmlessly
+    // when it is already the process default.
+    let _ = rustls::crypto::ring::default_provider().install_default();
+    RustlsConfig::from_pem_file(cert, key).await
+}
+
+/// Serves `app` over HTTPS on an already bound listener until `cancel` fires.
+pub async fn serve(
+    listener: TcpListener,
+    app: Router,
+    config: RustlsConfig,
+    cancel: CancellationToken,
+) -> io::Result<()> {
+    listener.set_nonblocking(true)?;
+    let handle = Handle::new();
+    let shutdown_handle = handle.clone();
+    tokio::spawn(async move {
+        cancel.cancelled().await;
+        shutdown_handle.graceful_shutdown(Some(GRACEFUL_SHUTDOWN));
+    });
+    axum_server::from_tcp_rustls(listener, config)
+        .handle(handle)
+        .serve(app.into_make_service())
+        .await
+}
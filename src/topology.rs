@@ -0,0 +1,88 @@
+//! Core-to-socket grouping (`--group-cores-by-socket`), read once at
+//! startup so per-core usage can be summarised per physical package. Where
+//! the topology cannot be read every core falls into one group.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CoreTopology {
+    /// Socket id of each core, indexed like `per_core_usage_pct`. Cores past
+    /// the end belong to socket 0.
+    sockets: Vec<u32>,
+}
+
+impl CoreTopology {
+    pub fn new(sockets: Vec<u32>) -> Self {
+        Self { sockets }
+    }
+
+    /// Every core in one group.
+    pub fn single() -> Self {
+        Self::default()
+    }
+
+    /// Reads `physical_package_id` for each CPU from sysfs on Linux; falls
+    /// back to [`single`](Self::single) elsewhere or when it is unreadable.
+    pub fn detect() -> Self {
+        #[cfg(target_os = "linux")]
+        if let Some(topology) = Self::from_sysfs(Path::new("/sys/devices/system/cpu")) {
+            return topology;
+        }
+        Self::single()
+    }
+
+    /// Socket ids from the `cpuN/topology/physical_package_id` files under
+    /// `cpu_dir`, in CPU number order. Numbering may have holes (offline or
+    /// absent CPUs); those have no topology and, like in
+    /// `per_core_usage_pct`, take no index. None when no CPU reports one.
+    pub fn from_sysfs(cpu_dir: &Path) -> Option<Self> {
+        let mut cpus: Vec<(u32, PathBuf)> = std::fs::read_dir(cpu_dir)
+            .ok()?
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                let name = entry.file_name();
+                let number = name.to_str()?.strip_prefix("cpu")?;
+                if number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit()) {
+                    return None;
+                }
+                Some((number.parse().ok()?, entry.path()))
+            })
+            .collect();
+        cpus.sort_unstable_by_key(|(number, _)| *number);
+        let sockets: Vec<u32> = cpus
+            .iter()
+            .filter_map(|(_, path)| {
+                std::fs::read_to_string(path.join("topology/physical_package_id"))
+                    .ok()?
+                    .trim()
+                    .parse::<i64>()
+                    .ok()
+            })
+            // -1 means the kernel does not know the package.
+            .map(|id| u32::try_from(id).unwrap_or(0))
+            .collect();
+        (!sockets.is_empty()).then(|| Self::new(sockets))
+    }
+
+    pub fn socket_of(&self, core: usize) -> u32 {
+        self.sockets.get(core).copied().unwrap_or(0)
+    }
+
+    /// Core indices of each socket among `cores` cores, by ascending socket id.
+    pub fn groups(&self, cores: usize) -> Vec<(u32, Vec<usize>)> {
+        let mut groups: BTreeMap<u32, Vec<usize>> = BTreeMap::new();
+        for core in 0..cores {
+            groups.entry(self.socket_of(core)).or_default().push(core);
+        }
+        groups.into_iter().collect()
+    }
+
+    /// Mean usage of each socket's cores, by ascending socket id.
+    pub fn per_socket_usage(&self, per_core: &[f32]) -> Vec<f32> {
+        self.groups(per_core.len())
+            .into_iter()
+            .map(|(_, cores)| cores.iter().map(|&c| per_core[c]).sum::<f32>() / cores.len() as f32)
+            .collect()
+    }
+}
@@ -1,8 +1,100 @@
+use axum::extract::Query;
 use axum::response::Html;
 use axum::response::IntoResponse;
+use axum::Router;
+use serde::{Deserialize, Serialize};
 
 mod templates;
 
-pub async fn index() -> impl IntoResponse {
-    Html(templates::render_index())
+/// Longest dashboard window a shared link may ask for.
+pub const MAX_VIEW_WINDOW_MS: u64 = 30 * 24 * 3600 * 1000;
+
+/// Dashboard view from a shared link: `/?window=180000&end=<ts>&live=0`.
+/// Values are kept as strings so a malformed link still loads the page.
+#[derive(Debug, Default, Deserialize)]
+pub struct ViewQuery {
+    pub window: Option<String>,
+    pub end: Option<String>,
+    pub live: Option<String>,
+}
+
+/// The validated view handed to the dashboard; unusable params are dropped.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct ViewState {
+    /// 0 shows all buffered data.
+    pub window_ms: Option<u64>,
+    /// End of a paused window; only set when not following live data.
+    pub end_ms: Option<u64>,
+    pub live: Option<bool>,
+}
+
+impl ViewState {
+    pub fn from_query(query: &ViewQuery) -> Self {
+        let window_ms = query
+            .window
+            .as_deref()
+            .and_then(|w| w.trim().parse::<u64>().ok())
+            .filter(|w| *w == 0 || *w >= 1000)
+            .map(|w| w.min(MAX_VIEW_WINDOW_MS));
+        let end_ms = query
+            .end
+            .as_deref()
+            .and_then(|e| e.trim().parse::<u64>().ok())
+            .filter(|e| *e > 0);
+        let live = match query.live.as_deref().map(str::trim) {
+            Some("1" | "true") => Some(true),
+            Some("0" | "false") => Some(false),
+            _ => None,
+        };
+        // An end timestamp implies a paused view unless the link says live.
+        let live = match (live, end_ms) {
+            (None, Some(_)) => Some(false),
+            (live, _) => live,
+        };
+        Self {
+            window_ms,
+            end_ms: end_ms.filter(|_| live == Some(false)),
+            live,
+        }
+    }
+}
+
+pub async fn index(Query(query): Query<ViewQuery>) -> impl IntoResponse {
+    Html(templates::render_index(&ViewState::from_query(&query)))
+}
+
+/// `--base-path` in the form routes are nested under: `/monitor/` and
+/// `monitor` both become `/monitor`, and `/` or an empty path become "" (no
+/// prefix). Segments are limited to URL-safe characters.
+pub fn normalize_base_path(raw: &str) -> Result<String, String> {
+    let trimmed = raw.trim_matches('/');
+    if trimmed.is_empty() {
+        return Ok(String::new());
+    }
+    let valid = trimmed.split('/').all(|segment| {
+        !segment.is_empty()
+            && segment != "."
+            && segment != ".."
+            && segment
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-_.~".contains(c))
+    });
+    if valid {
+        Ok(format!("/{trimmed}"))
+    } else {
+        Err(format!(
+            "'{raw}' is not a valid base path (letters, digits, '-', '_', '.', '~' separated by '/')"
+        ))
+    }
+}
+
+/// Serves `app` under `base_path` (as returned by [`normalize_base_path`]).
+/// The dashboard derives the prefix from its own URL, so the page itself
+/// needs no rewriting.
+pub fn with_base_path(app: Router, base_path: &str) -> Router {
+    if base_path.is_empty() {
+        app
+    } else {
+        Router::new().nest_service(base_path, app)
+    }
 }
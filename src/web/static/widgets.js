@@ -1,3 +1,10 @@
+// Prefix the page is served under (`--base-path`); the dashboard is always
+// served at the prefix root, so it is the page path minus trailing slashes.
+const BASE_PATH = location.pathname.replace(/\/+$/, '');
+function apiUrl(path) {
+    return BASE_PATH + path;
+}
+
 let data = { xs: [], series: {} };
 let followLive = true;
 let windowMs = 180000;
@@ -6,7 +13,26 @@ let windowStartMs = null;
 const tooltip = document.getElementById('tooltip');
 let lastView = null;
 let hiddenSeries = {};
-const GAP_THRESHOLD_MS = 5000;
+// Points further apart are drawn with a break in the line; /api/config
+// replaces this fallback with a multiple of the server's sampling interval.
+let gapThresholdMs = 5000;
+// Per-series {warn, crit} from /api/config; overrides the values in snapshots.
+let serverThresholds = {};
+// Network chart y-axis: server default from /api/config, user override in localStorage.
+let serverNetScale = { mode: 'auto', max_bytes_per_sec: null, axes: 'shared' };
+let netScaleOverride = localStorage.getItem('rm_netScale');
+let netAxesOverride = localStorage.getItem('rm_netAxes');
+// Buffered RX/TX maxima from /api/network/peaks, for split network axes.
+let networkPeaks = null;
+// Initial backfill: only `backfillWindowMs` of history is loaded on open
+// (0 = everything); older data is fetched when the view reaches past it.
+let backfillWindowMs = 0;
+// Points kept in memory before the oldest are dropped; /api/config may
+// replace this fallback.
+let maxBrowserPoints = 20000;
+let loadedFromTs = null;
+let oldestAvailableTs = null;
+let loadingOlder = false;
 
 let widgetOrder = JSON.parse(localStorage.getItem('rm_widgetOrder') || '[]');
 let hiddenWidgets = JSON.parse(localStorage.getItem('rm_hiddenWidgets') || '{}');
@@ -71,7 +97,9 @@ function formatValue(value, format) {
         case 'Percentage':
             return value.toFixed(format.params?.decimals || 1) + '%';
         case 'Bytes':
-            return fmtBytes(value, format.params?.suffix || 'B/s');
+            return fmtBytes(value, format.params?.suffix || 'B/s', format.params?.units);
+        case 'Bits':
+            return fmtBits(value);
         case 'Float':
             return value.toFixed(format.params?.decimals || 2);
         case 'Integer':
@@ -81,20 +109,42 @@ function formatValue(value, format) {
     }
 }
 
-function fmtBytes(v, suffix) {
+// `units` is 'si' for powers of 1000 (KB/s), IEC (KiB/s) otherwise.
+function fmtBytes(v, suffix, units) {
     suffix = suffix || 'B/s';
     if (!Number.isFinite(v) || v < 0) return '0 ' + suffix;
-    if (v >= 1073741824) return (v / 1073741824).toFixed(2) + ' Gi' + suffix;
-    if (v >= 1048576)    return (v / 1048576).toFixed(2)    + ' Mi' + suffix;
-    if (v >= 1024)       return (v / 1024).toFixed(1)       + ' Ki' + suffix;
-    return v.toFixed(0) + ' ' + suffix;
+    const scale = byteScale(v, units);
+    if (scale.div === 1) return v.toFixed(0) + ' ' + suffix;
+    const digits = scale.div >= (units === 'si' ? 1e6 : 1048576) ? 2 : 1;
+    return (v / scale.div).toFixed(digits) + ' ' + scale.prefix + suffix;
+}
+
+function fmtBits(v) {
+    if (!Number.isFinite(v) || v < 0) return '0 b/s';
+    if (v >= 1e9) return (v / 1e9).toFixed(2) + ' Gb/s';
+    if (v >= 1e6) return (v / 1e6).toFixed(2) + ' Mb/s';
+    if (v >= 1e3) return (v / 1e3).toFixed(2) + ' Kb/s';
+    return v.toFixed(0) + ' b/s';
 }
 
-function byteScale(maxY) {
-    if (maxY >= 1073741824) return { div: 1073741824, unit: 'GiB/s' };
-    if (maxY >= 1048576)    return { div: 1048576,    unit: 'MiB/s' };
-    if (maxY >= 1024)       return { div: 1024,       unit: 'KiB/s' };
-    return { div: 1, unit: 'B/s' }
+function bitScale(maxY) {
+    if (maxY >= 1e9) return { div: 1e9, unit: 'Gb/s' };
+    if (maxY >= 1e6) return { div: 1e6, unit: 'Mb/s' };
+    if (maxY >= 1e3) return { div: 1e3, unit: 'Kb/s' };
+    return { div: 1, unit: 'b/s' };
+}
+
+function byteScale(maxY, units) {
+    if (units === 'si') {
+        if (maxY >= 1e9) return { div: 1e9, prefix: 'G', unit: 'GB/s' };
+        if (maxY >= 1e6) return { div: 1e6, prefix: 'M', unit: 'MB/s' };
+        if (maxY >= 1e3) return { div: 1e3, prefix: 'K', unit: 'KB/s' };
+        return { div: 1, prefix: '', unit: 'B/s' };
+    }
+    if (maxY >= 1073741824) return { div: 1073741824, prefix: 'Gi', unit: 'GiB/s' };
+    if (maxY >= 1048576)    return { div: 1048576,    prefix: 'Mi', unit: 'MiB/s' };
+    if (maxY >= 1024)       return { div: 1024,       prefix: 'Ki', unit: 'KiB/s' };
+    return { div: 1, prefix: '', unit: 'B/s' };
 }
 
 function getOrderedSeries(snapshotData) {
@@ -392,7 +442,7 @@ function drawFullscreenChart() {
                 } else {
 
                     const lastTs = currentSegment.xs[currentSegment.xs.length - 1];
-                        if (ts - lastTs < GAP_THRESHOLD_MS) {
+                        if (ts - lastTs < gapThresholdMs) {
                             currentSegment.xs.push(ts);
                             currentSegment.ys.push(value);
                         } else {
@@ -445,26 +495,20 @@ function drawFullscreenChart() {
         return;
     }
 
-    let minY = 0, maxY = 100;
-    if (seriesData.format) {
-        const allYs = seriesList.flatMap(s => s.ys).filter(y => isFinite(y) && !isNaN(y));
-        if (allYs.length > 0) {
-            if (seriesData.format.type === 'Bytes' || seriesData.format.type !== 'Percentage') {
-                maxY = Math.max(1, ...allYs) * 1.1;
-            }
-        }
-    }
+    const { minY, maxY, logY, txFactor } = yAxisFor(name, seriesData, seriesList);
 
     drawLineChart(canvas, seriesList, {
         xs: view.xs, 
         minY, 
         maxY,
+        logY,
+        txFactor,
         byteY: seriesData.format?.type === 'Bytes',
+        bitY: seriesData.format?.type === 'Bits',
         seriesName: name, 
         seriesData, 
         startIdx: view.startIdx,
-        warn: seriesData.warn, 
-        crit: seriesData.crit
+        ...thresholdsFor(name, seriesData)
     });
 
     const legendDiv = document.getElementById('fs-legend');
@@ -499,7 +543,9 @@ function setupFullscreenTooltip() {
         if (!seriesData) return;
 
         const xScale = (meta.maxX === meta.minX) ? 0 : (meta.w - meta.leftPad - meta.rightPad) / (meta.maxX - meta.minX);
-        const yScale = (meta.maxY === meta.minY) ? 0 : (meta.h - meta.topPad - meta.bottomPad) / (meta.maxY - meta.minY);
+        const axisMin = axisValue(meta.minY, meta.logY);
+        const axisMax = axisValue(meta.maxY, meta.logY);
+        const yScale = (axisMax === axisMin) ? 0 : (meta.h - meta.topPad - meta.bottomPad) / (axisMax - axisMin);
 
         let nearestIdx = -1;
         let nearestDist = Infinity;
@@ -542,7 +588,7 @@ function setupFullscreenTooltip() {
 
             if (v === undefined || v === null || isNaN(v)) continue;
 
-            const yPos = meta.topPad + (meta.h - meta.topPad - meta.bottomPad) - (v - meta.minY) * yScale;
+            const yPos = meta.topPad + (meta.h - meta.topPad - meta.bottomPad) - (axisValue(v, meta.logY) - axisMin) * yScale;
             ctx.fillStyle = legend.color;
             ctx.beginPath();
             ctx.arc(nearestXPos, yPos, 5, 0, Math.PI * 2);
@@ -585,25 +631,40 @@ function pushDataPoint(rpcSnapshot) {
     if (ts <= last) return;
 
     data.xs.push(ts);
-
-    rpcSnapshot.data.forEach(series => {
+    const idx = data.xs.length - 1;
+
+    // Every series keeps one entry per timestamp: a series missing from this
+    // snapshot gets a null gap, one first seen now is back-filled with nulls,
+    // and non-numeric values become null rather than NaN.
+    const incoming = Array.isArray(rpcSnapshot.data) ? rpcSnapshot.data : [];
+    const seen = new Set();
+    incoming.forEach(series => {
+        if (!series || typeof series.name !== 'string' || seen.has(series.name)) return;
+        seen.add(series.name);
         if (!data.series[series.name]) {
             data.series[series.name] = {
-                values: [],
-                legends: [],
+                values: new Array(idx).fill(null),
+                legends: new Array(idx).fill(null),
                 format: series.format,
-                beautiful_name: series.beautiful_name,
+                beautiful_name: series.beautiful_name ?? series.name,
                 warn: series.warn ?? null,
                 crit: series.crit ?? null
             };
         }
-        data.series[series.name].values.push(series.series);
-        data.series[series.name].legends.push(series.legend);
+        const values = Array.isArray(series.series)
+            ? series.series.map(v => (typeof v === 'number' && isFinite(v)) ? v : null)
+            : null;
+        data.series[series.name].values.push(values);
+        data.series[series.name].legends.push(Array.isArray(series.legend) ? series.legend : null);
+    });
+    Object.keys(data.series).forEach(name => {
+        if (seen.has(name)) return;
+        data.series[name].values.push(null);
+        data.series[name].legends.push(null);
     });
 
-    const maxLen = 20000;
-    if (data.xs.length > maxLen) {
-        const drop = data.xs.length - maxLen;
+    if (data.xs.length > maxBrowserPoints) {
+        const drop = data.xs.length - maxBrowserPoints;
         data.xs.splice(0, drop);
         Object.keys(data.series).forEach(name => {
             data.series[name].values.splice(0, drop);
@@ -739,7 +800,7 @@ function drawAllCharts() {
                     } else {
 
                         const lastTs = currentSegment.xs[currentSegment.xs.length - 1];
-                        if (ts - lastTs < GAP_THRESHOLD_MS) {
+                        if (ts - lastTs < gapThresholdMs) {
                             currentSegment.xs.push(ts);
                             currentSegment.ys.push(value);
                         } else {
@@ -792,36 +853,114 @@ function drawAllCharts() {
             return;
         }
 
-        let minY = 0;
-        let maxY = 100;
-
-        if (seriesData.format) {
-            const allYs = seriesList.flatMap(s => s.ys).filter(y => isFinite(y) && !isNaN(y));
-            if (allYs.length > 0) {
-                if (seriesData.format.type === 'Bytes') {
-                    maxY = Math.max(1, ...allYs) * 1.1;
-                } else if (seriesData.format.type !== 'Percentage') {
-                    maxY = Math.max(1, ...allYs) * 1.1;
-                } else {
-                    maxY = 100;
-                }
-            }
-        }
+        const { minY, maxY, logY, txFactor } = yAxisFor(name, seriesData, seriesList);
 
         drawLineChart(canvas, seriesList, {
             xs: view.xs,
             minY: minY,
             maxY: maxY,
+            logY: logY,
+            txFactor: txFactor,
             byteY: seriesData.format?.type === 'Bytes',
+            bitY: seriesData.format?.type === 'Bits',
             seriesName: name,
             seriesData: seriesData,
             startIdx: view.startIdx,
-            warn: seriesData.warn,
-            crit: seriesData.crit
+            ...thresholdsFor(name, seriesData)
         });
     });
 
     updateRangeLabel(view);
+    maybeLoadOlder(view);
+}
+
+function netScaleMode() {
+    return netScaleOverride || serverNetScale.mode || 'auto';
+}
+
+function netAxesMode() {
+    return netAxesOverride || serverNetScale.axes || 'shared';
+}
+
+// Y range for a chart. Percentages are pinned to 0-100; everything else fits
+// the data in view, except the network chart in fixed or log mode. With
+// split network axes the TX lines are rescaled in place onto the RX axis and
+// `txFactor` is returned so the right-hand axis can label them.
+function yAxisFor(name, seriesData, seriesList) {
+    let minY = 0, maxY = 100;
+    let logY = false;
+    if (!seriesData.format || seriesData.format.type === 'Percentage') {
+        return { minY, maxY, logY };
+    }
+    const allYs = seriesList.flatMap(s => s.ys).filter(y => isFinite(y) && !isNaN(y));
+    if (allYs.length > 0) {
+        maxY = Math.max(1, ...allYs) * 1.1;
+    }
+    if (name === 'network') {
+        const mode = netScaleMode();
+        if (mode === 'log') {
+            return { minY, maxY, logY: true };
+        }
+        if (netAxesMode() === 'split') {
+            const split = splitNetworkAxes(seriesData, seriesList);
+            if (split) return { minY, maxY: split.rxMax * 1.1, logY, txFactor: split.txFactor };
+        }
+        if (mode === 'fixed') {
+            maxY = fixedNetworkMax(seriesData) || maxY;
+        }
+    }
+    return { minY, maxY, logY };
+}
+
+// RX (line 0) keeps its values; TX (line 1) is multiplied by rxMax / txMax so
+// both peaks reach the same height. Uses the server's buffered maxima, or
+// the lines in view before they have loaded.
+function splitNetworkAxes(seriesData, seriesList) {
+    const unit = seriesData.format?.type === 'Bits' ? 8 : 1;
+    const peakOf = (idx) => Math.max(0, ...seriesList
+        .filter(s => s.lineIdx === idx)
+        .flatMap(s => s.ys)
+        .filter(y => isFinite(y)));
+    const rxMax = networkPeaks ? networkPeaks.rx_max_bytes_per_sec * unit : peakOf(0);
+    const txMax = networkPeaks ? networkPeaks.tx_max_bytes_per_sec * unit : peakOf(1);
+    if (!(rxMax > 0) || !(txMax > 0)) return null;
+    const txFactor = rxMax / txMax;
+    seriesList.filter(s => s.lineIdx === 1).forEach(s => {
+        s.ys = s.ys.map(y => y * txFactor);
+    });
+    return { rxMax, txFactor };
+}
+
+async function loadNetworkPeaks() {
+    if (netAxesMode() !== 'split') return;
+    try {
+        const res = await fetch(apiUrl('/api/network/peaks'));
+        networkPeaks = res.ok ? await res.json() : null;
+    } catch (e) {
+        networkPeaks = null;
+    }
+}
+
+// The configured ceiling (in the chart's unit), or the peak over everything
+// buffered so the axis stays put while the window moves.
+function fixedNetworkMax(seriesData) {
+    const hint = serverNetScale.max_bytes_per_sec;
+    if (hint) {
+        return seriesData.format?.type === 'Bits' ? hint * 8 : hint;
+    }
+    let peak = 0;
+    for (const values of seriesData.values) {
+        for (const v of values || []) {
+            if (isFinite(v) && v > peak) peak = v;
+        }
+    }
+    return peak > 0 ? peak * 1.1 : null;
+}
+
+// Position of `v` along the y axis: log10(1 + v) on log charts so zero stays
+// at the bottom, the value itself otherwise.
+function axisValue(v, logY) {
+    return logY ? Math.log10(1 + Math.max(0, v)) : v;
 }
 
 function drawLineChart(canvas, seriesSegments, options) {
@@ -861,12 +1000,16 @@ function drawLineChart(canvas, seriesSegments, options) {
     const topPad = 10;
     const bottomPad = 20;
 
+    const logY = !!options.logY;
+    const axisMin = axisValue(minY, logY);
+    const axisMax = axisValue(maxY, logY);
+
     const warnVal = options.warn;
     const critVal = options.crit;
     if (warnVal != null || critVal != null) {
         const chartH = h - topPad - bottomPad;
         const chartW = w - leftPad - rightPad;
-        const valToY = (v) => topPad + chartH * (1 - (v - minY) / (maxY - minY));
+        const valToY = (v) => topPad + chartH * (1 - (axisValue(v, logY) - axisMin) / (axisMax - axisMin));
         const bottomY = topPad + chartH;
 
         ctx.setLineDash([6, 4]);
@@ -876,9 +1019,9 @@ function drawLineChart(canvas, seriesSegments, options) {
             const warnY = valToY(warnVal);
             const critY = valToY(critVal);
             ctx.fillStyle = 'rgba(250, 204, 21, 0.06)';
-            ctx.fillRect(leftPad, warnY, chartW, bottomY - warnY);
-            ctx.fillStyle = 'rgba(239, 68, 68, 0.08)';
             ctx.fillRect(leftPad, critY, chartW, warnY - critY);
+            ctx.fillStyle = 'rgba(239, 68, 68, 0.10)';
+            ctx.fillRect(leftPad, topPad, chartW, critY - topPad);
 
             ctx.strokeStyle = 'rgba(250, 204, 21, 0.35)';
             ctx.beginPath(); ctx.moveTo(leftPad, warnY); ctx.lineTo(w - rightPad, warnY); ctx.stroke();
@@ -894,12 +1037,13 @@ function drawLineChart(canvas, seriesSegments, options) {
             ctx.fillStyle = 'rgba(239, 68, 68, 0.7)';
             ctx.fillText(`crit ${critVal}%`, w - rightPad - 2, critY - 2);
         } else if (warnVal != null && critVal != null && critVal < warnVal) {
+            // Lower is worse: the critical region is below the crit line.
             const warnY = valToY(warnVal);
             const critY = valToY(critVal);
             ctx.fillStyle = 'rgba(250, 204, 21, 0.06)';
-            ctx.fillRect(leftPad, topPad, chartW, warnY - topPad);
-            ctx.fillStyle = 'rgba(239, 68, 68, 0.08)';
             ctx.fillRect(leftPad, warnY, chartW, critY - warnY);
+            ctx.fillStyle = 'rgba(239, 68, 68, 0.10)';
+            ctx.fillRect(leftPad, critY, chartW, bottomY - critY);
 
             ctx.strokeStyle = 'rgba(250, 204, 21, 0.35)';
             ctx.beginPath(); ctx.moveTo(leftPad, warnY); ctx.lineTo(w - rightPad, warnY); ctx.stroke();
@@ -926,6 +1070,9 @@ function drawLineChart(canvas, seriesSegments, options) {
             }
             if (critVal != null) {
                 const cy = valToY(critVal);
+                ctx.fillStyle = 'rgba(239, 68, 68, 0.10)';
+                ctx.fillRect(leftPad, topPad, chartW, cy - topPad);
+                ctx.setLineDash([6, 4]);
                 ctx.strokeStyle = 'rgba(239, 68, 68, 0.45)';
                 ctx.beginPath(); ctx.moveTo(leftPad, cy); ctx.lineTo(w - rightPad, cy); ctx.stroke();
                 ctx.setLineDash([]);
@@ -942,6 +1089,7 @@ function drawLineChart(canvas, seriesSegments, options) {
         maxX, 
         minY, 
         maxY, 
+        logY,
         w, 
         h, 
         leftPad, 
@@ -954,14 +1102,14 @@ function drawLineChart(canvas, seriesSegments, options) {
     };
 
     const xScale = (maxX === minX) ? 0 : (w - leftPad - rightPad) / (maxX - minX);
-    const yScale = (maxY === minY) ? 0 : (h - topPad - bottomPad) / (maxY - minY);
+    const yScale = (axisMax === axisMin) ? 0 : (h - topPad - bottomPad) / (axisMax - axisMin);
 
     function xToPx(x) {
         return leftPad + (x - minX) * xScale;
     }
 
     function yToPx(y) {
-        return topPad + (h - topPad - bottomPad) - (y - minY) * yScale;
+        return topPad + (h - topPad - bottomPad) - (axisValue(y, logY) - axisMin) * yScale;
     }
 
     ctx.fillStyle = '#9ca3af';
@@ -970,19 +1118,33 @@ function drawLineChart(canvas, seriesSegments, options) {
     ctx.textBaseline = 'middle';
 
     const yTicks = 4;
-    const scale = options.byteY ? byteScale(maxY) : null;
+    const byteUnits = options.seriesData?.format?.params?.units;
+    const scale = options.byteY ? byteScale(maxY, byteUnits) : (options.bitY ? bitScale(maxY) : null);
     for (let i = 0; i < yTicks; i++) {
-        const v = minY + (maxY - minY) * (i / (yTicks - 1));
+        const a = axisMin + (axisMax - axisMin) * (i / (yTicks - 1));
+        const v = logY ? Math.pow(10, a) - 1 : a;
         const py = yToPx(v);
         let label;
         if (scale) {
             label = (v / scale.div).toFixed(1) + ' ' + scale.unit;
         } else {
-            label = Math.round(v) + (options.byteY ? '' : '%');
+            label = Math.round(v) + (options.byteY || options.bitY ? '' : '%');
         }
         ctx.fillText(label, 4, py);
     }
 
+    if (options.txFactor) {
+        // Right-hand axis for the rescaled TX lines of split network axes.
+        const txScale = options.bitY ? bitScale(maxY / options.txFactor) : byteScale(maxY / options.txFactor, byteUnits);
+        ctx.textAlign = 'right';
+        for (let i = 0; i < yTicks; i++) {
+            const v = minY + (maxY - minY) * (i / (yTicks - 1));
+            const label = (v / options.txFactor / txScale.div).toFixed(1) + ' ' + txScale.unit;
+            ctx.fillText(label, w - 2, yToPx(v));
+        }
+        ctx.textAlign = 'left';
+    }
+
     ctx.textBaseline = 'alphabetic';
     const xTicks = 5;
     for (let i = 0; i < xTicks; i++) {
@@ -992,10 +1154,15 @@ function drawLineChart(canvas, seriesSegments, options) {
         ctx.fillText(txt, px - 25, h - 6);
     }
 
+    ctx.save();
+    ctx.beginPath();
+    ctx.rect(leftPad, topPad, w - leftPad - rightPad, h - topPad - bottomPad);
+    ctx.clip();
+
     for (const segment of seriesSegments) {
         if (segment.xs.length < 2) continue;
 
-        ctx.strokeStyle = segment.color;
+        ctx.strokeStyle = thresholdGradient(ctx, segment.color, warnVal, critVal, yToPx) || segment.color;
         ctx.lineWidth = segment.lineWidth || 2;
         ctx.beginPath();
 
@@ -1014,6 +1181,181 @@ function drawLineChart(canvas, seriesSegments, options) {
             }
         }
     }
+    ctx.restore();
+}
+
+// Vertical stroke gradient: the series colour below warn, amber between warn
+// and crit, red beyond crit. Null when the chart has no usable thresholds.
+function thresholdGradient(ctx, color, warnVal, critVal, yToPx) {
+    if (warnVal == null || critVal == null) return null;
+    const warnY = yToPx(warnVal);
+    const critY = yToPx(critVal);
+    if (!isFinite(warnY) || !isFinite(critY) || warnY === critY) return null;
+    // Outside the gradient line the end stop colours extend, so anything
+    // short of warn keeps the series colour and anything past crit is red.
+    const grad = ctx.createLinearGradient(0, warnY, 0, critY);
+    grad.addColorStop(0, color);
+    grad.addColorStop(0.001, '#fbbf24');
+    grad.addColorStop(0.999, '#fbbf24');
+    grad.addColorStop(1, '#ef4444');
+    return grad;
+}
+
+function thresholdsFor(name, seriesData) {
+    const t = serverThresholds[name];
+    if (!t) return { warn: seriesData.warn, crit: seriesData.crit };
+    return { warn: t.warn ?? null, crit: t.crit ?? null };
+}
+
+const HEALTH_POLL_MS = 5000;
+
+// Polls /api/health and shows whether the server is reachable and sampling.
+// A sample older than the gap threshold means the collector has stalled even
+// though the server still answers.
+async function pollHealth() {
+    let state = 'disconnected';
+    let detail = 'Server unreachable';
+    try {
+        const res = await fetch(apiUrl('/api/health'), { cache: 'no-store' });
+        if (res.ok) {
+            const health = await res.json();
+            const age = health.last_sample_age_ms;
+            if (age === undefined || age > gapThresholdMs) {
+                state = 'stale';
+                detail = age === undefined ? 'No samples yet' : `Last sample ${fmtAgeMs(age)} ago`;
+            } else {
+                state = health.status === 'ok' ? 'connected' : 'stale';
+                detail = `Status ${health.status}, last sample ${fmtAgeMs(age)} ago`;
+            }
+        }
+    } catch (e) {
+        // Network error: keep 'disconnected'.
+    }
+    setConnectionStatus(state, detail);
+}
+
+function fmtAgeMs(ms) {
+    return ms < 1000 ? `${ms} ms` : `${(ms / 1000).toFixed(1)} s`;
+}
+
+function setConnectionStatus(state, detail) {
+    const badge = document.getElementById('conn-status');
+    if (!badge) return;
+    badge.className = `conn-badge ${state}`;
+    badge.textContent = state;
+    badge.title = detail;
+}
+
+async function loadServerConfig() {
+    try {
+        const res = await fetch(apiUrl('/api/config'));
+        if (!res.ok) return;
+        const cfg = await res.json();
+        serverThresholds = cfg.thresholds || {};
+        serverNetScale = cfg.network_scale || serverNetScale;
+        await loadNetworkPeaks();
+        backfillWindowMs = cfg.initial_window_ms ?? 0;
+        if (cfg.gap_threshold_ms > 0) gapThresholdMs = cfg.gap_threshold_ms;
+        if (cfg.max_browser_points > 0) maxBrowserPoints = cfg.max_browser_points;
+        markNetScaleButton();
+        markNetAxesButton();
+        drawAllCharts();
+    } catch (e) {
+        console.warn('Failed to load /api/config, using snapshot thresholds', e);
+    }
+}
+
+async function loadAlerts() {
+    const container = document.getElementById('alerts');
+    if (!container) return;
+    try {
+        const res = await fetch(apiUrl('/api/alerts/history'));
+        if (!res.ok) return;
+        renderAlerts(container, await res.json());
+    } catch (e) {
+        console.warn('Failed to load /api/alerts/history', e);
+    }
+}
+
+function renderAlerts(container, alerts) {
+    container.innerHTML = '';
+    if (alerts.length === 0) {
+        container.textContent = 'No alerts';
+        return;
+    }
+    alerts.forEach(alert => {
+        const row = document.createElement('div');
+        const firing = alert.cleared_at_ms === null;
+        row.className = 'alert-row' + (firing ? ' firing' : '') + (alert.acknowledged ? ' acked' : '');
+
+        const text = document.createElement('span');
+        const until = firing ? 'firing' : `cleared ${fmtDateTime(alert.cleared_at_ms)}`;
+        text.textContent = `#${alert.id} ${alert.metric} \u2265 ${alert.threshold} ` +
+            `(peak ${alert.peak.toFixed(1)}) fired ${fmtDateTime(alert.fired_at_ms)}, ${until}` +
+            (alert.acknowledged ? ' \u00b7 acknowledged' : '');
+        row.appendChild(text);
+
+        if (firing && !alert.acknowledged) {
+            const btn = document.createElement('button');
+            btn.type = 'button';
+            btn.textContent = 'Ack';
+            btn.addEventListener('click', async () => {
+                const res = await fetch(apiUrl(`/api/alerts/${alert.id}/ack`), { method: 'POST' });
+                if (!res.ok) showNotification(`Could not acknowledge alert #${alert.id}`);
+                loadAlerts();
+            });
+            row.appendChild(btn);
+        }
+        container.appendChild(row);
+    });
+}
+
+async function loadLogs() {
+    const container = document.getElementById('logs');
+    if (!container) return;
+    const token = localStorage.getItem('apiToken');
+    try {
+        const res = await fetch(apiUrl('/api/logs?limit=200'), {
+            headers: token ? { Authorization: `Bearer ${token}` } : {},
+        });
+        if (res.status === 401 || res.status === 403) {
+            renderLogsLocked(container, (await res.json()).message);
+            return;
+        }
+        if (!res.ok) return;
+        const entries = await res.json();
+        const atBottom = container.scrollTop + container.clientHeight >= container.scrollHeight - 4;
+        container.innerHTML = '';
+        if (entries.length === 0) container.textContent = 'No log lines yet';
+        entries.forEach(e => {
+            const line = document.createElement('div');
+            line.className = `log-line ${e.level}`;
+            line.textContent = `${fmtTime(e.timestamp_ms)} ${e.level.padEnd(5)} ${e.target}: ${e.message}`;
+            container.appendChild(line);
+        });
+        if (atBottom) container.scrollTop = container.scrollHeight;
+    } catch (e) {
+        console.warn('Failed to load /api/logs', e);
+    }
+}
+
+function renderLogsLocked(container, message) {
+    if (container.querySelector('input')) return;
+    container.innerHTML = '';
+    const text = document.createElement('span');
+    text.textContent = `${message} `;
+    const input = document.createElement('input');
+    input.type = 'password';
+    input.placeholder = 'API token';
+    const btn = document.createElement('button');
+    btn.type = 'button';
+    btn.textContent = 'Use token';
+    btn.addEventListener('click', () => {
+        localStorage.setItem('apiToken', input.value);
+        container.innerHTML = 'Loading...';
+        loadLogs();
+    });
+    container.append(text, input, btn);
 }
 
 function drawTimeline() {
@@ -1057,7 +1399,7 @@ function drawTimeline() {
                     ctx.beginPath();
                     ctx.moveTo(x, y);
                     inSegment = true;
-                } else if (lastValidTs !== null && ts - lastValidTs >= GAP_THRESHOLD_MS) {
+                } else if (lastValidTs !== null && ts - lastValidTs >= gapThresholdMs) {
                     ctx.stroke();
                     ctx.beginPath();
                     ctx.moveTo(x, y);
@@ -1212,6 +1554,13 @@ function drawTimeline() {
 
 }
 
+// Traffic-light colour of the composite pressure score.
+function pressureColor(score, series) {
+    if (series.crit != null && score >= series.crit) return '#ef4444';
+    if (series.warn != null && score >= series.warn) return '#facc15';
+    return '#22c55e';
+}
+
 function updateStatCards(snapshot) {
     const container = document.getElementById('stat-cards');
     if (!container) return;
@@ -1232,6 +1581,9 @@ function updateStatCards(snapshot) {
         const value = document.createElement('div');
         value.className = 'stat-val';
         value.textContent = formatValue(series.series[0], series.format);
+        if (series.name === 'pressure') {
+            value.style.color = pressureColor(series.series[0], series);
+        }
         card.appendChild(value);
 
         container.appendChild(card);
@@ -1322,7 +1674,8 @@ function setupChartHandlers() {
 
                 if (v === undefined || v === null || isNaN(v)) continue;
 
-                const valueRatio = (v - meta.minY) / (meta.maxY - meta.minY);
+                const axisMin = axisValue(meta.minY, meta.logY);
+                const valueRatio = (axisValue(v, meta.logY) - axisMin) / (axisValue(meta.maxY, meta.logY) - axisMin);
                 const yPos = meta.topPad + (meta.h - meta.topPad - meta.bottomPad) - 
                             valueRatio * (meta.h - meta.topPad - meta.bottomPad);
 
@@ -1699,16 +2052,120 @@ function updateRangeLabel(view) {
     } else {
         label.textContent = `${fmtTime(view.startTs)} - ${fmtTime(view.endTs)} (${durationStr})`;
     }
+
+    syncViewUrl();
+}
+
+// Mirrors the window/end/live state into the query string so the address
+// bar always holds a link to the current view.
+function syncViewUrl() {
+    const params = new URLSearchParams(location.search);
+    params.set('window', String(Math.round(windowMs)));
+    if (followLive || pausedEndTs == null) {
+        params.delete('end');
+        params.set('live', '1');
+    } else {
+        params.set('end', String(Math.round(pausedEndTs)));
+        params.set('live', '0');
+    }
+    const search = '?' + params.toString();
+    if (search !== location.search) {
+        history.replaceState(null, '', location.pathname + search + location.hash);
+    }
+}
+
+// Applies the view the server parsed from the page URL (see `ViewState`).
+function applyInitialView() {
+    let view = {};
+    try {
+        view = JSON.parse(document.getElementById('initial-view')?.textContent || '{}');
+    } catch (e) {
+        console.warn('Ignoring malformed initial view', e);
+    }
+    if (view.window_ms != null) {
+        windowMs = view.window_ms;
+        document.querySelectorAll('button[data-win]').forEach(b => {
+            b.classList.toggle('active', parseInt(b.dataset.win, 10) === windowMs);
+        });
+        const winSlider = document.getElementById('win-slider');
+        const winLabel = document.getElementById('win-slider-label');
+        if (windowMs === 0) {
+            winSlider.value = '60';
+            winLabel.textContent = 'All';
+        } else {
+            const mins = clamp(Math.round(windowMs / 60000), 1, 60);
+            winSlider.value = mins;
+            winLabel.textContent = Math.round(windowMs / 60000) + 'm';
+        }
+    }
+    if (view.live === false && view.end_ms != null) {
+        followLive = false;
+        pausedEndTs = view.end_ms;
+    }
+}
+
+function initShareButton() {
+    const btn = document.getElementById('share-btn');
+    if (!btn) return;
+    btn.addEventListener('click', async () => {
+        syncViewUrl();
+        try {
+            await navigator.clipboard.writeText(location.href);
+            showNotification('Link to this view copied');
+        } catch (e) {
+            showNotification('Copy the address bar to share this view');
+        }
+    });
+}
+
+// Start of the initial load: the configured window, widened to cover the
+// window a deep link asked for.
+function initialFromTs(oldest, newest) {
+    if (backfillWindowMs <= 0 || windowMs === 0) return oldest;
+    const end = followLive ? newest : (pausedEndTs ?? newest);
+    return Math.max(oldest, Math.min(newest - backfillWindowMs, end - windowMs));
+}
+
+function maybeLoadOlder(view) {
+    if (loadingOlder || loadedFromTs === null || oldestAvailableTs === null) return;
+    if (loadedFromTs <= oldestAvailableTs) return;
+    const wanted = windowMs === 0 ? oldestAvailableTs : view.endTs - windowMs;
+    if (wanted >= loadedFromTs - gapThresholdMs) return;
+    loadOlderHistory(Math.max(oldestAvailableTs, wanted));
+}
+
+// Reloads everything from `fromTs` on; the live stream keeps appending.
+async function loadOlderHistory(fromTs) {
+    loadingOlder = true;
+    try {
+        const res = await fetch(apiUrl(`/api/range?from_ts=${Math.floor(fromTs)}&to_ts=${Date.now() + 60000}&limit=10000&decimals=1`));
+        if (!res.ok) throw new Error('HTTP ' + res.status);
+        const history = await res.json();
+        if (Array.isArray(history) && history.length > 0) {
+            history.sort((a, b) => a.timestamp_ms - b.timestamp_ms);
+            resetData();
+            history.forEach(p => pushDataPoint(p));
+        }
+        loadedFromTs = fromTs;
+        drawAllCharts();
+        updateEndSlider();
+    } catch (e) {
+        console.error('Older history fetch error:', e);
+        // Do not retry on every redraw.
+        loadedFromTs = oldestAvailableTs;
+    } finally {
+        loadingOlder = false;
+    }
 }
 
 async function fetchInitialData() {
     try {
-        const latestRes = await fetch('/api/latest');
+        const latestRes = await fetch(apiUrl('/api/latest'));
         if (!latestRes.ok) throw new Error('Failed to fetch latest');
 
         const latest = await latestRes.json();
 
-        const dbStatsRes = await fetch('/api/db/stats');
+        const dbStatsRes = await fetch(apiUrl('/api/db/stats'));
         let fromTs = 0;
         let toTs = Date.now();
 
@@ -1722,12 +2179,16 @@ async function fetchInitialData() {
             }
         }
 
-        const rangeRes = await fetch(`/api/range?from_ts=${fromTs}&to_ts=${toTs}&limit=10000`);
+        oldestAvailableTs = fromTs;
+        fromTs = initialFromTs(fromTs, toTs);
+
+        const rangeRes = await fetch(apiUrl(`/api/range?from_ts=${fromTs}&to_ts=${toTs}&limit=10000&decimals=1`));
         if (!rangeRes.ok) throw new Error('Failed to fetch range');
 
         const history = await rangeRes.json();
 
         resetData();
+        loadedFromTs = fromTs;
 
         if (Array.isArray(history) && history.length > 0) {
             history.sort((a, b) => a.timestamp_ms - b.timestamp_ms);
@@ -1746,7 +2207,8 @@ async function fetchInitialData() {
     } catch (e) {
         console.error('Fetch error:', e);
         try {
-            const res = await fetch('/api/history?limit=10000');
+            const windowParam = backfillWindowMs > 0 ? `&window_ms=${backfillWindowMs}` : '';
+            const res = await fetch(apiUrl(`/api/history?limit=10000&decimals=1${windowParam}`));
             if (!res.ok) throw new Error('HTTP ' + res.status);
 
             const hist = await res.json();
@@ -1802,6 +2264,47 @@ function updateEndSlider() {
     }
 }
 
+function initNetScaleButtons() {
+    document.querySelectorAll('button[data-net-scale]').forEach(btn => {
+        btn.addEventListener('click', () => {
+            netScaleOverride = btn.dataset.netScale;
+            localStorage.setItem('rm_netScale', netScaleOverride);
+            markNetScaleButton();
+            drawAllCharts();
+            if (fullscreenName) drawFullscreenChart();
+        });
+    });
+    markNetScaleButton();
+}
+
+function markNetScaleButton() {
+    const mode = netScaleMode();
+    document.querySelectorAll('button[data-net-scale]').forEach(b => {
+        b.classList.toggle('active', b.dataset.netScale === mode);
+    });
+}
+
+function initNetAxesButtons() {
+    document.querySelectorAll('button[data-net-axes]').forEach(btn => {
+        btn.addEventListener('click', async () => {
+            netAxesOverride = btn.dataset.netAxes;
+            localStorage.setItem('rm_netAxes', netAxesOverride);
+            markNetAxesButton();
+            await loadNetworkPeaks();
+            drawAllCharts();
+            if (fullscreenName) drawFullscreenChart();
+        });
+    });
+    markNetAxesButton();
+}
+
+function markNetAxesButton() {
+    const mode = netAxesMode();
+    document.querySelectorAll('button[data-net-axes]').forEach(b => {
+        b.classList.toggle('active', b.dataset.netAxes === mode);
+    });
+}
+
 function initWindowButtons() {
     const buttons = document.querySelectorAll('button[data-win]');
 
@@ -1902,25 +2405,101 @@ function initWidgetMenu() {
     });
 }
 
+let lastReceivedTs = null;
+let liveSocketOpened = false;
+let reconnectDelayMs = 1000;
+const RECONNECT_MAX_DELAY_MS = 30000;
+let backfilling = false;
+let pendingLive = [];
+
+function handleLiveSnapshot(p) {
+    document.getElementById('latest').textContent = JSON.stringify(p, null, 2);
+
+    if (data.xs.length === 0) {
+        createChartsFromSnapshot(p);
+    }
+
+    pushDataPoint(p);
+    updateStatCards(p);
+    lastReceivedTs = p.timestamp_ms;
+
+    if (followLive) {
+        drawAllCharts();
+        if (fullscreenName) drawFullscreenChart();
+    }
+}
+
+async function backfillSince(ts) {
+    backfilling = true;
+    try {
+        const res = await fetch(apiUrl(`/api/history?since_ts=${ts + 1}`));
+        if (!res.ok) throw new Error('HTTP ' + res.status);
+        const hist = await res.json();
+        if (Array.isArray(hist) && hist.length > 0) {
+            hist.sort((a, b) => a.timestamp_ms - b.timestamp_ms);
+            hist.forEach(p => pushDataPoint(p));
+            lastReceivedTs = hist[hist.length - 1].timestamp_ms;
+        }
+    } catch (e) {
+        console.error('Backfill error:', e);
+    } finally {
+        backfilling = false;
+        // Frames that arrived mid-backfill are newer than the history page.
+        const queued = pendingLive;
+        pendingLive = [];
+        queued.forEach(handleLiveSnapshot);
+        drawAllCharts();
+    }
+}
+
 function startStream() {
-    const es = new EventSource('/api/stream');
+    const proto = location.protocol === 'https:' ? 'wss:' : 'ws:';
+    const ws = new WebSocket(`${proto}//${location.host}${apiUrl('/api/ws')}`);
+    let opened = false;
+
+    ws.onopen = () => {
+        opened = true;
+        const resumed = liveSocketOpened;
+        liveSocketOpened = true;
+        reconnectDelayMs = 1000;
+        if (resumed && lastReceivedTs !== null) {
+            backfillSince(lastReceivedTs);
+        }
+    };
 
-    es.onmessage = (ev) => {
+    ws.onmessage = (ev) => {
         try {
             const p = JSON.parse(ev.data);
-            document.getElementById('latest').textContent = JSON.stringify(p, null, 2);
-
-            if (data.xs.length === 0) {
-                createChartsFromSnapshot(p);
+            if (backfilling) {
+                pendingLive.push(p);
+            } else {
+                handleLiveSnapshot(p);
             }
+        } catch (e) {
+            console.error('Stream error:', e);
+        }
+    };
 
-            pushDataPoint(p);
-            updateStatCards(p);
+    ws.onclose = () => {
+        if (!opened && !liveSocketOpened) {
+            // No WebSocket support behind this origin; use SSE instead.
+            startSseStream();
+            return;
+        }
+        if (opened) {
+            showNotification('Live stream disconnected, reconnecting...');
+        }
+        setTimeout(startStream, reconnectDelayMs);
+        reconnectDelayMs = Math.min(reconnectDelayMs * 2, RECONNECT_MAX_DELAY_MS);
+    };
+}
 
-            if (followLive) {
-                drawAllCharts();
-                if (fullscreenName) drawFullscreenChart();
-            }
+function startSseStream() {
+    const es = new EventSource(apiUrl('/api/stream'));
+
+    es.onmessage = (ev) => {
+        try {
+            handleLiveSnapshot(JSON.parse(ev.data));
         } catch (e) {
             console.error('Stream error:', e);
         }
@@ -1931,11 +2510,30 @@ function startStream() {
     };
 }
 
+// The page's API links are written root-relative; move them under BASE_PATH.
+function prefixApiLinks() {
+    document.querySelectorAll('a[href^="/api/"]').forEach(a => {
+        a.setAttribute('href', apiUrl(a.getAttribute('href')));
+    });
+}
+
 document.addEventListener('DOMContentLoaded', () => {
+    prefixApiLinks();
     initWindowButtons();
+    initNetScaleButtons();
+    initNetAxesButtons();
     initSliders();
+    applyInitialView();
+    initShareButton();
     initWidgetMenu();
-    fetchInitialData();
+    loadServerConfig().then(fetchInitialData);
+    loadAlerts();
+    setInterval(loadAlerts, 5000);
+    loadLogs();
+    setInterval(loadLogs, 5000);
+    setInterval(loadNetworkPeaks, 5000);
+    pollHealth();
+    setInterval(pollHealth, HEALTH_POLL_MS);
     startStream();
     setupTimelineDrag();
 });
\ No newline at end of file
@@ -1,4 +1,7 @@
-pub fn render_index() -> String {
+use super::ViewState;
+
+pub fn render_index(view: &ViewState) -> String {
+    let view_json = serde_json::to_string(view).unwrap_or_else(|_| "{}".to_string());
     format!(
         r#"<!doctype html>
 <html>
@@ -10,7 +13,7 @@ pub fn render_index() -> String {
   </style>
 </head>
 <body>
-  <h1>Resource Monitor</h1>
+  <h1>Resource Monitor <span id="conn-status" class="conn-badge" title="Connection to the server">connecting</span></h1>
 
   <!-- Stat cards are created dynamically from snapshot data -->
   <div class="stat-grid" id="stat-cards"></div>
@@ -37,8 +40,20 @@ pub fn render_index() -> String {
       <span class="label">|</span>
       <a href="/api/stream">/api/stream</a>
     </div>
+    <div class="controls">
+      <span class="label">Network scale</span>
+      <button data-net-scale="auto" type="button">Auto</button>
+      <button data-net-scale="fixed" type="button">Fixed</button>
+      <button data-net-scale="log" type="button">Log</button>
+    </div>
+    <div class="controls">
+      <span class="label">Network axes</span>
+      <button data-net-axes="shared" type="button">Shared</button>
+      <button data-net-axes="split" type="button">Split RX/TX</button>
+    </div>
     <div class="controls">
       <span class="label" id="range-label">Last 3 minutes</span>
+      <button id="share-btn" type="button">Copy link</button>
     </div>
     <div class="controls widget-menu-toggle">
       <button id="widget-menu-btn" type="button">Widgets</button>
@@ -75,16 +90,24 @@ pub fn render_index() -> String {
   <!-- Charts are created dynamically from snapshot data -->
   <div id="charts-container" class="widgets-grid"></div>
 
+  <h3 style="margin-top:20px;">Alerts</h3>
+  <div id="alerts" class="panel">Loading...</div>
+
+  <h3 style="margin-top:20px;">Server log</h3>
+  <div id="logs" class="panel log-tail">Loading...</div>
+
   <h3 style="margin-top:20px;">Latest snapshot</h3>
   <pre id="latest">Loading...</pre>
   <div id="tooltip"></div>
-  
+
+  <script id="initial-view" type="application/json">{}</script>
   <script>
     {}
   </script>
 </body>
 </html>"#,
         include_str!("static/styles.css"),
+        view_json,
         include_str!("static/widgets.js")
     )
 }
@@ -0,0 +1,660 @@
+use resource_monitor::aggregator::{
+    delay_to_boundary, purge_resumed, spawn_labeled_aggregator, Aggregator, AggregatorConfig,
+    IntervalAutoTune, MetricsSource, PublishGate, SampleClock, SyntheticSource,
+    AUTO_TUNE_SLOW_SAMPLES,
+};
+use resource_monitor::api::Sampling;
+use resource_monitor::config::ChangeDeltas;
+use resource_monitor::metrics::{
+    busy_cores, CpuMetrics, DiskMetrics, MemoryMetrics, MetricsSnapshot, NetworkMetrics,
+};
+use resource_monitor::rpc::{MetricsRpc, MetricsRpcServer};
+use resource_monitor::storage::MetricsBuffer;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+#[test]
+fn sample_clock_records_elapsed_interval() {
+    let interval = Duration::from_millis(500);
+    let mut clock = SampleClock::new(interval);
+
+    let first = Instant::now();
+    assert_eq!(clock.elapsed_secs(first), interval.as_secs_f32());
+    clock.record(first);
+
+    std::thread::sleep(Duration::from_millis(60));
+    let second = Instant::now();
+    let dt_ms = clock.elapsed_secs(second) * 1000.0;
+    let actual_ms = second.duration_since(first).as_secs_f32() * 1000.0;
+    assert!((dt_ms - actual_ms).abs() < 1.0);
+    assert!(dt_ms >= 60.0);
+}
+
+#[test]
+fn aligned_first_tick_lands_on_a_wall_clock_boundary() {
+    let second = Duration::from_secs(1);
+    for wall_ms in [1_700_000_000_250u128, 1_700_000_000_999, 1_700_000_000_001] {
+        let delay = delay_to_boundary(wall_ms, second);
+        assert!(delay < second);
+        assert_eq!((wall_ms + delay.as_millis()) % 1000, 0, "from {wall_ms}");
+    }
+    assert_eq!(
+        delay_to_boundary(1_700_000_000_250, second),
+        Duration::from_millis(750)
+    );
+    assert_eq!(delay_to_boundary(1_700_000_000_000, second), Duration::ZERO);
+    assert_eq!(
+        delay_to_boundary(1_700_000_000_100, Duration::from_millis(250)),
+        Duration::from_millis(150)
+    );
+    assert_eq!(
+        delay_to_boundary(1_700_000_000_100, Duration::from_micros(500)),
+        Duration::ZERO
+    );
+}
+
+fn empty_snapshot(timestamp_ms: u128, dt: f32) -> MetricsSnapshot {
+    MetricsSnapshot {
+        timestamp_ms,
+        sample_interval_ms: dt * 1000.0,
+        cpu: CpuMetrics {
+            total_usage_pct: 0.0,
+            per_core_usage_pct: vec![],
+            load_avg_1: Some(0.0),
+            load_avg_5: Some(0.0),
+            load_avg_15: Some(0.0),
+            temperature_celsius: None,
+            breakdown: None,
+            per_socket_usage_pct: None,
+            per_core_changes: None,
+            busy_cores: None,
+        },
+        memory: MemoryMetrics {
+            total_bytes: 0,
+            used_bytes: 0,
+            available_bytes: 0,
+            swap_total_bytes: 0,
+            swap_used_bytes: 0,
+            swap_in_bytes_per_sec: None,
+            swap_out_bytes_per_sec: None,
+        },
+        network: NetworkMetrics {
+            rx_bytes_total: 0,
+            tx_bytes_total: 0,
+            rx_bytes_per_sec: 0.0,
+            tx_bytes_per_sec: 0.0,
+        },
+        disk: DiskMetrics {
+            total_bytes: 0,
+            available_bytes: 0,
+            used_pct: 0.0,
+            used_bytes: None,
+        },
+        battery: None,
+        gpu: None,
+        scheduler: None,
+        watched_process: None,
+        net_top_processes: None,
+        pressure_score: None,
+        source: None,
+        resumed: false,
+        tags: Default::default(),
+    }
+}
+
+/// Panics on its first `panics` calls, then succeeds.
+struct FlakySource {
+    calls: Arc<AtomicU32>,
+    panics: u32,
+}
+
+impl MetricsSource for FlakySource {
+    fn sample(&mut self, timestamp_ms: u128, dt: f32) -> MetricsSnapshot {
+        let call = self.calls.fetch_add(1, Ordering::SeqCst) + 1;
+        if call <= self.panics {
+            panic!("simulated sysinfo failure");
+        }
+        empty_snapshot(timestamp_ms, dt)
+    }
+}
+
+#[test]
+fn resume_after_suspend_zeroes_rates_for_one_sample() {
+    let interval = Duration::from_secs(1);
+    let mut clock = SampleClock::new(interval);
+    let before = Instant::now();
+    clock.record(before);
+
+    let spiky = || {
+        let mut snap = empty_snapshot(1000, 1.0);
+        snap.network.rx_bytes_per_sec = 5e9;
+        snap.network.tx_bytes_per_sec = 1e9;
+        snap.memory.swap_in_bytes_per_sec = Some(1e8);
+        snap
+    };
+
+    // An hour passes between two ticks, as across a laptop suspend; only the
+    // wall clock sees it.
+    let dt = clock.elapsed_secs(before + Duration::from_millis(1100));
+    let mut resumed = spiky();
+    assert!(purge_resumed(&mut resumed, dt, 3600.0, interval, 10.0));
+    assert!(resumed.resumed);
+    assert_eq!(resumed.network.rx_bytes_per_sec, 0.0);
+    assert_eq!(resumed.network.tx_bytes_per_sec, 0.0);
+    assert_eq!(resumed.memory.swap_in_bytes_per_sec, Some(0.0));
+    assert!(resumed.to_rpc_format().resumed);
+
+    let dt = clock.elapsed_secs(before + Duration::from_millis(1100));
+    let mut normal = spiky();
+    assert!(!purge_resumed(&mut normal, dt, 1.1, interval, 10.0));
+    assert!(!normal.resumed);
+    assert_eq!(normal.network.rx_bytes_per_sec, 5e9);
+}
+
+/// Wall clock the suspend test moves forward, as a suspend would while the
+/// monotonic clock stands still.
+static SUSPENDED_MS: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
+fn suspendable_wall_clock() -> u128 {
+    resource_monitor::metrics::now_timestamp_ms() + u128::from(SUSPENDED_MS.load(Ordering::SeqCst))
+}
+
+/// Reports the same high network rate on every sample.
+struct BusySource;
+
+impl MetricsSource for BusySource {
+    fn sample(&mut self, timestamp_ms: u128, dt: f32) -> MetricsSnapshot {
+        let mut snap = empty_snapshot(timestamp_ms, dt);
+        snap.network.rx_bytes_per_sec = 5e9;
+        snap
+    }
+}
+
+#[tokio::test]
+async fn aggregator_zeroes_rates_after_a_wall_clock_only_gap() {
+    use resource_monitor::bus::register_storage_subscriber;
+    use resource_monitor::storage::MetricsBuffer;
+
+    let buffer = Arc::new(MetricsBuffer::new(64));
+    let _activity = register_storage_subscriber(buffer.clone());
+    let agg = Aggregator::new(
+        AggregatorConfig::new(Duration::from_millis(10))
+            .with_warmup_samples(0)
+            .with_resume_gap_factor(Some(10.0))
+            .with_wall_clock(suspendable_wall_clock),
+    );
+    let cancel = CancellationToken::new();
+    let handle = tokio::spawn(agg.run_with_source(BusySource, cancel.clone()));
+
+    let wait_for = |count: usize| {
+        let buffer = buffer.clone();
+        async move {
+            let deadline = Instant::now() + Duration::from_secs(2);
+            while buffer.history(None).len() < count {
+                assert!(Instant::now() < deadline, "too few snapshots published");
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        }
+    };
+    wait_for(2).await;
+    let before = buffer.history(None).len();
+    SUSPENDED_MS.store(3_600_000, Ordering::SeqCst);
+    wait_for(before + 2).await;
+    cancel.cancel();
+    handle.await.unwrap();
+
+    let history = buffer.history(None);
+    let flagged: Vec<bool> = history.iter().map(|s| s.resumed).collect();
+    assert_eq!(flagged.iter().filter(|&&r| r).count(), 1, "{flagged:?}");
+    let resumed = history.iter().position(|s| s.resumed).unwrap();
+    assert!(resumed >= before, "flagged before the jump: {flagged:?}");
+    assert_eq!(history[resumed].network.rx_bytes_per_sec, 0.0);
+    assert_eq!(history[resumed + 1].network.rx_bytes_per_sec, 5e9);
+}
+
+#[tokio::test]
+async fn aggregator_applies_its_own_transforms_before_publishing() {
+    use resource_monitor::bus::register_storage_subscriber;
+    use resource_monitor::storage::MetricsBuffer;
+
+    let buffer = Arc::new(MetricsBuffer::new(64));
+    let _activity = register_storage_subscriber(buffer.clone());
+    let agg = Aggregator::new(
+        AggregatorConfig::new(Duration::from_millis(10))
+            .with_warmup_samples(0)
+            .with_transform(|mut snap| {
+                snap.network.rx_bytes_per_sec = 0.0;
+                snap
+            }),
+    );
+    let cancel = CancellationToken::new();
+    let handle = tokio::spawn(agg.run_with_source(BusySource, cancel.clone()));
+
+    let deadline = Instant::now() + Duration::from_secs(2);
+    while buffer.history(None).len() < 2 {
+        assert!(Instant::now() < deadline, "too few snapshots published");
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    cancel.cancel();
+    handle.await.unwrap();
+
+    assert!(buffer
+        .history(None)
+        .iter()
+        .all(|s| s.network.rx_bytes_per_sec == 0.0));
+}
+
+/// A wall clock that never moves, so every sample lands on the same millisecond.
+fn frozen_wall_clock() -> u128 {
+    5_000
+}
+
+#[tokio::test]
+async fn only_aligned_timestamps_skip_colliding_samples() {
+    use resource_monitor::bus::register_storage_subscriber;
+    use resource_monitor::storage::MetricsBuffer;
+
+    for align in [false, true] {
+        let buffer = Arc::new(MetricsBuffer::new(64));
+        let _activity = register_storage_subscriber(buffer.clone());
+        let agg = Aggregator::new(
+            AggregatorConfig::new(Duration::from_millis(5))
+                .with_warmup_samples(0)
+                .with_aligned_timestamps(align)
+                .with_wall_clock(frozen_wall_clock),
+        );
+        let cancel = CancellationToken::new();
+        let handle = tokio::spawn(agg.run_with_source(BusySource, cancel.clone()));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        cancel.cancel();
+        handle.await.unwrap();
+
+        let published = buffer.history(None).len();
+        if align {
+            assert_eq!(published, 1);
+        } else {
+            assert!(published > 1, "unaligned samples were skipped");
+        }
+    }
+}
+
+/// Wall clock following tokio's clock, which the test pauses, from a start
+/// 250 ms past a second boundary.
+fn paused_wall_clock() -> u128 {
+    static START: std::sync::OnceLock<tokio::time::Instant> = std::sync::OnceLock::new();
+    let start = *START.get_or_init(tokio::time::Instant::now);
+    1_700_000_000_250 + start.elapsed().as_millis()
+}
+
+#[tokio::test(start_paused = true)]
+async fn aligned_ticks_land_on_boundaries_after_start_and_interval_change() {
+    use resource_monitor::bus::register_storage_subscriber;
+    use resource_monitor::storage::MetricsBuffer;
+
+    paused_wall_clock();
+    let buffer = Arc::new(MetricsBuffer::new(64));
+    let _activity = register_storage_subscriber(buffer.clone());
+    let (interval_tx, interval_rx) = tokio::sync::watch::channel(Duration::from_secs(1));
+    let agg = Aggregator::new(
+        AggregatorConfig::new(Duration::from_secs(1))
+            .with_warmup_samples(0)
+            .with_aligned_ticks(true)
+            .with_interval_updates(interval_rx)
+            .with_wall_clock(paused_wall_clock),
+    );
+    let cancel = CancellationToken::new();
+    let handle = tokio::spawn(agg.run_with_source(BusySource, cancel.clone()));
+    let timestamps = || {
+        buffer
+            .history(None)
+            .iter()
+            .map(|s| s.timestamp_ms)
+            .collect::<Vec<_>>()
+    };
+    let wait_for = |count: usize| async move {
+        while timestamps().len() < count {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    };
+
+    wait_for(1).await;
+    assert_eq!(timestamps(), vec![1_700_000_001_000]);
+
+    // Off any 300 ms boundary, and well before the next 1 s tick.
+    tokio::time::sleep(Duration::from_millis(120)).await;
+    interval_tx.send(Duration::from_millis(300)).unwrap();
+    wait_for(4).await;
+    cancel.cancel();
+    handle.await.unwrap();
+
+    let after_change = &timestamps()[1..];
+    assert!(
+        after_change.iter().all(|ts| ts % 300 == 0),
+        "{after_change:?}"
+    );
+    assert!(
+        after_change.windows(2).all(|w| w[1] - w[0] == 300),
+        "{after_change:?}"
+    );
+}
+
+#[tokio::test]
+async fn aggregator_survives_panicking_source() {
+    let calls = Arc::new(AtomicU32::new(0));
+    let source = FlakySource {
+        calls: calls.clone(),
+        panics: 2,
+    };
+    let agg = Aggregator::new(AggregatorConfig::new(Duration::from_millis(5)));
+    let health = agg.health();
+    let cancel = CancellationToken::new();
+    let handle = tokio::spawn(agg.run_with_source(source, cancel.clone()));
+
+    let deadline = Instant::now() + Duration::from_secs(2);
+    while calls.load(Ordering::SeqCst) < 4 {
+        assert!(Instant::now() < deadline, "aggregator stopped sampling");
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    cancel.cancel();
+    handle.await.expect("aggregator task should not die");
+
+    assert_eq!(health.panics(), 2);
+    assert!(!health.is_failing());
+}
+
+/// Like a real source: rates read zero until there is a previous sample.
+struct BaselineSource {
+    sampled: bool,
+}
+
+impl MetricsSource for BaselineSource {
+    fn sample(&mut self, timestamp_ms: u128, dt: f32) -> MetricsSnapshot {
+        let mut snapshot = empty_snapshot(timestamp_ms, dt);
+        if self.sampled {
+            snapshot.cpu.total_usage_pct = 12.5;
+            snapshot.network.rx_bytes_per_sec = 2048.0;
+        }
+        self.sampled = true;
+        snapshot
+    }
+}
+
+#[tokio::test]
+async fn warmup_discards_first_sample() {
+    use resource_monitor::bus::register_storage_subscriber;
+    use resource_monitor::storage::MetricsBuffer;
+
+    let buffer = Arc::new(MetricsBuffer::new(16));
+    let _activity = register_storage_subscriber(buffer.clone());
+    let agg =
+        Aggregator::new(AggregatorConfig::new(Duration::from_millis(5)).with_warmup_samples(1));
+    let cancel = CancellationToken::new();
+    let handle =
+        tokio::spawn(agg.run_with_source(BaselineSource { sampled: false }, cancel.clone()));
+
+    let deadline = Instant::now() + Duration::from_secs(2);
+    while buffer.latest().is_none() {
+        assert!(Instant::now() < deadline, "nothing was published");
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    cancel.cancel();
+    handle.await.unwrap();
+
+    let first = &buffer.history(None)[0];
+    assert!(first.cpu.total_usage_pct > 0.0);
+    assert!(first.network.rx_bytes_per_sec > 0.0);
+}
+
+/// Blocks inside `sample` until released, like a slow disk or process scan.
+struct SlowSource {
+    started: Arc<AtomicU32>,
+    release: std::sync::mpsc::Receiver<()>,
+}
+
+impl MetricsSource for SlowSource {
+    fn sample(&mut self, timestamp_ms: u128, dt: f32) -> MetricsSnapshot {
+        self.started.fetch_add(1, Ordering::SeqCst);
+        let _ = self.release.recv_timeout(Duration::from_secs(5));
+        empty_snapshot(timestamp_ms, dt)
+    }
+}
+
+#[tokio::test]
+async fn cancellation_does_not_wait_for_slow_collection() {
+    let started = Arc::new(AtomicU32::new(0));
+    let (release_tx, release) = std::sync::mpsc::channel();
+    let source = SlowSource {
+        started: started.clone(),
+        release,
+    };
+    let agg = Aggregator::new(AggregatorConfig::new(Duration::from_millis(5)));
+    let cancel = CancellationToken::new();
+    let handle = tokio::spawn(agg.run_with_source(source, cancel.clone()));
+
+    // The test runtime is single-threaded: this loop only makes progress if
+    // the collection is off the runtime thread.
+    let deadline = Instant::now() + Duration::from_secs(2);
+    while started.load(Ordering::SeqCst) == 0 {
+        assert!(Instant::now() < deadline, "collection never started");
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+
+    cancel.cancel();
+    tokio::time::timeout(Duration::from_millis(500), handle)
+        .await
+        .expect("aggregator should stop while a collection is in flight")
+        .unwrap();
+    assert_eq!(started.load(Ordering::SeqCst), 1);
+    release_tx.send(()).unwrap();
+}
+
+/// Every collection takes `delay`.
+struct SluggishSource {
+    delay: Duration,
+}
+
+impl MetricsSource for SluggishSource {
+    fn sample(&mut self, timestamp_ms: u128, dt: f32) -> MetricsSnapshot {
+        std::thread::sleep(self.delay);
+        empty_snapshot(timestamp_ms, dt)
+    }
+}
+
+#[tokio::test]
+async fn auto_tune_lengthens_interval_under_sustained_slow_collection() {
+    let interval = Duration::from_millis(20);
+    let agg = Aggregator::new(
+        AggregatorConfig::new(interval)
+            .with_auto_tune(Some(IntervalAutoTune::new(0.5, Duration::from_millis(80)))),
+    );
+    let health = agg.health();
+    let cancel = CancellationToken::new();
+    let source = SluggishSource {
+        delay: Duration::from_millis(45),
+    };
+    let handle = tokio::spawn(agg.run_with_source(source, cancel.clone()));
+
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while health.effective_interval() != Some(Duration::from_millis(80)) {
+        assert!(Instant::now() < deadline, "interval was not lengthened");
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    cancel.cancel();
+    handle.await.unwrap();
+    assert!(health.last_collection_duration() >= Duration::from_millis(45));
+
+    // `/api/config` and `server_info` report the tuned interval, not the
+    // configured one.
+    let (_interval_tx, interval_rx) = tokio::sync::watch::channel(interval);
+    let sampling = Sampling {
+        interval: Some(interval_rx.clone()),
+        ..Default::default()
+    };
+    assert_eq!(sampling.interval_ms(&health), Some(80));
+    let (stream_tx, _) = tokio::sync::broadcast::channel(1);
+    let info = MetricsRpcServer::new(Arc::new(MetricsBuffer::new(1)), stream_tx)
+        .with_interval(interval_rx)
+        .with_collector(health)
+        .server_info(tarpc::context::current())
+        .await;
+    assert_eq!(info.interval_ms, Some(80));
+}
+
+#[test]
+fn auto_tune_needs_consecutive_slow_collections() {
+    let interval = Duration::from_millis(100);
+    let mut tune = IntervalAutoTune::new(0.5, Duration::from_secs(1));
+    let slow = Duration::from_millis(60);
+    let fast = Duration::from_millis(10);
+
+    assert_eq!(tune.observe(slow, interval), None);
+    assert_eq!(tune.observe(slow, interval), None);
+    assert_eq!(tune.observe(fast, interval), None);
+    for _ in 1..AUTO_TUNE_SLOW_SAMPLES {
+        assert_eq!(tune.observe(slow, interval), None);
+    }
+    assert_eq!(
+        tune.observe(slow, interval),
+        Some(Duration::from_millis(200))
+    );
+    // Capped at the maximum, then left alone.
+    let mut capped = IntervalAutoTune::new(0.5, interval);
+    for _ in 0..AUTO_TUNE_SLOW_SAMPLES {
+        assert_eq!(capped.observe(slow, interval), None);
+    }
+}
+
+#[test]
+fn counter_rate_caps_implausible_spikes() {
+    use resource_monitor::aggregator::counter_rate;
+
+    // 1 MB over one second is fine under a 100 MB/s cap.
+    assert_eq!(
+        counter_rate("RX", 2_000_000, 1_000_000, 1.0, 0.0, Some(100e6)),
+        1_000_000.0
+    );
+    // 50 GB over 1 ms would be 50 TB/s: keep the previous rate instead.
+    assert_eq!(
+        counter_rate("RX", 50_000_000_000, 0, 0.001, 1234.0, Some(100e6)),
+        1234.0
+    );
+    // Without a cap the raw rate is reported.
+    assert_eq!(counter_rate("RX", 3000, 1000, 2.0, 0.0, None), 1000.0);
+    // A counter reset reports zero.
+    assert_eq!(counter_rate("TX", 10, 1000, 1.0, 500.0, Some(100e6)), 0.0);
+}
+
+#[test]
+fn publish_gate_sends_heartbeats_for_steady_series_and_every_change() {
+    let deltas: ChangeDeltas = "cpu=5,net=1000".parse().unwrap();
+    let with_cpu = |ts: u128, cpu: f32| {
+        let mut snap = empty_snapshot(ts, 1.0);
+        snap.cpu.total_usage_pct = cpu;
+        snap
+    };
+
+    let mut steady = PublishGate::new(deltas, 5_000);
+    let published: Vec<u128> = (0..12)
+        .map(|i| with_cpu(i * 1000, 20.0 + (i % 2) as f32))
+        .filter(|s| steady.admit(s))
+        .map(|s| s.timestamp_ms)
+        .collect();
+    assert_eq!(published, vec![0, 5_000, 10_000]);
+
+    let mut changing = PublishGate::new(deltas, 5_000);
+    let published = (0..6)
+        .map(|i| with_cpu(i * 1000, 10.0 * i as f32))
+        .filter(|s| changing.admit(s))
+        .count();
+    assert_eq!(published, 6);
+
+    let mut net = PublishGate::new(deltas, 60_000);
+    assert!(net.admit(&empty_snapshot(0, 1.0)));
+    let mut burst = empty_snapshot(1000, 1.0);
+    burst.network.tx_bytes_per_sec = 5_000.0;
+    assert!(net.admit(&burst));
+    let mut memory = empty_snapshot(2000, 1.0);
+    memory.network.tx_bytes_per_sec = 5_000.0;
+    memory.memory.total_bytes = 100;
+    memory.memory.used_bytes = 90;
+    assert!(!net.admit(&memory), "mem has no delta configured");
+}
+
+#[test]
+fn change_deltas_parse_and_reject_bad_input() {
+    let deltas: ChangeDeltas = " cpu=5, mem=2.5,disk=1 ,net=100000".parse().unwrap();
+    assert_eq!(deltas.cpu_pct, Some(5.0));
+    assert_eq!(deltas.mem_pct, Some(2.5));
+    assert_eq!(deltas.disk_pct, Some(1.0));
+    assert_eq!(deltas.net_bytes_per_sec, Some(100_000.0));
+
+    assert!("".parse::<ChangeDeltas>().is_err());
+    assert!("cpu".parse::<ChangeDeltas>().is_err());
+    assert!("gpu=1".parse::<ChangeDeltas>().is_err());
+    assert!("cpu=-1".parse::<ChangeDeltas>().is_err());
+}
+
+#[tokio::test]
+async fn labeled_aggregators_share_one_buffer() {
+    use resource_monitor::bus::register_storage_subscriber;
+    use resource_monitor::storage::MetricsBuffer;
+    use std::collections::BTreeSet;
+
+    let buffer = Arc::new(MetricsBuffer::new(256));
+    let _activity = register_storage_subscriber(buffer.clone());
+    let cancel = CancellationToken::new();
+    let config = || AggregatorConfig::new(Duration::from_millis(5)).with_warmup_samples(0);
+    let handles = [
+        spawn_labeled_aggregator(
+            "rack-a",
+            config(),
+            BaselineSource { sampled: false },
+            cancel.clone(),
+        ),
+        spawn_labeled_aggregator(
+            "rack-b",
+            config(),
+            SyntheticSource::new(1.0, 4),
+            cancel.clone(),
+        ),
+    ];
+
+    let sources = || -> BTreeSet<String> {
+        buffer
+            .history(None)
+            .into_iter()
+            .filter_map(|s| s.source)
+            .collect()
+    };
+    let deadline = Instant::now() + Duration::from_secs(2);
+    while sources().len() < 2 {
+        assert!(Instant::now() < deadline, "only saw {:?}", sources());
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    cancel.cancel();
+    for handle in handles {
+        handle.await.unwrap();
+    }
+
+    assert_eq!(
+        sources().into_iter().collect::<Vec<_>>(),
+        vec!["rack-a", "rack-b"]
+    );
+    let synthetic = buffer
+        .history(None)
+        .into_iter()
+        .find(|s| s.source.as_deref() == Some("rack-b"))
+        .unwrap();
+    assert_eq!(synthetic.cpu.per_core_usage_pct.len(), 4);
+    assert_eq!(
+        synthetic.cpu.busy_cores,
+        Some(busy_cores(&synthetic.cpu.per_core_usage_pct))
+    );
+    assert_eq!(
+        synthetic.to_rpc_format().source.as_deref(),
+        Some("rack-b"),
+        "the label reaches the RPC form"
+    );
+}
@@ -0,0 +1,194 @@
+use resource_monitor::alerts::{healthy_fraction, AckError, AlertTracker};
+use resource_monitor::config::{Threshold, Thresholds};
+use resource_monitor::db::MetricsDb;
+use resource_monitor::metrics::{
+    CpuMetrics, DiskMetrics, MemoryMetrics, MetricsSnapshot, NetworkMetrics,
+};
+use std::sync::Arc;
+use tempfile::tempdir;
+
+fn sample(ts: u128, cpu_pct: f32) -> MetricsSnapshot {
+    MetricsSnapshot {
+        timestamp_ms: ts,
+        sample_interval_ms: 1000.0,
+        cpu: CpuMetrics {
+            total_usage_pct: cpu_pct,
+            per_core_usage_pct: vec![cpu_pct],
+            load_avg_1: Some(0.1),
+            load_avg_5: Some(0.2),
+            load_avg_15: Some(0.3),
+            temperature_celsius: None,
+            breakdown: None,
+            per_socket_usage_pct: None,
+            per_core_changes: None,
+            busy_cores: None,
+        },
+        memory: MemoryMetrics {
+            total_bytes: 100,
+            used_bytes: 50,
+            available_bytes: 50,
+            swap_total_bytes: 0,
+            swap_used_bytes: 0,
+            swap_in_bytes_per_sec: None,
+            swap_out_bytes_per_sec: None,
+        },
+        network: NetworkMetrics {
+            rx_bytes_total: 0,
+            tx_bytes_total: 0,
+            rx_bytes_per_sec: 0.0,
+            tx_bytes_per_sec: 0.0,
+        },
+        disk: DiskMetrics {
+            total_bytes: 100,
+            available_bytes: 50,
+            used_pct: 50.0,
+            used_bytes: None,
+        },
+        battery: None,
+        gpu: None,
+        scheduler: None,
+        watched_process: None,
+        net_top_processes: None,
+        pressure_score: None,
+        source: None,
+        resumed: false,
+        tags: Default::default(),
+    }
+}
+
+#[test]
+fn fire_and_clear_cycle_is_recorded() {
+    let tracker = AlertTracker::default();
+    tracker.observe(&sample(1000, 50.0));
+    assert!(tracker.history().is_empty());
+
+    tracker.observe(&sample(2000, 92.0));
+    tracker.observe(&sample(3000, 97.0));
+    tracker.observe(&sample(4000, 40.0));
+
+    let history = tracker.history();
+    assert_eq!(history.len(), 1);
+    let alert = &history[0];
+    assert_eq!(alert.metric, "cpu_total");
+    assert_eq!(alert.threshold, 90.0);
+    assert_eq!(alert.fired_at_ms, 2000);
+    assert_eq!(alert.cleared_at_ms, Some(4000));
+    assert_eq!(alert.peak, 97.0);
+    assert!(!alert.acknowledged);
+}
+
+#[test]
+fn ack_marks_firing_alert_and_rejects_cleared() {
+    let tracker = AlertTracker::default();
+    tracker.observe(&sample(1000, 95.0));
+    let id = tracker.history()[0].id;
+
+    let acked = tracker.ack(id).unwrap();
+    assert!(acked.acknowledged);
+    assert!(acked.is_firing());
+    assert!(tracker.history()[0].acknowledged);
+
+    tracker.observe(&sample(2000, 10.0));
+    assert!(matches!(tracker.ack(id), Err(AckError::Cleared(_))));
+    assert!(matches!(tracker.ack(id + 100), Err(AckError::NotFound(_))));
+}
+
+#[test]
+fn history_is_bounded_newest_first() {
+    let tracker = AlertTracker::new(Thresholds::default(), 2);
+    for i in 0..3u128 {
+        tracker.observe(&sample(i * 2000, 95.0));
+        tracker.observe(&sample(i * 2000 + 1000, 10.0));
+    }
+
+    let history = tracker.history();
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0].fired_at_ms, 4000);
+    assert_eq!(history[1].fired_at_ms, 2000);
+}
+
+#[test]
+fn persisted_alerts_survive_restart() {
+    let dir = tempdir().unwrap();
+    let db = Arc::new(MetricsDb::new(&dir.path().join("test.db")).unwrap());
+
+    let tracker = AlertTracker::default().with_persistence(db.clone());
+    tracker.observe(&sample(1000, 95.0));
+    tracker.ack(1).unwrap();
+    tracker.observe(&sample(2000, 10.0));
+
+    let reloaded = AlertTracker::default().with_persistence(db);
+    let history = reloaded.history();
+    assert_eq!(history.len(), 1);
+    assert!(history[0].acknowledged);
+    assert_eq!(history[0].cleared_at_ms, Some(2000));
+
+    reloaded.observe(&sample(3000, 95.0));
+    assert_eq!(reloaded.history()[0].id, 2);
+}
+
+#[test]
+fn healthy_fraction_counts_samples_below_warn() {
+    let thresholds = Thresholds::default();
+    // Three of ten samples reach the 70% CPU warn level; memory stays at 50%.
+    let window: Vec<MetricsSnapshot> = [10.0, 20.0, 75.0, 30.0, 70.0, 40.0, 95.0, 50.0, 60.0, 69.9]
+        .iter()
+        .enumerate()
+        .map(|(i, cpu)| sample(1000 * (i as u128 + 1), *cpu))
+        .collect();
+
+    let fraction = healthy_fraction(&window, &thresholds).unwrap();
+    assert!((fraction - 0.7).abs() < 1e-9, "{fraction}");
+    assert_eq!(healthy_fraction(&[], &thresholds), None);
+}
+
+fn damped_cpu_tracker() -> AlertTracker {
+    let thresholds = Thresholds {
+        cpu: Threshold {
+            warn: Some(70.0),
+            crit: Some(90.0),
+            clear: Some(80.0),
+            min_duration_ms: Some(3000),
+        },
+        ..Default::default()
+    };
+    AlertTracker::new(thresholds, 16)
+}
+
+#[test]
+fn flapping_series_does_not_fire_with_min_duration() {
+    let tracker = damped_cpu_tracker();
+    for (i, cpu) in [91.0, 89.0, 92.0, 85.0, 95.0, 88.0, 91.0, 70.0]
+        .into_iter()
+        .enumerate()
+    {
+        tracker.observe(&sample(i as u128 * 1000, cpu));
+    }
+    assert!(tracker.history().is_empty());
+}
+
+#[test]
+fn sustained_breach_fires_and_clears_only_after_min_duration_below_clear() {
+    let tracker = damped_cpu_tracker();
+    for (ts, cpu) in [(0, 91.0), (1000, 93.0), (2000, 96.0)] {
+        tracker.observe(&sample(ts, cpu));
+    }
+    assert!(tracker.history().is_empty());
+    tracker.observe(&sample(3000, 92.0));
+    let alert = &tracker.history()[0];
+    assert_eq!(alert.fired_at_ms, 3000);
+    assert_eq!(alert.peak, 96.0);
+
+    // Dips below crit but above clear, then briefly below clear: still firing.
+    for (ts, cpu) in [(4000, 85.0), (5000, 75.0), (6000, 82.0), (7000, 70.0)] {
+        tracker.observe(&sample(ts, cpu));
+    }
+    assert!(tracker.history()[0].is_firing());
+
+    for (ts, cpu) in [(8000, 60.0), (9000, 65.0), (10000, 50.0)] {
+        tracker.observe(&sample(ts, cpu));
+    }
+    let history = tracker.history();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].cleared_at_ms, Some(10000));
+}
@@ -1,9 +1,11 @@
 use resource_monitor::api::{router, AppState};
+use resource_monitor::config::{ResourceCriticality, Threshold, Thresholds};
 use resource_monitor::db::MetricsDb;
 use resource_monitor::metrics::{
     CpuMetrics, DiskMetrics, MemoryMetrics, MetricsSnapshot, NetworkMetrics,
 };
-use resource_monitor::storage::MetricsBuffer;
+use resource_monitor::sqlite_store::SqliteStore;
+use resource_monitor::storage::{MetricsBuffer, SnapshotStore};
 use std::sync::Arc;
 use tempfile::tempdir;
 use tokio_util::sync::CancellationToken;
@@ -20,8 +22,21 @@ async fn history_initially_empty() {
     let app = router(AppState {
         buffer,
         db,
+        store: None,
         stream_tx,
         shutdown: CancellationToken::new(),
+        collector: Default::default(),
+        thresholds: Default::default(),
+        alerts: Default::default(),
+        limits: Default::default(),
+        logs: Default::default(),
+        api_token: None,
+        net_scale: Default::default(),
+        requests: Default::default(),
+        initial_window_ms: 0,
+        metrics_prefix: None,
+        sampling: Default::default(),
+        criticality: Default::default(),
     });
 
     let response = app
@@ -53,8 +68,21 @@ async fn health_ok() {
     let app = router(AppState {
         buffer,
         db,
+        store: None,
         stream_tx,
         shutdown: CancellationToken::new(),
+        collector: Default::default(),
+        thresholds: Default::default(),
+        alerts: Default::default(),
+        limits: Default::default(),
+        logs: Default::default(),
+        api_token: None,
+        net_scale: Default::default(),
+        requests: Default::default(),
+        initial_window_ms: 0,
+        metrics_prefix: None,
+        sampling: Default::default(),
+        criticality: Default::default(),
     });
 
     let response = app
@@ -84,8 +112,21 @@ async fn range_filters_by_timestamps() {
     let app = router(AppState {
         buffer,
         db,
+        store: None,
         stream_tx,
         shutdown: CancellationToken::new(),
+        collector: Default::default(),
+        thresholds: Default::default(),
+        alerts: Default::default(),
+        limits: Default::default(),
+        logs: Default::default(),
+        api_token: None,
+        net_scale: Default::default(),
+        requests: Default::default(),
+        initial_window_ms: 0,
+        metrics_prefix: None,
+        sampling: Default::default(),
+        criticality: Default::default(),
     });
 
     let response = app
@@ -107,6 +148,74 @@ async fn range_filters_by_timestamps() {
     assert_eq!(arr[0]["timestamp_ms"].as_u64().unwrap(), 2000);
 }
 
+#[tokio::test]
+async fn history_beyond_the_buffer_comes_from_the_sqlite_store() {
+    let dir = tempdir().unwrap();
+    let db_path = dir.path().join("test.db");
+    let db = Arc::new(MetricsDb::new(&db_path).unwrap());
+    // The archive table disagrees, so only the store can produce these.
+    db.insert(&sample_snapshot(9000)).unwrap();
+    let store = SqliteStore::open_in_memory().unwrap();
+    for ts in [1000, 2000, 3000] {
+        store.push(sample_snapshot(ts));
+    }
+
+    let buffer = Arc::new(MetricsBuffer::new(10));
+    let (stream_tx, _stream_rx) = tokio::sync::broadcast::channel(8);
+    let app = router(AppState {
+        buffer,
+        db,
+        store: Some(Arc::new(store)),
+        stream_tx,
+        shutdown: CancellationToken::new(),
+        collector: Default::default(),
+        thresholds: Default::default(),
+        alerts: Default::default(),
+        limits: Default::default(),
+        logs: Default::default(),
+        api_token: None,
+        net_scale: Default::default(),
+        requests: Default::default(),
+        initial_window_ms: 0,
+        metrics_prefix: None,
+        sampling: Default::default(),
+        criticality: Default::default(),
+    });
+
+    for (uri, expected) in [
+        ("/api/history?limit=2", vec![3000, 2000]),
+        ("/api/history?limit=2&from_start=1", vec![2000, 1000]),
+        ("/api/history?window_ms=1000", vec![3000, 2000]),
+        ("/api/range?from_ts=1500&to_ts=3000", vec![3000, 2000]),
+        ("/api/metrics", vec![3000]),
+    ] {
+        let response = app
+            .clone()
+            .oneshot(
+                axum::http::Request::builder()
+                    .uri(uri)
+                    .body(axum::body::Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200, "{uri}");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let items = match json.as_array() {
+            Some(arr) => arr.clone(),
+            None => vec![json],
+        };
+        let timestamps: Vec<u64> = items
+            .iter()
+            .map(|s| s["timestamp_ms"].as_u64().unwrap())
+            .collect();
+        assert_eq!(timestamps, expected, "{uri}");
+    }
+}
+
 #[tokio::test]
 async fn latest_returns_last_snapshot() {
     let dir = tempdir().unwrap();
@@ -121,8 +230,21 @@ async fn latest_returns_last_snapshot() {
     let app = router(AppState {
         buffer,
         db,
+        store: None,
         stream_tx,
         shutdown: CancellationToken::new(),
+        collector: Default::default(),
+        thresholds: Default::default(),
+        alerts: Default::default(),
+        limits: Default::default(),
+        logs: Default::default(),
+        api_token: None,
+        net_scale: Default::default(),
+        requests: Default::default(),
+        initial_window_ms: 0,
+        metrics_prefix: None,
+        sampling: Default::default(),
+        criticality: Default::default(),
     });
 
     let response = app
@@ -153,8 +275,21 @@ async fn stream_is_event_stream() {
     let app = router(AppState {
         buffer,
         db,
+        store: None,
         stream_tx,
         shutdown: CancellationToken::new(),
+        collector: Default::default(),
+        thresholds: Default::default(),
+        alerts: Default::default(),
+        limits: Default::default(),
+        logs: Default::default(),
+        api_token: None,
+        net_scale: Default::default(),
+        requests: Default::default(),
+        initial_window_ms: 0,
+        metrics_prefix: None,
+        sampling: Default::default(),
+        criticality: Default::default(),
     });
 
     let response = app
@@ -191,8 +326,21 @@ async fn range_with_limit() {
     let app = router(AppState {
         buffer,
         db,
+        store: None,
         stream_tx,
         shutdown: CancellationToken::new(),
+        collector: Default::default(),
+        thresholds: Default::default(),
+        alerts: Default::default(),
+        limits: Default::default(),
+        logs: Default::default(),
+        api_token: None,
+        net_scale: Default::default(),
+        requests: Default::default(),
+        initial_window_ms: 0,
+        metrics_prefix: None,
+        sampling: Default::default(),
+        criticality: Default::default(),
     });
 
     let response = app
@@ -230,8 +378,21 @@ async fn history_with_since() {
     let app = router(AppState {
         buffer,
         db,
+        store: None,
         stream_tx,
         shutdown: CancellationToken::new(),
+        collector: Default::default(),
+        thresholds: Default::default(),
+        alerts: Default::default(),
+        limits: Default::default(),
+        logs: Default::default(),
+        api_token: None,
+        net_scale: Default::default(),
+        requests: Default::default(),
+        initial_window_ms: 0,
+        metrics_prefix: None,
+        sampling: Default::default(),
+        criticality: Default::default(),
     });
 
     let response = app
@@ -267,8 +428,21 @@ async fn latest_no_data_returns_404() {
     let app = router(AppState {
         buffer,
         db,
+        store: None,
         stream_tx,
         shutdown: CancellationToken::new(),
+        collector: Default::default(),
+        thresholds: Default::default(),
+        alerts: Default::default(),
+        limits: Default::default(),
+        logs: Default::default(),
+        api_token: None,
+        net_scale: Default::default(),
+        requests: Default::default(),
+        initial_window_ms: 0,
+        metrics_prefix: None,
+        sampling: Default::default(),
+        criticality: Default::default(),
     });
 
     let response = app
@@ -283,6 +457,83 @@ async fn latest_no_data_returns_404() {
     assert_eq!(response.status(), 404);
 }
 
+#[tokio::test]
+async fn metrics_without_data_returns_typed_error() {
+    let dir = tempdir().unwrap();
+    let db = Arc::new(MetricsDb::new(&dir.path().join("test.db")).unwrap());
+    let buffer = Arc::new(MetricsBuffer::new(10));
+    let (stream_tx, _stream_rx) = tokio::sync::broadcast::channel(8);
+    let app = router(AppState {
+        buffer,
+        db,
+        store: None,
+        stream_tx,
+        shutdown: CancellationToken::new(),
+        collector: Default::default(),
+        thresholds: Default::default(),
+        alerts: Default::default(),
+        limits: Default::default(),
+        logs: Default::default(),
+        api_token: None,
+        net_scale: Default::default(),
+        requests: Default::default(),
+        initial_window_ms: 0,
+        metrics_prefix: None,
+        sampling: Default::default(),
+        criticality: Default::default(),
+    });
+
+    let fetch = |uri: &'static str| {
+        let app = app.clone();
+        async move {
+            let response = app
+                .oneshot(
+                    axum::http::Request::builder()
+                        .uri(uri)
+                        .body(axum::body::Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            let status = response.status();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let error: resource_monitor::metrics::ErrorResponse =
+                serde_json::from_slice(&body).unwrap();
+            (status, error)
+        }
+    };
+
+    let (status, error) = fetch("/api/metrics").await;
+    assert_eq!(status, 404);
+    assert_eq!(error.code, resource_monitor::metrics::ErrorCode::NoData);
+    assert_eq!(error.message, "no data yet");
+    assert!(error.details.is_none());
+
+    let (status, error) = fetch("/api/histogram?metric=bogus").await;
+    assert_eq!(status, 400);
+    assert_eq!(
+        error.code,
+        resource_monitor::metrics::ErrorCode::InvalidParameter
+    );
+    assert_eq!(error.details.unwrap()["expected"][0], "cpu");
+
+    let (status, error) = fetch("/api/range?from_ts=soon&to_ts=1").await;
+    assert_eq!(status, 400);
+    assert_eq!(
+        error.code,
+        resource_monitor::metrics::ErrorCode::InvalidParameter
+    );
+    assert!(
+        error
+            .message
+            .starts_with("Failed to deserialize query string"),
+        "{}",
+        error.message
+    );
+}
+
 #[tokio::test]
 async fn latest_prefers_buffer_over_db() {
     let dir = tempdir().unwrap();
@@ -298,8 +549,21 @@ async fn latest_prefers_buffer_over_db() {
     let app = router(AppState {
         buffer,
         db,
+        store: None,
         stream_tx,
         shutdown: CancellationToken::new(),
+        collector: Default::default(),
+        thresholds: Default::default(),
+        alerts: Default::default(),
+        limits: Default::default(),
+        logs: Default::default(),
+        api_token: None,
+        net_scale: Default::default(),
+        requests: Default::default(),
+        initial_window_ms: 0,
+        metrics_prefix: None,
+        sampling: Default::default(),
+        criticality: Default::default(),
     });
 
     let response = app
@@ -329,8 +593,21 @@ async fn db_stats_empty() {
     let app = router(AppState {
         buffer,
         db,
+        store: None,
         stream_tx,
         shutdown: CancellationToken::new(),
+        collector: Default::default(),
+        thresholds: Default::default(),
+        alerts: Default::default(),
+        limits: Default::default(),
+        logs: Default::default(),
+        api_token: None,
+        net_scale: Default::default(),
+        requests: Default::default(),
+        initial_window_ms: 0,
+        metrics_prefix: None,
+        sampling: Default::default(),
+        criticality: Default::default(),
     });
 
     let response = app
@@ -367,8 +644,21 @@ async fn db_stats_with_data() {
     let app = router(AppState {
         buffer,
         db,
+        store: None,
         stream_tx,
         shutdown: CancellationToken::new(),
+        collector: Default::default(),
+        thresholds: Default::default(),
+        alerts: Default::default(),
+        limits: Default::default(),
+        logs: Default::default(),
+        api_token: None,
+        net_scale: Default::default(),
+        requests: Default::default(),
+        initial_window_ms: 0,
+        metrics_prefix: None,
+        sampling: Default::default(),
+        criticality: Default::default(),
     });
 
     let response = app
@@ -401,8 +691,21 @@ async fn index_returns_html() {
     let app = router(AppState {
         buffer,
         db,
+        store: None,
         stream_tx,
         shutdown: CancellationToken::new(),
+        collector: Default::default(),
+        thresholds: Default::default(),
+        alerts: Default::default(),
+        limits: Default::default(),
+        logs: Default::default(),
+        api_token: None,
+        net_scale: Default::default(),
+        requests: Default::default(),
+        initial_window_ms: 0,
+        metrics_prefix: None,
+        sampling: Default::default(),
+        criticality: Default::default(),
     });
 
     let response = app
@@ -428,6 +731,60 @@ async fn index_returns_html() {
     assert!(html.contains("Resource Monitor"));
 }
 
+#[tokio::test]
+async fn index_ignores_invalid_view_params() {
+    let dir = tempdir().unwrap();
+    let db = Arc::new(MetricsDb::new(&dir.path().join("test.db")).unwrap());
+    let (stream_tx, _stream_rx) = tokio::sync::broadcast::channel(8);
+    let app = router(AppState {
+        buffer: Arc::new(MetricsBuffer::new(10)),
+        db,
+        store: None,
+        stream_tx,
+        shutdown: CancellationToken::new(),
+        collector: Default::default(),
+        thresholds: Default::default(),
+        alerts: Default::default(),
+        limits: Default::default(),
+        logs: Default::default(),
+        api_token: None,
+        net_scale: Default::default(),
+        requests: Default::default(),
+        initial_window_ms: 0,
+        metrics_prefix: None,
+        sampling: Default::default(),
+        criticality: Default::default(),
+    });
+
+    let fetch = |uri: &'static str| {
+        let app = app.clone();
+        async move {
+            let response = app
+                .oneshot(
+                    axum::http::Request::builder()
+                        .uri(uri)
+                        .body(axum::body::Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), 200);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            String::from_utf8_lossy(&body).into_owned()
+        }
+    };
+
+    let html = fetch("/?window=soon&end=-5&live=maybe").await;
+    assert!(html.contains(
+        r#"<script id="initial-view" type="application/json">{"window_ms":null,"end_ms":null,"live":null}</script>"#
+    ));
+
+    let html = fetch("/?window=300000&end=1700000000000").await;
+    assert!(html.contains(r#"{"window_ms":300000,"end_ms":1700000000000,"live":false}"#));
+}
+
 #[tokio::test]
 async fn health_response_has_ok_status() {
     let dir = tempdir().unwrap();
@@ -438,8 +795,21 @@ async fn health_response_has_ok_status() {
     let app = router(AppState {
         buffer,
         db,
+        store: None,
         stream_tx,
         shutdown: CancellationToken::new(),
+        collector: Default::default(),
+        thresholds: Default::default(),
+        alerts: Default::default(),
+        limits: Default::default(),
+        logs: Default::default(),
+        api_token: None,
+        net_scale: Default::default(),
+        requests: Default::default(),
+        initial_window_ms: 0,
+        metrics_prefix: None,
+        sampling: Default::default(),
+        criticality: Default::default(),
     });
 
     let response = app
@@ -456,6 +826,126 @@ async fn health_response_has_ok_status() {
         .unwrap();
     let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
     assert_eq!(json["status"].as_str().unwrap(), "ok");
+    assert_eq!(json["collector_panics"].as_u64().unwrap(), 0);
+    assert_eq!(json["estimated_buffer_bytes"].as_u64().unwrap(), 0);
+    assert!(json.get("last_sample_age_ms").is_none());
+}
+
+#[tokio::test]
+async fn health_reports_last_sample_age_once_data_exists() {
+    let dir = tempdir().unwrap();
+    let db = Arc::new(MetricsDb::new(&dir.path().join("test.db")).unwrap());
+    let buffer = Arc::new(MetricsBuffer::new(10));
+    let taken = resource_monitor::metrics::now_timestamp_ms() - 3000;
+    buffer.push(sample_snapshot(taken));
+    let (stream_tx, _stream_rx) = tokio::sync::broadcast::channel(8);
+    let app = router(AppState {
+        buffer,
+        db,
+        store: None,
+        stream_tx,
+        shutdown: CancellationToken::new(),
+        collector: Default::default(),
+        thresholds: Default::default(),
+        alerts: Default::default(),
+        limits: Default::default(),
+        logs: Default::default(),
+        api_token: None,
+        net_scale: Default::default(),
+        requests: Default::default(),
+        initial_window_ms: 0,
+        metrics_prefix: None,
+        sampling: Default::default(),
+        criticality: Default::default(),
+    });
+
+    let response = app
+        .oneshot(
+            axum::http::Request::builder()
+                .uri("/api/health")
+                .body(axum::body::Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let age = json["last_sample_age_ms"].as_u64().unwrap();
+    assert!((3000..60_000).contains(&age), "{age}");
+}
+
+async fn health_with_criticality(disk_used_pct: f32, swap_used_bytes: u64) -> serde_json::Value {
+    let dir = tempdir().unwrap();
+    let db = Arc::new(MetricsDb::new(&dir.path().join("test.db")).unwrap());
+    let buffer = Arc::new(MetricsBuffer::new(10));
+    let mut snapshot = sample_snapshot(resource_monitor::metrics::now_timestamp_ms());
+    snapshot.disk.used_pct = disk_used_pct;
+    snapshot.memory.swap_used_bytes = swap_used_bytes;
+    buffer.push(snapshot);
+    let (stream_tx, _stream_rx) = tokio::sync::broadcast::channel(8);
+    let app = router(AppState {
+        buffer,
+        db,
+        store: None,
+        stream_tx,
+        shutdown: CancellationToken::new(),
+        collector: Default::default(),
+        thresholds: Default::default(),
+        alerts: Default::default(),
+        limits: Default::default(),
+        logs: Default::default(),
+        api_token: None,
+        net_scale: Default::default(),
+        requests: Default::default(),
+        initial_window_ms: 0,
+        metrics_prefix: None,
+        sampling: Default::default(),
+        criticality: ResourceCriticality {
+            disk_used_pct: Some(90.0),
+            swap_used_pct: Some(80.0),
+        },
+    });
+
+    let response = app
+        .oneshot(
+            axum::http::Request::builder()
+                .uri("/api/health")
+                .body(axum::body::Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200, "liveness is unaffected");
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    serde_json::from_slice(&body).unwrap()
+}
+
+#[tokio::test]
+async fn health_degrades_when_disk_or_swap_is_critical() {
+    // 4000 of 4096 bytes of swap is about 97.7%.
+    let json = health_with_criticality(95.0, 4000).await;
+    assert_eq!(json["status"], "degraded");
+    assert_eq!(json["degraded"], true);
+    let reasons = json["reasons"].as_array().unwrap();
+    assert_eq!(reasons.len(), 2);
+    assert!(reasons[0].as_str().unwrap().starts_with("disk used 95.0%"));
+    assert!(reasons[1].as_str().unwrap().starts_with("swap used 97.7%"));
+
+    let json = health_with_criticality(50.0, 4000).await;
+    assert_eq!(json["degraded"], true);
+    assert_eq!(json["reasons"].as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn health_stays_ok_below_disk_and_swap_criticality() {
+    let json = health_with_criticality(89.9, 1024).await;
+    assert_eq!(json["status"], "ok");
+    assert_eq!(json["degraded"], false);
+    assert!(json.get("reasons").is_none());
 }
 
 #[tokio::test]
@@ -470,8 +960,21 @@ async fn range_empty_result_for_future_range() {
     let app = router(AppState {
         buffer,
         db,
+        store: None,
         stream_tx,
         shutdown: CancellationToken::new(),
+        collector: Default::default(),
+        thresholds: Default::default(),
+        alerts: Default::default(),
+        limits: Default::default(),
+        logs: Default::default(),
+        api_token: None,
+        net_scale: Default::default(),
+        requests: Default::default(),
+        initial_window_ms: 0,
+        metrics_prefix: None,
+        sampling: Default::default(),
+        criticality: Default::default(),
     });
 
     let response = app
@@ -503,8 +1006,21 @@ async fn latest_snapshot_has_valid_rpc_structure() {
     let app = router(AppState {
         buffer,
         db,
+        store: None,
         stream_tx,
         shutdown: CancellationToken::new(),
+        collector: Default::default(),
+        thresholds: Default::default(),
+        alerts: Default::default(),
+        limits: Default::default(),
+        logs: Default::default(),
+        api_token: None,
+        net_scale: Default::default(),
+        requests: Default::default(),
+        initial_window_ms: 0,
+        metrics_prefix: None,
+        sampling: Default::default(),
+        criticality: Default::default(),
     });
 
     let response = app
@@ -530,13 +1046,18 @@ async fn latest_snapshot_has_valid_rpc_structure() {
 fn sample_snapshot(ts: u128) -> MetricsSnapshot {
     MetricsSnapshot {
         timestamp_ms: ts,
+        sample_interval_ms: 1000.0,
         cpu: CpuMetrics {
             total_usage_pct: 10.0,
             per_core_usage_pct: vec![10.0, 20.0],
-            load_avg_1: 0.1,
-            load_avg_5: 0.2,
-            load_avg_15: 0.3,
+            load_avg_1: Some(0.1),
+            load_avg_5: Some(0.2),
+            load_avg_15: Some(0.3),
             temperature_celsius: Some(50.0),
+            breakdown: None,
+            per_socket_usage_pct: None,
+            per_core_changes: None,
+            busy_cores: None,
         },
         memory: MemoryMetrics {
             total_bytes: 100,
@@ -544,6 +1065,8 @@ fn sample_snapshot(ts: u128) -> MetricsSnapshot {
             available_bytes: 50,
             swap_total_bytes: 4096,
             swap_used_bytes: 1024,
+            swap_in_bytes_per_sec: None,
+            swap_out_bytes_per_sec: None,
         },
         network: NetworkMetrics {
             rx_bytes_total: 1000,
@@ -555,8 +1078,2164 @@ fn sample_snapshot(ts: u128) -> MetricsSnapshot {
             total_bytes: 500_000_000_000,
             available_bytes: 200_000_000_000,
             used_pct: 60.0,
+            used_bytes: None,
         },
         battery: None,
         gpu: None,
+        scheduler: None,
+        watched_process: None,
+        net_top_processes: None,
+        pressure_score: None,
+        source: None,
+        resumed: false,
+        tags: Default::default(),
+    }
+}
+
+#[tokio::test]
+async fn histogram_endpoint_bins_and_handles_empty() {
+    let dir = tempdir().unwrap();
+    let db_path = dir.path().join("test.db");
+    let db = Arc::new(MetricsDb::new(&db_path).unwrap());
+    let buffer = Arc::new(MetricsBuffer::new(10));
+    buffer.push(sample_snapshot(1000));
+    buffer.push(sample_snapshot(2000));
+
+    let (stream_tx, _stream_rx) = tokio::sync::broadcast::channel(8);
+    let app = router(AppState {
+        buffer,
+        db,
+        store: None,
+        stream_tx,
+        shutdown: CancellationToken::new(),
+        collector: Default::default(),
+        thresholds: Default::default(),
+        alerts: Default::default(),
+        limits: Default::default(),
+        logs: Default::default(),
+        api_token: None,
+        net_scale: Default::default(),
+        requests: Default::default(),
+        initial_window_ms: 0,
+        metrics_prefix: None,
+        sampling: Default::default(),
+        criticality: Default::default(),
+    });
+
+    let response = app
+        .clone()
+        .oneshot(
+            axum::http::Request::builder()
+                .uri("/api/histogram?metric=cpu&bins=10")
+                .body(axum::body::Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["metric"], "cpu");
+    assert_eq!(json["edges"].as_array().unwrap().len(), 11);
+    assert_eq!(json["counts"][1].as_u64().unwrap(), 2);
+    assert_eq!(json["total"].as_u64().unwrap(), 2);
+
+    let response = app
+        .clone()
+        .oneshot(
+            axum::http::Request::builder()
+                .uri("/api/histogram?metric=cpu&since_ms=5000")
+                .body(axum::body::Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+
+    let response = app
+        .oneshot(
+            axum::http::Request::builder()
+                .uri("/api/histogram?metric=bogus")
+                .body(axum::body::Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+}
+
+#[tokio::test]
+async fn cpu_heatmap_endpoint_returns_cores_by_columns() {
+    let dir = tempdir().unwrap();
+    let db = Arc::new(MetricsDb::new(&dir.path().join("test.db")).unwrap());
+    let buffer = Arc::new(MetricsBuffer::new(100));
+    for i in 0..50u128 {
+        let mut s = sample_snapshot(1000 + i * 100);
+        s.cpu.per_core_usage_pct = vec![i as f32, 50.0, 100.0 - i as f32];
+        buffer.push(s);
+    }
+
+    let (stream_tx, _stream_rx) = tokio::sync::broadcast::channel(8);
+    let app = router(AppState {
+        buffer,
+        db,
+        store: None,
+        stream_tx,
+        shutdown: CancellationToken::new(),
+        collector: Default::default(),
+        thresholds: Default::default(),
+        alerts: Default::default(),
+        limits: Default::default(),
+        logs: Default::default(),
+        api_token: None,
+        net_scale: Default::default(),
+        requests: Default::default(),
+        initial_window_ms: 0,
+        metrics_prefix: None,
+        sampling: Default::default(),
+        criticality: Default::default(),
+    });
+
+    let response = app
+        .clone()
+        .oneshot(
+            axum::http::Request::builder()
+                .uri("/api/heatmap/cpu?cols=10&since_ms=1000&until_ms=11000")
+                .body(axum::body::Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let cores = json["cores"].as_array().unwrap();
+    assert_eq!(cores.len(), 3);
+    assert!(cores.iter().all(|c| c.as_array().unwrap().len() == 10));
+    assert_eq!(json["bucket_ms"], 1000.0);
+    assert_eq!(cores[0][0], 4.5);
+    assert_eq!(cores[1][4], 50.0);
+    // Nothing was sampled after 5900.
+    assert!(cores[2][5].is_null());
+
+    let response = app
+        .oneshot(
+            axum::http::Request::builder()
+                .uri("/api/heatmap/cpu?cols=0")
+                .body(axum::body::Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+}
+
+#[tokio::test]
+async fn network_peaks_endpoint_reports_rx_and_tx_separately() {
+    let dir = tempdir().unwrap();
+    let db = Arc::new(MetricsDb::new(&dir.path().join("test.db")).unwrap());
+    let buffer = Arc::new(MetricsBuffer::new(10));
+    for (ts, rx, tx) in [
+        (1000, 5_000_000.0, 10.0),
+        (2000, 100.0, 40.0),
+        (3000, 0.0, 25.0),
+    ] {
+        let mut snap = sample_snapshot(ts);
+        snap.network.rx_bytes_per_sec = rx;
+        snap.network.tx_bytes_per_sec = tx;
+        buffer.push(snap);
     }
+
+    let (stream_tx, _stream_rx) = tokio::sync::broadcast::channel(8);
+    let app = router(AppState {
+        buffer,
+        db,
+        store: None,
+        stream_tx,
+        shutdown: CancellationToken::new(),
+        collector: Default::default(),
+        thresholds: Default::default(),
+        alerts: Default::default(),
+        limits: Default::default(),
+        logs: Default::default(),
+        api_token: None,
+        net_scale: Default::default(),
+        requests: Default::default(),
+        initial_window_ms: 0,
+        metrics_prefix: None,
+        sampling: Default::default(),
+        criticality: Default::default(),
+    });
+
+    let get = |uri: &'static str| {
+        app.clone().oneshot(
+            axum::http::Request::builder()
+                .uri(uri)
+                .body(axum::body::Body::empty())
+                .unwrap(),
+        )
+    };
+
+    let response = get("/api/network/peaks").await.unwrap();
+    assert_eq!(response.status(), 200);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["rx_max_bytes_per_sec"].as_f64().unwrap(), 5_000_000.0);
+    assert_eq!(json["tx_max_bytes_per_sec"].as_f64().unwrap(), 40.0);
+    assert_eq!(json["samples"].as_u64().unwrap(), 3);
+
+    let response = get("/api/network/peaks?since_ms=2000").await.unwrap();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["rx_max_bytes_per_sec"].as_f64().unwrap(), 100.0);
+    assert_eq!(json["tx_max_bytes_per_sec"].as_f64().unwrap(), 40.0);
+
+    let response = get("/api/network/peaks?since_ms=9000").await.unwrap();
+    assert_eq!(response.status(), 404);
+}
+
+#[tokio::test]
+async fn metrics_endpoint_honors_if_none_match() {
+    let dir = tempdir().unwrap();
+    let db = Arc::new(MetricsDb::new(&dir.path().join("test.db")).unwrap());
+    let buffer = Arc::new(MetricsBuffer::new(10));
+    buffer.push(sample_snapshot(1000));
+
+    let (stream_tx, _stream_rx) = tokio::sync::broadcast::channel(8);
+    let app = router(AppState {
+        buffer: buffer.clone(),
+        db,
+        store: None,
+        stream_tx,
+        shutdown: CancellationToken::new(),
+        collector: Default::default(),
+        thresholds: Default::default(),
+        alerts: Default::default(),
+        limits: Default::default(),
+        logs: Default::default(),
+        api_token: None,
+        net_scale: Default::default(),
+        requests: Default::default(),
+        initial_window_ms: 0,
+        metrics_prefix: None,
+        sampling: Default::default(),
+        criticality: Default::default(),
+    });
+
+    let get = |etag: Option<String>| {
+        let mut request = axum::http::Request::builder().uri("/api/metrics");
+        if let Some(etag) = etag {
+            request = request.header("if-none-match", etag);
+        }
+        app.clone()
+            .oneshot(request.body(axum::body::Body::empty()).unwrap())
+    };
+
+    let response = get(None).await.unwrap();
+    assert_eq!(response.status(), 200);
+    let etag = response.headers()["etag"].to_str().unwrap().to_string();
+    assert_eq!(etag, "\"1000\"");
+
+    let response = get(Some(etag.clone())).await.unwrap();
+    assert_eq!(response.status(), 304);
+    assert_eq!(response.headers()["etag"], etag.as_str());
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    assert!(body.is_empty());
+
+    // The same snapshot in other units is another representation.
+    let response = app
+        .clone()
+        .oneshot(
+            axum::http::Request::builder()
+                .uri("/api/metrics?net_units=bits&byte_units=si&decimals=1&pretty=1")
+                .header("if-none-match", etag.clone())
+                .body(axum::body::Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(
+        response.headers()["etag"],
+        "\"1000-net-bits-bytes-si-decimals-1-pretty\""
+    );
+
+    buffer.push(sample_snapshot(2000));
+    let response = get(Some(etag)).await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["etag"], "\"2000\"");
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["timestamp_ms"].as_u64().unwrap(), 2000);
+}
+
+#[tokio::test]
+async fn ws_frames_carry_timestamp_ms() {
+    use futures::StreamExt;
+
+    let dir = tempdir().unwrap();
+    let db_path = dir.path().join("test.db");
+    let db = Arc::new(MetricsDb::new(&db_path).unwrap());
+    let buffer = Arc::new(MetricsBuffer::new(10));
+    let (stream_tx, _stream_rx) = tokio::sync::broadcast::channel(8);
+    let shutdown = CancellationToken::new();
+    let app = router(AppState {
+        buffer,
+        db,
+        store: None,
+        stream_tx: stream_tx.clone(),
+        shutdown: shutdown.clone(),
+        collector: Default::default(),
+        thresholds: Default::default(),
+        alerts: Default::default(),
+        limits: Default::default(),
+        logs: Default::default(),
+        api_token: None,
+        net_scale: Default::default(),
+        requests: Default::default(),
+        initial_window_ms: 0,
+        metrics_prefix: None,
+        sampling: Default::default(),
+        criticality: Default::default(),
+    });
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let _ = axum::serve(listener, app).await;
+    });
+
+    let (mut socket, _) = tokio_tungstenite::connect_async(format!("ws://{addr}/api/ws"))
+        .await
+        .unwrap();
+
+    // The handler subscribes after the upgrade completes, so keep publishing.
+    let publisher = tokio::spawn(async move {
+        loop {
+            let _ = stream_tx.send(sample_snapshot(7000).to_rpc_format());
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+    });
+
+    let frame = tokio::time::timeout(std::time::Duration::from_secs(2), socket.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    publisher.abort();
+    shutdown.cancel();
+
+    let text = frame.into_text().unwrap();
+    let json: serde_json::Value = serde_json::from_str(&text).unwrap();
+    assert_eq!(json["timestamp_ms"].as_u64().unwrap(), 7000);
+    assert!(json["data"].is_array());
+}
+
+#[tokio::test]
+async fn history_pagination_returns_cursor() {
+    let dir = tempdir().unwrap();
+    let db_path = dir.path().join("test.db");
+    let db = Arc::new(MetricsDb::new(&db_path).unwrap());
+    let buffer = Arc::new(MetricsBuffer::new(10));
+    for ts in [1000, 2000, 3000] {
+        buffer.push(sample_snapshot(ts));
+    }
+    let (stream_tx, _stream_rx) = tokio::sync::broadcast::channel(8);
+    let app = router(AppState {
+        buffer,
+        db,
+        store: None,
+        stream_tx,
+        shutdown: CancellationToken::new(),
+        collector: Default::default(),
+        thresholds: Default::default(),
+        alerts: Default::default(),
+        limits: Default::default(),
+        logs: Default::default(),
+        api_token: None,
+        net_scale: Default::default(),
+        requests: Default::default(),
+        initial_window_ms: 0,
+        metrics_prefix: None,
+        sampling: Default::default(),
+        criticality: Default::default(),
+    });
+
+    let response = app
+        .clone()
+        .oneshot(
+            axum::http::Request::builder()
+                .uri("/api/history?page_size=2")
+                .body(axum::body::Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["items"].as_array().unwrap().len(), 2);
+    assert_eq!(json["next_cursor"].as_u64().unwrap(), 2000);
+
+    let response = app
+        .oneshot(
+            axum::http::Request::builder()
+                .uri("/api/history?after_ms=2000&page_size=2")
+                .body(axum::body::Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["items"][0]["timestamp_ms"].as_u64().unwrap(), 3000);
+    assert!(json["next_cursor"].is_null());
+}
+
+#[tokio::test]
+async fn grafana_query_returns_value_timestamp_pairs() {
+    let dir = tempdir().unwrap();
+    let db_path = dir.path().join("test.db");
+    let db = Arc::new(MetricsDb::new(&db_path).unwrap());
+    let buffer = Arc::new(MetricsBuffer::new(10));
+    for ts in [1000, 2000, 5000] {
+        buffer.push(sample_snapshot(ts));
+    }
+    let (stream_tx, _stream_rx) = tokio::sync::broadcast::channel(8);
+    let app = router(AppState {
+        buffer,
+        db,
+        store: None,
+        stream_tx,
+        shutdown: CancellationToken::new(),
+        collector: Default::default(),
+        thresholds: Default::default(),
+        alerts: Default::default(),
+        limits: Default::default(),
+        logs: Default::default(),
+        api_token: None,
+        net_scale: Default::default(),
+        requests: Default::default(),
+        initial_window_ms: 0,
+        metrics_prefix: None,
+        sampling: Default::default(),
+        criticality: Default::default(),
+    });
+
+    let response = app
+        .clone()
+        .oneshot(
+            axum::http::Request::builder()
+                .uri("/grafana/")
+                .body(axum::body::Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let response = app
+        .clone()
+        .oneshot(
+            axum::http::Request::builder()
+                .method("POST")
+                .uri("/grafana/search")
+                .header("content-type", "application/json")
+                .body(axum::body::Body::from("{}"))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let names: Vec<String> = serde_json::from_slice(&body).unwrap();
+    assert!(names.iter().any(|n| n == "cpu.total"));
+    assert!(names.iter().any(|n| n == "memory.used_pct"));
+
+    let query = serde_json::json!({
+        "range": { "from": "1970-01-01T00:00:00.500Z", "to": "1970-01-01T00:00:03Z" },
+        "targets": [{ "target": "cpu.total", "refId": "A", "type": "timeserie" }],
+    });
+    let response = app
+        .oneshot(
+            axum::http::Request::builder()
+                .method("POST")
+                .uri("/grafana/query")
+                .header("content-type", "application/json")
+                .body(axum::body::Body::from(query.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json[0]["target"], "cpu.total");
+    let points = json[0]["datapoints"].as_array().unwrap();
+    assert_eq!(points.len(), 2);
+    for (point, ts) in points.iter().zip([1000, 2000]) {
+        let pair = point.as_array().unwrap();
+        assert_eq!(pair.len(), 2);
+        assert!(pair[0].is_f64());
+        assert_eq!(pair[1].as_u64().unwrap(), ts);
+    }
+}
+
+#[tokio::test]
+async fn grafana_targets_carry_metrics_prefix() {
+    let dir = tempdir().unwrap();
+    let db_path = dir.path().join("test.db");
+    let db = Arc::new(MetricsDb::new(&db_path).unwrap());
+    let buffer = Arc::new(MetricsBuffer::new(10));
+    buffer.push(sample_snapshot(1000));
+    let (stream_tx, _stream_rx) = tokio::sync::broadcast::channel(8);
+    let app = router(AppState {
+        buffer,
+        db,
+        store: None,
+        stream_tx,
+        shutdown: CancellationToken::new(),
+        collector: Default::default(),
+        thresholds: Default::default(),
+        alerts: Default::default(),
+        limits: Default::default(),
+        logs: Default::default(),
+        api_token: None,
+        net_scale: Default::default(),
+        requests: Default::default(),
+        initial_window_ms: 0,
+        metrics_prefix: Some("hostmon".to_string()),
+        sampling: Default::default(),
+        criticality: Default::default(),
+    });
+
+    let response = app
+        .clone()
+        .oneshot(
+            axum::http::Request::builder()
+                .method("POST")
+                .uri("/grafana/search")
+                .header("content-type", "application/json")
+                .body(axum::body::Body::from("{}"))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let names: Vec<String> = serde_json::from_slice(&body).unwrap();
+    assert!(!names.is_empty());
+    assert!(names.iter().all(|n| n.starts_with("hostmon.")), "{names:?}");
+
+    let query = |target: &str| {
+        let body = serde_json::json!({
+            "range": { "from": "1970-01-01T00:00:00Z", "to": "1970-01-01T00:00:02Z" },
+            "targets": [{ "target": target }],
+        });
+        axum::http::Request::builder()
+            .method("POST")
+            .uri("/grafana/query")
+            .header("content-type", "application/json")
+            .body(axum::body::Body::from(body.to_string()))
+            .unwrap()
+    };
+    let response = app
+        .clone()
+        .oneshot(query("hostmon.cpu.total"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json[0]["target"], "hostmon.cpu.total");
+    assert_eq!(json[0]["datapoints"].as_array().unwrap().len(), 1);
+
+    let response = app.oneshot(query("cpu.total")).await.unwrap();
+    assert_eq!(response.status(), 400);
+}
+
+#[tokio::test]
+async fn pretty_query_indents_json() {
+    let dir = tempdir().unwrap();
+    let db_path = dir.path().join("test.db");
+    let db = Arc::new(MetricsDb::new(&db_path).unwrap());
+    let buffer = Arc::new(MetricsBuffer::new(10));
+    buffer.push(sample_snapshot(1000));
+    let (stream_tx, _stream_rx) = tokio::sync::broadcast::channel(8);
+    let app = router(AppState {
+        buffer,
+        db,
+        store: None,
+        stream_tx,
+        shutdown: CancellationToken::new(),
+        collector: Default::default(),
+        thresholds: Default::default(),
+        alerts: Default::default(),
+        limits: Default::default(),
+        logs: Default::default(),
+        api_token: None,
+        net_scale: Default::default(),
+        requests: Default::default(),
+        initial_window_ms: 0,
+        metrics_prefix: None,
+        sampling: Default::default(),
+        criticality: Default::default(),
+    });
+
+    let fetch = |uri: &'static str| {
+        let app = app.clone();
+        async move {
+            let response = app
+                .oneshot(
+                    axum::http::Request::builder()
+                        .uri(uri)
+                        .body(axum::body::Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), 200);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            String::from_utf8(body.to_vec()).unwrap()
+        }
+    };
+
+    let compact = fetch("/api/metrics").await;
+    assert!(!compact.contains('\n'));
+
+    let pretty = fetch("/api/metrics?pretty=1").await;
+    assert!(pretty.contains("\n  \"timestamp_ms\""));
+    let a: serde_json::Value = serde_json::from_str(&compact).unwrap();
+    let b: serde_json::Value = serde_json::from_str(&pretty).unwrap();
+    assert_eq!(a, b);
+}
+
+#[tokio::test]
+async fn history_columns_are_parallel_arrays() {
+    let dir = tempdir().unwrap();
+    let db_path = dir.path().join("test.db");
+    let db = Arc::new(MetricsDb::new(&db_path).unwrap());
+    let buffer = Arc::new(MetricsBuffer::new(10));
+    for ts in [1000, 2000, 3000, 4000, 5000] {
+        buffer.push(sample_snapshot(ts));
+    }
+    let (stream_tx, _stream_rx) = tokio::sync::broadcast::channel(8);
+    let app = router(AppState {
+        buffer,
+        db,
+        store: None,
+        stream_tx,
+        shutdown: CancellationToken::new(),
+        collector: Default::default(),
+        thresholds: Default::default(),
+        alerts: Default::default(),
+        limits: Default::default(),
+        logs: Default::default(),
+        api_token: None,
+        net_scale: Default::default(),
+        requests: Default::default(),
+        initial_window_ms: 0,
+        metrics_prefix: None,
+        sampling: Default::default(),
+        criticality: Default::default(),
+    });
+
+    let response = app
+        .oneshot(
+            axum::http::Request::builder()
+                .uri("/api/history/columns?since_ms=2000&until_ms=5000&limit=3")
+                .body(axum::body::Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+    let timestamps: Vec<u64> = json["timestamp_ms"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|v| v.as_u64().unwrap())
+        .collect();
+    assert_eq!(timestamps, vec![3000, 4000, 5000]);
+    for key in ["cpu_total", "mem_used_pct", "rx", "tx"] {
+        assert_eq!(
+            json[key].as_array().unwrap().len(),
+            timestamps.len(),
+            "{key}"
+        );
+    }
+    let expected = sample_snapshot(3000);
+    assert_eq!(
+        json["cpu_total"][0].as_f64().unwrap() as f32,
+        expected.cpu.total_usage_pct
+    );
+    assert_eq!(
+        json["rx"][0].as_f64().unwrap() as f32,
+        expected.network.rx_bytes_per_sec
+    );
+}
+
+#[tokio::test]
+async fn stats_compare_reports_mean_change() {
+    let dir = tempdir().unwrap();
+    let db_path = dir.path().join("test.db");
+    let db = Arc::new(MetricsDb::new(&db_path).unwrap());
+    let buffer = Arc::new(MetricsBuffer::new(10));
+    for (ts, cpu) in [(1000, 10.0), (2000, 30.0), (5000, 25.0), (6000, 25.0)] {
+        let mut snap = sample_snapshot(ts);
+        snap.cpu.total_usage_pct = cpu;
+        buffer.push(snap);
+    }
+    let (stream_tx, _stream_rx) = tokio::sync::broadcast::channel(8);
+    let app = router(AppState {
+        buffer,
+        db,
+        store: None,
+        stream_tx,
+        shutdown: CancellationToken::new(),
+        collector: Default::default(),
+        thresholds: Default::default(),
+        alerts: Default::default(),
+        limits: Default::default(),
+        logs: Default::default(),
+        api_token: None,
+        net_scale: Default::default(),
+        requests: Default::default(),
+        initial_window_ms: 0,
+        metrics_prefix: None,
+        sampling: Default::default(),
+        criticality: Default::default(),
+    });
+
+    let response = app
+        .clone()
+        .oneshot(
+            axum::http::Request::builder()
+                .uri("/api/stats/compare?a_from=0&a_to=2000&b_from=5000&b_to=6000")
+                .body(axum::body::Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["a"]["cpu"]["mean"].as_f64().unwrap(), 20.0);
+    assert_eq!(json["b"]["cpu"]["count"].as_u64().unwrap(), 2);
+    assert!((json["mean_change_pct"]["cpu"].as_f64().unwrap() - 25.0).abs() < 1e-4);
+    // Memory is identical in every sample.
+    assert_eq!(json["mean_change_pct"]["memory"].as_f64().unwrap(), 0.0);
+
+    let response = app
+        .oneshot(
+            axum::http::Request::builder()
+                .uri("/api/stats/compare?a_from=0&a_to=2000&b_from=8000&b_to=9000")
+                .body(axum::body::Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["code"], "no_data");
+    assert!(json["message"].as_str().unwrap().contains("window b"));
+}
+
+#[tokio::test]
+async fn config_serves_chart_thresholds() {
+    let dir = tempdir().unwrap();
+    let db_path = dir.path().join("test.db");
+    let db = Arc::new(MetricsDb::new(&db_path).unwrap());
+    let buffer = Arc::new(MetricsBuffer::new(10));
+    let (stream_tx, _stream_rx) = tokio::sync::broadcast::channel(8);
+    let app = router(AppState {
+        buffer,
+        db,
+        store: None,
+        stream_tx,
+        shutdown: CancellationToken::new(),
+        collector: Default::default(),
+        thresholds: Thresholds {
+            cpu: Threshold {
+                warn: Some(60.0),
+                crit: Some(85.0),
+                ..Default::default()
+            },
+            memory: Threshold {
+                warn: None,
+                crit: Some(95.0),
+                ..Default::default()
+            },
+        }
+        .into(),
+        alerts: Default::default(),
+        limits: Default::default(),
+        logs: Default::default(),
+        api_token: None,
+        net_scale: Default::default(),
+        requests: Default::default(),
+        initial_window_ms: 0,
+        metrics_prefix: None,
+        sampling: Default::default(),
+        criticality: Default::default(),
+    });
+
+    let response = app
+        .oneshot(
+            axum::http::Request::builder()
+                .uri("/api/config")
+                .body(axum::body::Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let thresholds = &json["thresholds"];
+    assert_eq!(thresholds["cpu_total"]["warn"].as_f64().unwrap(), 60.0);
+    assert_eq!(thresholds["cpu_total"]["crit"].as_f64().unwrap(), 85.0);
+    assert!(thresholds["memory"]["warn"].is_null());
+    assert_eq!(thresholds["memory"]["crit"].as_f64().unwrap(), 95.0);
+}
+
+#[tokio::test]
+async fn config_endpoint_reports_network_scale() {
+    use resource_monitor::config::{NetAxes, NetScale, NetScaleMode};
+
+    let dir = tempdir().unwrap();
+    let db = Arc::new(MetricsDb::new(&dir.path().join("test.db")).unwrap());
+    let (stream_tx, _stream_rx) = tokio::sync::broadcast::channel(8);
+    let app = router(AppState {
+        buffer: Arc::new(MetricsBuffer::new(10)),
+        db,
+        store: None,
+        stream_tx,
+        shutdown: CancellationToken::new(),
+        collector: Default::default(),
+        thresholds: Default::default(),
+        alerts: Default::default(),
+        limits: Default::default(),
+        logs: Default::default(),
+        api_token: None,
+        net_scale: NetScale {
+            mode: NetScaleMode::Fixed,
+            max_bytes_per_sec: Some(12_500_000.0),
+            axes: NetAxes::Split,
+        },
+        requests: Default::default(),
+        initial_window_ms: 0,
+        metrics_prefix: None,
+        sampling: Default::default(),
+        criticality: Default::default(),
+    });
+
+    let response = app
+        .oneshot(
+            axum::http::Request::builder()
+                .uri("/api/config")
+                .body(axum::body::Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["network_scale"]["mode"], "fixed");
+    assert_eq!(
+        json["network_scale"]["max_bytes_per_sec"].as_f64().unwrap(),
+        12_500_000.0
+    );
+    assert_eq!(json["network_scale"]["axes"], "split");
+}
+
+#[tokio::test]
+async fn config_endpoint_reports_interval_and_gap_threshold() {
+    use resource_monitor::api::Sampling;
+    use std::time::Duration;
+
+    let dir = tempdir().unwrap();
+    let db = Arc::new(MetricsDb::new(&dir.path().join("test.db")).unwrap());
+    let (stream_tx, _stream_rx) = tokio::sync::broadcast::channel(8);
+    let (interval_tx, interval_rx) = tokio::sync::watch::channel(Duration::from_millis(500));
+    let app = router(AppState {
+        buffer: Arc::new(MetricsBuffer::new(10)),
+        db,
+        store: None,
+        stream_tx,
+        shutdown: CancellationToken::new(),
+        collector: Default::default(),
+        thresholds: Default::default(),
+        alerts: Default::default(),
+        limits: Default::default(),
+        logs: Default::default(),
+        api_token: None,
+        net_scale: Default::default(),
+        requests: Default::default(),
+        initial_window_ms: 0,
+        metrics_prefix: None,
+        sampling: Sampling {
+            interval: Some(interval_rx),
+            gap_factor: 3.0,
+            aggregations: Default::default(),
+            max_browser_points: 5000,
+        },
+        criticality: Default::default(),
+    });
+
+    let fetch = || async {
+        let response = app
+            .clone()
+            .oneshot(
+                axum::http::Request::builder()
+                    .uri("/api/config")
+                    .body(axum::body::Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice::<serde_json::Value>(&body).unwrap()
+    };
+    let json = fetch().await;
+    assert_eq!(json["interval_ms"], 500);
+    assert_eq!(json["gap_threshold_ms"], 1500);
+    assert_eq!(json["max_browser_points"], 5000);
+
+    // A reloaded interval is reported on the next request.
+    interval_tx.send_replace(Duration::from_millis(2000));
+    let json = fetch().await;
+    assert_eq!(json["interval_ms"], 2000);
+    assert_eq!(json["gap_threshold_ms"], 6000);
+}
+
+#[tokio::test]
+async fn stream_payload_carries_dashboard_series() {
+    use futures::StreamExt;
+    use resource_monitor::metrics::DASHBOARD_SERIES;
+
+    let dir = tempdir().unwrap();
+    let db_path = dir.path().join("test.db");
+    let db = Arc::new(MetricsDb::new(&db_path).unwrap());
+    let buffer = Arc::new(MetricsBuffer::new(10));
+    let (stream_tx, _stream_rx) = tokio::sync::broadcast::channel(8);
+    let app = router(AppState {
+        buffer,
+        db,
+        store: None,
+        stream_tx: stream_tx.clone(),
+        shutdown: CancellationToken::new(),
+        collector: Default::default(),
+        thresholds: Default::default(),
+        alerts: Default::default(),
+        limits: Default::default(),
+        logs: Default::default(),
+        api_token: None,
+        net_scale: Default::default(),
+        requests: Default::default(),
+        initial_window_ms: 0,
+        metrics_prefix: None,
+        sampling: Default::default(),
+        criticality: Default::default(),
+    });
+
+    let response = app
+        .oneshot(
+            axum::http::Request::builder()
+                .uri("/api/stream")
+                .body(axum::body::Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let snap = sample_snapshot(1000);
+    stream_tx.send(snap.to_rpc_format()).unwrap();
+
+    let mut body = response.into_body().into_data_stream();
+    let mut text = String::new();
+    while !text.contains("\n\n") {
+        let chunk = tokio::time::timeout(std::time::Duration::from_secs(2), body.next())
+            .await
+            .expect("stream event")
+            .unwrap()
+            .unwrap();
+        text.push_str(std::str::from_utf8(&chunk).unwrap());
+    }
+    let payload = text
+        .lines()
+        .find_map(|l| l.strip_prefix("data:"))
+        .unwrap()
+        .trim();
+    let json: serde_json::Value = serde_json::from_str(payload).unwrap();
+    let series = |name: &str| {
+        json["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|s| s["name"] == name)
+            .unwrap_or_else(|| panic!("missing series {name}"))["series"]
+            .clone()
+    };
+    for name in DASHBOARD_SERIES {
+        series(name);
+    }
+    // cpu.total_usage_pct, memory used/total_bytes and network.rx_bytes_per_sec.
+    assert_eq!(
+        series("cpu_total")[0].as_f64().unwrap() as f32,
+        snap.cpu.total_usage_pct
+    );
+    assert_eq!(series("memory")[0].as_f64().unwrap(), 50.0);
+    assert_eq!(
+        series("network")[0].as_f64().unwrap() as f32,
+        snap.network.rx_bytes_per_sec
+    );
+}
+
+#[tokio::test]
+async fn stream_min_interval_coalesces_flood() {
+    use futures::StreamExt;
+
+    let dir = tempdir().unwrap();
+    let db_path = dir.path().join("test.db");
+    let db = Arc::new(MetricsDb::new(&db_path).unwrap());
+    let buffer = Arc::new(MetricsBuffer::new(10));
+    let (stream_tx, _stream_rx) = tokio::sync::broadcast::channel(64);
+    let app = router(AppState {
+        buffer,
+        db,
+        store: None,
+        stream_tx: stream_tx.clone(),
+        shutdown: CancellationToken::new(),
+        collector: Default::default(),
+        thresholds: Default::default(),
+        alerts: Default::default(),
+        limits: Default::default(),
+        logs: Default::default(),
+        api_token: None,
+        net_scale: Default::default(),
+        requests: Default::default(),
+        initial_window_ms: 0,
+        metrics_prefix: None,
+        sampling: Default::default(),
+        criticality: Default::default(),
+    });
+
+    let mut delivered = Vec::new();
+    for uri in [
+        "/api/stream?min_interval_ms=0",
+        "/api/stream?min_interval_ms=60000",
+    ] {
+        let response = app
+            .clone()
+            .oneshot(
+                axum::http::Request::builder()
+                    .uri(uri)
+                    .body(axum::body::Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        for ts in 1..=20 {
+            stream_tx.send(sample_snapshot(ts).to_rpc_format()).unwrap();
+        }
+
+        let mut body = response.into_body().into_data_stream();
+        let mut text = String::new();
+        while let Ok(Some(chunk)) =
+            tokio::time::timeout(std::time::Duration::from_millis(300), body.next()).await
+        {
+            text.push_str(std::str::from_utf8(&chunk.unwrap()).unwrap());
+        }
+        delivered.push(text.lines().filter(|l| l.starts_with("data:")).count());
+    }
+    assert_eq!(delivered, vec![20, 1]);
+}
+
+#[tokio::test]
+async fn stream_per_core_zero_omits_per_core_series() {
+    use futures::StreamExt;
+
+    let dir = tempdir().unwrap();
+    let db = Arc::new(MetricsDb::new(&dir.path().join("test.db")).unwrap());
+    let buffer = Arc::new(MetricsBuffer::new(10));
+    buffer.push(sample_snapshot(1000));
+    let (stream_tx, _stream_rx) = tokio::sync::broadcast::channel(8);
+    let app = router(AppState {
+        buffer: buffer.clone(),
+        db,
+        store: None,
+        stream_tx: stream_tx.clone(),
+        shutdown: CancellationToken::new(),
+        collector: Default::default(),
+        thresholds: Default::default(),
+        alerts: Default::default(),
+        limits: Default::default(),
+        logs: Default::default(),
+        api_token: None,
+        net_scale: Default::default(),
+        requests: Default::default(),
+        initial_window_ms: 0,
+        metrics_prefix: None,
+        sampling: Default::default(),
+        criticality: Default::default(),
+    });
+
+    let mut cores = Vec::new();
+    for uri in [
+        "/api/stream?replay=1&per_core=0",
+        "/api/stream?replay=1&per_core=1",
+        "/api/stream?replay=1",
+    ] {
+        let response = app
+            .clone()
+            .oneshot(
+                axum::http::Request::builder()
+                    .uri(uri)
+                    .body(axum::body::Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        stream_tx
+            .send(sample_snapshot(2000).to_rpc_format())
+            .unwrap();
+
+        let mut body = response.into_body().into_data_stream();
+        let mut text = String::new();
+        while let Ok(Some(chunk)) =
+            tokio::time::timeout(std::time::Duration::from_millis(300), body.next()).await
+        {
+            text.push_str(std::str::from_utf8(&chunk.unwrap()).unwrap());
+        }
+        let per_event: Vec<(usize, usize)> = text
+            .lines()
+            .filter_map(|l| l.strip_prefix("data:"))
+            .map(|data| {
+                let json: serde_json::Value = serde_json::from_str(data.trim()).unwrap();
+                let series = json["data"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .find(|s| s["name"] == "cpu_cores")
+                    .unwrap()
+                    .clone();
+                (
+                    series["series"].as_array().unwrap().len(),
+                    series["legend"].as_array().unwrap().len(),
+                )
+            })
+            .collect();
+        cores.push(per_event);
+    }
+    assert_eq!(cores[0], vec![(0, 0), (0, 0)]);
+    assert_eq!(cores[1], vec![(1, 1), (1, 1)]);
+    assert_eq!(cores[2], vec![(2, 2), (2, 2)]);
+    // History still has every core.
+    assert_eq!(buffer.latest().unwrap().cpu.per_core_usage_pct.len(), 2);
+}
+
+#[tokio::test]
+async fn stream_replay_sends_buffered_snapshots_before_live_ones() {
+    use futures::StreamExt;
+
+    let dir = tempdir().unwrap();
+    let db_path = dir.path().join("test.db");
+    let db = Arc::new(MetricsDb::new(&db_path).unwrap());
+    let buffer = Arc::new(MetricsBuffer::new(10));
+    for ts in 1..=8 {
+        buffer.push(sample_snapshot(ts * 1000));
+    }
+    let (stream_tx, _stream_rx) = tokio::sync::broadcast::channel(64);
+    let app = router(AppState {
+        buffer,
+        db,
+        store: None,
+        stream_tx: stream_tx.clone(),
+        shutdown: CancellationToken::new(),
+        collector: Default::default(),
+        thresholds: Default::default(),
+        alerts: Default::default(),
+        limits: Default::default(),
+        logs: Default::default(),
+        api_token: None,
+        net_scale: Default::default(),
+        requests: Default::default(),
+        initial_window_ms: 0,
+        metrics_prefix: None,
+        sampling: Default::default(),
+        criticality: Default::default(),
+    });
+
+    let mut delivered = Vec::new();
+    for uri in ["/api/stream?replay=5", "/api/stream?replay=100"] {
+        let response = app
+            .clone()
+            .oneshot(
+                axum::http::Request::builder()
+                    .uri(uri)
+                    .body(axum::body::Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        // Already replayed, so skipped; then two live ones.
+        for ts in [8000, 9000, 10000] {
+            stream_tx.send(sample_snapshot(ts).to_rpc_format()).unwrap();
+        }
+
+        let mut body = response.into_body().into_data_stream();
+        let mut text = String::new();
+        while let Ok(Some(chunk)) =
+            tokio::time::timeout(std::time::Duration::from_millis(300), body.next()).await
+        {
+            text.push_str(std::str::from_utf8(&chunk.unwrap()).unwrap());
+        }
+        let timestamps: Vec<u64> = text
+            .lines()
+            .filter_map(|l| l.strip_prefix("data:"))
+            .map(|data| {
+                let json: serde_json::Value = serde_json::from_str(data.trim()).unwrap();
+                json["timestamp_ms"].as_u64().unwrap()
+            })
+            .collect();
+        delivered.push(timestamps);
+    }
+    assert_eq!(
+        delivered[0],
+        vec![4000, 5000, 6000, 7000, 8000, 9000, 10000]
+    );
+    // Capped to what the buffer holds.
+    assert_eq!(delivered[1].len(), 10);
+    assert_eq!(delivered[1][0], 1000);
+}
+
+#[tokio::test]
+async fn stream_delta_sends_full_snapshot_then_patches() {
+    use futures::StreamExt;
+
+    let dir = tempdir().unwrap();
+    let db_path = dir.path().join("test.db");
+    let db = Arc::new(MetricsDb::new(&db_path).unwrap());
+    let buffer = Arc::new(MetricsBuffer::new(10));
+    let (stream_tx, _stream_rx) = tokio::sync::broadcast::channel(64);
+    let app = router(AppState {
+        buffer,
+        db,
+        store: None,
+        stream_tx: stream_tx.clone(),
+        shutdown: CancellationToken::new(),
+        collector: Default::default(),
+        thresholds: Default::default(),
+        alerts: Default::default(),
+        limits: Default::default(),
+        logs: Default::default(),
+        api_token: None,
+        net_scale: Default::default(),
+        requests: Default::default(),
+        initial_window_ms: 0,
+        metrics_prefix: None,
+        sampling: Default::default(),
+        criticality: Default::default(),
+    });
+
+    let response = app
+        .oneshot(
+            axum::http::Request::builder()
+                .uri("/api/stream?delta=1")
+                .body(axum::body::Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let first = sample_snapshot(1000);
+    let mut second = sample_snapshot(2000);
+    second.cpu.total_usage_pct += 5.0;
+    stream_tx.send(first.to_rpc_format()).unwrap();
+    stream_tx.send(second.to_rpc_format()).unwrap();
+
+    let mut body = response.into_body().into_data_stream();
+    let mut text = String::new();
+    while let Ok(Some(chunk)) =
+        tokio::time::timeout(std::time::Duration::from_millis(300), body.next()).await
+    {
+        text.push_str(std::str::from_utf8(&chunk.unwrap()).unwrap());
+    }
+    let events: Vec<(String, serde_json::Value)> = text
+        .split("\n\n")
+        .filter_map(|block| {
+            let kind = block.lines().find_map(|l| l.strip_prefix("event:"))?;
+            let data = block.lines().find_map(|l| l.strip_prefix("data:"))?;
+            Some((
+                kind.trim().to_string(),
+                serde_json::from_str(data.trim()).unwrap(),
+            ))
+        })
+        .collect();
+    assert_eq!(events.len(), 2);
+
+    let (kind, full) = &events[0];
+    assert_eq!(kind, "snapshot");
+    assert_eq!(full["timestamp_ms"], 1000);
+    assert!(full["data"]["memory"]["legend"].is_array());
+
+    let (kind, patch) = &events[1];
+    assert_eq!(kind, "patch");
+    assert_eq!(patch["timestamp_ms"], 2000);
+    assert!(patch["data"].get("cpu_total").is_some());
+    // Unchanged series are left out of the patch.
+    assert!(patch["data"].get("memory").is_none());
+    assert!(patch["data"]["cpu_total"].get("legend").is_none());
+
+    let mut doc = full.clone();
+    resource_monitor::delta::apply_merge_patch(&mut doc, patch);
+    assert_eq!(
+        doc,
+        resource_monitor::delta::keyed(&second.to_rpc_format()).unwrap()
+    );
+}
+
+#[tokio::test]
+async fn history_order_and_from_start_over_buffer_and_db() {
+    let dir = tempdir().unwrap();
+    let db_path = dir.path().join("test.db");
+    let db = Arc::new(MetricsDb::new(&db_path).unwrap());
+    let buffer = Arc::new(MetricsBuffer::new(10));
+    for ts in [1000, 2000, 3000, 4000, 5000] {
+        buffer.push(sample_snapshot(ts));
+        db.insert(&sample_snapshot(ts)).unwrap();
+    }
+    let (stream_tx, _stream_rx) = tokio::sync::broadcast::channel(8);
+    let app = router(AppState {
+        buffer,
+        db,
+        store: None,
+        stream_tx,
+        shutdown: CancellationToken::new(),
+        collector: Default::default(),
+        thresholds: Default::default(),
+        alerts: Default::default(),
+        limits: Default::default(),
+        logs: Default::default(),
+        api_token: None,
+        net_scale: Default::default(),
+        requests: Default::default(),
+        initial_window_ms: 0,
+        metrics_prefix: None,
+        sampling: Default::default(),
+        criticality: Default::default(),
+    });
+
+    // Without since_ts the database answers; with it, the buffer does.
+    for since in ["", "&since_ts=1000"] {
+        let cases = [
+            ("limit=2", vec![5000, 4000]),
+            ("limit=2&order=asc", vec![4000, 5000]),
+            ("limit=2&from_start=1", vec![2000, 1000]),
+            ("limit=2&from_start=true&order=asc", vec![1000, 2000]),
+        ];
+        for (params, expected) in cases {
+            let uri = format!("/api/history?{params}{since}");
+            let response = app
+                .clone()
+                .oneshot(
+                    axum::http::Request::builder()
+                        .uri(&uri)
+                        .body(axum::body::Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), 200);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            let timestamps: Vec<u64> = json
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|s| s["timestamp_ms"].as_u64().unwrap())
+                .collect();
+            assert_eq!(timestamps, expected, "{uri}");
+        }
+    }
+}
+
+#[tokio::test]
+async fn history_since_served_from_buffer_newest_first() {
+    let dir = tempdir().unwrap();
+    let db_path = dir.path().join("test.db");
+    let db = Arc::new(MetricsDb::new(&db_path).unwrap());
+    let buffer = Arc::new(MetricsBuffer::new(10));
+    for ts in [1000, 2000, 3000, 4000] {
+        buffer.push(sample_snapshot(ts));
+    }
+    let (stream_tx, _stream_rx) = tokio::sync::broadcast::channel(8);
+    let app = router(AppState {
+        buffer,
+        db,
+        store: None,
+        stream_tx,
+        shutdown: CancellationToken::new(),
+        collector: Default::default(),
+        thresholds: Default::default(),
+        alerts: Default::default(),
+        limits: Default::default(),
+        logs: Default::default(),
+        api_token: None,
+        net_scale: Default::default(),
+        requests: Default::default(),
+        initial_window_ms: 0,
+        metrics_prefix: None,
+        sampling: Default::default(),
+        criticality: Default::default(),
+    });
+
+    let response = app
+        .oneshot(
+            axum::http::Request::builder()
+                .uri("/api/history?since_ts=2000&limit=2")
+                .body(axum::body::Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let history: Vec<RpcMetricsSnapshot> = serde_json::from_slice(&body).unwrap();
+    let timestamps: Vec<u128> = history.iter().map(|s| s.timestamp_ms).collect();
+    assert_eq!(timestamps, vec![4000, 3000]);
+}
+
+#[tokio::test]
+async fn history_limit_larger_than_since_window_returns_all_of_it() {
+    let dir = tempdir().unwrap();
+    let db = Arc::new(MetricsDb::new(&dir.path().join("test.db")).unwrap());
+    let buffer = Arc::new(MetricsBuffer::new(10));
+    for ts in [1000, 2000, 3000, 4000] {
+        buffer.push(sample_snapshot(ts));
+        db.insert(&sample_snapshot(ts)).unwrap();
+    }
+    // Older than the buffer, so a window reaching it is read from the database.
+    db.insert(&sample_snapshot(500)).unwrap();
+    let (stream_tx, _stream_rx) = tokio::sync::broadcast::channel(8);
+    let app = router(AppState {
+        buffer,
+        db,
+        store: None,
+        stream_tx,
+        shutdown: CancellationToken::new(),
+        collector: Default::default(),
+        thresholds: Default::default(),
+        alerts: Default::default(),
+        limits: Default::default(),
+        logs: Default::default(),
+        api_token: None,
+        net_scale: Default::default(),
+        requests: Default::default(),
+        initial_window_ms: 0,
+        metrics_prefix: None,
+        sampling: Default::default(),
+        criticality: Default::default(),
+    });
+
+    for (uri, expected) in [
+        ("/api/history?since_ts=2500&limit=50", vec![4000, 3000]),
+        (
+            "/api/history?since_ts=2500&limit=50&from_start=1&order=asc",
+            vec![3000, 4000],
+        ),
+        ("/api/history?since_ts=9000&limit=50", vec![]),
+        (
+            "/api/history?since_ts=100&limit=50&order=asc",
+            vec![500, 1000, 2000, 3000, 4000],
+        ),
+    ] {
+        let response = app
+            .clone()
+            .oneshot(
+                axum::http::Request::builder()
+                    .uri(uri)
+                    .body(axum::body::Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200, "{uri}");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let history: Vec<RpcMetricsSnapshot> = serde_json::from_slice(&body).unwrap();
+        let timestamps: Vec<u128> = history.iter().map(|s| s.timestamp_ms).collect();
+        assert_eq!(timestamps, expected, "{uri}");
+    }
+}
+
+#[tokio::test]
+async fn health_and_stream_over_https() {
+    use futures::StreamExt;
+
+    let dir = tempdir().unwrap();
+    let db = Arc::new(MetricsDb::new(&dir.path().join("test.db")).unwrap());
+    let (stream_tx, _stream_rx) = tokio::sync::broadcast::channel(8);
+    let shutdown = CancellationToken::new();
+    let app = resource_monitor::api::api_only_router(AppState {
+        buffer: Arc::new(MetricsBuffer::new(10)),
+        db,
+        store: None,
+        stream_tx: stream_tx.clone(),
+        shutdown: shutdown.clone(),
+        collector: Default::default(),
+        thresholds: Default::default(),
+        alerts: Default::default(),
+        limits: Default::default(),
+        logs: Default::default(),
+        api_token: None,
+        net_scale: Default::default(),
+        requests: Default::default(),
+        initial_window_ms: 0,
+        metrics_prefix: None,
+        sampling: Default::default(),
+        criticality: Default::default(),
+    });
+
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+    let cert_pem = cert.cert.pem();
+    let cert_path = dir.path().join("cert.pem");
+    let key_path = dir.path().join("key.pem");
+    std::fs::write(&cert_path, &cert_pem).unwrap();
+    std::fs::write(&key_path, cert.key_pair.serialize_pem()).unwrap();
+
+    let config = resource_monitor::tls::load_config(&cert_path, &key_path)
+        .await
+        .unwrap();
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = tokio::spawn(resource_monitor::tls::serve(
+        listener,
+        app,
+        config,
+        shutdown.clone(),
+    ));
+
+    let client = reqwest::Client::builder()
+        .add_root_certificate(reqwest::Certificate::from_pem(cert_pem.as_bytes()).unwrap())
+        .resolve("localhost", addr)
+        .build()
+        .unwrap();
+    let base = format!("https://localhost:{}", addr.port());
+
+    let health = client
+        .get(format!("{base}/api/health"))
+        .send()
+        .await
+        .unwrap()
+        .bytes()
+        .await
+        .unwrap();
+    let health: serde_json::Value = serde_json::from_slice(&health).unwrap();
+    assert_eq!(health["status"], "ok");
+
+    let response = client
+        .get(format!("{base}/api/stream"))
+        .send()
+        .await
+        .unwrap();
+    let ct = response.headers()["content-type"].to_str().unwrap();
+    assert!(ct.starts_with("text/event-stream"));
+    let publisher = tokio::spawn(async move {
+        loop {
+            let _ = stream_tx.send(sample_snapshot(8000).to_rpc_format());
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+    });
+    let mut body = response.bytes_stream();
+    let chunk = tokio::time::timeout(std::time::Duration::from_secs(2), body.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    publisher.abort();
+    assert!(String::from_utf8_lossy(&chunk).contains("data:"));
+
+    drop(body);
+    shutdown.cancel();
+    tokio::time::timeout(std::time::Duration::from_secs(5), server)
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+}
+
+#[tokio::test]
+async fn alert_ack_flips_acknowledged_flag() {
+    let dir = tempdir().unwrap();
+    let db = Arc::new(MetricsDb::new(&dir.path().join("test.db")).unwrap());
+    let alerts = Arc::new(resource_monitor::alerts::AlertTracker::default());
+    let mut hot = sample_snapshot(1000);
+    hot.cpu.total_usage_pct = 99.0;
+    alerts.observe(&hot);
+
+    let (stream_tx, _stream_rx) = tokio::sync::broadcast::channel(8);
+    let app = router(AppState {
+        buffer: Arc::new(MetricsBuffer::new(10)),
+        db,
+        store: None,
+        stream_tx,
+        shutdown: CancellationToken::new(),
+        collector: Default::default(),
+        thresholds: Default::default(),
+        alerts,
+        limits: Default::default(),
+        logs: Default::default(),
+        api_token: None,
+        net_scale: Default::default(),
+        requests: Default::default(),
+        initial_window_ms: 0,
+        metrics_prefix: None,
+        sampling: Default::default(),
+        criticality: Default::default(),
+    });
+
+    let request = |method: &str, uri: &str| {
+        axum::http::Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(axum::body::Body::empty())
+            .unwrap()
+    };
+    let json = |response: axum::response::Response| async move {
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice::<serde_json::Value>(&body).unwrap()
+    };
+
+    let history = json(
+        app.clone()
+            .oneshot(request("GET", "/api/alerts/history"))
+            .await
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(history[0]["metric"], "cpu_total");
+    assert_eq!(history[0]["acknowledged"], false);
+    let id = history[0]["id"].as_u64().unwrap();
+
+    let response = app
+        .clone()
+        .oneshot(request("POST", &format!("/api/alerts/{id}/ack")))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(json(response).await["acknowledged"], true);
+
+    let history = json(
+        app.clone()
+            .oneshot(request("GET", "/api/alerts/history"))
+            .await
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(history[0]["acknowledged"], true);
+    assert!(history[0]["cleared_at_ms"].is_null());
+
+    let response = app
+        .oneshot(request("POST", "/api/alerts/999/ack"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+}
+
+#[tokio::test]
+async fn oversized_body_is_rejected_with_413() {
+    let dir = tempdir().unwrap();
+    let db = Arc::new(MetricsDb::new(&dir.path().join("test.db")).unwrap());
+    let (stream_tx, _stream_rx) = tokio::sync::broadcast::channel(8);
+    let app = router(AppState {
+        buffer: Arc::new(MetricsBuffer::new(10)),
+        db,
+        store: None,
+        stream_tx,
+        shutdown: CancellationToken::new(),
+        collector: Default::default(),
+        thresholds: Default::default(),
+        alerts: Default::default(),
+        limits: resource_monitor::config::HttpLimits {
+            max_body_bytes: 256,
+            ..Default::default()
+        },
+        logs: Default::default(),
+        api_token: None,
+        net_scale: Default::default(),
+        requests: Default::default(),
+        initial_window_ms: 0,
+        metrics_prefix: None,
+        sampling: Default::default(),
+        criticality: Default::default(),
+    });
+    let query = |body: String| {
+        axum::http::Request::builder()
+            .method("POST")
+            .uri("/grafana/query")
+            .header("content-type", "application/json")
+            .body(axum::body::Body::from(body))
+            .unwrap()
+    };
+
+    let small = r#"{"range":{"from":"2024-01-01T00:00:00Z","to":"2024-01-01T01:00:00Z"},"targets":[{"target":"cpu.total"}]}"#;
+    let response = app.clone().oneshot(query(small.to_string())).await.unwrap();
+    assert_eq!(response.status(), 200);
+
+    let padding = "x".repeat(1024);
+    let large = format!(
+        r#"{{"range":{{"from":"2024-01-01T00:00:00Z","to":"2024-01-01T01:00:00Z"}},"targets":[{{"target":"{padding}"}}]}}"#
+    );
+    let response = app.oneshot(query(large)).await.unwrap();
+    assert_eq!(response.status(), 413);
+}
+
+#[tokio::test]
+async fn stalled_request_times_out_with_an_error_body() {
+    let dir = tempdir().unwrap();
+    let db = Arc::new(MetricsDb::new(&dir.path().join("test.db")).unwrap());
+    let (stream_tx, _stream_rx) = tokio::sync::broadcast::channel(8);
+    let app = router(AppState {
+        buffer: Arc::new(MetricsBuffer::new(10)),
+        db,
+        store: None,
+        stream_tx,
+        shutdown: CancellationToken::new(),
+        collector: Default::default(),
+        thresholds: Default::default(),
+        alerts: Default::default(),
+        limits: resource_monitor::config::HttpLimits {
+            request_timeout: std::time::Duration::from_millis(50),
+            ..Default::default()
+        },
+        logs: Default::default(),
+        api_token: None,
+        net_scale: Default::default(),
+        requests: Default::default(),
+        initial_window_ms: 0,
+        metrics_prefix: None,
+        sampling: Default::default(),
+        criticality: Default::default(),
+    });
+    // A body that never arrives keeps the handler waiting.
+    let body = futures::stream::pending::<Result<Vec<u8>, std::io::Error>>();
+    let response = app
+        .oneshot(
+            axum::http::Request::builder()
+                .method("POST")
+                .uri("/grafana/query")
+                .header("content-type", "application/json")
+                .body(axum::body::Body::from_stream(body))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 408);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let error: resource_monitor::metrics::ErrorResponse = serde_json::from_slice(&body).unwrap();
+    assert_eq!(error.code, resource_monitor::metrics::ErrorCode::Timeout);
+}
+
+#[tokio::test]
+async fn logs_endpoint_returns_captured_warnings() {
+    use resource_monitor::logs::LogRing;
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::util::SubscriberInitExt;
+
+    let logs = Arc::new(LogRing::new(16));
+    let _guard = tracing_subscriber::registry()
+        .with(logs.layer())
+        .set_default();
+    tracing::warn!(interface = "eth0", "network counter reset");
+
+    let dir = tempdir().unwrap();
+    let db = Arc::new(MetricsDb::new(&dir.path().join("test.db")).unwrap());
+    let (stream_tx, _stream_rx) = tokio::sync::broadcast::channel(8);
+    let app = router(AppState {
+        buffer: Arc::new(MetricsBuffer::new(10)),
+        db,
+        store: None,
+        stream_tx,
+        shutdown: CancellationToken::new(),
+        collector: Default::default(),
+        thresholds: Default::default(),
+        alerts: Default::default(),
+        limits: Default::default(),
+        logs,
+        api_token: Some("secret".to_string()),
+        net_scale: Default::default(),
+        requests: Default::default(),
+        initial_window_ms: 0,
+        metrics_prefix: None,
+        sampling: Default::default(),
+        criticality: Default::default(),
+    });
+    let request = |auth: Option<&str>| {
+        let mut builder = axum::http::Request::builder().uri("/api/logs?limit=10");
+        if let Some(auth) = auth {
+            builder = builder.header("authorization", auth);
+        }
+        builder.body(axum::body::Body::empty()).unwrap()
+    };
+
+    let response = app.clone().oneshot(request(None)).await.unwrap();
+    assert_eq!(response.status(), 401);
+    let response = app
+        .clone()
+        .oneshot(request(Some("Bearer wrong")))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 401);
+
+    let response = app.oneshot(request(Some("Bearer secret"))).await.unwrap();
+    assert_eq!(response.status(), 200);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let entry = json
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|e| {
+            e["message"]
+                .as_str()
+                .unwrap()
+                .contains("network counter reset")
+        })
+        .expect("warning should be captured");
+    assert_eq!(entry["level"], "WARN");
+    assert_eq!(entry["message"], "network counter reset interface=eth0");
+}
+
+#[tokio::test]
+async fn requests_are_access_logged_and_counted() {
+    use resource_monitor::access::ACCESS_LOG_TARGET;
+    use resource_monitor::logs::LogRing;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    let ring = Arc::new(LogRing::new(100));
+    let _guard =
+        tracing::subscriber::set_default(tracing_subscriber::registry().with(ring.layer()));
+
+    let dir = tempdir().unwrap();
+    let db = Arc::new(MetricsDb::new(&dir.path().join("test.db")).unwrap());
+    let (stream_tx, _stream_rx) = tokio::sync::broadcast::channel(8);
+    let app = router(AppState {
+        buffer: Arc::new(MetricsBuffer::new(10)),
+        db,
+        store: None,
+        stream_tx,
+        shutdown: CancellationToken::new(),
+        collector: Default::default(),
+        thresholds: Default::default(),
+        alerts: Default::default(),
+        limits: Default::default(),
+        logs: Default::default(),
+        api_token: None,
+        net_scale: Default::default(),
+        requests: Default::default(),
+        initial_window_ms: 0,
+        metrics_prefix: None,
+        sampling: Default::default(),
+        criticality: Default::default(),
+    });
+
+    let get = |uri: &'static str| {
+        let app = app.clone();
+        async move {
+            app.oneshot(
+                axum::http::Request::builder()
+                    .uri(uri)
+                    .body(axum::body::Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap()
+        }
+    };
+
+    assert_eq!(get("/api/health").await.status(), 200);
+    assert_eq!(get("/api/alerts/7/ack").await.status(), 405);
+
+    let access: Vec<_> = ring
+        .recent(None)
+        .into_iter()
+        .filter(|e| e.target == ACCESS_LOG_TARGET)
+        .collect();
+    assert_eq!(access.len(), 2);
+    assert!(access[0].message.contains("status=200"));
+    assert!(access[0].message.contains("latency_ms="));
+    assert!(access[1].message.contains("status=405"));
+
+    let response = get("/api/stats").await;
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["routes"]["/api/health"]["count"], 1);
+    assert_eq!(json["routes"]["/api/alerts/:id/ack"]["count"], 1);
+    let buckets = json["routes"]["/api/health"]["latency_buckets"]
+        .as_array()
+        .unwrap();
+    assert_eq!(
+        buckets.len(),
+        json["latency_bucket_bounds_ms"].as_array().unwrap().len() + 1
+    );
+    // No samples buffered, so no health figure either.
+    assert!(json["healthy_fraction"].is_null());
+}
+
+#[tokio::test]
+async fn history_filters_by_tag_query_parameters() {
+    use resource_monitor::metrics::RpcMetricsSnapshot;
+
+    let dir = tempdir().unwrap();
+    let db = Arc::new(MetricsDb::new(&dir.path().join("test.db")).unwrap());
+    let buffer = Arc::new(MetricsBuffer::new(10));
+    for (ts, role, dc) in [
+        (1000, "web", "fra1"),
+        (2000, "db", "fra1"),
+        (3000, "web", "ams1"),
+        (4000, "db", "ams1"),
+    ] {
+        let mut snap = sample_snapshot(ts);
+        snap.tags.insert("role".to_string(), role.to_string());
+        snap.tags.insert("dc".to_string(), dc.to_string());
+        buffer.push(snap);
+    }
+    let (stream_tx, _stream_rx) = tokio::sync::broadcast::channel(8);
+    let app = router(AppState {
+        buffer,
+        db,
+        store: None,
+        stream_tx,
+        shutdown: CancellationToken::new(),
+        collector: Default::default(),
+        thresholds: Default::default(),
+        alerts: Default::default(),
+        limits: Default::default(),
+        logs: Default::default(),
+        api_token: None,
+        net_scale: Default::default(),
+        requests: Default::default(),
+        initial_window_ms: 0,
+        metrics_prefix: None,
+        sampling: Default::default(),
+        criticality: Default::default(),
+    });
+
+    let timestamps = |uri: &'static str| {
+        let app = app.clone();
+        async move {
+            let response = app
+                .oneshot(
+                    axum::http::Request::builder()
+                        .uri(uri)
+                        .body(axum::body::Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), 200);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let history: Vec<RpcMetricsSnapshot> = serde_json::from_slice(&body).unwrap();
+            history.iter().map(|s| s.timestamp_ms).collect::<Vec<_>>()
+        }
+    };
+
+    assert_eq!(
+        timestamps("/api/history?since_ts=1000&order=asc&tag.role=web").await,
+        vec![1000, 3000]
+    );
+    assert_eq!(
+        timestamps("/api/history?since_ts=1000&tag.role=db&tag.dc=ams1").await,
+        vec![4000]
+    );
+    assert_eq!(
+        timestamps("/api/history?since_ts=1000&order=asc&limit=1&tag.dc=ams1").await,
+        vec![4000]
+    );
+    assert!(timestamps("/api/history?since_ts=1000&tag.role=cache")
+        .await
+        .is_empty());
+    assert_eq!(
+        timestamps("/api/history?since_ts=1000&order=asc").await,
+        vec![1000, 2000, 3000, 4000]
+    );
+}
+
+#[tokio::test]
+async fn initial_window_is_configured_and_history_honors_window_ms() {
+    let dir = tempdir().unwrap();
+    let db = Arc::new(MetricsDb::new(&dir.path().join("test.db")).unwrap());
+    let buffer = Arc::new(MetricsBuffer::new(10));
+    for ts in [1000, 2000, 3000, 4000, 5000] {
+        buffer.push(sample_snapshot(ts));
+    }
+    let (stream_tx, _stream_rx) = tokio::sync::broadcast::channel(8);
+    let app = router(AppState {
+        buffer,
+        db,
+        store: None,
+        stream_tx,
+        shutdown: CancellationToken::new(),
+        collector: Default::default(),
+        thresholds: Default::default(),
+        alerts: Default::default(),
+        limits: Default::default(),
+        logs: Default::default(),
+        api_token: None,
+        net_scale: Default::default(),
+        requests: Default::default(),
+        initial_window_ms: 2000,
+        metrics_prefix: None,
+        sampling: Default::default(),
+        criticality: Default::default(),
+    });
+
+    let fetch = |uri: &'static str| {
+        let app = app.clone();
+        async move {
+            let response = app
+                .oneshot(
+                    axum::http::Request::builder()
+                        .uri(uri)
+                        .body(axum::body::Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), 200);
+            axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap()
+        }
+    };
+
+    let config: serde_json::Value = serde_json::from_slice(&fetch("/api/config").await).unwrap();
+    assert_eq!(config["initial_window_ms"], 2000);
+    assert_eq!(
+        config["max_browser_points"],
+        resource_monitor::api::DEFAULT_MAX_BROWSER_POINTS
+    );
+
+    let history: Vec<RpcMetricsSnapshot> =
+        serde_json::from_slice(&fetch("/api/history?window_ms=2000").await).unwrap();
+    let timestamps: Vec<u128> = history.iter().map(|s| s.timestamp_ms).collect();
+    assert_eq!(timestamps, vec![5000, 4000, 3000]);
+
+    // The later of since_ts and the window wins.
+    let history: Vec<RpcMetricsSnapshot> =
+        serde_json::from_slice(&fetch("/api/history?window_ms=2000&since_ts=4000").await).unwrap();
+    assert_eq!(history.len(), 2);
+}
+
+#[tokio::test]
+async fn base_path_moves_every_route_under_the_prefix() {
+    use resource_monitor::web::{normalize_base_path, with_base_path};
+
+    let dir = tempdir().unwrap();
+    let db = Arc::new(MetricsDb::new(&dir.path().join("test.db")).unwrap());
+    let (stream_tx, _stream_rx) = tokio::sync::broadcast::channel(8);
+    let base_path = normalize_base_path("/monitor/").unwrap();
+    assert_eq!(base_path, "/monitor");
+    let app = with_base_path(
+        router(AppState {
+            buffer: Arc::new(MetricsBuffer::new(10)),
+            db,
+            store: None,
+            stream_tx,
+            shutdown: CancellationToken::new(),
+            collector: Default::default(),
+            thresholds: Default::default(),
+            alerts: Default::default(),
+            limits: Default::default(),
+            logs: Default::default(),
+            api_token: None,
+            net_scale: Default::default(),
+            requests: Default::default(),
+            initial_window_ms: 0,
+            metrics_prefix: None,
+            sampling: Default::default(),
+            criticality: Default::default(),
+        }),
+        &base_path,
+    );
+
+    for (uri, status) in [
+        ("/monitor/api/health", 200),
+        ("/monitor", 200),
+        ("/monitor/", 200),
+        ("/api/health", 404),
+        ("/", 404),
+    ] {
+        let response = app
+            .clone()
+            .oneshot(
+                axum::http::Request::builder()
+                    .uri(uri)
+                    .body(axum::body::Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), status, "{uri}");
+    }
+
+    assert_eq!(normalize_base_path("/").unwrap(), "");
+    assert!(normalize_base_path("/a b").is_err());
+    assert!(normalize_base_path("/../x").is_err());
 }
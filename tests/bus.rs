@@ -0,0 +1,172 @@
+use resource_monitor::bus::{
+    publish_snapshot, register_storage_and_stream_subscriber, register_storage_subscriber,
+    TransformChain,
+};
+use resource_monitor::logs::LogRing;
+use resource_monitor::metrics::{
+    CpuMetrics, DiskMetrics, MemoryMetrics, MetricsSnapshot, NetworkMetrics,
+};
+use resource_monitor::storage::MetricsBuffer;
+use std::sync::Arc;
+
+fn sample(ts: u128) -> MetricsSnapshot {
+    MetricsSnapshot {
+        timestamp_ms: ts,
+        sample_interval_ms: 1000.0,
+        cpu: CpuMetrics {
+            total_usage_pct: 10.0,
+            per_core_usage_pct: vec![10.0, 20.0],
+            load_avg_1: Some(0.1),
+            load_avg_5: Some(0.2),
+            load_avg_15: Some(0.3),
+            temperature_celsius: None,
+            breakdown: None,
+            per_socket_usage_pct: None,
+            per_core_changes: None,
+            busy_cores: None,
+        },
+        memory: MemoryMetrics {
+            total_bytes: 100,
+            used_bytes: 50,
+            available_bytes: 50,
+            swap_total_bytes: 0,
+            swap_used_bytes: 0,
+            swap_in_bytes_per_sec: None,
+            swap_out_bytes_per_sec: None,
+        },
+        network: NetworkMetrics {
+            rx_bytes_total: 1000,
+            tx_bytes_total: 2000,
+            rx_bytes_per_sec: 10.0,
+            tx_bytes_per_sec: 20.0,
+        },
+        disk: DiskMetrics {
+            total_bytes: 100,
+            available_bytes: 50,
+            used_pct: 50.0,
+            used_bytes: None,
+        },
+        battery: None,
+        gpu: None,
+        scheduler: None,
+        watched_process: None,
+        net_top_processes: None,
+        pressure_score: None,
+        source: None,
+        resumed: false,
+        tags: Default::default(),
+    }
+}
+
+#[test]
+fn transforms_apply_in_order_before_storage() {
+    let buffer = Arc::new(MetricsBuffer::new(10));
+    let _activity = register_storage_subscriber(buffer.clone());
+
+    let mut chain = TransformChain::default();
+    chain.push(|mut snap| {
+        snap.network = NetworkMetrics {
+            rx_bytes_total: 0,
+            tx_bytes_total: 0,
+            rx_bytes_per_sec: 0.0,
+            tx_bytes_per_sec: 0.0,
+        };
+        snap
+    });
+    chain.push(|mut snap| {
+        snap.cpu.per_core_usage_pct.clear();
+        snap
+    });
+    publish_snapshot(chain.apply(sample(1000)));
+
+    publish_snapshot(TransformChain::default().apply(sample(2000)));
+
+    let stored = buffer.history(None);
+    assert_eq!(stored.len(), 2);
+    assert_eq!(stored[0].network.rx_bytes_per_sec, 0.0);
+    assert_eq!(stored[0].network.tx_bytes_total, 0);
+    assert!(stored[0].cpu.per_core_usage_pct.is_empty());
+    assert_eq!(stored[1].network.rx_bytes_per_sec, 10.0);
+    assert_eq!(stored[1].cpu.per_core_usage_pct.len(), 2);
+}
+
+#[test]
+fn stream_without_receivers_stores_snapshot_without_warning() {
+    use tracing_subscriber::layer::SubscriberExt;
+
+    let ring = Arc::new(LogRing::new(16));
+    let _guard =
+        tracing::subscriber::set_default(tracing_subscriber::registry().with(ring.layer()));
+
+    let buffer = Arc::new(MetricsBuffer::new(10));
+    let (stream_tx, stream_rx) = tokio::sync::broadcast::channel(4);
+    drop(stream_rx);
+    let _activity = register_storage_and_stream_subscriber(buffer.clone(), stream_tx);
+
+    publish_snapshot(sample(1000));
+    publish_snapshot(sample(2000));
+
+    assert_eq!(buffer.history(None).len(), 2);
+    let warnings: Vec<_> = ring
+        .recent(None)
+        .into_iter()
+        .filter(|e| e.level == "WARN" || e.level == "ERROR")
+        .collect();
+    assert!(warnings.is_empty(), "unexpected warnings: {:?}", warnings);
+}
+
+#[cfg(unix)]
+#[test]
+fn sink_writes_published_snapshot_to_datagram_socket() {
+    use resource_monitor::sink::{register_sink_subscriber, SocketSink};
+    use std::os::unix::net::UnixDatagram;
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("sink.sock");
+    let reader = UnixDatagram::bind(&path).unwrap();
+    reader
+        .set_read_timeout(Some(std::time::Duration::from_secs(5)))
+        .unwrap();
+    let _activity = register_sink_subscriber(SocketSink::open(&path).unwrap());
+
+    publish_snapshot(sample(1000));
+
+    let mut buf = vec![0; 64 * 1024];
+    let n = reader.recv(&mut buf).unwrap();
+    assert_eq!(buf[n - 1], b'\n');
+    let json: serde_json::Value = serde_json::from_slice(&buf[..n - 1]).unwrap();
+    assert_eq!(json["timestamp_ms"], 1000);
+
+    // With the reader gone the snapshot is dropped instead of blocking.
+    drop(reader);
+    publish_snapshot(sample(2000));
+}
+
+#[test]
+fn statsd_sends_gauges_for_published_snapshot() {
+    use resource_monitor::statsd::{register_statsd_subscriber, StatsdSink};
+    use std::net::UdpSocket;
+
+    let agent = UdpSocket::bind("127.0.0.1:0").unwrap();
+    agent
+        .set_read_timeout(Some(std::time::Duration::from_secs(5)))
+        .unwrap();
+    let sink = StatsdSink::open(agent.local_addr().unwrap(), Some("host".to_string())).unwrap();
+    let _activity = register_statsd_subscriber(sink);
+
+    publish_snapshot(sample(1000));
+
+    let mut buf = [0; 1500];
+    let n = agent.recv(&mut buf).unwrap();
+    let packet = std::str::from_utf8(&buf[..n]).unwrap();
+    assert_eq!(
+        packet.lines().collect::<Vec<_>>(),
+        [
+            "host.cpu.total:10|g",
+            "host.memory.used_pct:50|g",
+            "host.network.rx_bytes_per_sec:10|g",
+            "host.network.tx_bytes_per_sec:20|g",
+            "host.disk.used_pct:50|g",
+        ]
+    );
+}
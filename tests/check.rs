@@ -0,0 +1,88 @@
+use std::net::TcpListener;
+use std::process::Command;
+use tempfile::tempdir;
+
+fn server_check(rpc_addr: &str, db_dir: &std::path::Path) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_server"))
+        .args(["--check", "--no-http", "--rpc-addr", rpc_addr, "--db-path"])
+        .arg(db_dir.join("metrics.db"))
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn check_fails_when_rpc_port_in_use() {
+    let dir = tempdir().unwrap();
+    let taken = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = taken.local_addr().unwrap().to_string();
+
+    let output = server_check(&addr, dir.path());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(!output.status.success());
+    assert!(
+        stdout.contains(&format!("[FAIL] rpc bind: cannot bind {addr}")),
+        "unexpected report:\n{stdout}"
+    );
+    assert!(stdout.contains("check failed: 1 of"));
+}
+
+#[test]
+fn check_passes_with_free_ports() {
+    let dir = tempdir().unwrap();
+
+    let output = server_check("127.0.0.1:0", dir.path());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(output.status.success(), "unexpected report:\n{stdout}");
+    assert!(stdout.contains("[ ok ] rpc bind"));
+    assert!(stdout.ends_with("check passed\n"));
+    assert!(
+        !dir.path().join("metrics.db").exists(),
+        "--check created the database"
+    );
+}
+
+#[test]
+fn check_opens_an_existing_database_without_touching_it() {
+    let dir = tempdir().unwrap();
+    let db_path = dir.path().join("metrics.db");
+    drop(resource_monitor::db::MetricsDb::new(&db_path).unwrap());
+
+    let output = server_check("127.0.0.1:0", dir.path());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "unexpected report:\n{stdout}");
+    assert!(stdout.contains("[ ok ] database"), "{stdout}");
+
+    std::fs::write(&db_path, "not a database, just some text").unwrap();
+    let output = server_check("127.0.0.1:0", dir.path());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!output.status.success());
+    assert!(stdout.contains("[FAIL] database"), "{stdout}");
+    assert_eq!(
+        std::fs::read_to_string(&db_path).unwrap(),
+        "not a database, just some text"
+    );
+}
+
+#[test]
+fn startup_rejects_invalid_arguments_before_opening_anything() {
+    let dir = tempdir().unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_server"))
+        .args([
+            "--no-http",
+            "--standalone",
+            "--interval-ms",
+            "0",
+            "--db-path",
+        ])
+        .arg(dir.path().join("metrics.db"))
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert!(
+        !dir.path().join("metrics.db").exists(),
+        "invalid arguments still created the database"
+    );
+}
@@ -0,0 +1,51 @@
+use resource_monitor::config::{Aggregation, Aggregations, CpuTotalMethod, Tag};
+
+#[test]
+fn cpu_total_methods_over_known_cores() {
+    // One pegged core among nineteen lightly loaded ones.
+    let mut per_core: Vec<f32> = (1..=19).map(|i| i as f32).collect();
+    per_core.push(100.0);
+
+    assert_eq!(CpuTotalMethod::Mean.aggregate(&per_core), 14.5);
+    assert_eq!(CpuTotalMethod::Max.aggregate(&per_core), 100.0);
+    // Nearest rank: the 19th of 20 sorted values.
+    assert_eq!(CpuTotalMethod::P95.aggregate(&per_core), 19.0);
+
+    assert_eq!(CpuTotalMethod::P95.aggregate(&[42.0]), 42.0);
+    for method in [
+        CpuTotalMethod::Mean,
+        CpuTotalMethod::Max,
+        CpuTotalMethod::P95,
+    ] {
+        assert_eq!(method.aggregate(&[]), 0.0);
+    }
+}
+
+#[test]
+fn downsample_aggregations_default_per_metric_and_parse_overrides() {
+    let defaults = Aggregations::default();
+    assert_eq!(defaults.for_metric("net_rx"), Aggregation::Max);
+    assert_eq!(defaults.for_metric("cpu"), Aggregation::Mean);
+    assert_eq!(defaults.for_metric("memory"), Aggregation::Mean);
+    assert_eq!(defaults.for_metric("disk"), Aggregation::Last);
+
+    let parsed: Aggregations = "net=mean, cpu=MAX".parse().unwrap();
+    assert_eq!(parsed.for_metric("net_tx"), Aggregation::Mean);
+    assert_eq!(parsed.for_metric("cpu"), Aggregation::Max);
+    assert_eq!(parsed.for_metric("disk"), Aggregation::Last);
+
+    assert!("cpu=median".parse::<Aggregations>().is_err());
+    assert!("iops=max".parse::<Aggregations>().is_err());
+    assert!("cpu".parse::<Aggregations>().is_err());
+}
+
+#[test]
+fn tags_parse_from_key_value() {
+    let tag: Tag = "role = web".parse().unwrap();
+    assert_eq!(tag.key, "role");
+    assert_eq!(tag.value, "web");
+    let tag: Tag = "note=a=b".parse().unwrap();
+    assert_eq!(tag.value, "a=b");
+    assert!("role".parse::<Tag>().is_err());
+    assert!("=web".parse::<Tag>().is_err());
+}
@@ -7,13 +7,18 @@ use tempfile::tempdir;
 fn sample(ts: u128) -> MetricsSnapshot {
     MetricsSnapshot {
         timestamp_ms: ts,
+        sample_interval_ms: 1000.0,
         cpu: CpuMetrics {
             total_usage_pct: 10.0,
             per_core_usage_pct: vec![10.0, 20.0],
-            load_avg_1: 0.1,
-            load_avg_5: 0.2,
-            load_avg_15: 0.3,
+            load_avg_1: Some(0.1),
+            load_avg_5: Some(0.2),
+            load_avg_15: Some(0.3),
             temperature_celsius: Some(50.0),
+            breakdown: None,
+            per_socket_usage_pct: None,
+            per_core_changes: None,
+            busy_cores: None,
         },
         memory: MemoryMetrics {
             total_bytes: 100,
@@ -21,6 +26,8 @@ fn sample(ts: u128) -> MetricsSnapshot {
             available_bytes: 50,
             swap_total_bytes: 4096,
             swap_used_bytes: 1024,
+            swap_in_bytes_per_sec: None,
+            swap_out_bytes_per_sec: None,
         },
         network: NetworkMetrics {
             rx_bytes_total: 1000,
@@ -32,9 +39,17 @@ fn sample(ts: u128) -> MetricsSnapshot {
             total_bytes: 500_000_000_000,
             available_bytes: 200_000_000_000,
             used_pct: 60.0,
+            used_bytes: None,
         },
         battery: None,
         gpu: None,
+        scheduler: None,
+        watched_process: None,
+        net_top_processes: None,
+        pressure_score: None,
+        source: None,
+        resumed: false,
+        tags: Default::default(),
     }
 }
 
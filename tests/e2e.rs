@@ -0,0 +1,177 @@
+use axum::extract::State;
+use axum::routing::get;
+use axum::{Json, Router};
+use resource_monitor::metrics::{
+    CpuMetrics, DiskMetrics, MemoryMetrics, MetricsSnapshot, NetworkMetrics, RpcMetricsSnapshot,
+};
+use resource_monitor::rpc::{run_rpc_client_streamer, serve_rpc, MetricsRpcServer};
+use resource_monitor::runtime;
+use resource_monitor::storage::MetricsBuffer;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+
+fn snapshot(ts: u128) -> MetricsSnapshot {
+    MetricsSnapshot {
+        timestamp_ms: ts,
+        sample_interval_ms: 1000.0,
+        cpu: CpuMetrics {
+            total_usage_pct: 10.0,
+            per_core_usage_pct: vec![10.0, 20.0],
+            load_avg_1: Some(0.1),
+            load_avg_5: Some(0.2),
+            load_avg_15: Some(0.3),
+            temperature_celsius: None,
+            breakdown: None,
+            per_socket_usage_pct: None,
+            per_core_changes: None,
+            busy_cores: None,
+        },
+        memory: MemoryMetrics {
+            total_bytes: 100,
+            used_bytes: 50,
+            available_bytes: 50,
+            swap_total_bytes: 0,
+            swap_used_bytes: 0,
+            swap_in_bytes_per_sec: None,
+            swap_out_bytes_per_sec: None,
+        },
+        network: NetworkMetrics {
+            rx_bytes_total: 1000,
+            tx_bytes_total: 2000,
+            rx_bytes_per_sec: 10.0,
+            tx_bytes_per_sec: 20.0,
+        },
+        disk: DiskMetrics {
+            total_bytes: 1000,
+            available_bytes: 400,
+            used_pct: 60.0,
+            used_bytes: None,
+        },
+        battery: None,
+        gpu: None,
+        scheduler: None,
+        watched_process: None,
+        net_top_processes: None,
+        pressure_score: None,
+        source: None,
+        resumed: false,
+        tags: Default::default(),
+    }
+}
+
+/// What the client holds of the server's stream, in arrival order.
+type ClientBuffer = Arc<RwLock<Vec<RpcMetricsSnapshot>>>;
+
+fn client_router(buffer: ClientBuffer) -> Router {
+    Router::new()
+        .route(
+            "/api/metrics",
+            get(|State(buffer): State<ClientBuffer>| async move {
+                Json(buffer.read().unwrap().last().cloned())
+            }),
+        )
+        .route(
+            "/api/history",
+            get(|State(buffer): State<ClientBuffer>| async move {
+                Json(buffer.read().unwrap().clone())
+            }),
+        )
+        .with_state(buffer)
+}
+
+async fn get_json(url: String) -> serde_json::Value {
+    let resp = reqwest::get(url).await.unwrap();
+    assert_eq!(resp.status(), 200);
+    serde_json::from_str(&resp.text().await.unwrap()).unwrap()
+}
+
+async fn wait_for(buffer: &ClientBuffer, len: usize) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while buffer.read().unwrap().len() < len {
+        assert!(
+            Instant::now() < deadline,
+            "client buffer stuck at {:?}",
+            buffer
+                .read()
+                .unwrap()
+                .iter()
+                .map(|s| s.timestamp_ms)
+                .collect::<Vec<_>>()
+        );
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+}
+
+#[tokio::test]
+async fn seeded_server_snapshots_reach_the_client_http_api() {
+    let cancel = CancellationToken::new();
+    let server_buffer = Arc::new(MetricsBuffer::new(10));
+    for ts in [1000, 2000, 3000] {
+        server_buffer.push(snapshot(ts));
+    }
+    let (stream_tx, _) = broadcast::channel::<RpcMetricsSnapshot>(8);
+    let rpc_listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let rpc_addr = rpc_listener.local_addr().unwrap();
+    let server = tokio::spawn(serve_rpc(
+        rpc_listener,
+        MetricsRpcServer::new(server_buffer.clone(), stream_tx.clone()),
+        None,
+        cancel.clone(),
+    ));
+
+    let client_buffer: ClientBuffer = Arc::default();
+    let streamer = tokio::spawn(run_rpc_client_streamer(
+        rpc_addr,
+        false,
+        Duration::from_millis(100),
+        cancel.clone(),
+        {
+            let client_buffer = client_buffer.clone();
+            move |snap| client_buffer.write().unwrap().push(snap)
+        },
+    ));
+    let http_listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let http_addr = http_listener.local_addr().unwrap();
+    let web = tokio::spawn(runtime::serve_with_drain(
+        http_listener,
+        client_router(client_buffer.clone()),
+        cancel.clone(),
+        Duration::from_secs(1),
+    ));
+
+    // A fresh stream starts at the newest seeded snapshot...
+    wait_for(&client_buffer, 1).await;
+    let latest = get_json(format!("http://{http_addr}/api/metrics")).await;
+    assert_eq!(latest["timestamp_ms"], 3000);
+
+    // ...and then follows what the server publishes.
+    let published = snapshot(4000);
+    server_buffer.push(published.clone());
+    let _ = stream_tx.send(published.to_rpc_format());
+    wait_for(&client_buffer, 2).await;
+    let latest = get_json(format!("http://{http_addr}/api/metrics")).await;
+    assert_eq!(latest["timestamp_ms"], 4000);
+    let history = get_json(format!("http://{http_addr}/api/history")).await;
+    let timestamps: Vec<u64> = history
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|s| s["timestamp_ms"].as_u64().unwrap())
+        .collect();
+    assert_eq!(timestamps, vec![3000, 4000]);
+
+    cancel.cancel();
+    for (name, task) in [("RPC client", streamer), ("RPC server", server)] {
+        tokio::time::timeout(Duration::from_secs(5), task)
+            .await
+            .unwrap_or_else(|_| panic!("{name} did not stop after cancellation"))
+            .unwrap();
+    }
+    tokio::time::timeout(Duration::from_secs(5), web)
+        .await
+        .expect("client HTTP server did not stop after cancellation")
+        .unwrap()
+        .unwrap();
+}
@@ -0,0 +1,285 @@
+use futures::StreamExt;
+use resource_monitor::config::FsyncPolicy;
+use resource_monitor::journal::{
+    replay, replay_from, rotated_path, run_journal_flusher, run_rpc_recorder_with, ReplayStats,
+    Rotation, SnapshotJournal,
+};
+use resource_monitor::metrics::{
+    CpuMetrics, DiskMetrics, MemoryMetrics, MetricsSnapshot, NetworkMetrics, RpcMetricsSnapshot,
+};
+use resource_monitor::rpc::{MetricsRpc, MetricsRpcClient, MetricsRpcServer};
+use resource_monitor::storage::MetricsBuffer;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tarpc::server::{self, Channel};
+use tempfile::tempdir;
+use tokio_util::sync::CancellationToken;
+
+fn sample_snapshot(ts: u128) -> MetricsSnapshot {
+    MetricsSnapshot {
+        timestamp_ms: ts,
+        sample_interval_ms: 1000.0,
+        cpu: CpuMetrics {
+            total_usage_pct: 12.5,
+            per_core_usage_pct: vec![10.0, 15.0],
+            load_avg_1: Some(0.1),
+            load_avg_5: Some(0.2),
+            load_avg_15: Some(0.3),
+            temperature_celsius: None,
+            breakdown: None,
+            per_socket_usage_pct: None,
+            per_core_changes: None,
+            busy_cores: None,
+        },
+        memory: MemoryMetrics {
+            total_bytes: 100,
+            used_bytes: 50,
+            available_bytes: 50,
+            swap_total_bytes: 0,
+            swap_used_bytes: 0,
+            swap_in_bytes_per_sec: None,
+            swap_out_bytes_per_sec: None,
+        },
+        network: NetworkMetrics {
+            rx_bytes_total: 1000,
+            tx_bytes_total: 2000,
+            rx_bytes_per_sec: 10.0,
+            tx_bytes_per_sec: 20.0,
+        },
+        disk: DiskMetrics {
+            total_bytes: 1000,
+            available_bytes: 400,
+            used_pct: 60.0,
+            used_bytes: None,
+        },
+        battery: None,
+        gpu: None,
+        scheduler: None,
+        watched_process: None,
+        net_top_processes: None,
+        pressure_score: None,
+        source: None,
+        resumed: false,
+        tags: Default::default(),
+    }
+}
+
+fn read_timestamps(path: &std::path::Path) -> Vec<u128> {
+    std::fs::read_to_string(path)
+        .unwrap()
+        .lines()
+        .map(|line| {
+            serde_json::from_str::<MetricsSnapshot>(line)
+                .unwrap()
+                .timestamp_ms
+        })
+        .collect()
+}
+
+#[test]
+fn always_policy_writes_each_snapshot_immediately() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("journal.ndjson");
+    let mut journal = SnapshotJournal::open(&path, FsyncPolicy::Always).unwrap();
+
+    journal.append(&sample_snapshot(1000)).unwrap();
+    assert_eq!(read_timestamps(&path), vec![1000]);
+    journal.append(&sample_snapshot(2000)).unwrap();
+    assert_eq!(read_timestamps(&path), vec![1000, 2000]);
+}
+
+#[tokio::test]
+async fn interval_policy_writes_on_the_flush_tick() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("journal.ndjson");
+    let journal = Arc::new(Mutex::new(
+        SnapshotJournal::open(&path, FsyncPolicy::Interval).unwrap(),
+    ));
+    let cancel = CancellationToken::new();
+    let flusher = tokio::spawn(run_journal_flusher(
+        journal.clone(),
+        Duration::from_millis(300),
+        cancel.clone(),
+    ));
+
+    journal
+        .lock()
+        .unwrap()
+        .append(&sample_snapshot(1000))
+        .unwrap();
+    assert!(read_timestamps(&path).is_empty(), "buffered until the tick");
+
+    tokio::time::sleep(Duration::from_millis(600)).await;
+    assert_eq!(read_timestamps(&path), vec![1000]);
+
+    journal
+        .lock()
+        .unwrap()
+        .append(&sample_snapshot(2000))
+        .unwrap();
+    cancel.cancel();
+    flusher.await.unwrap();
+    assert_eq!(
+        read_timestamps(&path),
+        vec![1000, 2000],
+        "flushed on shutdown"
+    );
+}
+
+async fn wait_for_lines(path: &std::path::Path, lines: usize) {
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+    while std::fs::read_to_string(path).unwrap().lines().count() < lines {
+        assert!(
+            tokio::time::Instant::now() < deadline,
+            "{lines} snapshots recorded"
+        );
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+}
+
+#[test]
+fn rotation_moves_full_files_aside_and_keeps_the_newest() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("record.ndjson");
+    let line_len = serde_json::to_vec(&sample_snapshot(1000)).unwrap().len() as u64 + 1;
+    let mut journal = SnapshotJournal::open(&path, FsyncPolicy::Never)
+        .unwrap()
+        .with_rotation(Rotation {
+            max_bytes: line_len * 2,
+            keep: 2,
+        });
+
+    for ts in 1..=7 {
+        journal.append(&sample_snapshot(ts * 1000)).unwrap();
+    }
+    journal.flush().unwrap();
+
+    assert_eq!(read_timestamps(&path), vec![7000]);
+    assert_eq!(read_timestamps(&rotated_path(&path, 1)), vec![5000, 6000]);
+    assert_eq!(read_timestamps(&rotated_path(&path, 2)), vec![3000, 4000]);
+    assert!(!rotated_path(&path, 3).exists());
+}
+
+#[tokio::test]
+async fn recorder_appends_snapshots_received_over_rpc() {
+    let buffer = Arc::new(MetricsBuffer::new(10));
+    buffer.push(sample_snapshot(1000));
+    let (stream_tx, _) = tokio::sync::broadcast::channel::<RpcMetricsSnapshot>(16);
+    let server_impl = MetricsRpcServer::new(buffer.clone(), stream_tx.clone());
+    // Each (re)connect gets a fresh in-memory channel to the same server.
+    let connect = move || {
+        let (client_transport, server_transport) = tarpc::transport::channel::unbounded();
+        tokio::spawn(
+            server::BaseChannel::with_defaults(server_transport)
+                .execute(server_impl.clone().serve())
+                .for_each(|fut| async move {
+                    tokio::spawn(fut);
+                }),
+        );
+        std::future::ready(Ok(MetricsRpcClient::new(
+            tarpc::client::Config::default(),
+            client_transport,
+        )
+        .spawn()))
+    };
+
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("record.ndjson");
+    let journal = Arc::new(Mutex::new(
+        SnapshotJournal::open(&path, FsyncPolicy::Interval)
+            .unwrap()
+            .with_rotation(Rotation::default()),
+    ));
+    let cancel = CancellationToken::new();
+    let recorder = tokio::spawn(run_rpc_recorder_with(
+        journal,
+        connect,
+        Duration::from_millis(20),
+        Duration::from_millis(50),
+        cancel.clone(),
+    ));
+
+    wait_for_lines(&path, 1).await;
+    buffer.push(sample_snapshot(2000));
+    let _ = stream_tx.send(sample_snapshot(2000).to_rpc_format());
+    wait_for_lines(&path, 2).await;
+    cancel.cancel();
+    tokio::time::timeout(Duration::from_secs(5), recorder)
+        .await
+        .unwrap()
+        .unwrap();
+
+    let recorded: Vec<RpcMetricsSnapshot> = std::fs::read_to_string(&path)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(
+        recorded.iter().map(|s| s.timestamp_ms).collect::<Vec<_>>(),
+        vec![1000, 2000]
+    );
+    assert_eq!(
+        serde_json::to_value(&recorded[1]).unwrap(),
+        serde_json::to_value(sample_snapshot(2000).to_rpc_format()).unwrap()
+    );
+}
+
+fn journal_lines(timestamps: &[u128]) -> Vec<u8> {
+    let mut text = Vec::new();
+    for &ts in timestamps {
+        serde_json::to_writer(&mut text, &sample_snapshot(ts)).unwrap();
+        text.push(b'\n');
+    }
+    text
+}
+
+#[test]
+fn replay_skips_a_truncated_final_line() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("journal.ndjson");
+    let mut text = journal_lines(&[1000, 2000, 3000]);
+    let last = journal_lines(&[4000]);
+    text.extend_from_slice(&last[..last.len() / 2]);
+    std::fs::write(&path, &text).unwrap();
+
+    let mut loaded = Vec::new();
+    let stats = replay(&path, |s| loaded.push(s.timestamp_ms)).unwrap();
+    assert_eq!(loaded, vec![1000, 2000, 3000]);
+    assert_eq!(
+        stats,
+        ReplayStats {
+            loaded: 3,
+            skipped: 1
+        }
+    );
+}
+
+#[test]
+fn replay_of_a_compressed_journal_keeps_frames_before_a_cut() {
+    // One frame per line, as appending compressed writers produce; the last
+    // is cut mid-frame.
+    let mut compressed = Vec::new();
+    for ts in [1000, 2000, 3000] {
+        compressed.extend(zstd::bulk::compress(&journal_lines(&[ts]), 3).unwrap());
+    }
+    let last = zstd::bulk::compress(&journal_lines(&[4000]), 3).unwrap();
+    compressed.extend_from_slice(&last[..last.len() / 2]);
+
+    let mut loaded = Vec::new();
+    let stats = replay_from(&compressed[..], |s| loaded.push(s.timestamp_ms)).unwrap();
+    assert_eq!(loaded, vec![1000, 2000, 3000]);
+    assert_eq!(stats.loaded, 3);
+
+    // A whole frame whose text ends in a truncated line.
+    let mut text = journal_lines(&[1000, 2000]);
+    text.extend_from_slice(b"{\"timestamp_ms\":30");
+    let compressed = zstd::bulk::compress(&text, 3).unwrap();
+    let stats = replay_from(&compressed[..], |_| {}).unwrap();
+    assert_eq!(
+        stats,
+        ReplayStats {
+            loaded: 2,
+            skipped: 1
+        }
+    );
+}
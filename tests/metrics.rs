@@ -3,13 +3,18 @@ use resource_monitor::metrics::*;
 fn base_snapshot() -> MetricsSnapshot {
     MetricsSnapshot {
         timestamp_ms: 1700000000000,
+        sample_interval_ms: 1000.0,
         cpu: CpuMetrics {
             total_usage_pct: 45.5,
             per_core_usage_pct: vec![30.0, 60.0, 40.0, 50.0],
-            load_avg_1: 1.5,
-            load_avg_5: 1.2,
-            load_avg_15: 0.8,
+            load_avg_1: Some(1.5),
+            load_avg_5: Some(1.2),
+            load_avg_15: Some(0.8),
             temperature_celsius: None,
+            breakdown: None,
+            per_socket_usage_pct: None,
+            per_core_changes: None,
+            busy_cores: None,
         },
         memory: MemoryMetrics {
             total_bytes: 16_000_000_000,
@@ -17,6 +22,8 @@ fn base_snapshot() -> MetricsSnapshot {
             available_bytes: 8_000_000_000,
             swap_total_bytes: 4_000_000_000,
             swap_used_bytes: 1_000_000_000,
+            swap_in_bytes_per_sec: None,
+            swap_out_bytes_per_sec: None,
         },
         network: NetworkMetrics {
             rx_bytes_total: 1_000_000,
@@ -28,9 +35,17 @@ fn base_snapshot() -> MetricsSnapshot {
             total_bytes: 500_000_000_000,
             available_bytes: 200_000_000_000,
             used_pct: 60.0,
+            used_bytes: None,
         },
         battery: None,
         gpu: None,
+        scheduler: None,
+        watched_process: None,
+        net_top_processes: None,
+        pressure_score: None,
+        source: None,
+        resumed: false,
+        tags: Default::default(),
     }
 }
 
@@ -69,6 +84,25 @@ fn to_rpc_format_cpu_values() {
     assert_eq!(cpu_cores.legend[3].name, "C3");
 }
 
+#[test]
+fn decimals_round_percentage_series_when_serialized() {
+    let mut snap = base_snapshot();
+    snap.cpu.total_usage_pct = 42.857143;
+    snap.network.rx_bytes_per_sec = 1234.5678;
+    let series_json = |rpc: &RpcMetricsSnapshot, name: &str| {
+        let series = rpc.data.iter().find(|s| s.name == name).unwrap();
+        serde_json::to_string(&series.series).unwrap()
+    };
+
+    let rounded = snap.to_rpc_format().with_decimals(Some(1));
+    assert_eq!(series_json(&rounded, "cpu_total"), "[42.9]");
+    // Only percentages are rounded.
+    assert!(series_json(&rounded, "network").starts_with("[1234.56"));
+
+    let full = snap.to_rpc_format().with_decimals(None);
+    assert_eq!(series_json(&full, "cpu_total"), "[42.857143]");
+}
+
 #[test]
 fn to_rpc_format_memory_percentage() {
     let snap = base_snapshot();
@@ -288,13 +322,627 @@ fn load_avg_in_rpc() {
     assert_eq!(la.legend[2].name, "15m");
 }
 
+#[test]
+fn missing_load_average_serializes_as_null_and_is_not_charted() {
+    use resource_monitor::config::DisplayUnits;
+    use resource_monitor::console::{render_frame, Peaks};
+
+    // What the collector publishes where the OS has no load average.
+    let mut snap = base_snapshot();
+    snap.cpu.load_avg_1 = None;
+    snap.cpu.load_avg_5 = None;
+    snap.cpu.load_avg_15 = None;
+
+    let json = serde_json::to_value(&snap).unwrap();
+    for field in ["load_avg_1", "load_avg_5", "load_avg_15"] {
+        assert!(json["cpu"][field].is_null(), "{field}");
+    }
+    let back: MetricsSnapshot = serde_json::from_value(json).unwrap();
+    assert_eq!(back.cpu.load_avg_1, None);
+
+    assert!(snap
+        .to_rpc_format()
+        .data
+        .iter()
+        .all(|s| s.name != "load_avg"));
+    let frame = render_frame(
+        Some(&snap),
+        &Peaks::default(),
+        DisplayUnits::default(),
+        None,
+        None,
+    );
+    assert!(strip_ansi(&frame[3]).ends_with("Load avg: N/A / N/A / N/A"));
+}
+
 #[test]
 fn error_response_serializes() {
     let err = ErrorResponse {
-        error: "test error".to_string(),
+        code: ErrorCode::InvalidParameter,
+        message: "test error".to_string(),
+        details: None,
     };
     let json = serde_json::to_string(&err).unwrap();
-    assert!(json.contains("test error"));
+    assert_eq!(
+        json,
+        r#"{"code":"invalid_parameter","message":"test error"}"#
+    );
     let deser: ErrorResponse = serde_json::from_str(&json).unwrap();
-    assert_eq!(deser.error, "test error");
+    assert_eq!(deser.code, ErrorCode::InvalidParameter);
+    assert_eq!(deser.message, "test error");
+}
+
+#[test]
+fn cpu_breakdown_in_rpc() {
+    let mut snap = base_snapshot();
+    snap.cpu.breakdown = Some(CpuTimeBreakdown {
+        user_pct: 40.0,
+        system_pct: 10.0,
+        iowait_pct: 5.0,
+        steal_pct: 2.0,
+        idle_pct: 43.0,
+    });
+    let rpc = snap.to_rpc_format();
+    let bd = rpc.data.iter().find(|s| s.name == "cpu_breakdown").unwrap();
+    assert_eq!(bd.series, vec![40.0, 10.0, 5.0, 2.0, 43.0]);
+    assert_eq!(bd.legend[2].name, "iowait");
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn cpu_breakdown_from_proc_stat_deltas() {
+    use resource_monitor::procfs::CpuTimes;
+
+    let prev = CpuTimes::parse_line("cpu  1000 100 500 8000 200 50 50 100 0 0").unwrap();
+    let now = CpuTimes::parse_line("cpu  1300 100 600 8400 300 50 100 150 0 0").unwrap();
+    let bd = now.breakdown_since(&prev).unwrap();
+
+    // Deltas: user 300, system 100+50, idle 400, iowait 100, steal 50 => 1000 total.
+    assert!((bd.user_pct - 30.0).abs() < 0.01);
+    assert!((bd.system_pct - 15.0).abs() < 0.01);
+    assert!((bd.idle_pct - 40.0).abs() < 0.01);
+    assert!((bd.iowait_pct - 10.0).abs() < 0.01);
+    assert!((bd.steal_pct - 5.0).abs() < 0.01);
+    let sum = bd.user_pct + bd.system_pct + bd.idle_pct + bd.iowait_pct + bd.steal_pct;
+    assert!((sum - 100.0).abs() < 0.01);
+
+    assert!(CpuTimes::parse_line("cpu0 1 2 3 4 5").is_none());
+    assert!(prev.breakdown_since(&now).is_none());
+}
+
+#[test]
+fn swap_rates_from_vmstat_deltas() {
+    use resource_monitor::procfs::{page_bytes, VmStat};
+
+    let prev = VmStat::parse("nr_free_pages 1000\npswpin 100\npswpout 40\npgfault 9\n").unwrap();
+    let now = VmStat::parse("nr_free_pages 900\npswpin 150\npswpout 60\npgfault 12\n").unwrap();
+    let (rate_in, rate_out) = now.swap_rates_since(&prev, 2.0).unwrap();
+
+    assert!(page_bytes().is_power_of_two() && page_bytes() >= 4096);
+    assert_eq!(rate_in, (50 * page_bytes()) as f32 / 2.0);
+    assert_eq!(rate_out, (20 * page_bytes()) as f32 / 2.0);
+    assert!(prev.swap_rates_since(&now, 2.0).is_none());
+    assert!(now.swap_rates_since(&prev, 0.0).is_none());
+    assert!(VmStat::parse("nr_free_pages 1000\n").is_none());
+}
+
+#[test]
+fn scheduler_rates_from_proc_stat_deltas() {
+    use resource_monitor::procfs::SchedStat;
+
+    let prev = SchedStat::parse(
+        "cpu  4705 356 584 3699 23 23 0 0 0 0\nintr 1000 20 0 5\nctxt 5000\nbtime 1700000000\n",
+    )
+    .unwrap();
+    let now = SchedStat::parse(
+        "cpu  4805 356 684 3799 23 23 0 0 0 0\nintr 1400 25 0 9\nctxt 8000\nbtime 1700000000\n",
+    )
+    .unwrap();
+    let rates = now.rates_since(&prev, 2.0).unwrap();
+
+    assert_eq!(rates.context_switches_per_sec, 1500.0);
+    assert_eq!(rates.interrupts_per_sec, 200.0);
+    assert!(prev.rates_since(&now, 2.0).is_none());
+    assert!(now.rates_since(&prev, 0.0).is_none());
+    assert!(SchedStat::parse("cpu  1 2 3 4 5\nctxt 10\n").is_none());
+}
+
+#[test]
+fn proc_stat_yields_cpu_times_and_scheduler_counters_in_one_pass() {
+    use resource_monitor::procfs::{CpuTimes, ProcStat, SchedStat};
+
+    let text =
+        "cpu  4705 356 584 3699 23 23 0 0 0 0\ncpu0 1 2 3 4 5 6 7 8\nintr 1000 20 0 5\nctxt 5000\n";
+    let stat = ProcStat::parse(text);
+    assert_eq!(stat.cpu_times, CpuTimes::parse_stat(text));
+    assert_eq!(stat.cpu_times.unwrap().user, 4705);
+    assert_eq!(
+        stat.sched,
+        Some(SchedStat {
+            ctxt: 5000,
+            intr: 1000
+        })
+    );
+    assert_eq!(ProcStat::parse(""), ProcStat::default());
+}
+
+#[test]
+fn aligned_timestamps_land_on_interval_grid() {
+    assert_eq!(
+        align_timestamp_ms(1_700_000_000_499, 1000),
+        1_700_000_000_000
+    );
+    assert_eq!(
+        align_timestamp_ms(1_700_000_000_500, 1000),
+        1_700_000_001_000
+    );
+    assert_eq!(
+        align_timestamp_ms(1_700_000_000_987, 250),
+        1_700_000_001_000
+    );
+    assert_eq!(align_timestamp_ms(1234, 0), 1234);
+
+    for interval in [100u128, 250, 1000, 5000] {
+        for jitter in 0..interval {
+            let ts = 1_700_000_000_000 + jitter;
+            assert_eq!(align_timestamp_ms(ts, interval) % interval, 0);
+        }
+    }
+}
+
+#[test]
+fn net_rate_bits_formatting() {
+    use resource_monitor::config::NetUnits;
+
+    assert_eq!(format_net_rate(1000.0, NetUnits::Bits), "8.00 Kb/s");
+    assert_eq!(format_net_rate(125_000.0, NetUnits::Bits), "1.00 Mb/s");
+    assert_eq!(format_net_rate(100.0, NetUnits::Bits), "800 b/s");
+    assert_eq!(format_net_rate(1000.0, NetUnits::Bytes), "1000 B/s");
+}
+
+#[test]
+fn with_net_units_bits_scales_network_series_only() {
+    use resource_monitor::config::NetUnits;
+
+    let rpc = base_snapshot()
+        .to_rpc_format()
+        .with_net_units(NetUnits::Bits);
+    let net = rpc.data.iter().find(|s| s.name == "network").unwrap();
+    assert_eq!(net.series, vec![400_000.0, 80_000.0]);
+    assert!(matches!(net.format, DisplayFormat::Bits { .. }));
+
+    let cpu = rpc.data.iter().find(|s| s.name == "cpu_total").unwrap();
+    assert_eq!(cpu.series, vec![45.5]);
+}
+
+#[test]
+fn tap_lines_in_tsv_and_json() {
+    use resource_monitor::config::TapFormat;
+    use resource_monitor::console::format_tap_line;
+
+    let first = base_snapshot().to_rpc_format();
+    let mut second = base_snapshot();
+    second.timestamp_ms += 1000;
+    second.cpu.total_usage_pct = 12.34;
+    second.network.rx_bytes_per_sec = 1234.6;
+    let second = second.to_rpc_format();
+
+    assert_eq!(
+        format_tap_line(&first, TapFormat::Tsv),
+        "1700000000000\t45.5\t50.0\t50000\t10000"
+    );
+    assert_eq!(
+        format_tap_line(&second, TapFormat::Tsv),
+        "1700000001000\t12.3\t50.0\t1235\t10000"
+    );
+
+    let json: serde_json::Value =
+        serde_json::from_str(&format_tap_line(&second, TapFormat::Json)).unwrap();
+    assert_eq!(json["timestamp_ms"].as_u64().unwrap(), 1700000001000);
+    assert!((json["cpu"].as_f64().unwrap() - 12.34).abs() < 1e-4);
+    assert_eq!(json["mem_pct"].as_f64().unwrap(), 50.0);
+    assert_eq!(json["tx"].as_f64().unwrap(), 10000.0);
+
+    let empty = RpcMetricsSnapshot {
+        timestamp_ms: 5,
+        sample_interval_ms: 0.0,
+        data: vec![],
+        source: None,
+        resumed: false,
+        tags: Default::default(),
+    };
+    assert_eq!(format_tap_line(&empty, TapFormat::Tsv), "5\t-\t-\t-\t-");
+}
+
+#[test]
+fn peak_hold_tracks_series_maximum() {
+    use resource_monitor::console::{peak, Peaks};
+
+    assert_eq!(peak([12.0, 97.3, 42.1]), Some(97.3));
+    assert_eq!(peak([f32::NAN, 5.0, f32::INFINITY]), Some(5.0));
+    assert_eq!(peak(std::iter::empty()), None);
+
+    let mut quiet = base_snapshot();
+    quiet.cpu.total_usage_pct = 20.0;
+    quiet.network.rx_bytes_per_sec = 5000.0;
+    let mut busy = base_snapshot();
+    busy.cpu.total_usage_pct = 97.3;
+    busy.network.rx_bytes_per_sec = 100.0;
+    let peaks = Peaks::from_snapshots(&[quiet, busy]);
+    assert_eq!(peaks.cpu_pct, Some(97.3));
+    assert_eq!(peaks.rx_bytes_per_sec, Some(5000.0));
+    assert_eq!(Peaks::from_snapshots(&[]), Peaks::default());
+}
+
+#[test]
+fn peak_tracker_holds_maxima_over_the_newest_snapshots() {
+    use resource_monitor::console::{PeakTracker, Peaks};
+    use resource_monitor::storage::MetricsBuffer;
+
+    let buffer = MetricsBuffer::new(10);
+    let mut tracker = PeakTracker::new(2);
+    assert_eq!(tracker.update(&buffer), Peaks::default());
+
+    let mut feed = |ts: u128, cpu: f32| {
+        let mut snap = base_snapshot();
+        snap.timestamp_ms = ts;
+        snap.cpu.total_usage_pct = cpu;
+        buffer.push(snap);
+        tracker.update(&buffer).cpu_pct
+    };
+    assert_eq!(feed(1000, 90.0), Some(90.0));
+    assert_eq!(feed(2000, 20.0), Some(90.0));
+    assert_eq!(feed(3000, 30.0), Some(30.0), "90 left the window");
+    assert_eq!(feed(4000, f32::NAN), Some(30.0));
+    assert_eq!(feed(5000, 10.0), Some(10.0));
+
+    // The same as computing the peaks over the window from scratch.
+    let snaps: Vec<_> = [40.0, 70.0, 10.0, 50.0]
+        .into_iter()
+        .map(|cpu| {
+            let mut snap = base_snapshot();
+            snap.cpu.total_usage_pct = cpu;
+            snap
+        })
+        .collect();
+    let mut tracker = PeakTracker::new(3);
+    for snap in &snaps {
+        tracker.push(snap);
+    }
+    assert_eq!(tracker.peaks(), Peaks::from_snapshots(&snaps[1..]));
+}
+
+/// Drops ANSI escape sequences (colors, cursor moves) from console output.
+fn strip_ansi(text: &str) -> String {
+    let mut out = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+#[test]
+fn tags_round_trip_through_serialization() {
+    let mut snap = base_snapshot();
+    let json = serde_json::to_value(&snap).unwrap();
+    assert!(json.get("tags").is_none(), "empty tags are not serialized");
+
+    snap.tags
+        .insert("datacenter".to_string(), "fra1".to_string());
+    snap.tags.insert("role".to_string(), "web".to_string());
+    let back: MetricsSnapshot =
+        serde_json::from_str(&serde_json::to_string(&snap).unwrap()).unwrap();
+    assert_eq!(back.tags, snap.tags);
+
+    let rpc: RpcMetricsSnapshot =
+        serde_json::from_str(&serde_json::to_string(&snap.to_rpc_format()).unwrap()).unwrap();
+    assert_eq!(rpc.tags, snap.tags);
+    assert_eq!(rpc.tags["role"], "web");
+}
+
+#[test]
+fn busy_cores_sums_per_core_usage() {
+    use resource_monitor::config::{CpuDisplay, DisplayUnits};
+    use resource_monitor::console::{render_frame, Peaks};
+
+    assert!((busy_cores(&[100.0, 100.0, 100.0, 40.0, 0.0, 0.0, 0.0, 0.0]) - 3.4).abs() < 1e-6);
+    assert_eq!(busy_cores(&[]), 0.0);
+    assert!((busy_cores(&[f32::NAN, 50.0, f32::INFINITY]) - 0.5).abs() < 1e-6);
+
+    // base_snapshot's cores: 30 + 60 + 40 + 50.
+    let units = DisplayUnits {
+        cpu: CpuDisplay::Cores,
+        ..Default::default()
+    };
+    let frame = render_frame(Some(&base_snapshot()), &Peaks::default(), units, None, None);
+    assert!(strip_ansi(&frame[3]).starts_with("CPU total: 45.5%  (1.8 of 4 cores busy)"));
+
+    let mut no_cores = base_snapshot();
+    no_cores.cpu.per_core_usage_pct.clear();
+    let frame = render_frame(Some(&no_cores), &Peaks::default(), units, None, None);
+    assert!(!strip_ansi(&frame[3]).contains("cores busy"));
+}
+
+#[test]
+fn busy_cores_series_follows_the_snapshot_value() {
+    let mut snap = base_snapshot();
+    assert!(!snap
+        .to_rpc_format()
+        .data
+        .iter()
+        .any(|s| s.name == "cpu_busy_cores"));
+
+    snap.cpu.busy_cores = Some(1.8);
+    let rpc = snap.to_rpc_format();
+    let series = rpc
+        .data
+        .iter()
+        .find(|s| s.name == "cpu_busy_cores")
+        .expect("cpu_busy_cores series missing");
+    assert_eq!(series.series, vec![1.8]);
+
+    let json = serde_json::to_value(&snap).unwrap();
+    assert_eq!(json["cpu"]["busy_cores"].as_f64().unwrap() as f32, 1.8);
+}
+
+#[test]
+fn console_frame_renders_snapshot_lines() {
+    use resource_monitor::config::DisplayUnits;
+    use resource_monitor::console::{render_frame, Peaks};
+
+    let snap = base_snapshot();
+    let peaks = Peaks::from_snapshots(std::slice::from_ref(&snap));
+    let frame: Vec<String> = render_frame(Some(&snap), &peaks, DisplayUnits::default(), None, None)
+        .iter()
+        .map(|line| strip_ansi(line))
+        .collect();
+    assert_eq!(
+        frame,
+        vec![
+            "Resource Monitor (console)",
+            "Press Ctrl+C to exit.",
+            "",
+            "CPU total: 45.5% (peak 45.5%)   Load avg: 1.50 / 1.20 / 0.80",
+            "Memory: 7.45 GiB used / 14.90 GiB total (50.0%) (peak 50.0%)",
+            "Network: RX 50000 B/s (peak 50000 B/s)  TX 10000 B/s (peak 10000 B/s)   (total RX 976.56 KiB / TX 488.28 KiB)",
+            "",
+            "Per-core CPU usage:",
+            "  Core  0: 30.0%",
+            "  Core  1: 60.0%",
+            "  Core  2: 40.0%",
+            "  Core  3: 50.0%",
+        ]
+    );
+
+    let waiting = render_frame(None, &Peaks::default(), DisplayUnits::default(), None, None);
+    assert_eq!(waiting.last().unwrap(), "Waiting for first sample...");
+}
+
+#[test]
+fn busiest_cores_picks_top_n_of_a_large_machine() {
+    use resource_monitor::config::DisplayUnits;
+    use resource_monitor::console::{busiest_cores, render_frame, Peaks};
+
+    let mut per_core: Vec<f32> = (0..128).map(|i| (i % 10) as f32).collect();
+    per_core[7] = 99.0;
+    per_core[100] = 95.0;
+    per_core[64] = f32::NAN;
+    per_core[3] = 80.0;
+    assert_eq!(busiest_cores(&per_core, 3), vec![3, 7, 100]);
+    // Ties on 9% resolve to the lowest indices.
+    assert_eq!(busiest_cores(&per_core, 5), vec![3, 7, 9, 19, 100]);
+    assert_eq!(busiest_cores(&per_core, 500).len(), 128);
+    assert!(busiest_cores(&per_core, 0).is_empty());
+
+    let mut snap = base_snapshot();
+    snap.cpu.per_core_usage_pct = vec![10.0, 90.0, 20.0, 70.0, 30.0];
+    let frame: Vec<String> = render_frame(
+        Some(&snap),
+        &Peaks::default(),
+        DisplayUnits::default(),
+        None,
+        Some(2),
+    )
+    .iter()
+    .map(|line| strip_ansi(line))
+    .collect();
+    assert_eq!(
+        frame[frame.len() - 3..],
+        [
+            "  Core  1: 90.0%",
+            "  Core  3: 70.0%",
+            "  ... 3 quieter cores: avg 20.0%, max 30.0%",
+        ]
+    );
+}
+
+#[test]
+fn console_screen_repaints_only_changed_lines() {
+    use resource_monitor::console::Screen;
+
+    const CLEAR_ALL: &str = "\x1b[2J";
+    let lines = |v: &[&str]| v.iter().map(|l| l.to_string()).collect::<Vec<_>>();
+    let mut screen = Screen::new();
+
+    let mut first = Vec::new();
+    screen
+        .paint(&mut first, lines(&["header", "cpu 10%", "mem 50%"]))
+        .unwrap();
+    let first = String::from_utf8(first).unwrap();
+    assert!(first.contains(CLEAR_ALL));
+    assert!(first.contains("header") && first.contains("mem 50%"));
+
+    let mut second = Vec::new();
+    screen
+        .paint(&mut second, lines(&["header", "cpu 20%", "mem 50%"]))
+        .unwrap();
+    let second = String::from_utf8(second).unwrap();
+    assert!(!second.contains(CLEAR_ALL));
+    assert_eq!(strip_ansi(&second), "cpu 20%");
+
+    // A shorter frame erases what was below it.
+    let mut third = Vec::new();
+    screen.paint(&mut third, lines(&["header"])).unwrap();
+    let third = String::from_utf8(third).unwrap();
+    assert!(!third.contains(CLEAR_ALL));
+    assert_eq!(strip_ansi(&third), "");
+    assert!(third.contains("\x1b[J"));
+}
+
+#[test]
+fn watched_process_sums_parent_and_descendants() {
+    use resource_monitor::config::ProcessSelector;
+
+    let entry = |pid, parent_pid, name: &str, cpu, rss| ProcessEntry {
+        pid,
+        parent_pid,
+        name: name.to_string(),
+        cpu_usage_pct: cpu,
+        rss_bytes: rss,
+    };
+    let processes = vec![
+        entry(1, None, "init", 1.0, 10),
+        entry(100, Some(1), "server", 20.0, 1000),
+        entry(101, Some(100), "worker", 30.0, 200),
+        entry(102, Some(101), "helper", 5.0, 50),
+        entry(200, Some(1), "other", 90.0, 9000),
+    ];
+
+    let by_name = WatchedProcess::from_processes(&"server".parse().unwrap(), &processes);
+    assert_eq!(by_name.target, "server");
+    assert_eq!(by_name.process_count, 3);
+    assert_eq!(by_name.cpu_usage_pct, 55.0);
+    assert_eq!(by_name.rss_bytes, 1250);
+
+    let by_pid = WatchedProcess::from_processes(&ProcessSelector::Pid(101), &processes);
+    assert_eq!(by_pid.process_count, 2);
+    assert_eq!(by_pid.rss_bytes, 250);
+
+    let missing = WatchedProcess::from_processes(&"nginx".parse().unwrap(), &processes);
+    assert_eq!(missing.process_count, 0);
+    assert_eq!(missing.cpu_usage_pct, 0.0);
+    assert_eq!(missing.rss_bytes, 0);
+}
+
+#[test]
+fn delta_encoder_resends_full_snapshot_periodically() {
+    use resource_monitor::delta::{Delta, DeltaEncoder, FULL_EVERY};
+
+    let mut encoder = DeltaEncoder::default();
+    let mut kinds = Vec::new();
+    for i in 0..=FULL_EVERY {
+        let mut snap = base_snapshot();
+        snap.timestamp_ms += u128::from(i) * 1000;
+        match encoder.encode(&snap.to_rpc_format()).unwrap() {
+            Delta::Full(_) => kinds.push('F'),
+            Delta::Patch(patch) => {
+                // Only the timestamp moved.
+                assert_eq!(patch.as_object().unwrap().len(), 1);
+                kinds.push('P');
+            }
+        }
+    }
+    let expected: String = std::iter::once('F')
+        .chain(std::iter::repeat_n('P', FULL_EVERY as usize - 1))
+        .chain(std::iter::once('F'))
+        .collect();
+    assert_eq!(kinds.into_iter().collect::<String>(), expected);
+}
+
+#[test]
+fn pressure_score_weights_and_clamps() {
+    use resource_monitor::config::PressureWeights;
+
+    // CPU 45.5%, memory 50%, disk 60%, swap 25%.
+    let mut snap = base_snapshot();
+    let score = snap.pressure_score(&PressureWeights::default());
+    assert!((score - 47.7).abs() < 1e-3, "{score}");
+
+    let weights: PressureWeights = "cpu=1,mem=1,swap=2".parse().unwrap();
+    assert_eq!(weights.disk, 0.0);
+    let score = snap.pressure_score(&weights);
+    assert!((score - 36.375).abs() < 1e-3, "{score}");
+
+    snap.cpu.total_usage_pct = 250.0;
+    assert_eq!(snap.pressure_score(&"cpu=1".parse().unwrap()), 100.0);
+    assert!("cpu=0".parse::<PressureWeights>().is_err());
+
+    snap.pressure_score = Some(91.0);
+    let rpc = snap.to_rpc_format();
+    let series = rpc.data.iter().find(|s| s.name == "pressure").unwrap();
+    assert_eq!(series.series, vec![91.0]);
+    assert_eq!(series.crit, Some(PRESSURE_CRIT));
+}
+
+#[test]
+fn byte_units_format_iec_and_si() {
+    use resource_monitor::config::ByteUnits;
+    use resource_monitor::console::format_bytes;
+
+    assert_eq!(format_bytes(1_500_000, ByteUnits::Iec), "1.43 MiB");
+    assert_eq!(format_bytes(1_500_000, ByteUnits::Si), "1.50 MB");
+    assert_eq!(format_bytes(999, ByteUnits::Si), "999 B");
+    assert_eq!(format_bytes(1024, ByteUnits::Iec), "1.00 KiB");
+    assert_eq!(format_bytes(2_000_000_000_000, ByteUnits::Si), "2.00 TB");
+
+    let rpc = base_snapshot()
+        .to_rpc_format()
+        .with_byte_units(ByteUnits::Si);
+    let network = rpc.data.iter().find(|s| s.name == "network").unwrap();
+    let json = serde_json::to_value(&network.format).unwrap();
+    assert_eq!(json["params"]["units"], "si");
+}
+
+#[test]
+fn disk_used_pct_of_total_and_of_available_differ() {
+    use resource_monitor::config::DiskUsageBasis;
+
+    // 50 bytes are reserved: neither used nor available to users.
+    let disk = DiskMetrics {
+        total_bytes: 1000,
+        available_bytes: 100,
+        used_pct: 0.0,
+        used_bytes: Some(850),
+    };
+    assert!((disk.used_pct_of_total() - 85.0).abs() < 1e-3);
+    assert!((disk.used_pct_available() - 850.0 / 950.0 * 100.0).abs() < 1e-3);
+    assert!((disk.used_pct_by(DiskUsageBasis::Available) - 90.0).abs() < 1e-3);
+    assert!(disk.used_pct_of_total() < disk.used_pct_available());
+
+    let unknown = DiskMetrics {
+        used_bytes: None,
+        ..disk
+    };
+    assert!((unknown.used_pct_of_total() - 90.0).abs() < 1e-3);
+    assert!((unknown.used_pct_available() - 90.0).abs() < 1e-3);
+    let json = serde_json::to_value(&unknown).unwrap();
+    assert!(json.get("used_bytes").is_none());
+}
+
+#[test]
+fn sparse_cores_diff_and_apply_round_trip() {
+    let base = vec![10.0, 20.0, 30.0, 40.0];
+    let current = vec![10.0, 20.5, 30.0, 47.0];
+
+    let exact = SparseCores::diff(&base, &current, 0.0);
+    assert_eq!(exact.indices, vec![1, 3]);
+    let mut rebuilt = base.clone();
+    exact.apply(&mut rebuilt);
+    assert_eq!(rebuilt, current);
+
+    // Moves within the threshold are not recorded.
+    let coarse = SparseCores::diff(&base, &current, 1.0);
+    assert_eq!(coarse.indices, vec![3]);
+    let mut rebuilt = base.clone();
+    coarse.apply(&mut rebuilt);
+    assert_eq!(rebuilt, vec![10.0, 20.0, 30.0, 47.0]);
 }
@@ -0,0 +1,109 @@
+use resource_monitor::monitor::{Monitor, MonitorError};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+#[tokio::test]
+async fn embedded_monitor_collects_and_serves_http() {
+    let cancel = CancellationToken::new();
+    let handle = Monitor::builder()
+        .interval(Duration::from_millis(100))
+        .history(10)
+        .http(([127, 0, 0, 1], 0).into())
+        .rpc(([127, 0, 0, 1], 0).into())
+        .dashboard(false)
+        .build()
+        .start(cancel.clone())
+        .await
+        .unwrap();
+    assert_ne!(
+        handle.rpc_addr().expect("RPC address not reported").port(),
+        0
+    );
+    let mut live = handle.subscribe();
+
+    let mut latest = None;
+    for _ in 0..50 {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        latest = handle.latest();
+        if latest.is_some() {
+            break;
+        }
+    }
+    let latest = latest.expect("no snapshot collected after several ticks");
+    assert!(latest.cpu.total_usage_pct.is_finite());
+    let streamed = tokio::time::timeout(Duration::from_secs(5), live.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(streamed.timestamp_ms >= latest.timestamp_ms);
+
+    let addr = handle.http_addr().unwrap();
+    let resp = reqwest::get(format!("http://{addr}/api/metrics"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    cancel.cancel();
+    tokio::time::timeout(Duration::from_secs(5), handle.join())
+        .await
+        .expect("monitor did not stop after cancellation");
+}
+
+#[tokio::test]
+async fn monitor_without_archive_leaves_the_database_empty() {
+    let cancel = CancellationToken::new();
+    let handle = Monitor::builder()
+        .interval(Duration::from_millis(20))
+        .archive(false)
+        .build()
+        .start(cancel.clone())
+        .await
+        .unwrap();
+    let mut live = handle.subscribe();
+    for _ in 0..2 {
+        tokio::time::timeout(Duration::from_secs(5), live.recv())
+            .await
+            .unwrap()
+            .unwrap();
+    }
+    assert_eq!(handle.db().get_stats().unwrap().total_records, 0);
+
+    cancel.cancel();
+    tokio::time::timeout(Duration::from_secs(5), handle.join())
+        .await
+        .expect("monitor did not stop after cancellation");
+}
+
+#[tokio::test]
+async fn zero_interval_is_rejected() {
+    let result = Monitor::builder()
+        .interval(Duration::ZERO)
+        .build()
+        .start(CancellationToken::new())
+        .await;
+    assert!(matches!(result, Err(MonitorError::ZeroInterval)));
+}
+
+#[tokio::test]
+async fn failed_bind_starts_nothing() {
+    let taken = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let started = Arc::new(AtomicBool::new(false));
+    let result = Monitor::builder()
+        .interval(Duration::from_millis(10))
+        .http(taken.local_addr().unwrap())
+        .on_collector_thread({
+            let started = started.clone();
+            move || {
+                started.store(true, Ordering::SeqCst);
+                Box::new(())
+            }
+        })
+        .build()
+        .start(CancellationToken::new())
+        .await;
+    assert!(matches!(result, Err(MonitorError::Bind { .. })));
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(!started.load(Ordering::SeqCst), "collector started anyway");
+}
@@ -0,0 +1,46 @@
+use resource_monitor::net::{bind_listener, bind_tokio_listener, ConnectionLimit, ListenOptions};
+use socket2::SockRef;
+use std::net::TcpStream;
+use std::time::Duration;
+
+#[test]
+fn listener_with_options_accepts_connections() {
+    let options = ListenOptions {
+        backlog: 16,
+        keepalive: Some(Duration::from_secs(30)),
+    };
+    let listener = bind_listener("127.0.0.1:0".parse().unwrap(), &options).unwrap();
+    assert!(SockRef::from(&listener).keepalive().unwrap());
+    let addr = listener.local_addr().unwrap();
+
+    let _client = TcpStream::connect(addr).unwrap();
+    listener.set_nonblocking(false).unwrap();
+    let (accepted, _) = listener.accept().unwrap();
+    assert!(SockRef::from(&accepted).keepalive().unwrap());
+}
+
+#[tokio::test]
+async fn default_options_leave_keepalive_off() {
+    let listener =
+        bind_tokio_listener("127.0.0.1:0".parse().unwrap(), &ListenOptions::default()).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let connect = tokio::net::TcpStream::connect(addr);
+    let (accepted, client) = tokio::join!(listener.accept(), connect);
+    client.unwrap();
+    let (accepted, _) = accepted.unwrap();
+    assert!(!SockRef::from(&accepted).keepalive().unwrap());
+}
+
+#[test]
+fn connection_limit_hands_out_slots_until_full() {
+    let limit = ConnectionLimit::new(2);
+    let first = limit.try_acquire().unwrap();
+    let _second = limit.try_acquire().unwrap();
+    assert_eq!(limit.in_use(), 2);
+    assert!(limit.try_acquire().is_none());
+
+    drop(first);
+    assert_eq!(limit.in_use(), 1);
+    assert!(limit.try_acquire().is_some());
+}
@@ -0,0 +1,125 @@
+use resource_monitor::config::{SharedThresholds, Threshold, Thresholds};
+use resource_monitor::reload::{ConfigFile, Reloader, Settings};
+use std::time::Duration;
+use tempfile::tempdir;
+
+fn settings() -> Settings {
+    Settings {
+        interval: Duration::from_millis(1000),
+        thresholds: Thresholds::default(),
+        bind: "127.0.0.1".parse().unwrap(),
+        port: 9000,
+        rpc_addr: "127.0.0.1:50051".parse().unwrap(),
+    }
+}
+
+#[test]
+fn reload_updates_thresholds_and_interval() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("monitor.json");
+    std::fs::write(&path, "{}").unwrap();
+
+    let thresholds = SharedThresholds::new(Thresholds::default());
+    let (interval_tx, interval_rx) = tokio::sync::watch::channel(Duration::from_millis(1000));
+    let mut reloader = Reloader::new(&path, settings(), thresholds.clone(), interval_tx);
+    let report = reloader.reload().unwrap();
+    assert!(report.applied.is_empty());
+
+    std::fs::write(
+        &path,
+        r#"{
+            "interval_ms": 500,
+            "thresholds": {
+                "cpu_total": {"warn": 50.0, "crit": 80.0},
+                "memory": {"crit": 95.0}
+            }
+        }"#,
+    )
+    .unwrap();
+    let report = reloader.reload().unwrap();
+    assert_eq!(report.applied.len(), 2);
+    assert!(report.restart_required.is_empty());
+    assert_eq!(
+        thresholds.get(),
+        Thresholds {
+            cpu: Threshold {
+                warn: Some(50.0),
+                crit: Some(80.0),
+                ..Default::default()
+            },
+            memory: Threshold {
+                warn: None,
+                crit: Some(95.0),
+                ..Default::default()
+            },
+        }
+    );
+    assert_eq!(*interval_rx.borrow(), Duration::from_millis(500));
+
+    // Dropping the key falls back to the command-line thresholds.
+    std::fs::write(&path, r#"{"interval_ms": 500}"#).unwrap();
+    reloader.reload().unwrap();
+    assert_eq!(thresholds.get(), Thresholds::default());
+}
+
+#[test]
+fn reload_rejects_invalid_file_and_reports_restart_only_settings() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("monitor.json");
+    let thresholds = SharedThresholds::new(Thresholds::default());
+    let (interval_tx, _interval_rx) = tokio::sync::watch::channel(Duration::from_millis(1000));
+    let mut reloader = Reloader::new(&path, settings(), thresholds.clone(), interval_tx);
+
+    std::fs::write(
+        &path,
+        r#"{"thresholds": {"cpu_total": {"warn": 90.0, "crit": 50.0}, "memory": {}}}"#,
+    )
+    .unwrap();
+    assert!(reloader.reload().is_err());
+    assert_eq!(thresholds.get(), Thresholds::default());
+
+    std::fs::write(&path, r#"{"port": 9100, "bind": "127.0.0.1"}"#).unwrap();
+    let report = reloader.reload().unwrap();
+    assert!(report.applied.is_empty());
+    assert_eq!(report.restart_required, vec!["port: 9000 -> 9100"]);
+}
+
+#[test]
+fn command_line_interval_below_the_reload_minimum_is_kept() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("monitor.json");
+    std::fs::write(&path, "{}").unwrap();
+    let thresholds = SharedThresholds::new(Thresholds::default());
+    let (interval_tx, interval_rx) = tokio::sync::watch::channel(Duration::from_millis(50));
+    let mut reloader = Reloader::new(
+        &path,
+        Settings {
+            interval: Duration::from_millis(50),
+            ..settings()
+        },
+        thresholds,
+        interval_tx,
+    );
+
+    assert!(reloader.reload().unwrap().applied.is_empty());
+    assert_eq!(*interval_rx.borrow(), Duration::from_millis(50));
+
+    // A value from the file is still bounded.
+    std::fs::write(&path, r#"{"interval_ms": 20}"#).unwrap();
+    reloader.reload().unwrap();
+    assert_eq!(*interval_rx.borrow(), Duration::from_millis(100));
+}
+
+#[test]
+fn listen_addresses_from_the_file_override_the_command_line() {
+    let file: ConfigFile = serde_json::from_str(r#"{"port": 9100}"#).unwrap();
+    let mut running = settings();
+    file.apply_listen_addrs(&mut running.bind, &mut running.port, &mut running.rpc_addr);
+    assert_eq!(
+        running,
+        Settings {
+            port: 9100,
+            ..settings()
+        }
+    );
+}
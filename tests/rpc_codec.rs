@@ -0,0 +1,210 @@
+use futures::{SinkExt, StreamExt};
+use resource_monitor::metrics::{
+    CpuMetrics, DiskMetrics, MemoryMetrics, MetricsSnapshot, NetworkMetrics, RpcMetricsSnapshot,
+};
+use resource_monitor::rpc_codec::RpcCodec;
+use std::pin::Pin;
+use tokio::net::{TcpListener, TcpStream};
+use tokio_serde::{Deserializer, Serializer};
+use tokio_util::bytes::BytesMut;
+use tokio_util::codec::{Framed, LengthDelimitedCodec};
+
+type Request = (Option<usize>, Option<u64>);
+
+fn sample_snapshot(ts: u128) -> MetricsSnapshot {
+    MetricsSnapshot {
+        timestamp_ms: ts,
+        sample_interval_ms: 1000.0,
+        cpu: CpuMetrics {
+            total_usage_pct: (ts % 100) as f32,
+            per_core_usage_pct: (0..16).map(|c| ((ts as usize + c) % 100) as f32).collect(),
+            load_avg_1: Some(0.1),
+            load_avg_5: Some(0.2),
+            load_avg_15: Some(0.3),
+            temperature_celsius: Some(50.0),
+            breakdown: None,
+            per_socket_usage_pct: None,
+            per_core_changes: None,
+            busy_cores: None,
+        },
+        memory: MemoryMetrics {
+            total_bytes: 16_000_000_000,
+            used_bytes: 8_000_000_000 + (ts % 1_000_000) as u64,
+            available_bytes: 8_000_000_000 - (ts % 1_000_000) as u64,
+            swap_total_bytes: 4096,
+            swap_used_bytes: 1024,
+            swap_in_bytes_per_sec: None,
+            swap_out_bytes_per_sec: None,
+        },
+        network: NetworkMetrics {
+            rx_bytes_total: 1000 * ts as u64,
+            tx_bytes_total: 2000 * ts as u64,
+            rx_bytes_per_sec: 1000.0,
+            tx_bytes_per_sec: 2000.0,
+        },
+        disk: DiskMetrics {
+            total_bytes: 500_000_000_000,
+            available_bytes: 200_000_000_000,
+            used_pct: 60.0,
+            used_bytes: None,
+        },
+        battery: None,
+        gpu: None,
+        scheduler: None,
+        watched_process: None,
+        net_top_processes: None,
+        pressure_score: None,
+        source: None,
+        resumed: false,
+        tags: Default::default(),
+    }
+}
+
+fn large_history() -> Vec<RpcMetricsSnapshot> {
+    (0..2_000)
+        .map(|i| sample_snapshot(1_700_000_000_000 + i * 1000).to_rpc_format())
+        .collect()
+}
+
+fn encode<T: serde::Serialize>(codec: &mut RpcCodec, item: &T) -> BytesMut {
+    BytesMut::from(&Serializer::<T>::serialize(Pin::new(codec), item).unwrap()[..])
+}
+
+fn decode<T: serde::de::DeserializeOwned>(codec: &mut RpcCodec, frame: &BytesMut) -> T {
+    Deserializer::<T>::deserialize(Pin::new(codec), frame).unwrap()
+}
+
+#[test]
+fn compressed_frames_are_smaller_and_decode_identically() {
+    let history = large_history();
+    let plain = encode(&mut RpcCodec::plain(), &history);
+    let compressed = encode(&mut RpcCodec::compressed(), &history);
+
+    assert!(compressed.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]));
+    assert!(
+        compressed.len() * 4 < plain.len(),
+        "compressed {} bytes vs plain {}",
+        compressed.len(),
+        plain.len()
+    );
+
+    let decoded: Vec<RpcMetricsSnapshot> = decode(&mut RpcCodec::plain(), &compressed);
+    assert_eq!(
+        serde_json::to_value(&decoded).unwrap(),
+        serde_json::to_value(&history).unwrap()
+    );
+}
+
+#[test]
+fn mirroring_codec_answers_in_the_form_it_received() {
+    let mut server = RpcCodec::mirroring();
+    assert!(!server.compresses());
+
+    let _: Request = decode(
+        &mut server,
+        &encode(&mut RpcCodec::compressed(), &(Some(10), None::<u64>)),
+    );
+    assert!(server.compresses());
+    assert!(encode(&mut server, &"reply").starts_with(&[0x28, 0xb5, 0x2f, 0xfd]));
+
+    let _: Request = decode(
+        &mut server,
+        &encode(&mut RpcCodec::plain(), &(Some(10), None::<u64>)),
+    );
+    assert!(!server.compresses());
+    assert_eq!(&encode(&mut server, &"reply")[..], b"\"reply\"");
+}
+
+#[test]
+fn oversized_or_corrupt_frames_are_rejected() {
+    let mut frame = encode(&mut RpcCodec::compressed(), &large_history());
+    let len = frame.len();
+    frame[len / 2] ^= 0xff;
+    let res = Deserializer::<Vec<RpcMetricsSnapshot>>::deserialize(
+        Pin::new(&mut RpcCodec::mirroring()),
+        &frame,
+    );
+    assert!(res.is_err());
+}
+
+#[tokio::test]
+async fn compressed_client_and_server_round_trip_a_large_history() {
+    let history = large_history();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let served = history.clone();
+    let server = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut transport = tokio_serde::Framed::<_, Request, Vec<RpcMetricsSnapshot>, _>::new(
+            Framed::new(stream, LengthDelimitedCodec::new()),
+            RpcCodec::mirroring(),
+        );
+        let (limit, _since) = transport.next().await.unwrap().unwrap();
+        let reply: Vec<_> = served
+            .into_iter()
+            .take(limit.unwrap_or(usize::MAX))
+            .collect();
+        transport.send(reply).await.unwrap();
+    });
+
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+    let mut client = RpcCodec::compressed();
+    framed
+        .send(encode(&mut client, &(None::<usize>, None::<u64>)).freeze())
+        .await
+        .unwrap();
+    let frame = framed.next().await.unwrap().unwrap();
+    assert!(
+        frame.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]),
+        "server should reply compressed to a compressed request"
+    );
+    let received: Vec<RpcMetricsSnapshot> = decode(&mut client, &frame);
+    server.await.unwrap();
+
+    assert_eq!(received.len(), history.len());
+    assert_eq!(
+        serde_json::to_value(&received).unwrap(),
+        serde_json::to_value(&history).unwrap()
+    );
+}
+
+#[tokio::test]
+async fn metrics_rpc_client_round_trips_history_with_compression_on() {
+    use resource_monitor::rpc::{connect_client, serve_rpc, MetricsRpcServer};
+    use resource_monitor::storage::MetricsBuffer;
+    use std::sync::Arc;
+    use tarpc::context;
+    use tokio_util::sync::CancellationToken;
+
+    let buffer = Arc::new(MetricsBuffer::new(500));
+    for i in 0..500 {
+        buffer.push(sample_snapshot(1_700_000_000_000 + i * 1000));
+    }
+    let (stream_tx, _) = tokio::sync::broadcast::channel(8);
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let cancel = CancellationToken::new();
+    let server = tokio::spawn(serve_rpc(
+        listener,
+        MetricsRpcServer::new(buffer, stream_tx),
+        None,
+        cancel.clone(),
+    ));
+
+    let client = connect_client(addr, true).await.unwrap();
+    let received = client
+        .history(context::current(), None, None)
+        .await
+        .unwrap();
+    assert_eq!(
+        serde_json::to_value(&received).unwrap(),
+        serde_json::to_value(&large_history()[..500]).unwrap()
+    );
+    let latest = client.latest(context::current()).await.unwrap().unwrap();
+    assert_eq!(latest.timestamp_ms, 1_700_000_000_000 + 499 * 1000);
+
+    cancel.cancel();
+    server.await.unwrap();
+}
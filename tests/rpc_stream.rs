@@ -2,8 +2,9 @@ use futures::StreamExt;
 use resource_monitor::metrics::{
     CpuMetrics, DiskMetrics, MemoryMetrics, MetricsSnapshot, NetworkMetrics, RpcMetricsSnapshot,
 };
-use resource_monitor::rpc::{MetricsRpc, MetricsRpcClient, MetricsRpcServer};
-use resource_monitor::storage::MetricsBuffer;
+use resource_monitor::rpc::{MetricsRpc, MetricsRpcClient, MetricsRpcServer, StreamEvent};
+use resource_monitor::storage::{HistoryOrder, MetricsBuffer};
+use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::Duration;
 use tarpc::context;
@@ -94,6 +95,50 @@ async fn rpc_history_with_limit() {
     assert_eq!(res[1].timestamp_ms, 4000);
 }
 
+#[tokio::test]
+async fn rpc_history_ordered_from_either_end() {
+    use resource_monitor::storage::HistoryOrder;
+
+    let buffer = Arc::new(MetricsBuffer::new(10));
+    for ts in [1000, 2000, 3000, 4000, 5000] {
+        buffer.push(sample_snapshot(ts));
+    }
+    let (stream_tx, _) = broadcast::channel::<RpcMetricsSnapshot>(8);
+    let client = spawn_rpc_pair(buffer, stream_tx);
+
+    let timestamps = |res: Vec<RpcMetricsSnapshot>| -> Vec<u128> {
+        res.iter().map(|s| s.timestamp_ms).collect()
+    };
+    let cases = [
+        (false, HistoryOrder::Asc, vec![4000, 5000]),
+        (false, HistoryOrder::Desc, vec![5000, 4000]),
+        (true, HistoryOrder::Asc, vec![1000, 2000]),
+        (true, HistoryOrder::Desc, vec![2000, 1000]),
+    ];
+    for (from_start, order, expected) in cases {
+        let res = client
+            .history_ordered(context::current(), Some(2), None, from_start, order)
+            .await
+            .unwrap();
+        assert_eq!(
+            timestamps(res),
+            expected,
+            "from_start={from_start} {order:?}"
+        );
+    }
+    let res = client
+        .history_ordered(
+            context::current(),
+            Some(2),
+            Some(2500),
+            true,
+            HistoryOrder::Asc,
+        )
+        .await
+        .unwrap();
+    assert_eq!(timestamps(res), vec![3000, 4000]);
+}
+
 #[tokio::test]
 async fn rpc_history_with_since() {
     let buffer = Arc::new(MetricsBuffer::new(10));
@@ -126,6 +171,43 @@ async fn rpc_history_with_since_and_limit() {
     assert_eq!(res[1].timestamp_ms, 5000);
 }
 
+#[tokio::test]
+async fn rpc_history_limit_larger_than_since_window_returns_all_of_it() {
+    let buffer = Arc::new(MetricsBuffer::new(10));
+    for ts in [1000, 2000, 3000, 4000, 5000] {
+        buffer.push(sample_snapshot(ts));
+    }
+    let (stream_tx, _) = broadcast::channel::<RpcMetricsSnapshot>(8);
+    let client = spawn_rpc_pair(buffer, stream_tx);
+
+    let timestamps = |res: Vec<RpcMetricsSnapshot>| -> Vec<u128> {
+        res.iter().map(|s| s.timestamp_ms).collect()
+    };
+    let res = client
+        .history(context::current(), Some(10), Some(3500))
+        .await
+        .unwrap();
+    assert_eq!(timestamps(res), vec![4000, 5000]);
+
+    let res = client
+        .history_ordered(
+            context::current(),
+            Some(10),
+            Some(3500),
+            true,
+            HistoryOrder::Desc,
+        )
+        .await
+        .unwrap();
+    assert_eq!(timestamps(res), vec![5000, 4000]);
+
+    let res = client
+        .history(context::current(), Some(10), Some(9000))
+        .await
+        .unwrap();
+    assert!(res.is_empty());
+}
+
 #[tokio::test]
 async fn next_after_returns_next_snapshot() {
     let buffer = Arc::new(MetricsBuffer::new(10));
@@ -142,11 +224,41 @@ async fn next_after_returns_next_snapshot() {
 
     let mut ctx = context::current();
     ctx.deadline = std::time::SystemTime::now() + Duration::from_secs(2);
-    let res = client.next_after(ctx, 0, 1_000).await.unwrap();
+    let res = client.next_after(ctx, 0, 1_000, None).await.unwrap();
     assert!(res.is_some());
     assert_eq!(res.unwrap().timestamp_ms, 2000);
 }
 
+#[tokio::test]
+async fn latest_blocking_coalesces_snapshots_published_during_the_wait() {
+    let buffer = Arc::new(MetricsBuffer::new(10));
+    buffer.push(sample_snapshot(1000));
+    let (stream_tx, _) = broadcast::channel::<RpcMetricsSnapshot>(8);
+    let stream_tx_clone = stream_tx.clone();
+    let client = spawn_rpc_pair(buffer.clone(), stream_tx);
+
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        for ts in [2000, 3000, 4000] {
+            let snap = sample_snapshot(ts);
+            buffer.push(snap.clone());
+            let _ = stream_tx_clone.send(snap.to_rpc_format());
+        }
+    });
+
+    let mut ctx = context::current();
+    ctx.deadline = std::time::SystemTime::now() + Duration::from_secs(2);
+    let res = client.latest_blocking(ctx, 1000, 1_000).await.unwrap();
+    assert_eq!(res.unwrap().timestamp_ms, 4000);
+
+    // Nothing newer than what the caller has seen: waits out the timeout.
+    let res = client
+        .latest_blocking(context::current(), 4000, 50)
+        .await
+        .unwrap();
+    assert!(res.is_none());
+}
+
 #[tokio::test]
 async fn next_after_returns_existing_if_newer() {
     let buffer = Arc::new(MetricsBuffer::new(10));
@@ -156,7 +268,7 @@ async fn next_after_returns_existing_if_newer() {
 
     let mut ctx = context::current();
     ctx.deadline = std::time::SystemTime::now() + Duration::from_secs(2);
-    let res = client.next_after(ctx, 1000, 500).await.unwrap();
+    let res = client.next_after(ctx, 1000, 500, None).await.unwrap();
     assert!(res.is_some());
     assert_eq!(res.unwrap().timestamp_ms, 5000);
 }
@@ -169,20 +281,153 @@ async fn next_after_timeout_returns_none() {
 
     let mut ctx = context::current();
     ctx.deadline = std::time::SystemTime::now() + Duration::from_secs(2);
-    let res = client.next_after(ctx, 0, 100).await.unwrap();
+    let res = client.next_after(ctx, 0, 100, None).await.unwrap();
     assert!(res.is_none());
 }
 
+#[tokio::test]
+async fn next_after_with_ceiling_drains_window_then_stops() {
+    let buffer = Arc::new(MetricsBuffer::new(10));
+    for ts in [1000, 2000, 3000, 4000] {
+        buffer.push(sample_snapshot(ts));
+    }
+    let (stream_tx, _) = broadcast::channel::<RpcMetricsSnapshot>(8);
+    let client = spawn_rpc_pair(buffer, stream_tx);
+
+    let mut seen = Vec::new();
+    let mut since = 1000;
+    loop {
+        let mut ctx = context::current();
+        ctx.deadline = std::time::SystemTime::now() + Duration::from_secs(2);
+        let Some(snap) = client
+            .next_after(ctx, since, 100, Some(3000))
+            .await
+            .unwrap()
+        else {
+            break;
+        };
+        since = snap.timestamp_ms as u64;
+        seen.push(snap.timestamp_ms);
+    }
+    assert_eq!(seen, vec![2000, 3000]);
+
+    // Without a ceiling the newest snapshot is still returned.
+    let mut ctx = context::current();
+    ctx.deadline = std::time::SystemTime::now() + Duration::from_secs(2);
+    let res = client.next_after(ctx, 1000, 100, None).await.unwrap();
+    assert_eq!(res.unwrap().timestamp_ms, 4000);
+}
+
+#[tokio::test]
+async fn next_event_reports_gap_when_subscriber_lags() {
+    let buffer = Arc::new(MetricsBuffer::new(10));
+    let (stream_tx, _) = broadcast::channel::<RpcMetricsSnapshot>(1);
+    let flood_tx = stream_tx.clone();
+    let client = spawn_rpc_pair(buffer, stream_tx);
+
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        // No await between sends: the waiting subscriber cannot keep up.
+        for ts in 1..=10u128 {
+            let _ = flood_tx.send(sample_snapshot(ts * 1000).to_rpc_format());
+        }
+    });
+
+    let mut ctx = context::current();
+    ctx.deadline = std::time::SystemTime::now() + Duration::from_secs(2);
+    let res = client.next_event(ctx, 0, 1_000).await.unwrap();
+    match res {
+        Some(StreamEvent::Gap { skipped, latest }) => {
+            assert!(skipped > 0);
+            assert_eq!(latest.timestamp_ms, 10_000);
+        }
+        other => panic!("expected a gap event, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn next_event_without_lag_is_plain_snapshot() {
+    let buffer = Arc::new(MetricsBuffer::new(10));
+    buffer.push(sample_snapshot(5000));
+    let (stream_tx, _) = broadcast::channel::<RpcMetricsSnapshot>(8);
+    let client = spawn_rpc_pair(buffer, stream_tx);
+
+    let mut ctx = context::current();
+    ctx.deadline = std::time::SystemTime::now() + Duration::from_secs(2);
+    let res = client.next_event(ctx, 1000, 500).await.unwrap().unwrap();
+    assert!(matches!(res, StreamEvent::Snapshot(_)));
+    assert_eq!(res.snapshot().timestamp_ms, 5000);
+}
+
+#[tokio::test]
+async fn nearest_returns_closest_snapshot() {
+    let buffer = Arc::new(MetricsBuffer::new(10));
+    for ts in [1000, 2000, 3000] {
+        buffer.push(sample_snapshot(ts));
+    }
+    let (stream_tx, _) = broadcast::channel::<RpcMetricsSnapshot>(8);
+    let client = spawn_rpc_pair(buffer, stream_tx);
+
+    let nearest = |ts| client.nearest(context::current(), ts);
+    assert_eq!(nearest(2300).await.unwrap().unwrap().timestamp_ms, 2000);
+    assert_eq!(nearest(2700).await.unwrap().unwrap().timestamp_ms, 3000);
+    // Equidistant: the earlier sample wins.
+    assert_eq!(nearest(1500).await.unwrap().unwrap().timestamp_ms, 1000);
+    assert_eq!(nearest(3000).await.unwrap().unwrap().timestamp_ms, 3000);
+    assert!(nearest(500).await.unwrap().is_none());
+    assert!(nearest(3500).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn poller_reports_failure_after_retry_limit() {
+    use resource_monitor::rpc::run_rpc_client_poller_with;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use tokio_util::sync::CancellationToken;
+
+    let attempts = Arc::new(AtomicU32::new(0));
+    let attempts_in_connect = attempts.clone();
+    let (failed_tx, failed_rx) = tokio::sync::oneshot::channel();
+
+    let poller = run_rpc_client_poller_with(
+        move || {
+            attempts_in_connect.fetch_add(1, Ordering::SeqCst);
+            async {
+                Err::<MetricsRpcClient, _>(std::io::Error::from(
+                    std::io::ErrorKind::ConnectionRefused,
+                ))
+            }
+        },
+        Duration::from_millis(5),
+        Some(3),
+        CancellationToken::new(),
+        |_| panic!("no snapshot expected"),
+        move |failures| {
+            let _ = failed_tx.send(failures);
+        },
+    );
+    tokio::time::timeout(Duration::from_secs(2), poller)
+        .await
+        .expect("poller should stop after the retry limit");
+
+    assert_eq!(failed_rx.await.unwrap(), 3);
+    assert_eq!(attempts.load(Ordering::SeqCst), 3);
+}
+
 fn sample_snapshot(ts: u128) -> MetricsSnapshot {
     MetricsSnapshot {
         timestamp_ms: ts,
+        sample_interval_ms: 1000.0,
         cpu: CpuMetrics {
             total_usage_pct: 10.0,
             per_core_usage_pct: vec![10.0, 20.0],
-            load_avg_1: 0.1,
-            load_avg_5: 0.2,
-            load_avg_15: 0.3,
+            load_avg_1: Some(0.1),
+            load_avg_5: Some(0.2),
+            load_avg_15: Some(0.3),
             temperature_celsius: Some(50.0),
+            breakdown: None,
+            per_socket_usage_pct: None,
+            per_core_changes: None,
+            busy_cores: None,
         },
         memory: MemoryMetrics {
             total_bytes: 100,
@@ -190,6 +435,8 @@ fn sample_snapshot(ts: u128) -> MetricsSnapshot {
             available_bytes: 50,
             swap_total_bytes: 4096,
             swap_used_bytes: 1024,
+            swap_in_bytes_per_sec: None,
+            swap_out_bytes_per_sec: None,
         },
         network: NetworkMetrics {
             rx_bytes_total: 1000,
@@ -201,8 +448,278 @@ fn sample_snapshot(ts: u128) -> MetricsSnapshot {
             total_bytes: 500_000_000_000,
             available_bytes: 200_000_000_000,
             used_pct: 60.0,
+            used_bytes: None,
         },
         battery: None,
         gpu: None,
+        scheduler: None,
+        watched_process: None,
+        net_top_processes: None,
+        pressure_score: None,
+        source: None,
+        resumed: false,
+        tags: Default::default(),
+    }
+}
+
+#[tokio::test]
+async fn rpc_server_info_reports_interval_and_capacity() {
+    use resource_monitor::rpc::{FEATURE_NEXT_EVENT, RPC_SCHEMA_VERSION};
+
+    let buffer = Arc::new(MetricsBuffer::new(42));
+    buffer.push(sample_snapshot(1000));
+    let (stream_tx, _) = broadcast::channel::<RpcMetricsSnapshot>(8);
+    let (_interval_tx, interval_rx) = tokio::sync::watch::channel(Duration::from_millis(250));
+    let server_impl = MetricsRpcServer::new(buffer, stream_tx).with_interval(interval_rx);
+    let (client_transport, server_transport) = tarpc::transport::channel::unbounded();
+    tokio::spawn(
+        server::BaseChannel::with_defaults(server_transport)
+            .execute(server_impl.serve())
+            .for_each(|fut| async move {
+                tokio::spawn(fut);
+            }),
+    );
+    let client = MetricsRpcClient::new(tarpc::client::Config::default(), client_transport).spawn();
+
+    let info = client.server_info(context::current()).await.unwrap();
+    assert_eq!(info.schema_version, RPC_SCHEMA_VERSION);
+    assert_eq!(info.interval_ms, Some(250));
+    assert_eq!(info.history_capacity, 42);
+    assert!(info.collectors.iter().any(|c| c == "cpu_total"));
+    assert!(info.supports(FEATURE_NEXT_EVENT));
+}
+
+/// A server from before streaming: `server_info` advertises no features and
+/// the long-poll methods never answer.
+#[derive(Clone)]
+struct PollOnlyServer(Arc<MetricsBuffer>);
+
+impl MetricsRpc for PollOnlyServer {
+    async fn latest(self, _ctx: context::Context) -> Option<RpcMetricsSnapshot> {
+        self.0.latest().map(|snap| snap.to_rpc_format())
+    }
+
+    async fn history(
+        self,
+        _ctx: context::Context,
+        _limit: Option<usize>,
+        _since_ms: Option<u64>,
+    ) -> Vec<RpcMetricsSnapshot> {
+        Vec::new()
+    }
+
+    async fn history_ordered(
+        self,
+        _ctx: context::Context,
+        _limit: Option<usize>,
+        _since_ms: Option<u64>,
+        _from_start: bool,
+        _order: resource_monitor::storage::HistoryOrder,
+    ) -> Vec<RpcMetricsSnapshot> {
+        Vec::new()
+    }
+
+    async fn next_after(
+        self,
+        _ctx: context::Context,
+        _since_ms: u64,
+        _timeout_ms: u64,
+        _until_ms: Option<u64>,
+    ) -> Option<RpcMetricsSnapshot> {
+        std::future::pending().await
+    }
+
+    async fn next_event(
+        self,
+        _ctx: context::Context,
+        _since_ms: u64,
+        _timeout_ms: u64,
+    ) -> Option<StreamEvent> {
+        std::future::pending().await
+    }
+
+    async fn nearest(
+        self,
+        _ctx: context::Context,
+        _timestamp_ms: u64,
+    ) -> Option<RpcMetricsSnapshot> {
+        None
     }
+
+    async fn server_info(self, _ctx: context::Context) -> resource_monitor::rpc::ServerInfo {
+        resource_monitor::rpc::ServerInfo {
+            schema_version: 0,
+            server_version: "0.0.0".to_string(),
+            interval_ms: None,
+            history_capacity: self.0.capacity(),
+            collectors: Vec::new(),
+            features: Vec::new(),
+        }
+    }
+
+    async fn latest_blocking(
+        self,
+        _ctx: context::Context,
+        _since_ms: u64,
+        _timeout_ms: u64,
+    ) -> Option<RpcMetricsSnapshot> {
+        None
+    }
+}
+
+#[tokio::test]
+async fn streamer_falls_back_to_polling_without_streaming_support() {
+    use resource_monitor::rpc::run_rpc_client_streamer;
+    use tokio_util::sync::CancellationToken;
+
+    let buffer = Arc::new(MetricsBuffer::new(10));
+    buffer.push(sample_snapshot(1000));
+    let mut incoming = tarpc::serde_transport::tcp::listen(
+        "127.0.0.1:0".parse::<SocketAddr>().unwrap(),
+        tokio_serde::formats::Json::default,
+    )
+    .await
+    .unwrap();
+    let addr = incoming.local_addr();
+    let server = PollOnlyServer(buffer);
+    tokio::spawn(async move {
+        while let Some(Ok(transport)) = incoming.next().await {
+            let server = server.clone();
+            tokio::spawn(
+                server::BaseChannel::with_defaults(transport)
+                    .execute(server.serve())
+                    .for_each(|fut| async move {
+                        tokio::spawn(fut);
+                    }),
+            );
+        }
+    });
+
+    let (snap_tx, mut snap_rx) = tokio::sync::mpsc::unbounded_channel();
+    let cancel = CancellationToken::new();
+    let streamer = tokio::spawn(run_rpc_client_streamer(
+        addr,
+        false,
+        Duration::from_millis(20),
+        cancel.clone(),
+        move |snap| {
+            let _ = snap_tx.send(snap.timestamp_ms);
+        },
+    ));
+
+    for _ in 0..2 {
+        let ts = tokio::time::timeout(Duration::from_secs(5), snap_rx.recv())
+            .await
+            .expect("snapshot via polling")
+            .unwrap();
+        assert_eq!(ts, 1000);
+    }
+    cancel.cancel();
+    tokio::time::timeout(Duration::from_secs(5), streamer)
+        .await
+        .unwrap()
+        .unwrap();
+}
+
+#[tokio::test]
+async fn streamer_backfills_snapshots_missed_in_a_timestamp_jump() {
+    use resource_monitor::rpc::run_rpc_client_streamer;
+    use tokio_util::sync::CancellationToken;
+
+    let buffer = Arc::new(MetricsBuffer::new(20));
+    buffer.push(sample_snapshot(1000));
+    let (stream_tx, _) = broadcast::channel::<RpcMetricsSnapshot>(8);
+    let (_interval_tx, interval_rx) = tokio::sync::watch::channel(Duration::from_millis(100));
+    let server_impl =
+        MetricsRpcServer::new(buffer.clone(), stream_tx.clone()).with_interval(interval_rx);
+    let mut incoming = tarpc::serde_transport::tcp::listen(
+        "127.0.0.1:0".parse::<SocketAddr>().unwrap(),
+        tokio_serde::formats::Json::default,
+    )
+    .await
+    .unwrap();
+    let addr = incoming.local_addr();
+    tokio::spawn(async move {
+        while let Some(Ok(transport)) = incoming.next().await {
+            tokio::spawn(
+                server::BaseChannel::with_defaults(transport)
+                    .execute(server_impl.clone().serve())
+                    .for_each(|fut| async move {
+                        tokio::spawn(fut);
+                    }),
+            );
+        }
+    });
+
+    let (snap_tx, mut snap_rx) = tokio::sync::mpsc::unbounded_channel();
+    let cancel = CancellationToken::new();
+    let streamer = tokio::spawn(run_rpc_client_streamer(
+        addr,
+        false,
+        Duration::from_millis(100),
+        cancel.clone(),
+        move |snap| {
+            let _ = snap_tx.send(snap.timestamp_ms);
+        },
+    ));
+    async fn next(rx: &mut tokio::sync::mpsc::UnboundedReceiver<u128>) -> u128 {
+        tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .expect("snapshot from the streamer")
+            .unwrap()
+    }
+    assert_eq!(next(&mut snap_rx).await, 1000);
+
+    // Samples 1100..=1500 reach the buffer but not the stream; the next
+    // streamed snapshot jumps past them.
+    for ts in (1100..=1500).step_by(100) {
+        buffer.push(sample_snapshot(ts));
+    }
+    buffer.push(sample_snapshot(2000));
+    let _ = stream_tx.send(sample_snapshot(2000).to_rpc_format());
+
+    let mut received = Vec::new();
+    for _ in 0..6 {
+        received.push(next(&mut snap_rx).await);
+    }
+    assert_eq!(received, vec![1100, 1200, 1300, 1400, 1500, 2000]);
+
+    cancel.cancel();
+    tokio::time::timeout(Duration::from_secs(5), streamer)
+        .await
+        .unwrap()
+        .unwrap();
+}
+
+#[tokio::test]
+async fn connection_past_the_limit_is_closed_on_accept() {
+    use resource_monitor::rpc::serve_rpc;
+    use tokio::io::AsyncReadExt;
+    use tokio_util::sync::CancellationToken;
+
+    let buffer = Arc::new(MetricsBuffer::new(10));
+    let (stream_tx, _) = broadcast::channel(8);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let cancel = CancellationToken::new();
+    let server = tokio::spawn(serve_rpc(
+        listener,
+        MetricsRpcServer::new(buffer, stream_tx),
+        Some(2),
+        cancel.clone(),
+    ));
+
+    let mut open = Vec::new();
+    for _ in 0..2 {
+        open.push(tokio::net::TcpStream::connect(addr).await.unwrap());
+    }
+    let mut rejected = tokio::net::TcpStream::connect(addr).await.unwrap();
+    let mut byte = [0u8; 1];
+    let read = tokio::time::timeout(Duration::from_secs(5), rejected.read(&mut byte))
+        .await
+        .expect("closed by the server");
+    assert!(matches!(read, Ok(0) | Err(_)), "{read:?}");
+
+    cancel.cancel();
+    server.await.unwrap();
 }
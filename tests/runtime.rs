@@ -0,0 +1,47 @@
+use axum::routing::get;
+use axum::Router;
+use resource_monitor::runtime::serve_with_drain;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+#[tokio::test]
+async fn in_flight_request_completes_during_drain() {
+    let app = Router::new().route(
+        "/slow",
+        get(|| async {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            "done"
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let cancel = CancellationToken::new();
+    let server = tokio::spawn(serve_with_drain(
+        listener,
+        app,
+        cancel.clone(),
+        Duration::from_secs(5),
+    ));
+
+    let request = tokio::spawn(async move {
+        let response = reqwest::get(format!("http://{addr}/slow")).await?;
+        response.text().await
+    });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    cancel.cancel();
+
+    let body = tokio::time::timeout(Duration::from_secs(5), request)
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert_eq!(body, "done");
+    tokio::time::timeout(Duration::from_secs(5), server)
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+
+    // The listener is closed once the server has drained.
+    assert!(tokio::net::TcpStream::connect(addr).await.is_err());
+}
@@ -0,0 +1,154 @@
+use resource_monitor::metrics::{
+    CpuMetrics, DiskMetrics, MemoryMetrics, MetricsSnapshot, NetworkMetrics,
+};
+use resource_monitor::sqlite_store::SqliteStore;
+use resource_monitor::storage::{MetricsBuffer, SnapshotStore};
+
+fn sample(ts: u128) -> MetricsSnapshot {
+    MetricsSnapshot {
+        timestamp_ms: ts,
+        sample_interval_ms: 1000.0,
+        cpu: CpuMetrics {
+            total_usage_pct: 10.0,
+            per_core_usage_pct: vec![10.0, 20.0],
+            load_avg_1: Some(0.1),
+            load_avg_5: Some(0.2),
+            load_avg_15: Some(0.3),
+            temperature_celsius: Some(50.0),
+            breakdown: None,
+            per_socket_usage_pct: None,
+            per_core_changes: None,
+            busy_cores: None,
+        },
+        memory: MemoryMetrics {
+            total_bytes: 100,
+            used_bytes: 50,
+            available_bytes: 50,
+            swap_total_bytes: 4096,
+            swap_used_bytes: 1024,
+            swap_in_bytes_per_sec: None,
+            swap_out_bytes_per_sec: None,
+        },
+        network: NetworkMetrics {
+            rx_bytes_total: 1000,
+            tx_bytes_total: 2000,
+            rx_bytes_per_sec: 10.0,
+            tx_bytes_per_sec: 20.0,
+        },
+        disk: DiskMetrics {
+            total_bytes: 500_000_000_000,
+            available_bytes: 200_000_000_000,
+            used_pct: 60.0,
+            used_bytes: None,
+        },
+        battery: None,
+        gpu: None,
+        scheduler: None,
+        watched_process: None,
+        net_top_processes: None,
+        pressure_score: None,
+        source: None,
+        resumed: false,
+        tags: Default::default(),
+    }
+}
+
+fn store_with(timestamps: &[u128]) -> SqliteStore {
+    let store = SqliteStore::open_in_memory().unwrap();
+    for ts in timestamps {
+        store.push(sample(*ts));
+    }
+    store
+}
+
+fn timestamps(snapshots: &[MetricsSnapshot]) -> Vec<u128> {
+    snapshots.iter().map(|s| s.timestamp_ms).collect()
+}
+
+#[test]
+fn latest_empty_returns_none() {
+    let store = SqliteStore::open_in_memory().unwrap();
+    assert!(store.latest().is_none());
+}
+
+#[test]
+fn latest_returns_most_recent_full_snapshot() {
+    let store = store_with(&[3000, 1000, 2000]);
+    let latest = store.latest().unwrap();
+    assert_eq!(latest.timestamp_ms, 3000);
+    assert_eq!(latest.cpu.per_core_usage_pct, vec![10.0, 20.0]);
+    assert_eq!(latest.memory.swap_used_bytes, 1024);
+}
+
+#[test]
+fn window_filters_by_inclusive_time_range() {
+    let store = store_with(&[1000, 2000, 3000, 4000, 5000]);
+    assert_eq!(
+        timestamps(&store.window(Some(2000), Some(4000), None)),
+        vec![2000, 3000, 4000]
+    );
+    assert_eq!(
+        timestamps(&store.window(Some(2000), None, Some(2))),
+        vec![4000, 5000]
+    );
+    assert!(store.window(Some(6000), None, None).is_empty());
+}
+
+#[test]
+fn window_matches_memory_buffer() {
+    let store = store_with(&[1000, 2000, 3000, 4000]);
+    let buffer = MetricsBuffer::new(10);
+    for ts in [1000, 2000, 3000, 4000] {
+        SnapshotStore::push(&buffer, sample(ts));
+    }
+    for (since, until, limit) in [
+        (None, None, None),
+        (Some(1500), None, Some(1)),
+        (None, Some(3000), Some(10)),
+    ] {
+        assert_eq!(
+            timestamps(&store.window(since, until, limit)),
+            timestamps(&buffer.window(since, until, limit))
+        );
+    }
+}
+
+#[test]
+fn columns_are_queryable_with_sql() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("store.db");
+    let store = SqliteStore::open(&path).unwrap();
+    store.push(sample(1000));
+    store.push(sample(2000));
+    drop(store);
+
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    let avg: f64 = conn
+        .query_row(
+            "SELECT AVG(cpu_total_pct) FROM snapshots WHERE timestamp_ms >= 1000",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(avg, 10.0);
+}
+
+#[test]
+fn cleanup_drops_snapshots_older_than_the_retention() {
+    let now = chrono::Utc::now().timestamp_millis() as u128;
+    let store = store_with(&[now - 3 * 3600 * 1000, now - 1000]);
+    assert_eq!(store.cleanup_old(2).unwrap(), 1);
+    assert_eq!(
+        timestamps(&store.window(None, None, None)),
+        vec![now - 1000]
+    );
+}
+
+#[test]
+fn stats_count_the_snapshots_table() {
+    let store = store_with(&[3000, 1000, 2000]);
+    let stats = store.stats().unwrap();
+    assert_eq!(stats.total_records, 3);
+    assert_eq!(stats.oldest_timestamp, Some(1000));
+    assert_eq!(stats.newest_timestamp, Some(3000));
+}
@@ -0,0 +1,78 @@
+use std::net::TcpListener;
+use std::process::{Child, Command, Stdio};
+use std::time::Duration;
+use tempfile::tempdir;
+
+/// Kills the server when the test ends, pass or fail.
+struct Server(Child);
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+#[tokio::test]
+async fn standalone_serves_collected_metrics_and_dashboard_without_rpc() {
+    let dir = tempdir().unwrap();
+    let port = free_port();
+    // Held for the whole test: standalone mode must not need it.
+    let rpc_taken = TcpListener::bind("127.0.0.1:0").unwrap();
+    let _server = Server(
+        Command::new(env!("CARGO_BIN_EXE_server"))
+            .args([
+                "--standalone",
+                "--interval-ms",
+                "100",
+                "--bind",
+                "127.0.0.1",
+            ])
+            .args(["--port", &port.to_string()])
+            .args(["--rpc-addr", &rpc_taken.local_addr().unwrap().to_string()])
+            .arg("--db-path")
+            .arg(dir.path().join("metrics.db"))
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap(),
+    );
+
+    let http = reqwest::Client::new();
+    let base = format!("http://127.0.0.1:{port}");
+    let mut timestamps = Vec::new();
+    for _ in 0..100 {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let Ok(resp) = http.get(format!("{base}/api/metrics")).send().await else {
+            continue;
+        };
+        if resp.status() != 200 {
+            continue;
+        }
+        let json: serde_json::Value = serde_json::from_slice(&resp.bytes().await.unwrap()).unwrap();
+        let ts = json["timestamp_ms"].as_u64().unwrap();
+        assert!(json["data"].as_array().is_some_and(|d| !d.is_empty()));
+        if timestamps.last() != Some(&ts) {
+            timestamps.push(ts);
+        }
+        if timestamps.len() >= 2 {
+            break;
+        }
+    }
+    assert!(
+        timestamps.len() >= 2,
+        "expected fresh snapshots after a couple of ticks, got {timestamps:?}"
+    );
+
+    let index = http.get(format!("{base}/")).send().await.unwrap();
+    assert_eq!(index.status(), 200);
+    assert!(index.text().await.unwrap().contains("<html"));
+}
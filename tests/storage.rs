@@ -1,18 +1,24 @@
+use resource_monitor::config::Aggregation;
 use resource_monitor::metrics::{
     CpuMetrics, DiskMetrics, MemoryMetrics, MetricsSnapshot, NetworkMetrics,
 };
-use resource_monitor::storage::MetricsBuffer;
+use resource_monitor::storage::{aggregate, downsample, MetricsBuffer};
 
 fn sample(i: u128) -> MetricsSnapshot {
     MetricsSnapshot {
         timestamp_ms: i,
+        sample_interval_ms: 1000.0,
         cpu: CpuMetrics {
             total_usage_pct: 10.0,
             per_core_usage_pct: vec![10.0, 20.0],
-            load_avg_1: 0.1,
-            load_avg_5: 0.2,
-            load_avg_15: 0.3,
+            load_avg_1: Some(0.1),
+            load_avg_5: Some(0.2),
+            load_avg_15: Some(0.3),
             temperature_celsius: Some(50.0),
+            breakdown: None,
+            per_socket_usage_pct: None,
+            per_core_changes: None,
+            busy_cores: None,
         },
         memory: MemoryMetrics {
             total_bytes: 100,
@@ -20,6 +26,8 @@ fn sample(i: u128) -> MetricsSnapshot {
             available_bytes: 50,
             swap_total_bytes: 4096,
             swap_used_bytes: 1024,
+            swap_in_bytes_per_sec: None,
+            swap_out_bytes_per_sec: None,
         },
         network: NetworkMetrics {
             rx_bytes_total: 1000,
@@ -31,9 +39,17 @@ fn sample(i: u128) -> MetricsSnapshot {
             total_bytes: 500_000_000_000,
             available_bytes: 200_000_000_000,
             used_pct: 60.0,
+            used_bytes: None,
         },
         battery: None,
         gpu: None,
+        scheduler: None,
+        watched_process: None,
+        net_top_processes: None,
+        pressure_score: None,
+        source: None,
+        resumed: false,
+        tags: Default::default(),
     }
 }
 
@@ -58,6 +74,18 @@ fn push_and_latest_returns_last() {
     assert_eq!(buf.latest().unwrap().timestamp_ms, 200);
 }
 
+#[test]
+fn latest_skips_labeled_sources() {
+    let buf = MetricsBuffer::new(5);
+    buf.push(sample(100));
+    buf.push(MetricsSnapshot {
+        source: Some("rack-a".to_string()),
+        ..sample(200)
+    });
+    assert_eq!(buf.latest().unwrap().timestamp_ms, 100);
+    assert_eq!(buf.history(None).len(), 2);
+}
+
 #[test]
 fn history_returns_all_when_no_limit() {
     let buf = MetricsBuffer::new(10);
@@ -123,3 +151,389 @@ fn capacity_one_keeps_only_last() {
     assert_eq!(buf.latest().unwrap().timestamp_ms, 30);
     assert_eq!(buf.history(None).len(), 1);
 }
+
+#[test]
+fn panicking_writer_poisons_buffer_observably() {
+    let buf = MetricsBuffer::new(5);
+    buf.push(sample(1));
+    assert!(!buf.is_poisoned());
+
+    let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        buf.retain(|_| panic!("writer failure"));
+    }));
+    assert!(res.is_err());
+    assert!(buf.is_poisoned());
+
+    // Reads keep working best-effort.
+    assert_eq!(buf.latest().unwrap().timestamp_ms, 1);
+
+    // Strict writes refuse, lenient writes proceed and are counted.
+    assert!(buf.try_push(sample(2)).is_err());
+    assert_eq!(buf.poison_recoveries(), 0);
+    buf.push(sample(3));
+    assert_eq!(buf.poison_recoveries(), 1);
+    assert_eq!(buf.latest().unwrap().timestamp_ms, 3);
+}
+
+#[test]
+fn histogram_counts_per_bin() {
+    let buf = MetricsBuffer::new(20);
+    for (i, pct) in [5.0, 15.0, 15.0, 25.0, 95.0, 100.0, 50.0]
+        .iter()
+        .enumerate()
+    {
+        let mut s = sample(i as u128 + 1);
+        s.cpu.total_usage_pct = *pct;
+        buf.push(s);
+    }
+
+    let h = buf
+        .histogram(None, None, 10, Some((0.0, 100.0)), |s| {
+            Some(s.cpu.total_usage_pct)
+        })
+        .unwrap();
+    assert_eq!(h.edges.len(), 11);
+    assert_eq!(h.edges[0], 0.0);
+    assert_eq!(h.edges[10], 100.0);
+    assert_eq!(h.counts, vec![1, 2, 1, 0, 0, 1, 0, 0, 0, 2]);
+    assert_eq!(h.total, 7);
+
+    // Window filter is inclusive on both ends: timestamps 2..=4.
+    let h = buf
+        .histogram(Some(2), Some(4), 10, Some((0.0, 100.0)), |s| {
+            Some(s.cpu.total_usage_pct)
+        })
+        .unwrap();
+    assert_eq!(h.counts, vec![0, 2, 1, 0, 0, 0, 0, 0, 0, 0]);
+}
+
+#[test]
+fn histogram_empty_window_is_none() {
+    let buf = MetricsBuffer::new(5);
+    buf.push(sample(10));
+    assert!(buf
+        .histogram(Some(100), None, 10, None, |s| Some(s.cpu.total_usage_pct))
+        .is_none());
+}
+
+#[test]
+fn stats_summarize_window() {
+    let buf = MetricsBuffer::new(10);
+    for (i, pct) in [10.0, 40.0, 20.0, 90.0].iter().enumerate() {
+        let mut s = sample(i as u128 + 1);
+        s.cpu.total_usage_pct = *pct;
+        buf.push(s);
+    }
+
+    let st = buf
+        .stats(Some(1), Some(3), |s| Some(s.cpu.total_usage_pct))
+        .unwrap();
+    assert_eq!(st.count, 3);
+    assert_eq!(st.min, 10.0);
+    assert_eq!(st.max, 40.0);
+    assert!((st.mean - 23.333).abs() < 0.01);
+    assert!(buf
+        .stats(Some(50), None, |s| Some(s.cpu.total_usage_pct))
+        .is_none());
+}
+
+#[test]
+fn range_includes_exact_boundaries() {
+    let buf = MetricsBuffer::new(10);
+    for ts in [10, 20, 30, 40, 50] {
+        buf.push(sample(ts));
+    }
+    let ts = |v: Vec<MetricsSnapshot>| -> Vec<u128> { v.iter().map(|s| s.timestamp_ms).collect() };
+
+    assert_eq!(ts(buf.range(Some(20), Some(40))), vec![20, 30, 40]);
+    assert_eq!(ts(buf.range(Some(21), Some(39))), vec![30]);
+    assert_eq!(ts(buf.range(None, Some(10))), vec![10]);
+    assert_eq!(ts(buf.range(Some(50), None)), vec![50]);
+    assert_eq!(ts(buf.range(None, None)).len(), 5);
+}
+
+#[test]
+fn range_empty_cases() {
+    let buf = MetricsBuffer::new(10);
+    assert!(buf.range(None, None).is_empty());
+    for ts in [10, 20, 30] {
+        buf.push(sample(ts));
+    }
+    assert!(buf.range(Some(31), None).is_empty());
+    assert!(buf.range(None, Some(9)).is_empty());
+    assert!(buf.range(Some(21), Some(29)).is_empty());
+    // Inverted bounds yield nothing rather than panicking.
+    assert!(buf.range(Some(30), Some(10)).is_empty());
+}
+
+#[test]
+fn page_after_walks_full_history() {
+    let buf = MetricsBuffer::new(200);
+    for i in 1..=100 {
+        buf.push(sample(i * 10));
+    }
+
+    let mut seen = Vec::new();
+    let mut cursor = None;
+    let mut pages = 0;
+    loop {
+        let page = buf.page_after(cursor, 10);
+        pages += 1;
+        assert!(page.items.len() <= 10);
+        seen.extend(page.items.iter().map(|s| s.timestamp_ms));
+        match page.next_cursor {
+            Some(c) => cursor = Some(c),
+            None => break,
+        }
+    }
+
+    assert_eq!(pages, 10);
+    let expected: Vec<u128> = (1..=100).map(|i| i * 10).collect();
+    assert_eq!(seen, expected);
+}
+
+#[test]
+fn page_after_cursor_between_timestamps() {
+    let buf = MetricsBuffer::new(10);
+    for ts in [100, 200, 300] {
+        buf.push(sample(ts));
+    }
+    let page = buf.page_after(Some(150), 10);
+    assert_eq!(page.items.len(), 2);
+    assert_eq!(page.items[0].timestamp_ms, 200);
+    assert!(page.next_cursor.is_none());
+    assert!(buf.page_after(Some(300), 10).items.is_empty());
+}
+
+#[test]
+fn per_core_detail_kept_only_for_recent_snapshots() {
+    let buf = MetricsBuffer::new(10).with_per_core_retention(2);
+    for ts in 1..=5 {
+        buf.push(sample(ts));
+    }
+
+    let history = buf.history(None);
+    assert_eq!(history.len(), 5);
+    for old in &history[..3] {
+        assert!(old.cpu.per_core_usage_pct.is_empty());
+        assert_eq!(old.cpu.total_usage_pct, 10.0);
+    }
+    for recent in &history[3..] {
+        assert_eq!(recent.cpu.per_core_usage_pct, vec![10.0, 20.0]);
+    }
+}
+
+#[test]
+fn strictly_increasing_buffer_rejects_duplicate_and_out_of_order() {
+    let buf = MetricsBuffer::new(10).with_strictly_increasing();
+    assert!(buf.push(sample(100)));
+    assert!(buf.push(sample(200)));
+    assert!(!buf.push(sample(200)));
+    assert!(!buf.push(sample(150)));
+    assert!(buf.push(sample(300)));
+
+    let timestamps: Vec<u128> = buf.history(None).iter().map(|s| s.timestamp_ms).collect();
+    assert_eq!(timestamps, vec![100, 200, 300]);
+
+    // The default buffer keeps whatever it is given.
+    let lenient = MetricsBuffer::new(10);
+    assert!(lenient.push(sample(100)));
+    assert!(lenient.push(sample(100)));
+    assert_eq!(lenient.history(None).len(), 2);
+}
+
+#[test]
+fn overflow_policy_drops_oldest_or_rejects_newest() {
+    use resource_monitor::config::OverflowPolicy;
+
+    let timestamps = |buf: &MetricsBuffer| -> Vec<u128> {
+        buf.history(None).iter().map(|s| s.timestamp_ms).collect()
+    };
+
+    let dropping = MetricsBuffer::new(3);
+    for i in 1..=5 {
+        assert!(dropping.push(sample(i * 100)));
+    }
+    assert_eq!(timestamps(&dropping), vec![300, 400, 500]);
+    assert_eq!(dropping.overflow_rejections(), 0);
+
+    let rejecting = MetricsBuffer::new(3).with_overflow_policy(OverflowPolicy::RejectNewest);
+    for i in 1..=3 {
+        assert!(rejecting.push(sample(i * 100)));
+    }
+    assert!(!rejecting.push(sample(400)));
+    assert!(!rejecting.push(sample(500)));
+    assert_eq!(timestamps(&rejecting), vec![100, 200, 300]);
+    assert_eq!(rejecting.overflow_rejections(), 2);
+
+    // Room freed by retention is filled again.
+    rejecting.retain(|s| s.timestamp_ms > 100);
+    assert!(rejecting.push(sample(600)));
+    assert_eq!(timestamps(&rejecting), vec![200, 300, 600]);
+}
+
+#[test]
+fn sparse_per_core_history_rebuilds_original_vectors() {
+    use resource_monitor::storage::SPARSE_KEYFRAME_EVERY;
+
+    // 64 cores, of which only a few move each tick.
+    let snapshot = |i: u128| {
+        let mut s = sample(i * 1000);
+        s.cpu.per_core_usage_pct = (0..64)
+            .map(|c| {
+                if c as u128 % 16 == i % 16 {
+                    i as f32
+                } else {
+                    c as f32
+                }
+            })
+            .collect();
+        s
+    };
+    let pushed = 2 * SPARSE_KEYFRAME_EVERY as u128 + 7;
+    let capacity = SPARSE_KEYFRAME_EVERY + 20;
+    let sparse = MetricsBuffer::new(capacity).with_sparse_per_core(0.0);
+    let dense = MetricsBuffer::new(capacity);
+    for i in 0..pushed {
+        sparse.push(snapshot(i));
+        dense.push(snapshot(i));
+    }
+
+    let cores = |history: Vec<MetricsSnapshot>| -> Vec<Vec<f32>> {
+        history
+            .into_iter()
+            .map(|s| {
+                assert!(s.cpu.per_core_changes.is_none());
+                s.cpu.per_core_usage_pct
+            })
+            .collect()
+    };
+    let expected = cores(dense.history(None));
+    assert_eq!(expected.len(), capacity);
+    assert_eq!(cores(sparse.history(None)), expected);
+    assert_eq!(
+        cores(sparse.range(Some(100_000), Some(110_000))),
+        cores(dense.range(Some(100_000), Some(110_000)))
+    );
+    assert_eq!(
+        sparse.nearest(120_400).unwrap().cpu.per_core_usage_pct,
+        dense.nearest(120_400).unwrap().cpu.per_core_usage_pct
+    );
+    assert_eq!(
+        sparse.latest().unwrap().cpu.per_core_usage_pct,
+        snapshot(pushed - 1).cpu.per_core_usage_pct
+    );
+    assert!(sparse.estimated_bytes() < dense.estimated_bytes());
+
+    // Removing snapshots re-links the rest.
+    sparse.retain(|s| s.timestamp_ms % 3000 != 0);
+    dense.retain(|s| s.timestamp_ms % 3000 != 0);
+    assert_eq!(cores(sparse.history(None)), cores(dense.history(None)));
+    sparse.push(snapshot(pushed));
+    dense.push(snapshot(pushed));
+    assert_eq!(cores(sparse.history(None)), cores(dense.history(None)));
+}
+
+#[test]
+fn compact_thins_old_snapshots_and_keeps_recent_ones() {
+    let buf = MetricsBuffer::new(100);
+    for i in 0..20 {
+        buf.push(sample(i * 1000));
+    }
+
+    // Latest is 19_000: everything before 9_000 is old.
+    assert_eq!(buf.compact(10_000, 3), 6);
+    let timestamps: Vec<u128> = buf.history(None).iter().map(|s| s.timestamp_ms).collect();
+    assert_eq!(
+        timestamps,
+        vec![
+            0, 3000, 6000, 9000, 10000, 11000, 12000, 13000, 14000, 15000, 16000, 17000, 18000,
+            19000
+        ]
+    );
+
+    // Already-thinned data is not thinned again; only newly aged snapshots are.
+    assert_eq!(buf.compact(10_000, 3), 0);
+    for i in 20..23 {
+        buf.push(sample(i * 1000));
+    }
+    assert_eq!(buf.compact(10_000, 3), 2);
+    let timestamps: Vec<u128> = buf.history(None).iter().map(|s| s.timestamp_ms).collect();
+    assert_eq!(&timestamps[..5], &[0, 3000, 6000, 9000, 12000]);
+    assert_eq!(timestamps.len(), 15);
+}
+
+#[test]
+fn estimated_bytes_grow_with_snapshots_and_core_count() {
+    let buf = MetricsBuffer::new(10);
+    assert_eq!(buf.estimated_bytes(), 0);
+    buf.push(sample(1));
+    let one = buf.estimated_bytes();
+    assert!(one >= std::mem::size_of::<MetricsSnapshot>());
+    buf.push(sample(2));
+    assert_eq!(buf.estimated_bytes(), 2 * one);
+
+    let mut wide = sample(3);
+    wide.cpu.per_core_usage_pct = vec![0.0; 130];
+    let narrow = sample(3).estimated_bytes();
+    assert_eq!(
+        wide.estimated_bytes() - narrow,
+        128 * std::mem::size_of::<f32>()
+    );
+    buf.push(wide);
+    assert_eq!(
+        buf.estimated_bytes(),
+        3 * one + 128 * std::mem::size_of::<f32>()
+    );
+}
+
+#[test]
+fn aggregation_strategies_over_a_known_bucket() {
+    let bucket = [4.0, 9.0, f32::NAN, 1.0, 6.0];
+    assert_eq!(aggregate(&bucket, Aggregation::Mean), Some(5.0));
+    assert_eq!(aggregate(&bucket, Aggregation::Max), Some(9.0));
+    assert_eq!(aggregate(&bucket, Aggregation::Min), Some(1.0));
+    assert_eq!(aggregate(&bucket, Aggregation::Last), Some(6.0));
+    for how in [
+        Aggregation::Mean,
+        Aggregation::Max,
+        Aggregation::Min,
+        Aggregation::Last,
+    ] {
+        assert_eq!(aggregate(&[], how), None);
+    }
+}
+
+#[test]
+fn downsample_combines_runs_ending_at_the_newest_point() {
+    // 7 points into 3: runs of 3, the oldest run short.
+    let points: Vec<(u64, f32)> = [5.0, 1.0, 8.0, 2.0, 3.0, 7.0, 4.0]
+        .into_iter()
+        .enumerate()
+        .map(|(i, v)| (i as u64 * 1000, v))
+        .collect();
+
+    assert_eq!(
+        downsample(&points, 3, Aggregation::Max),
+        vec![(0, 5.0), (3000, 8.0), (6000, 7.0)]
+    );
+    assert_eq!(
+        downsample(&points, 3, Aggregation::Last),
+        vec![(0, 5.0), (3000, 2.0), (6000, 4.0)]
+    );
+    assert_eq!(downsample(&points, 7, Aggregation::Min), points);
+
+    let mut buffer_cores = Vec::new();
+    for (ts, v) in &points {
+        buffer_cores.push((u128::from(*ts), vec![*v]));
+    }
+    let heatmap = resource_monitor::storage::CpuHeatmap::from_samples(
+        buffer_cores.iter().map(|(ts, c)| (*ts, c.as_slice())),
+        1,
+        None,
+        None,
+        Aggregation::Min,
+    )
+    .unwrap();
+    assert_eq!(heatmap.cores, vec![vec![Some(1.0)]]);
+}
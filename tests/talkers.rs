@@ -0,0 +1,66 @@
+use resource_monitor::talkers::{SocketBytes, SocketCounterSource, TopTalkers};
+use std::collections::VecDeque;
+
+/// Replays scripted counter readings.
+struct ScriptedSource(VecDeque<Option<Vec<SocketBytes>>>);
+
+impl SocketCounterSource for ScriptedSource {
+    fn read(&mut self) -> Option<Vec<SocketBytes>> {
+        self.0.pop_front().flatten()
+    }
+}
+
+fn socket(inode: u64, pid: u32, rx_bytes: u64, tx_bytes: u64) -> SocketBytes {
+    SocketBytes {
+        inode,
+        pid,
+        rx_bytes,
+        tx_bytes,
+    }
+}
+
+#[test]
+fn top_talkers_sorts_processes_by_traffic_since_last_reading() {
+    let source = ScriptedSource(VecDeque::from([
+        Some(vec![
+            socket(1, 100, 1_000, 1_000),
+            socket(2, 200, 5_000, 0),
+            socket(3, 300, 0, 0),
+        ]),
+        Some(vec![
+            // pid 100 has two sockets, one opened since the last reading.
+            socket(1, 100, 1_500, 1_500),
+            socket(4, 100, 1_000, 0),
+            socket(2, 200, 9_000, 0),
+            socket(3, 300, 100, 0),
+            // A quiet newcomer is left out.
+            socket(5, 400, 0, 0),
+        ]),
+        None,
+    ]));
+    let mut talkers = TopTalkers::new(source, 2);
+    let name_of = |pid: u32| (pid == 200).then(|| "curl".to_string());
+
+    // The first reading is only a baseline.
+    assert_eq!(talkers.sample(1.0, name_of), Some(Vec::new()));
+
+    let top = talkers.sample(2.0, name_of).unwrap();
+    let summary: Vec<(u32, &str, f32, f32)> = top
+        .iter()
+        .map(|p| {
+            (
+                p.pid,
+                p.name.as_str(),
+                p.rx_bytes_per_sec,
+                p.tx_bytes_per_sec,
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        vec![(200, "curl", 2_000.0, 0.0), (100, "", 750.0, 250.0)]
+    );
+
+    // Unreadable counters drop the section rather than reporting zeros.
+    assert_eq!(talkers.sample(1.0, name_of), None);
+}
@@ -0,0 +1,59 @@
+use resource_monitor::topology::CoreTopology;
+
+#[test]
+fn per_socket_usage_averages_each_sockets_cores() {
+    // Interleaved the way two-socket machines often number their cores.
+    let topology = CoreTopology::new(vec![0, 1, 0, 1, 0, 1]);
+    let per_core = [10.0, 80.0, 20.0, 90.0, 30.0, 100.0];
+
+    assert_eq!(topology.per_socket_usage(&per_core), vec![20.0, 90.0]);
+    assert_eq!(
+        topology.groups(per_core.len()),
+        vec![(0, vec![0, 2, 4]), (1, vec![1, 3, 5])]
+    );
+}
+
+#[test]
+fn unknown_topology_is_one_group() {
+    let per_core = [10.0, 30.0, 50.0];
+    assert_eq!(
+        CoreTopology::single().per_socket_usage(&per_core),
+        vec![30.0]
+    );
+
+    // Cores the mapping does not cover fall into socket 0.
+    let partial = CoreTopology::new(vec![1]);
+    assert_eq!(partial.groups(3), vec![(0, vec![1, 2]), (1, vec![0])]);
+    assert!(CoreTopology::single().per_socket_usage(&[]).is_empty());
+}
+
+#[test]
+fn detected_topology_covers_at_least_one_socket() {
+    let topology = CoreTopology::detect();
+    assert!(!topology.groups(4).is_empty());
+}
+
+#[test]
+fn sysfs_topology_skips_gaps_in_cpu_numbering() {
+    let dir = tempfile::tempdir().unwrap();
+    let cpu = |name: &str, package: Option<&str>| {
+        let path = dir.path().join(name);
+        std::fs::create_dir_all(path.join("topology")).unwrap();
+        if let Some(package) = package {
+            std::fs::write(path.join("topology/physical_package_id"), package).unwrap();
+        }
+    };
+    // cpu2 is offline and cpu3 absent; cpu10 must sort after cpu4.
+    cpu("cpu0", Some("0\n"));
+    cpu("cpu1", Some("1\n"));
+    cpu("cpu2", None);
+    cpu("cpu4", Some("0\n"));
+    cpu("cpu10", Some("1\n"));
+    cpu("cpufreq", None);
+    cpu("cpuidle", None);
+    std::fs::write(dir.path().join("online"), "0-1,4,10\n").unwrap();
+
+    let topology = CoreTopology::from_sysfs(dir.path()).unwrap();
+    assert_eq!(topology, CoreTopology::new(vec![0, 1, 0, 1]));
+    assert!(CoreTopology::from_sysfs(&dir.path().join("missing")).is_none());
+}